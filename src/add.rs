@@ -0,0 +1,208 @@
+//! Adding a dependency to several manifests at once, like the selected members of a workspace
+use std::path::{Path, PathBuf};
+
+use super::errors::*;
+use super::manifest::replace_file;
+use super::{Dependency, LocalManifest, MergeStrategy, WriteOptions};
+
+/// What adding a dependency did to one manifest, see [`add_dependency_to_manifests`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DependencyAddition {
+    /// Manifest the dependency was added to
+    pub manifest_path: PathBuf,
+    /// Entry the table had before, if any
+    pub existing: Option<Dependency>,
+    /// Entry written, or `None` when the existing one was left alone
+    pub written: Option<Dependency>,
+}
+
+/// Add `dependency` to the `table` of each manifest, like `["dev-dependencies"]`, together
+///
+/// Every manifest is edited before any is written, so one that can't take the dependency leaves
+/// all of them unchanged, and if writing one fails, those already written are put back.  Entries
+/// already in a table are handled by `strategy`, and manifests are written as `options` says.
+/// The additions are returned in the order of `manifest_paths`, for a combined report.
+pub fn add_dependency_to_manifests(
+    manifest_paths: &[PathBuf],
+    table: &[String],
+    dependency: &Dependency,
+    strategy: MergeStrategy,
+    options: &WriteOptions,
+) -> CargoResult<Vec<DependencyAddition>> {
+    let mut manifests = Vec::new();
+    let mut additions = Vec::new();
+    for path in manifest_paths {
+        let mut manifest = LocalManifest::try_new(path)?;
+        let addition =
+            insert_dependency(&mut manifest, table, dependency, strategy).with_context(|| {
+                format!(
+                    "Failed to add `{}` to `{}`",
+                    dependency.name,
+                    path.display()
+                )
+            })?;
+        additions.push(addition);
+        manifests.push(manifest);
+    }
+    write_together(&manifests, options)?;
+    Ok(additions)
+}
+
+/// Write `dependency` into the `table` of `manifest`, without writing the manifest
+pub(crate) fn insert_dependency(
+    manifest: &mut LocalManifest,
+    table: &[String],
+    dependency: &Dependency,
+    strategy: MergeStrategy,
+) -> CargoResult<DependencyAddition> {
+    let manifest_path = manifest.path.clone();
+    let crate_root = manifest_path.parent().expect("manifests have a parent");
+    let dep_table = manifest
+        .get_or_insert_table_mut(table)?
+        .as_table_like_mut()
+        .expect("tables are table-like");
+    let existing = dep_table
+        .iter_mut()
+        .find(|(key, _)| key.get() == dependency.toml_key());
+    match existing {
+        Some((mut key, item)) => {
+            let existing = Dependency::from_toml(crate_root, dependency.toml_key(), item)?;
+            let written = strategy.apply(dependency.clone(), &existing);
+            if let Some(written) = &written {
+                written.update_toml(crate_root, &mut key, item);
+            }
+            Ok(DependencyAddition {
+                manifest_path,
+                existing: Some(existing),
+                written,
+            })
+        }
+        None => {
+            dep_table.insert(dependency.toml_key(), dependency.to_toml(crate_root));
+            Ok(DependencyAddition {
+                manifest_path,
+                existing: None,
+                written: Some(dependency.clone()),
+            })
+        }
+    }
+}
+
+/// Write all of `manifests`, or none of them
+///
+/// If a write fails, the manifests written before it get their previous contents back.
+pub(crate) fn write_together(
+    manifests: &[LocalManifest],
+    options: &WriteOptions,
+) -> CargoResult<()> {
+    let mut written: Vec<(&Path, Vec<u8>)> = Vec::new();
+    for manifest in manifests {
+        let result = std::fs::read(&manifest.path)
+            .with_context(|| format!("Failed to read `{}`", manifest.path.display()))
+            .and_then(|previous| {
+                manifest.write_with(options)?;
+                Ok(previous)
+            });
+        match result {
+            Ok(previous) => written.push((&manifest.path, previous)),
+            Err(err) => {
+                for (path, previous) in written.iter().rev() {
+                    replace_file(path, previous, options).with_context(|| {
+                        format!(
+                            "Failed to restore `{}` after a failed write",
+                            path.display()
+                        )
+                    })?;
+                }
+                return Err(err);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::RegistrySource;
+
+    #[test]
+    fn add_to_several_manifests() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let mut paths = Vec::new();
+        for (name, deps) in [
+            ("app", ""),
+            (
+                "model",
+                "serde = { version = \"1.0.100\", features = [\"rc\"] }\n",
+            ),
+        ] {
+            let dir = temp.path().join(name);
+            std::fs::create_dir(&dir).unwrap();
+            let path = dir.join("Cargo.toml");
+            std::fs::write(
+                &path,
+                format!(
+                    "[package]\nname = \"{}\"\nversion = \"0.1.0\"\n\n[dependencies]\n{}",
+                    name, deps
+                ),
+            )
+            .unwrap();
+            paths.push(path);
+        }
+        let serde = Dependency::new("serde")
+            .set_source(RegistrySource::new("1.0"))
+            .set_features(vec!["derive".to_owned()]);
+
+        let additions = add_dependency_to_manifests(
+            &paths,
+            &["dependencies".to_owned()],
+            &serde,
+            MergeStrategy::Merge,
+            &WriteOptions::new(),
+        )
+        .unwrap();
+        assert_eq!(additions.len(), 2);
+        assert_eq!(additions[0].existing, None);
+        assert!(additions[1].existing.is_some());
+
+        let app = LocalManifest::try_new(&paths[0]).unwrap();
+        assert_eq!(
+            app.data["dependencies"]["serde"]["version"].as_str(),
+            Some("1.0")
+        );
+        let model = LocalManifest::try_new(&paths[1]).unwrap();
+        let features: Vec<_> = model.data["dependencies"]["serde"]["features"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| f.as_str().unwrap())
+            .collect();
+        assert_eq!(features, ["rc", "derive"]);
+    }
+
+    #[test]
+    fn failing_manifest_leaves_all_unchanged() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let app = temp.path().join("Cargo.toml");
+        let app_contents = "[package]\nname = \"app\"\nversion = \"0.1.0\"\n";
+        std::fs::write(&app, app_contents).unwrap();
+        let broken = temp.path().join("broken.toml");
+        std::fs::write(
+            &broken,
+            "dependencies = 1\n\n[package]\nname = \"broken\"\n",
+        )
+        .unwrap();
+
+        let serde = Dependency::new("serde").set_source(RegistrySource::new("1.0"));
+        assert!(add_dependency_to_manifests(
+            &[app.clone(), broken],
+            &["dependencies".to_owned()],
+            &serde,
+            MergeStrategy::Merge,
+            &WriteOptions::new(),
+        )
+        .is_err());
+        assert_eq!(std::fs::read_to_string(&app).unwrap(), app_contents);
+    }
+}
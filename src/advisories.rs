@@ -0,0 +1,311 @@
+//! Security advisories from the RustSec advisory database
+//!
+//! RustSec publishes its advisories to OSV, whose API answers which advisories concern a crate
+//! without cloning the whole database.
+use std::path::Path;
+
+use semver::{Version, VersionReq};
+use url::Url;
+
+use super::errors::*;
+use super::fetch::CrateVersion;
+use super::http::http_agent;
+use super::select::VersionSelector;
+use super::timings::time;
+
+const OSV_QUERY_API: &str = "https://api.osv.dev/v1/query";
+
+/// A RustSec advisory against a crate, like `RUSTSEC-2021-0078`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Advisory {
+    /// Identifier of the advisory
+    pub id: String,
+    /// One-line description, if the advisory has one
+    pub summary: Option<String>,
+    /// Whether the advisory is informational, like an unmaintained crate, rather than a
+    /// vulnerability
+    pub informational: bool,
+    ranges: Vec<AffectedRange>,
+}
+
+/// Versions from `introduced` on, up to `fixed` or through `last_affected`
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct AffectedRange {
+    introduced: Version,
+    fixed: Option<Version>,
+    last_affected: Option<Version>,
+}
+
+impl Advisory {
+    /// Whether `version` is affected
+    pub fn affects(&self, version: &Version) -> bool {
+        self.ranges.iter().any(|range| {
+            range.introduced <= *version
+                && range.fixed.as_ref().map_or(true, |fixed| version < fixed)
+                && range
+                    .last_affected
+                    .as_ref()
+                    .map_or(true, |last| version <= last)
+        })
+    }
+}
+
+/// Query the RustSec advisories against a crate from crates.io, for the manifest at
+/// `manifest_path`
+///
+/// Withdrawn advisories are left out.
+pub fn get_advisories(crate_name: &str, manifest_path: &Path) -> CargoResult<Vec<Advisory>> {
+    let url = Url::parse(OSV_QUERY_API)?;
+    let work_dir = manifest_path
+        .parent()
+        .expect("there must be a parent directory");
+    let response = time("advisory-request", crate_name, || {
+        http_agent(&url, work_dir)?
+            .post(url.as_str())
+            .send_json(serde_json::json!({
+                "package": { "name": crate_name, "ecosystem": "crates.io" },
+            }))
+            .with_context(|| format!("Failed to look up advisories for `{}`", crate_name))?
+            .into_string()
+            .with_context(|| format!("Invalid response from `{}`", url))
+    })?;
+    let response: OsvResponse = serde_json::from_str(&response)
+        .with_context(|| format!("Invalid response from `{}`", url))?;
+    Ok(read_advisories(crate_name, response))
+}
+
+fn read_advisories(crate_name: &str, response: OsvResponse) -> Vec<Advisory> {
+    let mut advisories = Vec::new();
+    for vuln in response.vulns {
+        // OSV also serves GitHub's advisories, which mostly repeat RustSec's
+        if !vuln.id.starts_with("RUSTSEC-") || vuln.withdrawn.is_some() {
+            continue;
+        }
+        let mut informational = is_informational(&vuln.database_specific);
+        let mut ranges = Vec::new();
+        for affected in vuln.affected {
+            if affected.package.ecosystem != "crates.io" || affected.package.name != crate_name {
+                continue;
+            }
+            informational |= is_informational(&affected.database_specific);
+            for range in affected.ranges.iter().filter(|r| r.kind == "SEMVER") {
+                ranges.extend(read_range(&range.events));
+            }
+        }
+        if ranges.is_empty() {
+            continue;
+        }
+        advisories.push(Advisory {
+            id: vuln.id,
+            summary: vuln.summary,
+            informational,
+            ranges,
+        });
+    }
+    advisories
+}
+
+/// Pair each `introduced` event with the `fixed` or `last_affected` event following it
+fn read_range(events: &[OsvEvent]) -> Vec<AffectedRange> {
+    let parse = |version: &Option<String>| version.as_deref().and_then(|v| v.parse().ok());
+    let mut ranges: Vec<AffectedRange> = Vec::new();
+    let mut open = false;
+    for event in events {
+        if let Some(introduced) = &event.introduced {
+            // OSV writes `0` for "every version"
+            let introduced = introduced.parse().unwrap_or_else(|_| Version::new(0, 0, 0));
+            ranges.push(AffectedRange {
+                introduced,
+                fixed: None,
+                last_affected: None,
+            });
+            open = true;
+        } else if let (true, Some(range)) = (open, ranges.last_mut()) {
+            range.fixed = parse(&event.fixed);
+            range.last_affected = parse(&event.last_affected);
+            open = false;
+        }
+    }
+    ranges
+}
+
+fn is_informational(database_specific: &Option<serde_json::Value>) -> bool {
+    database_specific
+        .as_ref()
+        .and_then(|d| d.get("informational"))
+        .map_or(false, |i| !i.is_null())
+}
+
+#[derive(Debug, Deserialize)]
+struct OsvResponse {
+    #[serde(default)]
+    vulns: Vec<OsvVuln>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OsvVuln {
+    id: String,
+    summary: Option<String>,
+    withdrawn: Option<String>,
+    #[serde(default)]
+    affected: Vec<OsvAffected>,
+    database_specific: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OsvAffected {
+    package: OsvPackage,
+    #[serde(default)]
+    ranges: Vec<OsvRange>,
+    database_specific: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OsvPackage {
+    name: String,
+    ecosystem: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OsvRange {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    events: Vec<OsvEvent>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OsvEvent {
+    introduced: Option<String>,
+    fixed: Option<String>,
+    last_affected: Option<String>,
+}
+
+/// The oldest version after `current` that none of `advisories` affect, for security fixes that
+/// change as little as possible
+///
+/// Yanked versions are skipped, and so are pre-releases unless `current` is one.
+#[derive(Copy, Clone, Debug)]
+pub struct PatchedVersion<'a> {
+    current: &'a Version,
+    advisories: &'a [Advisory],
+}
+
+impl<'a> PatchedVersion<'a> {
+    /// Select the oldest version after `current` without any of `advisories`
+    pub fn new(current: &'a Version, advisories: &'a [Advisory]) -> Self {
+        Self {
+            current,
+            advisories,
+        }
+    }
+}
+
+impl VersionSelector for PatchedVersion<'_> {
+    fn select<'c>(
+        &self,
+        candidates: &'c [CrateVersion],
+        _current: Option<&VersionReq>,
+    ) -> Option<&'c CrateVersion> {
+        candidates
+            .iter()
+            .filter(|v| {
+                !v.yanked
+                    && v.version > *self.current
+                    && (v.version.pre.is_empty() || !self.current.pre.is_empty())
+                    && !self.advisories.iter().any(|a| a.affects(&v.version))
+            })
+            .min_by(|a, b| a.version.cmp(&b.version))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const RESPONSE: &str = r#"{"vulns": [
+        {
+            "id": "RUSTSEC-2020-0001",
+            "summary": "Stack overflow when parsing deeply nested input",
+            "affected": [{
+                "package": {"name": "demo", "ecosystem": "crates.io"},
+                "ranges": [{"type": "SEMVER", "events": [
+                    {"introduced": "0.0.0-0"}, {"fixed": "1.2.4"},
+                    {"introduced": "1.3.0"}, {"fixed": "1.3.2"}
+                ]}]
+            }]
+        },
+        {
+            "id": "GHSA-xxxx-yyyy-zzzz",
+            "affected": [{
+                "package": {"name": "demo", "ecosystem": "crates.io"},
+                "ranges": [{"type": "SEMVER", "events": [{"introduced": "0"}]}]
+            }]
+        },
+        {
+            "id": "RUSTSEC-2021-0002",
+            "withdrawn": "2021-06-01T00:00:00Z",
+            "affected": [{
+                "package": {"name": "demo", "ecosystem": "crates.io"},
+                "ranges": [{"type": "SEMVER", "events": [{"introduced": "0"}]}]
+            }]
+        },
+        {
+            "id": "RUSTSEC-2022-0003",
+            "affected": [{
+                "package": {"name": "demo", "ecosystem": "crates.io"},
+                "ranges": [{"type": "SEMVER", "events": [{"introduced": "0"}]}],
+                "database_specific": {"informational": "unmaintained"}
+            }]
+        }
+    ]}"#;
+
+    fn candidate(version: &str, yanked: bool) -> CrateVersion {
+        CrateVersion {
+            name: "demo".to_owned(),
+            version: version.parse().unwrap(),
+            yanked,
+            available_features: Default::default(),
+            rust_version: None,
+            edition: None,
+            checksum: None,
+        }
+    }
+
+    #[test]
+    fn patched_versions() {
+        let response: OsvResponse = serde_json::from_str(RESPONSE).unwrap();
+        let advisories = read_advisories("demo", response);
+        let ids: Vec<_> = advisories.iter().map(|a| a.id.as_str()).collect();
+        assert_eq!(ids, ["RUSTSEC-2020-0001", "RUSTSEC-2022-0003"]);
+        assert!(!advisories[0].informational);
+        assert!(advisories[1].informational);
+
+        let advisory = &advisories[..1];
+        let version = |v: &str| v.parse::<Version>().unwrap();
+        assert!(advisory[0].affects(&version("1.2.3")));
+        assert!(!advisory[0].affects(&version("1.2.4")));
+        assert!(advisory[0].affects(&version("1.3.1")));
+
+        let candidates = [
+            candidate("1.2.3", false),
+            candidate("1.2.4", true),
+            candidate("1.2.5-rc.1", false),
+            candidate("1.2.5", false),
+            candidate("1.3.0", false),
+            candidate("1.3.2", false),
+        ];
+        let current = version("1.2.3");
+        let selected = PatchedVersion::new(&current, advisory).select(&candidates, None);
+        assert_eq!(
+            selected.map(|v| v.version.to_string()).as_deref(),
+            Some("1.2.5")
+        );
+        let current = version("1.3.0");
+        let selected = PatchedVersion::new(&current, advisory).select(&candidates, None);
+        assert_eq!(
+            selected.map(|v| v.version.to_string()).as_deref(),
+            Some("1.3.2")
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//! Copies of manifests taken before they are written, for `cargo edit restore`
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::errors::*;
+use super::manifest::WriteOptions;
+
+/// Backup directory used when none is named, relative to the current directory
+pub const DEFAULT_BACKUP_DIR: &str = ".cargo-edit-backups";
+
+/// Environment variable naming a backup directory, for commands without `--backup-dir`
+pub const BACKUP_DIR_ENV: &str = "CARGO_EDIT_BACKUP_DIR";
+
+/// Backups kept per manifest; taking another removes the oldest
+const BACKUPS_KEPT: usize = 10;
+
+/// Backup directory set in [`BACKUP_DIR_ENV`], if any, for [`WriteOptions::set_backup_dir`]
+pub fn backup_dir() -> Option<PathBuf> {
+    std::env::var_os(BACKUP_DIR_ENV)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+}
+
+/// A copy of a manifest, taken before it was written
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ManifestBackup {
+    /// Manifest the copy is of
+    pub manifest_path: PathBuf,
+    /// Where the copy is
+    pub backup_path: PathBuf,
+    /// When the copy was taken
+    pub taken_at: SystemTime,
+}
+
+impl ManifestBackup {
+    /// Write the copy back over the manifest, and remove it
+    ///
+    /// The manifest isn't backed up first, so restoring again brings back the backup before.
+    pub fn restore(&self) -> CargoResult<()> {
+        let contents = fs::read(&self.backup_path)
+            .with_context(|| format!("Failed to read `{}`", self.backup_path.display()))?;
+        crate::manifest::replace_file(&self.manifest_path, &contents, &WriteOptions::new())?;
+        fs::remove_file(&self.backup_path)
+            .with_context(|| format!("Failed to remove `{}`", self.backup_path.display()))
+    }
+}
+
+/// Copy the file at `path` to the backup directory `dir`
+pub(crate) fn backup_manifest(dir: &Path, path: &Path) -> CargoResult<()> {
+    backup_to(dir, path, SystemTime::now()).map(|_| ())
+}
+
+fn backup_to(dir: &Path, path: &Path, now: SystemTime) -> CargoResult<PathBuf> {
+    let path = dunce::canonicalize(path)
+        .with_context(|| format!("Failed to read `{}`", path.display()))?;
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create backup directory `{}`", dir.display()))?;
+    let key = encode_path(&path);
+    // Writes within the same millisecond each get their own backup
+    let mut millis = now
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let backup_path = loop {
+        let backup_path = dir.join(format!("{}@{}", key, millis));
+        if !backup_path.exists() {
+            break backup_path;
+        }
+        millis += 1;
+    };
+    fs::copy(&path, &backup_path).with_context(|| {
+        format!(
+            "Failed to back up `{}` to `{}`",
+            path.display(),
+            backup_path.display()
+        )
+    })?;
+
+    let mut backups: Vec<_> = list_backups(dir)?
+        .into_iter()
+        .filter(|backup| backup.manifest_path == path)
+        .collect();
+    if backups.len() > BACKUPS_KEPT {
+        backups.sort_by_key(|backup| backup.taken_at);
+        for old in &backups[..backups.len() - BACKUPS_KEPT] {
+            let _ = fs::remove_file(&old.backup_path);
+        }
+    }
+    Ok(backup_path)
+}
+
+/// Backups in `dir`, newest first
+///
+/// A missing directory has no backups; files in it that aren't backups are ignored.
+pub fn list_backups(dir: &Path) -> CargoResult<Vec<ManifestBackup>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to read `{}`", dir.display()));
+        }
+    };
+    let mut backups = Vec::new();
+    for entry in entries {
+        let entry = entry.with_context(|| format!("Failed to read `{}`", dir.display()))?;
+        let file_name = entry.file_name();
+        let parsed = file_name.to_str().and_then(|name| {
+            let (key, millis) = name.rsplit_once('@')?;
+            Some((decode_path(key)?, millis.parse::<u64>().ok()?))
+        });
+        if let Some((manifest_path, millis)) = parsed {
+            backups.push(ManifestBackup {
+                manifest_path,
+                backup_path: entry.path(),
+                taken_at: UNIX_EPOCH + Duration::from_millis(millis),
+            });
+        }
+    }
+    backups.sort_by(|a, b| b.taken_at.cmp(&a.taken_at));
+    Ok(backups)
+}
+
+/// The newest backup of each manifest in `dir`
+pub fn latest_backups(dir: &Path) -> CargoResult<Vec<ManifestBackup>> {
+    let mut latest: Vec<ManifestBackup> = Vec::new();
+    for backup in list_backups(dir)? {
+        if !latest
+            .iter()
+            .any(|other| other.manifest_path == backup.manifest_path)
+        {
+            latest.push(backup);
+        }
+    }
+    Ok(latest)
+}
+
+/// A file name standing for `path`, which [`decode_path`] turns back into it
+fn encode_path(path: &Path) -> String {
+    let mut key = String::new();
+    for c in path.to_string_lossy().chars() {
+        match c {
+            '%' => key.push_str("%25"),
+            '/' => key.push_str("%2F"),
+            '\\' => key.push_str("%5C"),
+            ':' => key.push_str("%3A"),
+            '@' => key.push_str("%40"),
+            c => key.push(c),
+        }
+    }
+    key
+}
+
+fn decode_path(key: &str) -> Option<PathBuf> {
+    let mut path = String::new();
+    let mut rest = key;
+    while let Some(i) = rest.find('%') {
+        path.push_str(&rest[..i]);
+        let c = match rest.get(i + 1..i + 3)? {
+            "25" => '%',
+            "2F" => '/',
+            "5C" => '\\',
+            "3A" => ':',
+            "40" => '@',
+            _ => return None,
+        };
+        path.push(c);
+        rest = &rest[i + 3..];
+    }
+    path.push_str(rest);
+    Some(PathBuf::from(path))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn backups_are_restored_newest_first() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let dir = temp.path().join("backups");
+        let manifest = temp.path().join("Cargo.toml");
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        for i in 0..BACKUPS_KEPT + 2 {
+            fs::write(&manifest, format!("# {}\n", i)).unwrap();
+            backup_to(&dir, &manifest, start + Duration::from_secs(i as u64)).unwrap();
+        }
+        fs::write(&manifest, "# current\n").unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+
+        let backups = list_backups(&dir).unwrap();
+        assert_eq!(backups.len(), BACKUPS_KEPT);
+        let latest = latest_backups(&dir).unwrap();
+        assert_eq!(latest.len(), 1);
+        assert_eq!(
+            latest[0].manifest_path,
+            dunce::canonicalize(&manifest).unwrap()
+        );
+        latest[0].restore().unwrap();
+        assert_eq!(
+            fs::read_to_string(&manifest).unwrap(),
+            format!("# {}\n", BACKUPS_KEPT + 1)
+        );
+        latest_backups(&dir).unwrap()[0].restore().unwrap();
+        assert_eq!(
+            fs::read_to_string(&manifest).unwrap(),
+            format!("# {}\n", BACKUPS_KEPT)
+        );
+    }
+
+    #[test]
+    fn paths_round_trip() {
+        for path in ["/home/me/Cargo.toml", r"C:\a%b\c@d\Cargo.toml"] {
+            let key = encode_path(Path::new(path));
+            assert!(!key.contains(['/', '\\', ':', '@']));
+            assert_eq!(decode_path(&key), Some(PathBuf::from(path)));
+        }
+        assert_eq!(decode_path("%zz"), None);
+    }
+}
@@ -0,0 +1,35 @@
+use cargo_edit::CargoResult;
+use clap::{Parser, Subcommand};
+
+#[derive(Debug, Parser)]
+#[clap(bin_name = "cargo")]
+pub enum Command {
+    /// Inspect the dependencies of a workspace
+    #[clap(subcommand)]
+    Deps(DepsCommand),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DepsCommand {
+    Features(crate::features::FeaturesArgs),
+    Normalize(crate::normalize::NormalizeArgs),
+    Verify(crate::verify::VerifyArgs),
+    Why(crate::why::WhyArgs),
+}
+
+impl Command {
+    pub fn exec(self) -> CargoResult<()> {
+        match self {
+            Self::Deps(DepsCommand::Features(features)) => features.exec(),
+            Self::Deps(DepsCommand::Normalize(normalize)) => normalize.exec(),
+            Self::Deps(DepsCommand::Verify(verify)) => verify.exec(),
+            Self::Deps(DepsCommand::Why(why)) => why.exec(),
+        }
+    }
+}
+
+#[test]
+fn verify_app() {
+    use clap::CommandFactory;
+    Command::command().debug_assert()
+}
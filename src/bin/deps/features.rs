@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use cargo_edit::{
+    feature_requests, find, get_compatible_dependency, registry_url, set_cargo_flags,
+    set_output_config, shell_warn, shell_write_stderr, unify_features, update_registry_index,
+    workspace_members, workspace_root_manifest, CargoFlags, CargoResult, ColorWhen, FeatureRequest,
+    LocalManifest, LookupOptions, OutputConfig,
+};
+use clap::Args;
+use termcolor::ColorSpec;
+
+/// Show the features each workspace member enables on a dependency
+#[derive(Debug, Args)]
+#[clap(version)]
+#[clap(after_help = "\
+Features are listed as written in the manifests, see `cargo tree -e features` for what a build \
+resolves to.")]
+pub struct FeaturesArgs {
+    /// Dependency to report on, by package name
+    #[clap(value_name = "CRATE")]
+    crate_name: String,
+
+    /// Path to the workspace's manifest
+    #[clap(long, value_name = "PATH", action)]
+    manifest_path: Option<PathBuf>,
+
+    /// Include the features that enabled features imply, from the registry
+    #[clap(long)]
+    implied: bool,
+
+    /// Coloring
+    #[clap(long, value_name = "WHEN", possible_values = ["auto", "always", "never"])]
+    color: Option<ColorWhen>,
+
+    /// Run without accessing the network
+    #[clap(long)]
+    offline: bool,
+
+    /// Require `Cargo.lock` to be up to date
+    #[clap(long)]
+    locked: bool,
+
+    /// Require `Cargo.lock` and the registry cache to be up to date
+    #[clap(long)]
+    frozen: bool,
+
+    /// Override a configuration value
+    #[clap(long, value_name = "KEY=VALUE")]
+    config: Vec<String>,
+
+    /// Unstable (nightly-only) flags
+    #[clap(short = 'Z', value_name = "FLAG", global = true, arg_enum)]
+    unstable_features: Vec<UnstableOptions>,
+}
+
+impl FeaturesArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum UnstableOptions {}
+
+/// Main processing function. Allows us to return a `Result` so that `main` can print pretty error
+/// messages.
+fn exec(args: FeaturesArgs) -> CargoResult<()> {
+    let mut output = OutputConfig::from_env()?;
+    if let Some(color) = args.color {
+        output = output.set_color(color);
+    }
+    set_output_config(output);
+
+    let cargo_flags = args
+        .config
+        .iter()
+        .fold(CargoFlags::new(), |flags, config| flags.add_config(config))
+        .set_offline(args.offline)
+        .set_locked(args.locked)
+        .set_frozen(args.frozen);
+    set_cargo_flags(cargo_flags.clone())?;
+
+    let manifest_path = args.manifest_path.as_deref();
+    let members = workspace_members(manifest_path)?
+        .iter()
+        .map(|package| LocalManifest::try_new(package.manifest_path.as_std_path()))
+        .collect::<CargoResult<Vec<_>>>()?;
+    let workspace_root = LocalManifest::try_new(&workspace_root_manifest(manifest_path)?)?;
+
+    let requests = feature_requests(&args.crate_name, &members, Some(&workspace_root));
+    if requests.is_empty() {
+        anyhow::bail!("no workspace member depends on `{}`", args.crate_name);
+    }
+
+    let available = if args.implied {
+        let manifest_path = find(manifest_path)?;
+        let offline = cargo_flags.offline(&manifest_path)?;
+        available_features(&args.crate_name, &requests, &manifest_path, offline)?
+    } else {
+        BTreeMap::new()
+    };
+
+    print_line(&args.crate_name)?;
+    for request in &requests {
+        let mut features = Vec::new();
+        if request.default_features {
+            features.push("default");
+        }
+        features.extend(request.features.iter().map(|f| f.as_str()));
+        print_line(format_args!(
+            "  {} [{}] {}: {}",
+            request.package,
+            request.table.join("."),
+            request.version_req.as_deref().unwrap_or("*"),
+            list(&features)
+        ))?;
+        for (feature, dep_feature) in &request.conditional {
+            print_line(format_args!("    with `{}`: {}", feature, dep_feature))?;
+        }
+    }
+    let unified = unify_features(&requests, &available);
+    print_line(format_args!(
+        "unified: {}",
+        list(&unified.iter().map(|f| f.as_str()).collect::<Vec<_>>())
+    ))?;
+    Ok(())
+}
+
+/// The feature table of the newest version matching the first registry requirement
+fn available_features(
+    crate_name: &str,
+    requests: &[FeatureRequest],
+    manifest_path: &Path,
+    offline: bool,
+) -> CargoResult<BTreeMap<String, Vec<String>>> {
+    let version_req = match requests.iter().find_map(|r| r.version_req.as_deref()) {
+        Some(version_req) => version_req,
+        None => {
+            shell_warn(&format!(
+                "`{}` isn't from a registry, so implied features are left out",
+                crate_name
+            ))?;
+            return Ok(BTreeMap::new());
+        }
+    };
+    let registry = registry_url(manifest_path, None)?;
+    if !offline {
+        update_registry_index(&registry, &LookupOptions::new(), manifest_path, false)?;
+    }
+    let dependency = get_compatible_dependency(
+        crate_name,
+        &version_req.parse::<semver::VersionReq>()?,
+        manifest_path,
+        Some(&registry),
+    )?;
+    Ok(dependency.available_features)
+}
+
+fn list(features: &[&str]) -> String {
+    if features.is_empty() {
+        "(none)".to_owned()
+    } else {
+        features.join(", ")
+    }
+}
+
+/// Print a line of the report through the output sink
+fn print_line(line: impl std::fmt::Display) -> CargoResult<()> {
+    shell_write_stderr(format!("{}\n", line), &ColorSpec::new())
+}
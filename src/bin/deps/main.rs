@@ -0,0 +1,32 @@
+//! `cargo deps`
+#![warn(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+mod cli;
+mod features;
+mod normalize;
+mod verify;
+mod why;
+
+use std::process;
+
+use clap::Parser;
+
+fn main() {
+    let args = cli::Command::parse();
+
+    if let Err(err) = args.exec() {
+        eprintln!("Error: {:?}", err);
+
+        process::exit(1);
+    }
+}
@@ -0,0 +1,157 @@
+use std::path::PathBuf;
+
+use cargo_edit::{
+    backup_dir, normalize_dependencies, set_cargo_flags, set_hook_command, set_output_config,
+    shell_status, shell_write_stderr, unified_diff, workspace_members, workspace_root_manifest,
+    CargoFlags, CargoResult, ColorWhen, LocalManifest, OutputConfig, RequirementStyle,
+    WriteOptions,
+};
+use clap::Args;
+use termcolor::ColorSpec;
+
+/// Rewrite the dependency tables of a workspace in one canonical style
+#[derive(Debug, Args)]
+#[clap(version)]
+#[clap(after_help = "\
+Caret requirements are written without `^`, features are sorted, and entries only setting a \
+version are written as a string.")]
+pub struct NormalizeArgs {
+    /// Path to the workspace's manifest
+    #[clap(long, value_name = "PATH", action)]
+    manifest_path: Option<PathBuf>,
+
+    /// How to write caret requirements, like `1.2` or `1.2.0`
+    #[clap(long, value_name = "STYLE", arg_enum, default_value = "short")]
+    style: Style,
+
+    /// Print the changes and fail if there are any, without writing the manifests
+    #[clap(long)]
+    check: bool,
+
+    /// Fail on symlinked manifests instead of writing to their target
+    #[clap(long)]
+    no_follow_symlinks: bool,
+
+    /// Edit vendored crates, which invalidates their checksums
+    #[clap(long)]
+    force_vendored: bool,
+
+    /// Do not print any output in case of success
+    #[clap(long, short)]
+    quiet: bool,
+
+    /// Coloring
+    #[clap(long, value_name = "WHEN", possible_values = ["auto", "always", "never"])]
+    color: Option<ColorWhen>,
+
+    /// Run without accessing the network
+    #[clap(long)]
+    offline: bool,
+
+    /// Require `Cargo.lock` to be up to date
+    #[clap(long)]
+    locked: bool,
+
+    /// Require `Cargo.lock` and the registry cache to be up to date
+    #[clap(long)]
+    frozen: bool,
+
+    /// Override a configuration value
+    #[clap(long, value_name = "KEY=VALUE")]
+    config: Vec<String>,
+
+    /// Unstable (nightly-only) flags
+    #[clap(short = 'Z', value_name = "FLAG", global = true, arg_enum)]
+    unstable_features: Vec<UnstableOptions>,
+}
+
+impl NormalizeArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum Style {
+    Short,
+    Full,
+}
+
+impl From<Style> for RequirementStyle {
+    fn from(style: Style) -> Self {
+        match style {
+            Style::Short => Self::Short,
+            Style::Full => Self::Full,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum UnstableOptions {}
+
+/// Main processing function. Allows us to return a `Result` so that `main` can print pretty error
+/// messages.
+fn exec(args: NormalizeArgs) -> CargoResult<()> {
+    let mut output = OutputConfig::from_env()?;
+    if let Some(color) = args.color {
+        output = output.set_color(color);
+    }
+    set_output_config(output);
+
+    let cargo_flags = args
+        .config
+        .iter()
+        .fold(CargoFlags::new(), |flags, config| flags.add_config(config))
+        .set_offline(args.offline)
+        .set_locked(args.locked)
+        .set_frozen(args.frozen);
+    set_cargo_flags(cargo_flags)?;
+    let write_options = WriteOptions::new()
+        .set_allow_vendored(args.force_vendored)
+        .set_follow_symlinks(!args.no_follow_symlinks)
+        .set_backup_dir(backup_dir());
+    set_hook_command(Some("deps"));
+
+    let manifest_path = args.manifest_path.as_deref();
+    let root_path = workspace_root_manifest(manifest_path)?;
+    let root_dir = root_path.parent().expect("manifests are files").to_owned();
+    let mut paths = vec![root_path];
+    for member in workspace_members(manifest_path)? {
+        let path = member.manifest_path.into_std_path_buf();
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+
+    let mut unnormalized = false;
+    for path in paths {
+        let mut manifest = LocalManifest::try_new(&path)?;
+        let original = manifest.to_string();
+        let changed = normalize_dependencies(&mut manifest, args.style.into());
+        if changed.is_empty() {
+            continue;
+        }
+        unnormalized = true;
+
+        let label = path.strip_prefix(&root_dir).unwrap_or(&path).display();
+        if args.check {
+            shell_write_stderr(
+                unified_diff(&original, &manifest.to_string(), &label.to_string(), 3),
+                &ColorSpec::new(),
+            )?;
+        } else {
+            if !args.quiet {
+                shell_status(
+                    "Normalizing",
+                    &format!("{} in {}", changed.join(", "), label),
+                )?;
+            }
+            manifest.write_with(&write_options)?;
+        }
+    }
+
+    if args.check && unnormalized {
+        anyhow::bail!("Dependencies aren't normalized, run `cargo deps normalize` to fix them");
+    }
+    Ok(())
+}
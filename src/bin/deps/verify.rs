@@ -0,0 +1,138 @@
+use std::path::PathBuf;
+
+use cargo_edit::{
+    find, get_crate_version, registry_url, set_cargo_flags, set_output_config, shell_status,
+    shell_warn, update_registry_indexes, workspace_members, CargoFlags, CargoResult, ColorWhen,
+    LocalManifest, LookupOptions, ManifestQuery, OutputConfig,
+};
+use clap::Args;
+
+/// Check recorded checksums against the registry
+#[derive(Debug, Args)]
+#[clap(version)]
+#[clap(after_help = "\
+Checksums are recorded in `[package.metadata.cargo-edit.checksums]`, like \
+`serde = { version = \"1.0.147\", checksum = \"d193d69b...\" }`. A registry listing another \
+checksum for a recorded version has changed what that version is.")]
+pub struct VerifyArgs {
+    /// Path to the workspace's manifest
+    #[clap(long, value_name = "PATH", action)]
+    manifest_path: Option<PathBuf>,
+
+    /// Coloring
+    #[clap(long, value_name = "WHEN", possible_values = ["auto", "always", "never"])]
+    color: Option<ColorWhen>,
+
+    /// Run without accessing the network
+    #[clap(long)]
+    offline: bool,
+
+    /// Require `Cargo.lock` to be up to date
+    #[clap(long)]
+    locked: bool,
+
+    /// Require `Cargo.lock` and the registry cache to be up to date
+    #[clap(long)]
+    frozen: bool,
+
+    /// Override a configuration value
+    #[clap(long, value_name = "KEY=VALUE")]
+    config: Vec<String>,
+
+    /// Unstable (nightly-only) flags
+    #[clap(short = 'Z', value_name = "FLAG", global = true, arg_enum)]
+    unstable_features: Vec<UnstableOptions>,
+}
+
+impl VerifyArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum UnstableOptions {}
+
+/// Main processing function. Allows us to return a `Result` so that `main` can print pretty error
+/// messages.
+fn exec(args: VerifyArgs) -> CargoResult<()> {
+    let mut output = OutputConfig::from_env()?;
+    if let Some(color) = args.color {
+        output = output.set_color(color);
+    }
+    set_output_config(output);
+
+    let cargo_flags = args
+        .config
+        .iter()
+        .fold(CargoFlags::new(), |flags, config| flags.add_config(config))
+        .set_offline(args.offline)
+        .set_locked(args.locked)
+        .set_frozen(args.frozen);
+    set_cargo_flags(cargo_flags.clone())?;
+
+    let manifest_path = args.manifest_path.as_deref();
+    let manifests = workspace_members(manifest_path)?
+        .iter()
+        .map(|package| LocalManifest::try_new(package.manifest_path.as_std_path()))
+        .collect::<CargoResult<Vec<_>>>()?;
+    let mut recorded = Vec::new();
+    for (i, manifest) in manifests.iter().enumerate() {
+        for checksum in manifest.recorded_checksums()? {
+            recorded.push((i, manifest.path.clone(), checksum));
+        }
+    }
+    let query = ManifestQuery::new(manifests);
+    let members: Vec<_> = query.members().collect();
+
+    // Checksums are looked up in the registry the member declares the crate from
+    let mut lookups = Vec::new();
+    for (i, path, checksum) in recorded {
+        let member = members[i];
+        let registry = query
+            .declared_by(member, &checksum.name)?
+            .iter()
+            .find_map(|declared| declared.dependency.registry().map(str::to_owned));
+        let url = registry_url(&path, registry.as_deref())?;
+        lookups.push((member, path, checksum, url));
+    }
+
+    let root_path = find(manifest_path)?;
+    if !cargo_flags.offline(&root_path)? {
+        let registries: Vec<_> = lookups.iter().map(|(_, _, _, url)| url.clone()).collect();
+        update_registry_indexes(&registries, &LookupOptions::new(), &root_path, false)?;
+    }
+
+    let mut changed = 0;
+    for (member, path, checksum, url) in &lookups {
+        let version = checksum.version.parse()?;
+        let listed = get_crate_version(&checksum.name, &version, path, Some(url))?;
+        let problem = match listed.map(|listed| listed.checksum) {
+            None => Some("the registry no longer lists it".to_owned()),
+            Some(None) => {
+                shell_warn(&format!(
+                    "the registry of {} doesn't list checksums, {} {} can't be verified",
+                    member, checksum.name, version
+                ))?;
+                None
+            }
+            Some(Some(listed)) if !listed.eq_ignore_ascii_case(&checksum.checksum) => {
+                Some(format!("the registry lists {}", listed))
+            }
+            Some(Some(_)) => None,
+        };
+        if let Some(problem) = problem {
+            changed += 1;
+            shell_warn(&format!(
+                "{} recorded {} {} with checksum {}, but {}",
+                member, checksum.name, version, checksum.checksum, problem
+            ))?;
+        }
+    }
+
+    match changed {
+        0 => shell_status("Verified", &format!("{} checksums", lookups.len())),
+        1 => anyhow::bail!("1 recorded checksum doesn't match the registry"),
+        _ => anyhow::bail!("{} recorded checksums don't match the registry", changed),
+    }
+}
@@ -0,0 +1,254 @@
+use std::path::{Path, PathBuf};
+
+use cargo_edit::{
+    feature_requests, find, get_latest_dependency, read_lockfile, registry_url, set_cargo_flags,
+    set_output_config, shell_warn, shell_write_stderr, update_registry_index, workspace_members,
+    workspace_root_manifest, CargoFlags, CargoResult, ColorWhen, LocalManifest, LockedPackage,
+    LookupOptions, ManifestQuery, OutputConfig,
+};
+use clap::Args;
+use termcolor::ColorSpec;
+
+/// Explain which workspace members require a dependency, and what holds it back
+#[derive(Debug, Args)]
+#[clap(version)]
+#[clap(after_help = "\
+Only the requirements of workspace members are checked against the latest version. Other packages \
+of the lockfile depending on the crate are listed, see `cargo tree -i` for their requirements.")]
+pub struct WhyArgs {
+    /// Dependency to explain, by package name
+    #[clap(value_name = "CRATE")]
+    crate_name: String,
+
+    /// Path to the workspace's manifest
+    #[clap(long, value_name = "PATH", action)]
+    manifest_path: Option<PathBuf>,
+
+    /// Coloring
+    #[clap(long, value_name = "WHEN", possible_values = ["auto", "always", "never"])]
+    color: Option<ColorWhen>,
+
+    /// Run without accessing the network
+    #[clap(long)]
+    offline: bool,
+
+    /// Require `Cargo.lock` to be up to date
+    #[clap(long)]
+    locked: bool,
+
+    /// Require `Cargo.lock` and the registry cache to be up to date
+    #[clap(long)]
+    frozen: bool,
+
+    /// Override a configuration value
+    #[clap(long, value_name = "KEY=VALUE")]
+    config: Vec<String>,
+
+    /// Unstable (nightly-only) flags
+    #[clap(short = 'Z', value_name = "FLAG", global = true, arg_enum)]
+    unstable_features: Vec<UnstableOptions>,
+}
+
+impl WhyArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum UnstableOptions {}
+
+/// Main processing function. Allows us to return a `Result` so that `main` can print pretty error
+/// messages.
+fn exec(args: WhyArgs) -> CargoResult<()> {
+    let mut output = OutputConfig::from_env()?;
+    if let Some(color) = args.color {
+        output = output.set_color(color);
+    }
+    set_output_config(output);
+
+    let cargo_flags = args
+        .config
+        .iter()
+        .fold(CargoFlags::new(), |flags, config| flags.add_config(config))
+        .set_offline(args.offline)
+        .set_locked(args.locked)
+        .set_frozen(args.frozen);
+    set_cargo_flags(cargo_flags.clone())?;
+
+    let manifest_path = args.manifest_path.as_deref();
+    let members = workspace_members(manifest_path)?
+        .iter()
+        .map(|package| LocalManifest::try_new(package.manifest_path.as_std_path()))
+        .collect::<CargoResult<Vec<_>>>()?;
+    let root_manifest = workspace_root_manifest(manifest_path)?;
+    let workspace_root = LocalManifest::try_new(&root_manifest)?;
+
+    let requests = feature_requests(&args.crate_name, &members, Some(&workspace_root));
+    if requests.is_empty() {
+        anyhow::bail!("no workspace member depends on `{}`", args.crate_name);
+    }
+
+    let lockfile = root_manifest.with_file_name("Cargo.lock");
+    let locked = if lockfile.exists() {
+        read_lockfile(&lockfile)?
+    } else {
+        shell_warn("there is no `Cargo.lock`, so locked versions are left out")?;
+        Vec::new()
+    };
+
+    let latest = if requests.iter().any(|r| r.version_req.is_some()) {
+        let query = ManifestQuery::new(members);
+        let registry = query
+            .declarations_of(&args.crate_name)?
+            .iter()
+            .find_map(|declared| declared.dependency.registry().map(str::to_owned));
+        let manifest_path = find(manifest_path)?;
+        let offline = cargo_flags.offline(&manifest_path)?;
+        latest_version(
+            &args.crate_name,
+            registry.as_deref(),
+            &manifest_path,
+            offline,
+        )?
+    } else {
+        None
+    };
+
+    let member_names: Vec<_> = requests.iter().map(|r| r.package.as_str()).collect();
+    let versions: Vec<_> = locked
+        .iter()
+        .filter(|p| p.name == args.crate_name)
+        .map(|p| &p.version)
+        .collect();
+
+    print_line(&args.crate_name)?;
+    let mut blocking = 0;
+    for request in &requests {
+        let mut features = Vec::new();
+        if request.default_features {
+            features.push("default");
+        }
+        features.extend(request.features.iter().map(|f| f.as_str()));
+        let locked_versions = member_versions(&locked, &request.package, &args.crate_name)
+            .map(|member_versions| {
+                if member_versions.is_empty() {
+                    versions.clone()
+                } else {
+                    member_versions
+                }
+            })
+            .unwrap_or_default();
+        let locked_note = if locked_versions.is_empty() {
+            String::new()
+        } else {
+            format!(", locked {}", join(&locked_versions))
+        };
+        print_line(format_args!(
+            "  {} [{}] {}{}: {}",
+            request.package,
+            request.table.join("."),
+            request.version_req.as_deref().unwrap_or("*"),
+            locked_note,
+            if features.is_empty() {
+                "(none)".to_owned()
+            } else {
+                features.join(", ")
+            }
+        ))?;
+        let blocks = match (&latest, &request.version_req) {
+            (Some(latest), Some(version_req)) => semver::VersionReq::parse(version_req)
+                .map_or(false, |version_req| !version_req.matches(latest)),
+            _ => false,
+        };
+        if blocks {
+            blocking += 1;
+            print_line(format_args!(
+                "    blocks {}",
+                latest.as_ref().expect("checked above")
+            ))?;
+        }
+    }
+
+    let others: Vec<_> = locked
+        .iter()
+        .filter(|p| !(p.source.is_none() && member_names.contains(&p.name.as_str())))
+        .filter(|p| p.dependency_versions(&args.crate_name).is_some())
+        .collect();
+    if !others.is_empty() {
+        print_line("also required by:")?;
+        for package in others {
+            print_line(format_args!("  {} {}", package.name, package.version))?;
+        }
+    }
+
+    if let Some(latest) = &latest {
+        match blocking {
+            0 => print_line(format_args!("latest: {}", latest))?,
+            1 => print_line(format_args!("latest: {}, blocked by 1 requirement", latest))?,
+            _ => print_line(format_args!(
+                "latest: {}, blocked by {} requirements",
+                latest, blocking
+            ))?,
+        }
+    }
+    Ok(())
+}
+
+/// The versions of `crate_name` the lockfile resolves `member` to
+///
+/// Returns an empty list when the lockfile only records one version of the crate.
+fn member_versions<'l>(
+    locked: &'l [LockedPackage],
+    member: &str,
+    crate_name: &str,
+) -> Option<Vec<&'l semver::Version>> {
+    locked
+        .iter()
+        .find(|p| p.source.is_none() && p.name == member)
+        .and_then(|p| p.dependency_versions(crate_name))
+}
+
+/// The newest stable version of `crate_name`, or `None` with a warning when it can't be looked up
+fn latest_version(
+    crate_name: &str,
+    registry: Option<&str>,
+    manifest_path: &Path,
+    offline: bool,
+) -> CargoResult<Option<semver::Version>> {
+    let registry = registry_url(manifest_path, registry)?;
+    if !offline {
+        update_registry_index(&registry, &LookupOptions::new(), manifest_path, false)?;
+    }
+    let lookup = get_latest_dependency(
+        crate_name,
+        &LookupOptions::new(),
+        manifest_path,
+        Some(&registry),
+    );
+    let version = lookup.ok().and_then(|(dependency, _)| {
+        dependency
+            .version()
+            .and_then(|version| version.parse::<semver::Version>().ok())
+    });
+    if version.is_none() {
+        shell_warn(&format!(
+            "couldn't find the latest version of `{}`, so blocking requirements are left out",
+            crate_name
+        ))?;
+    }
+    Ok(version)
+}
+
+fn join(versions: &[&semver::Version]) -> String {
+    versions
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Print a line of the explanation through the output sink
+fn print_line(line: impl std::fmt::Display) -> CargoResult<()> {
+    shell_write_stderr(format!("{}\n", line), &ColorSpec::new())
+}
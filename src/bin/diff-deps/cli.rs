@@ -0,0 +1,22 @@
+use cargo_edit::CargoResult;
+use clap::Parser;
+
+#[derive(Debug, Parser)]
+#[clap(bin_name = "cargo")]
+pub enum Command {
+    DiffDeps(crate::diff_deps::DiffDepsArgs),
+}
+
+impl Command {
+    pub fn exec(self) -> CargoResult<()> {
+        match self {
+            Self::DiffDeps(add) => add.exec(),
+        }
+    }
+}
+
+#[test]
+fn verify_app() {
+    use clap::CommandFactory;
+    Command::command().debug_assert()
+}
@@ -0,0 +1,262 @@
+use std::path::{Path, PathBuf};
+
+use cargo_edit::{
+    diff_dependencies, find, set_cargo_flags, set_output_config, shell_note, shell_write_stderr,
+    CargoFlags, CargoResult, ChangeKind, ColorWhen, Context, DependencyChange, Manifest,
+    OutputConfig,
+};
+use clap::Args;
+use termcolor::ColorSpec;
+
+/// Compare the dependencies of two versions of a manifest
+#[derive(Debug, Args)]
+#[clap(version)]
+#[clap(after_help = "\
+Without a revision or `--old`, the manifest is compared with its version at `HEAD`.
+
+Versions are compared as written in the manifest, see `cargo tree` for what they resolve to.")]
+pub struct DiffDepsArgs {
+    /// Git revisions to compare, as `OLD..NEW`, or one to compare the working tree with
+    #[clap(value_name = "REVS", conflicts_with_all = &["base", "old", "new"])]
+    revs: Option<String>,
+
+    /// Revision to compare the working tree with [default: HEAD]
+    #[clap(long, value_name = "REV", conflicts_with = "old")]
+    base: Option<String>,
+
+    /// Manifest to compare from, instead of a git revision
+    #[clap(long, value_name = "PATH")]
+    old: Option<PathBuf>,
+
+    /// Manifest to compare to, instead of the working tree
+    #[clap(long, value_name = "PATH")]
+    new: Option<PathBuf>,
+
+    /// Path to the manifest to compare
+    #[clap(long, value_name = "PATH", action)]
+    manifest_path: Option<PathBuf>,
+
+    /// Output format
+    #[clap(long, value_name = "FMT", arg_enum, default_value = "text")]
+    format: Format,
+
+    /// Coloring
+    #[clap(long, value_name = "WHEN", possible_values = ["auto", "always", "never"])]
+    color: Option<ColorWhen>,
+
+    /// Run without accessing the network
+    #[clap(long)]
+    offline: bool,
+
+    /// Require `Cargo.lock` to be up to date
+    #[clap(long)]
+    locked: bool,
+
+    /// Require `Cargo.lock` and the registry cache to be up to date
+    #[clap(long)]
+    frozen: bool,
+
+    /// Override a configuration value
+    #[clap(long, value_name = "KEY=VALUE")]
+    config: Vec<String>,
+
+    /// Unstable (nightly-only) flags
+    #[clap(short = 'Z', value_name = "FLAG", global = true, arg_enum)]
+    unstable_features: Vec<UnstableOptions>,
+}
+
+impl DiffDepsArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum UnstableOptions {}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum Format {
+    Text,
+    Json,
+    Markdown,
+}
+
+/// Main processing function. Allows us to return a `Result` so that `main` can print pretty error
+/// messages.
+fn exec(args: DiffDepsArgs) -> CargoResult<()> {
+    let mut output = OutputConfig::from_env()?;
+    if let Some(color) = args.color {
+        output = output.set_color(color);
+    }
+    set_output_config(output);
+
+    let cargo_flags = args
+        .config
+        .iter()
+        .fold(CargoFlags::new(), |flags, config| flags.add_config(config))
+        .set_offline(args.offline)
+        .set_locked(args.locked)
+        .set_frozen(args.frozen);
+    set_cargo_flags(cargo_flags)?;
+
+    let (old_rev, new_rev) = match args.revs.as_deref().map(|revs| revs.split_once("..")) {
+        Some(Some((old, new))) => (Some(old), Some(new)),
+        Some(None) => (args.revs.as_deref(), None),
+        None => (args.base.as_deref(), None),
+    };
+
+    // The working tree manifest is only needed when one side isn't a standalone file
+    let manifest_path = if args.old.is_none() || args.new.is_none() {
+        Some(dunce::canonicalize(find(args.manifest_path.as_deref())?)?)
+    } else {
+        None
+    };
+    let manifest_path = manifest_path.as_deref();
+
+    let old = match &args.old {
+        Some(path) => read_manifest(path)?,
+        None => manifest_at_revision(
+            manifest_path.expect("resolved without `--old`"),
+            old_rev.unwrap_or("HEAD"),
+        )?,
+    };
+    let (new, new_path) = match (&args.new, new_rev) {
+        (Some(path), _) => (read_manifest(path)?, dunce::canonicalize(path)?),
+        (None, Some(rev)) => {
+            let path = manifest_path.expect("resolved without `--new`");
+            (manifest_at_revision(path, rev)?, path.to_owned())
+        }
+        (None, None) => {
+            let path = manifest_path.expect("resolved without `--new`");
+            (read_manifest(path)?, path.to_owned())
+        }
+    };
+    let crate_root = new_path.parent().expect("manifests are files");
+
+    let changes = diff_dependencies(crate_root, &old, &new)?;
+    match args.format {
+        Format::Text => print_text(&changes)?,
+        Format::Json => print_json(&changes)?,
+        Format::Markdown => print_markdown(&changes)?,
+    }
+    Ok(())
+}
+
+fn read_manifest(path: &Path) -> CargoResult<Manifest> {
+    let data = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read `{}`", path.display()))?;
+    data.parse()
+        .with_context(|| format!("Unable to parse `{}`", path.display()))
+}
+
+/// Read the manifest as it was committed at `rev`
+///
+/// A manifest that didn't exist yet has no dependencies.
+fn manifest_at_revision(manifest_path: &Path, rev: &str) -> CargoResult<Manifest> {
+    let repo = git2::Repository::discover(manifest_path.parent().expect("manifests are files"))
+        .with_context(|| {
+            format!(
+                "`{}` is not in a git repository, use `--old` to compare with another manifest",
+                manifest_path.display()
+            )
+        })?;
+    let workdir = repo
+        .workdir()
+        .context("Cannot compare revisions of a bare repository")?;
+    let workdir = dunce::canonicalize(workdir)?;
+    let relative = manifest_path.strip_prefix(&workdir).with_context(|| {
+        format!(
+            "`{}` is outside of `{}`",
+            manifest_path.display(),
+            workdir.display()
+        )
+    })?;
+
+    let tree = repo
+        .revparse_single(rev)
+        .and_then(|object| object.peel_to_tree())
+        .with_context(|| format!("Unknown revision `{}`", rev))?;
+    let entry = match tree.get_path(relative) {
+        Ok(entry) => entry,
+        Err(err) if err.code() == git2::ErrorCode::NotFound => return "".parse(),
+        Err(err) => return Err(err.into()),
+    };
+    let blob = repo.find_blob(entry.id())?;
+    let data = std::str::from_utf8(blob.content())
+        .with_context(|| format!("`{}` at `{}` is not UTF-8", relative.display(), rev))?;
+    data.parse()
+        .with_context(|| format!("Unable to parse `{}` at `{}`", relative.display(), rev))
+}
+
+fn print_text(changes: &[DependencyChange]) -> CargoResult<()> {
+    if changes.is_empty() {
+        return shell_note("No dependency changes");
+    }
+    let mut table = None;
+    for change in changes {
+        if table != Some(&change.table) {
+            shell_write_stderr(format!("{}\n", change.table), &ColorSpec::new())?;
+            table = Some(&change.table);
+        }
+        let line = match change.kind {
+            ChangeKind::Added => format!("  + {} {}\n", change.name, cell(&change.new)),
+            ChangeKind::Removed => format!("  - {} {}\n", change.name, cell(&change.old)),
+            ChangeKind::Changed => format!(
+                "  ~ {} {} -> {}\n",
+                change.name,
+                cell(&change.old),
+                cell(&change.new)
+            ),
+        };
+        shell_write_stderr(line, &ColorSpec::new())?;
+    }
+    Ok(())
+}
+
+fn print_json(changes: &[DependencyChange]) -> CargoResult<()> {
+    let changes = changes
+        .iter()
+        .map(|change| {
+            serde_json::json!({
+                "table": change.table,
+                "name": change.name,
+                "change": change.kind.as_str(),
+                "old": change.old,
+                "new": change.new,
+            })
+        })
+        .collect::<Vec<_>>();
+    println!("{}", serde_json::to_string_pretty(&changes)?);
+    Ok(())
+}
+
+fn print_markdown(changes: &[DependencyChange]) -> CargoResult<()> {
+    if changes.is_empty() {
+        return shell_note("No dependency changes");
+    }
+    println!("| Table | Dependency | Change | Old | New |");
+    println!("| ----- | ---------- | ------ | --- | --- |");
+    for change in changes {
+        println!(
+            "| {} | {} | {} | {} | {} |",
+            markdown_code(Some(change.table.as_str())),
+            markdown_code(Some(change.name.as_str())),
+            change.kind.as_str(),
+            markdown_code(change.old.as_deref()),
+            markdown_code(change.new.as_deref()),
+        );
+    }
+    Ok(())
+}
+
+fn cell(content: &Option<String>) -> &str {
+    content.as_deref().unwrap_or_default()
+}
+
+/// Format a table cell as inline code
+fn markdown_code(content: Option<&str>) -> String {
+    match content {
+        Some(content) => format!("`{}`", content.replace('|', "\\|")),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,35 @@
+use cargo_edit::CargoResult;
+use clap::{Parser, Subcommand};
+
+#[derive(Debug, Parser)]
+#[clap(bin_name = "cargo")]
+pub enum Command {
+    /// Manage the edits cargo-edit made
+    #[clap(subcommand)]
+    Edit(EditCommand),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum EditCommand {
+    Init(crate::init::InitArgs),
+    Preset(crate::preset::PresetArgs),
+    FeatureGate(crate::feature_gate::FeatureGateArgs),
+    Restore(crate::restore::RestoreArgs),
+}
+
+impl Command {
+    pub fn exec(self) -> CargoResult<()> {
+        match self {
+            Self::Edit(EditCommand::Init(init)) => init.exec(),
+            Self::Edit(EditCommand::Preset(preset)) => preset.exec(),
+            Self::Edit(EditCommand::FeatureGate(feature_gate)) => feature_gate.exec(),
+            Self::Edit(EditCommand::Restore(restore)) => restore.exec(),
+        }
+    }
+}
+
+#[test]
+fn verify_app() {
+    use clap::CommandFactory;
+    Command::command().debug_assert()
+}
@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+
+use cargo_edit::{
+    backup_dir, find, manifest_from_pkgid, set_cargo_flags, set_hook_command, set_output_config,
+    shell_status, shell_warn, CargoFlags, CargoResult, ColorWhen, LocalManifest, OutputConfig,
+    WriteOptions,
+};
+use clap::Args;
+
+/// Make a dependency optional, enabled by a single feature
+#[derive(Debug, Args)]
+#[clap(version)]
+#[clap(after_help = "\
+The feature gets `dep:<DEP>`, or `<DEP>/<NAME>` for each `--dep-feature`, and is created if \
+needed. Fails if another feature enables the dependency too.")]
+pub struct FeatureGateArgs {
+    /// Dependency to make optional
+    #[clap(value_name = "DEP")]
+    dependency: String,
+
+    /// Feature that enables it
+    #[clap(value_name = "FEATURE")]
+    feature: String,
+
+    /// Features of the dependency to enable, instead of the dependency
+    #[clap(long, short = 'F', value_name = "NAME")]
+    dep_feature: Vec<String>,
+
+    /// Path to the manifest to change
+    #[clap(long, value_name = "PATH", action)]
+    manifest_path: Option<PathBuf>,
+
+    /// Package id of the crate to change
+    #[clap(long = "package", short = 'p', value_name = "PKGID")]
+    pkgid: Option<String>,
+
+    /// Print changes to be made without making them
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Fail on symlinked manifests instead of writing to their target
+    #[clap(long)]
+    no_follow_symlinks: bool,
+
+    /// Edit vendored crates, which invalidates their checksums
+    #[clap(long)]
+    force_vendored: bool,
+
+    /// Do not print any output in case of success
+    #[clap(long, short)]
+    quiet: bool,
+
+    /// Coloring
+    #[clap(long, value_name = "WHEN", possible_values = ["auto", "always", "never"])]
+    color: Option<ColorWhen>,
+
+    /// Override a configuration value
+    #[clap(long, value_name = "KEY=VALUE")]
+    config: Vec<String>,
+}
+
+impl FeatureGateArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+fn exec(args: FeatureGateArgs) -> CargoResult<()> {
+    let mut output = OutputConfig::from_env()?;
+    if let Some(color) = args.color {
+        output = output.set_color(color);
+    }
+    set_output_config(output);
+
+    let cargo_flags = args
+        .config
+        .iter()
+        .fold(CargoFlags::new(), |flags, config| flags.add_config(config));
+    set_cargo_flags(cargo_flags)?;
+    let write_options = WriteOptions::new()
+        .set_allow_vendored(args.force_vendored)
+        .set_follow_symlinks(!args.no_follow_symlinks)
+        .set_backup_dir(backup_dir());
+    set_hook_command(Some("feature-gate"));
+
+    let manifest_path = match &args.pkgid {
+        Some(pkgid) => manifest_from_pkgid(args.manifest_path.as_deref(), pkgid)?
+            .manifest_path
+            .into_std_path_buf(),
+        None => find(args.manifest_path.as_deref())?,
+    };
+    let mut manifest = LocalManifest::try_new(&manifest_path)?;
+    manifest.add_dep_to_feature(&args.dependency, &args.feature, &args.dep_feature)?;
+    if !args.quiet {
+        shell_status(
+            "Gating",
+            &format!("`{}` behind feature `{}`", args.dependency, args.feature),
+        )?;
+    }
+
+    if args.dry_run {
+        shell_warn("aborting feature-gate due to dry run")?;
+    } else {
+        manifest.write_with(&write_options)?;
+    }
+    Ok(())
+}
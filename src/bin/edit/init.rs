@@ -0,0 +1,64 @@
+use std::path::PathBuf;
+
+use cargo_edit::{
+    init_manifest, set_output_config, shell_status, CargoResult, ColorWhen, Context, LookupOptions,
+    OutputConfig,
+};
+use clap::Args;
+
+/// Create a `Cargo.toml` with dependencies
+#[derive(Debug, Args)]
+#[clap(version)]
+#[clap(after_help = "\
+The package is named after the directory, with version 0.1.0 and the 2021 edition. Dependencies \
+without a version requirement are resolved to their latest release.")]
+pub struct InitArgs {
+    /// Directory to create the manifest in, instead of the current one
+    #[clap(value_name = "DIR")]
+    dir: Option<PathBuf>,
+
+    /// Dependencies to add, like `serde,tokio@1+full`
+    #[clap(long, value_name = "DEP_ID", use_value_delimiter = true)]
+    deps: Vec<String>,
+
+    /// Only look crates up by their name, not other `-`/`_` spellings
+    #[clap(long, visible_alias = "no-fuzzy")]
+    exact: bool,
+
+    /// Do not print any output in case of success
+    #[clap(long, short)]
+    quiet: bool,
+
+    /// Coloring
+    #[clap(long, value_name = "WHEN", possible_values = ["auto", "always", "never"])]
+    color: Option<ColorWhen>,
+}
+
+impl InitArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+fn exec(args: InitArgs) -> CargoResult<()> {
+    let mut output = OutputConfig::from_env()?;
+    if let Some(color) = args.color {
+        output = output.set_color(color);
+    }
+    set_output_config(output);
+
+    let dir = match args.dir {
+        Some(dir) => dir,
+        None => std::env::current_dir().context("Failed to read the current directory")?,
+    };
+    let deps: Vec<&str> = args.deps.iter().map(String::as_str).collect();
+    let options = LookupOptions::new().set_fuzzy(!args.exact);
+    let (manifest, warnings) = init_manifest(&dir, &deps, &options)?;
+    for warning in warnings {
+        warning.report()?;
+    }
+    if !args.quiet {
+        shell_status("Created", &format!("`{}`", manifest.path.display()))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,211 @@
+use std::path::PathBuf;
+
+use cargo_edit::{
+    add_preset, add_preset_to_manifests, backup_dir, find, find_preset, resolve_manifests,
+    set_cargo_flags, set_hook_command, set_output_config, shell_status, shell_warn, CargoFlags,
+    CargoResult, ColorWhen, DependencyAddition, LocalManifest, LookupOptions, MergeStrategy,
+    OutputConfig, WriteOptions,
+};
+use clap::Args;
+
+/// Add a named group of dependencies
+#[derive(Debug, Args)]
+#[clap(version)]
+#[clap(after_help = "\
+Presets are defined in `[package.metadata.cargo-edit.presets]`, \
+`[workspace.metadata.cargo-edit.presets]` or `[cargo-edit.presets]` in cargo config, like \
+`web = [\"axum\", \"tokio@1+full\"]`. `--on-existing merge` keeps the customizations and git \
+source of dependencies already in the table, `replace` turns them into registry entries.")]
+pub struct PresetArgs {
+    /// Preset to add
+    #[clap(value_name = "NAME")]
+    name: String,
+
+    /// Add as development dependencies
+    #[clap(long, short = 'D', conflicts_with = "build")]
+    dev: bool,
+
+    /// Add as build dependencies
+    #[clap(long, short = 'B', conflicts_with = "dev")]
+    build: bool,
+
+    /// Add as dependencies of the given target platform
+    #[clap(long, value_parser = clap::builder::NonEmptyStringValueParser::new())]
+    target: Option<String>,
+
+    /// How to treat dependencies already in the table
+    #[clap(
+        long,
+        value_name = "MODE",
+        default_value = "merge",
+        possible_values = ["merge", "replace", "skip"]
+    )]
+    on_existing: MergeStrategy,
+
+    /// Path to the manifest to add the preset to
+    #[clap(long, value_name = "PATH", action)]
+    manifest_path: Option<PathBuf>,
+
+    /// Packages to add the preset to
+    #[clap(
+        long = "package",
+        short = 'p',
+        value_name = "PKGID",
+        conflicts_with = "workspace"
+    )]
+    pkgid: Vec<String>,
+
+    /// Add the preset to all workspace members
+    #[clap(long, conflicts_with = "pkgid")]
+    workspace: bool,
+
+    /// Only look crates up by their name, not other `-`/`_` spellings
+    #[clap(long, visible_alias = "no-fuzzy")]
+    exact: bool,
+
+    /// Print changes to be made without making them
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Fail on symlinked manifests instead of writing to their target
+    #[clap(long)]
+    no_follow_symlinks: bool,
+
+    /// Edit vendored crates, which invalidates their checksums
+    #[clap(long)]
+    force_vendored: bool,
+
+    /// Do not print any output in case of success
+    #[clap(long, short)]
+    quiet: bool,
+
+    /// Coloring
+    #[clap(long, value_name = "WHEN", possible_values = ["auto", "always", "never"])]
+    color: Option<ColorWhen>,
+
+    /// Run without accessing the network
+    #[clap(long)]
+    offline: bool,
+
+    /// Override a configuration value
+    #[clap(long, value_name = "KEY=VALUE")]
+    config: Vec<String>,
+}
+
+impl PresetArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+
+    /// Get dependency section
+    fn get_section(&self) -> Vec<String> {
+        let section_name = if self.dev {
+            "dev-dependencies"
+        } else if self.build {
+            "build-dependencies"
+        } else {
+            "dependencies"
+        };
+
+        if let Some(ref target) = self.target {
+            vec!["target".to_owned(), target.clone(), section_name.to_owned()]
+        } else {
+            vec![section_name.to_owned()]
+        }
+    }
+}
+
+fn exec(args: PresetArgs) -> CargoResult<()> {
+    let mut output = OutputConfig::from_env()?;
+    if let Some(color) = args.color {
+        output = output.set_color(color);
+    }
+    set_output_config(output);
+
+    let cargo_flags = args
+        .config
+        .iter()
+        .fold(CargoFlags::new(), |flags, config| flags.add_config(config))
+        .set_offline(args.offline);
+    set_cargo_flags(cargo_flags)?;
+    let write_options = WriteOptions::new()
+        .set_allow_vendored(args.force_vendored)
+        .set_follow_symlinks(!args.no_follow_symlinks)
+        .set_backup_dir(backup_dir());
+    let lookup_options = LookupOptions::new().set_fuzzy(!args.exact);
+    set_hook_command(Some("preset"));
+
+    let manifest_paths = if args.workspace || !args.pkgid.is_empty() {
+        resolve_manifests(
+            args.manifest_path.as_deref(),
+            args.workspace,
+            args.pkgid.iter().map(String::as_str).collect(),
+        )?
+        .into_iter()
+        .map(|package| package.manifest_path.into_std_path_buf())
+        .collect()
+    } else {
+        vec![find(args.manifest_path.as_deref())?]
+    };
+    let preset = find_preset(&manifest_paths[0], &args.name)?;
+    let section = args.get_section();
+
+    let (additions, warnings) = if args.dry_run {
+        let mut additions = Vec::new();
+        let mut warnings = Vec::new();
+        for path in &manifest_paths {
+            let mut manifest = LocalManifest::try_new(path)?;
+            let (added, lookup_warnings) = add_preset(
+                &mut manifest,
+                &preset,
+                &section,
+                args.on_existing,
+                &lookup_options,
+            )?;
+            additions.extend(added);
+            if warnings.is_empty() {
+                warnings = lookup_warnings;
+            }
+        }
+        (additions, warnings)
+    } else {
+        add_preset_to_manifests(
+            &manifest_paths,
+            &preset,
+            &section,
+            args.on_existing,
+            &lookup_options,
+            &write_options,
+        )?
+    };
+    for warning in warnings {
+        warning.report()?;
+    }
+    if !args.quiet {
+        for addition in &additions {
+            report(addition, &section)?;
+        }
+    }
+    if args.dry_run {
+        shell_warn("aborting preset due to dry run")?;
+    }
+    Ok(())
+}
+
+fn report(addition: &DependencyAddition, section: &[String]) -> CargoResult<()> {
+    let (status, dependency) = match (&addition.existing, &addition.written) {
+        (None, Some(written)) => ("Adding", written),
+        (Some(_), Some(written)) => ("Updating", written),
+        (Some(existing), None) => ("Skipping", existing),
+        (None, None) => return Ok(()),
+    };
+    shell_status(
+        status,
+        &format!(
+            "{} in `{}` of `{}`",
+            dependency.toml_key(),
+            section.join("."),
+            addition.manifest_path.display()
+        ),
+    )
+}
@@ -0,0 +1,103 @@
+use std::path::PathBuf;
+
+use cargo_edit::{
+    backup_dir, latest_backups, set_output_config, shell_status, shell_warn, CargoResult,
+    ColorWhen, Context, OutputConfig, DEFAULT_BACKUP_DIR,
+};
+use clap::Args;
+
+/// Bring manifests back to their most recent backup
+#[derive(Debug, Args)]
+#[clap(version)]
+#[clap(after_help = "\
+Backups are taken before each write with `--backup-dir`, or when `CARGO_EDIT_BACKUP_DIR` is set. \
+A restored backup is removed, so restoring again goes back one more write.")]
+pub struct RestoreArgs {
+    /// Manifests to restore, instead of all those with backups
+    #[clap(value_name = "PATH")]
+    manifests: Vec<PathBuf>,
+
+    /// Directory the backups are in, instead of `CARGO_EDIT_BACKUP_DIR` or `.cargo-edit-backups`
+    #[clap(long, value_name = "DIR")]
+    backup_dir: Option<PathBuf>,
+
+    /// Print changes to be made without making them.
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Do not print any output in case of success
+    #[clap(long, short)]
+    quiet: bool,
+
+    /// Coloring
+    #[clap(long, value_name = "WHEN", possible_values = ["auto", "always", "never"])]
+    color: Option<ColorWhen>,
+}
+
+impl RestoreArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+/// Main processing function. Allows us to return a `Result` so that `main` can print pretty error
+/// messages.
+fn exec(args: RestoreArgs) -> CargoResult<()> {
+    let mut output = OutputConfig::from_env()?;
+    if let Some(color) = args.color {
+        output = output.set_color(color);
+    }
+    set_output_config(output);
+
+    let dir = args
+        .backup_dir
+        .or_else(backup_dir)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_BACKUP_DIR));
+    let mut backups = latest_backups(&dir)?;
+    if !args.manifests.is_empty() {
+        let mut manifests = Vec::new();
+        for manifest in &args.manifests {
+            manifests.push(
+                dunce::canonicalize(manifest)
+                    .with_context(|| format!("Failed to read `{}`", manifest.display()))?,
+            );
+        }
+        for manifest in &manifests {
+            if !backups
+                .iter()
+                .any(|backup| &backup.manifest_path == manifest)
+            {
+                anyhow::bail!(
+                    "`{}` has no backup in `{}`",
+                    manifest.display(),
+                    dir.display()
+                );
+            }
+        }
+        backups.retain(|backup| manifests.contains(&backup.manifest_path));
+    }
+    if backups.is_empty() {
+        shell_warn(&format!("no backups in `{}`", dir.display()))?;
+        return Ok(());
+    }
+
+    for backup in &backups {
+        if !args.quiet {
+            shell_status(
+                "Restoring",
+                &format!(
+                    "`{}` from `{}`",
+                    backup.manifest_path.display(),
+                    backup.backup_path.display()
+                ),
+            )?;
+        }
+        if !args.dry_run {
+            backup.restore()?;
+        }
+    }
+    if args.dry_run {
+        shell_warn("aborting restore due to dry run")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use cargo_edit::CargoResult;
+use clap::{Parser, Subcommand};
+
+#[derive(Debug, Parser)]
+#[clap(bin_name = "cargo")]
+pub enum Command {
+    /// Change the `[lints]` of a package, or the `[workspace.lints]` its members inherit
+    #[clap(subcommand)]
+    Lints(LintsCommand),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum LintsCommand {
+    Set(crate::set::SetArgs),
+    Remove(crate::remove::RemoveArgs),
+}
+
+impl Command {
+    pub fn exec(self) -> CargoResult<()> {
+        match self {
+            Self::Lints(LintsCommand::Set(set)) => set.exec(),
+            Self::Lints(LintsCommand::Remove(remove)) => remove.exec(),
+        }
+    }
+}
+
+#[test]
+fn verify_app() {
+    use clap::CommandFactory;
+    Command::command().debug_assert()
+}
@@ -0,0 +1,132 @@
+use std::path::PathBuf;
+
+use cargo_edit::{
+    backup_dir, find, manifest_from_pkgid, set_cargo_flags, set_hook_command, set_output_config,
+    shell_status, shell_warn, workspace_root_manifest, CargoFlags, CargoResult, ColorWhen, LintKey,
+    LocalManifest, OutputConfig, WriteOptions,
+};
+use clap::Args;
+
+/// Remove a lint, leaving it at its default level
+#[derive(Debug, Args)]
+#[clap(version)]
+pub struct RemoveArgs {
+    /// Lint to remove, like `clippy.unwrap_used`
+    #[clap(value_name = "LINT")]
+    lint: LintKey,
+
+    /// Remove the lint from `[workspace.lints]`
+    #[clap(long, conflicts_with = "pkgid")]
+    workspace: bool,
+
+    /// Path to the manifest to change
+    #[clap(long, value_name = "PATH", action)]
+    manifest_path: Option<PathBuf>,
+
+    /// Package id of the crate to change
+    #[clap(long = "package", short = 'p', value_name = "PKGID")]
+    pkgid: Option<String>,
+
+    /// Print changes to be made without making them.
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Fail on symlinked manifests instead of writing to their target
+    #[clap(long)]
+    no_follow_symlinks: bool,
+
+    /// Edit vendored crates, which invalidates their checksums
+    #[clap(long)]
+    force_vendored: bool,
+
+    /// Do not print any output in case of success
+    #[clap(long, short)]
+    quiet: bool,
+
+    /// Coloring
+    #[clap(long, value_name = "WHEN", possible_values = ["auto", "always", "never"])]
+    color: Option<ColorWhen>,
+
+    /// Run without accessing the network
+    #[clap(long)]
+    offline: bool,
+
+    /// Require `Cargo.lock` to be up to date
+    #[clap(long)]
+    locked: bool,
+
+    /// Require `Cargo.lock` and the registry cache to be up to date
+    #[clap(long)]
+    frozen: bool,
+
+    /// Override a configuration value
+    #[clap(long, value_name = "KEY=VALUE")]
+    config: Vec<String>,
+
+    /// Unstable (nightly-only) flags
+    #[clap(short = 'Z', value_name = "FLAG", global = true, arg_enum)]
+    unstable_features: Vec<UnstableOptions>,
+}
+
+impl RemoveArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum UnstableOptions {}
+
+/// Main processing function. Allows us to return a `Result` so that `main` can print pretty error
+/// messages.
+fn exec(args: RemoveArgs) -> CargoResult<()> {
+    let mut output = OutputConfig::from_env()?;
+    if let Some(color) = args.color {
+        output = output.set_color(color);
+    }
+    set_output_config(output);
+
+    let cargo_flags = args
+        .config
+        .iter()
+        .fold(CargoFlags::new(), |flags, config| flags.add_config(config))
+        .set_offline(args.offline)
+        .set_locked(args.locked)
+        .set_frozen(args.frozen);
+    set_cargo_flags(cargo_flags)?;
+    let write_options = WriteOptions::new()
+        .set_allow_vendored(args.force_vendored)
+        .set_follow_symlinks(!args.no_follow_symlinks)
+        .set_backup_dir(backup_dir());
+    set_hook_command(Some("lints"));
+
+    let manifest_path = if args.workspace {
+        workspace_root_manifest(args.manifest_path.as_deref())?
+    } else {
+        match &args.pkgid {
+            Some(pkgid) => manifest_from_pkgid(args.manifest_path.as_deref(), pkgid)?
+                .manifest_path
+                .into_std_path_buf(),
+            None => find(args.manifest_path.as_deref())?,
+        }
+    };
+    let table = if args.workspace {
+        "[workspace.lints]"
+    } else {
+        "[lints]"
+    };
+    let mut manifest = LocalManifest::try_new(&manifest_path)?;
+    if !manifest.remove_lint(&args.lint, args.workspace)? {
+        anyhow::bail!("`{}` isn't set in `{}`", args.lint, table);
+    }
+    if !args.quiet {
+        shell_status("Removing", &format!("`{}` from `{}`", args.lint, table))?;
+    }
+
+    if args.dry_run {
+        shell_warn("aborting remove due to dry run")?;
+    } else {
+        manifest.write_unchecked_with(&write_options)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,174 @@
+use std::path::PathBuf;
+
+use cargo_edit::{
+    backup_dir, find, manifest_from_pkgid, resolve_manifests, set_cargo_flags, set_hook_command,
+    set_output_config, shell_status, shell_warn, workspace_root_manifest, CargoFlags, CargoResult,
+    ColorWhen, LintKey, LintLevel, LocalManifest, OutputConfig, WriteOptions,
+};
+use clap::Args;
+
+/// Set the level of a lint
+#[derive(Debug, Args)]
+#[clap(version)]
+#[clap(after_help = "\
+With `--workspace`, members without `[lints]` of their own get `lints.workspace = true`, so they \
+inherit the lint. Members with their own lints are warned about, as cargo doesn't allow both.")]
+pub struct SetArgs {
+    /// Lint to set, like `clippy.unwrap_used` or `rust.unsafe_code`
+    #[clap(value_name = "LINT")]
+    lint: LintKey,
+
+    /// How the lint is reported
+    #[clap(value_name = "LEVEL", possible_values = LintLevel::VARIANTS)]
+    level: LintLevel,
+
+    /// Order the lint is applied in among the others, for lints that override a group
+    #[clap(long, value_name = "N", allow_hyphen_values = true)]
+    priority: Option<i64>,
+
+    /// Set the lint in `[workspace.lints]`, and make members inherit it
+    #[clap(long, conflicts_with = "pkgid")]
+    workspace: bool,
+
+    /// Path to the manifest to change
+    #[clap(long, value_name = "PATH", action)]
+    manifest_path: Option<PathBuf>,
+
+    /// Package id of the crate to change
+    #[clap(long = "package", short = 'p', value_name = "PKGID")]
+    pkgid: Option<String>,
+
+    /// Print changes to be made without making them.
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Fail on symlinked manifests instead of writing to their target
+    #[clap(long)]
+    no_follow_symlinks: bool,
+
+    /// Edit vendored crates, which invalidates their checksums
+    #[clap(long)]
+    force_vendored: bool,
+
+    /// Do not print any output in case of success
+    #[clap(long, short)]
+    quiet: bool,
+
+    /// Coloring
+    #[clap(long, value_name = "WHEN", possible_values = ["auto", "always", "never"])]
+    color: Option<ColorWhen>,
+
+    /// Run without accessing the network
+    #[clap(long)]
+    offline: bool,
+
+    /// Require `Cargo.lock` to be up to date
+    #[clap(long)]
+    locked: bool,
+
+    /// Require `Cargo.lock` and the registry cache to be up to date
+    #[clap(long)]
+    frozen: bool,
+
+    /// Override a configuration value
+    #[clap(long, value_name = "KEY=VALUE")]
+    config: Vec<String>,
+
+    /// Unstable (nightly-only) flags
+    #[clap(short = 'Z', value_name = "FLAG", global = true, arg_enum)]
+    unstable_features: Vec<UnstableOptions>,
+}
+
+impl SetArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum UnstableOptions {}
+
+/// Main processing function. Allows us to return a `Result` so that `main` can print pretty error
+/// messages.
+fn exec(args: SetArgs) -> CargoResult<()> {
+    let mut output = OutputConfig::from_env()?;
+    if let Some(color) = args.color {
+        output = output.set_color(color);
+    }
+    set_output_config(output);
+
+    let cargo_flags = args
+        .config
+        .iter()
+        .fold(CargoFlags::new(), |flags, config| flags.add_config(config))
+        .set_offline(args.offline)
+        .set_locked(args.locked)
+        .set_frozen(args.frozen);
+    set_cargo_flags(cargo_flags)?;
+    let write_options = WriteOptions::new()
+        .set_allow_vendored(args.force_vendored)
+        .set_follow_symlinks(!args.no_follow_symlinks)
+        .set_backup_dir(backup_dir());
+    set_hook_command(Some("lints"));
+
+    let manifest_path = if args.workspace {
+        workspace_root_manifest(args.manifest_path.as_deref())?
+    } else {
+        match &args.pkgid {
+            Some(pkgid) => manifest_from_pkgid(args.manifest_path.as_deref(), pkgid)?
+                .manifest_path
+                .into_std_path_buf(),
+            None => find(args.manifest_path.as_deref())?,
+        }
+    };
+    let mut manifest = LocalManifest::try_new(&manifest_path)?;
+    manifest.set_lint(&args.lint, args.level, args.priority, args.workspace)?;
+    if !args.quiet {
+        let table = if args.workspace {
+            "[workspace.lints]"
+        } else {
+            "[lints]"
+        };
+        shell_status(
+            "Setting",
+            &format!("`{}` to `{}` in `{}`", args.lint, args.level, table),
+        )?;
+    }
+
+    let mut members = Vec::new();
+    if args.workspace {
+        for package in resolve_manifests(Some(&manifest_path), true, Vec::new())? {
+            let member_path = dunce::canonicalize(package.manifest_path.as_std_path())?;
+            let inherited = if member_path == manifest.path {
+                manifest.inherit_workspace_lints()
+            } else {
+                let mut member = LocalManifest::try_new(&member_path)?;
+                let inherited = member.inherit_workspace_lints();
+                if let Ok(true) = inherited {
+                    members.push(member);
+                }
+                inherited
+            };
+            match inherited {
+                Ok(true) if !args.quiet => {
+                    shell_status(
+                        "Inheriting",
+                        &format!("`[workspace.lints]` in `{}`", package.name),
+                    )?;
+                }
+                Ok(_) => {}
+                Err(err) => shell_warn(&format!("{:#}, not inheriting the lint", err))?,
+            }
+        }
+    }
+
+    if args.dry_run {
+        shell_warn("aborting set due to dry run")?;
+    } else {
+        manifest.write_unchecked_with(&write_options)?;
+        for member in members {
+            member.write_with(&write_options)?;
+        }
+    }
+    Ok(())
+}
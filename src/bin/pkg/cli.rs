@@ -0,0 +1,29 @@
+use cargo_edit::CargoResult;
+use clap::{Parser, Subcommand};
+
+#[derive(Debug, Parser)]
+#[clap(bin_name = "cargo")]
+pub enum Command {
+    /// Edit the `[package]` fields of a manifest
+    #[clap(subcommand)]
+    Pkg(PkgCommand),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PkgCommand {
+    Set(crate::set::SetArgs),
+}
+
+impl Command {
+    pub fn exec(self) -> CargoResult<()> {
+        match self {
+            Self::Pkg(PkgCommand::Set(set)) => set.exec(),
+        }
+    }
+}
+
+#[test]
+fn verify_app() {
+    use clap::CommandFactory;
+    Command::command().debug_assert()
+}
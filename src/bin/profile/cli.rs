@@ -0,0 +1,31 @@
+use cargo_edit::CargoResult;
+use clap::{Parser, Subcommand};
+
+#[derive(Debug, Parser)]
+#[clap(bin_name = "cargo")]
+pub enum Command {
+    /// Read and change the `[profile]` settings of a workspace
+    #[clap(subcommand)]
+    Profile(ProfileCommand),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ProfileCommand {
+    Get(crate::get::GetArgs),
+    Set(crate::set::SetArgs),
+}
+
+impl Command {
+    pub fn exec(self) -> CargoResult<()> {
+        match self {
+            Self::Profile(ProfileCommand::Get(get)) => get.exec(),
+            Self::Profile(ProfileCommand::Set(set)) => set.exec(),
+        }
+    }
+}
+
+#[test]
+fn verify_app() {
+    use clap::CommandFactory;
+    Command::command().debug_assert()
+}
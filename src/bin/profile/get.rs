@@ -0,0 +1,86 @@
+use std::path::PathBuf;
+
+use cargo_edit::{
+    set_cargo_flags, set_output_config, workspace_root_manifest, CargoFlags, CargoResult,
+    ColorWhen, LocalManifest, OutputConfig, ProfileKey,
+};
+use clap::Args;
+
+/// Print a profile setting of the workspace
+#[derive(Debug, Args)]
+#[clap(version)]
+#[clap(after_help = "\
+Strings are printed without quotes. Only what the manifest sets is printed, not cargo's defaults.")]
+pub struct GetArgs {
+    /// Setting to print, like `release.lto` or `dev.package."*".opt-level`
+    #[clap(value_name = "KEY")]
+    key: ProfileKey,
+
+    /// Path to the workspace's manifest
+    #[clap(long, value_name = "PATH", action)]
+    manifest_path: Option<PathBuf>,
+
+    /// Coloring
+    #[clap(long, value_name = "WHEN", possible_values = ["auto", "always", "never"])]
+    color: Option<ColorWhen>,
+
+    /// Run without accessing the network
+    #[clap(long)]
+    offline: bool,
+
+    /// Require `Cargo.lock` to be up to date
+    #[clap(long)]
+    locked: bool,
+
+    /// Require `Cargo.lock` and the registry cache to be up to date
+    #[clap(long)]
+    frozen: bool,
+
+    /// Override a configuration value
+    #[clap(long, value_name = "KEY=VALUE")]
+    config: Vec<String>,
+
+    /// Unstable (nightly-only) flags
+    #[clap(short = 'Z', value_name = "FLAG", global = true, arg_enum)]
+    unstable_features: Vec<UnstableOptions>,
+}
+
+impl GetArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum UnstableOptions {}
+
+/// Main processing function. Allows us to return a `Result` so that `main` can print pretty error
+/// messages.
+fn exec(args: GetArgs) -> CargoResult<()> {
+    let mut output = OutputConfig::from_env()?;
+    if let Some(color) = args.color {
+        output = output.set_color(color);
+    }
+    set_output_config(output);
+
+    let cargo_flags = args
+        .config
+        .iter()
+        .fold(CargoFlags::new(), |flags, config| flags.add_config(config))
+        .set_offline(args.offline)
+        .set_locked(args.locked)
+        .set_frozen(args.frozen);
+    set_cargo_flags(cargo_flags)?;
+
+    let manifest =
+        LocalManifest::try_new(&workspace_root_manifest(args.manifest_path.as_deref())?)?;
+    let value = manifest
+        .profile_setting(&args.key)
+        .and_then(|item| item.as_value())
+        .ok_or_else(|| anyhow::format_err!("`{}` isn't set", args.key))?;
+    match value.as_str() {
+        Some(value) => println!("{}", value),
+        None => println!("{}", value.to_string().trim()),
+    }
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use std::path::PathBuf;
+
+use cargo_edit::{
+    backup_dir, set_cargo_flags, set_hook_command, set_output_config, shell_status, shell_warn,
+    workspace_root_manifest, CargoFlags, CargoResult, ColorWhen, LocalManifest, OutputConfig,
+    ProfileKey, WriteOptions,
+};
+use clap::Args;
+
+/// Change a profile setting of the workspace
+#[derive(Debug, Args)]
+#[clap(version)]
+#[clap(after_help = "\
+Values are checked against what cargo accepts for the setting: `true` and `false` are booleans, \
+numbers are integers, and anything else is a string.")]
+pub struct SetArgs {
+    /// Setting to change, like `release.lto` or `dev.package."*".opt-level`
+    #[clap(value_name = "KEY")]
+    key: ProfileKey,
+
+    /// New value, like `thin`, `true` or `3`
+    #[clap(value_name = "VALUE")]
+    value: String,
+
+    /// Path to the workspace's manifest
+    #[clap(long, value_name = "PATH", action)]
+    manifest_path: Option<PathBuf>,
+
+    /// Print changes to be made without making them.
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Fail on symlinked manifests instead of writing to their target
+    #[clap(long)]
+    no_follow_symlinks: bool,
+
+    /// Edit vendored crates, which invalidates their checksums
+    #[clap(long)]
+    force_vendored: bool,
+
+    /// Do not print any output in case of success
+    #[clap(long, short)]
+    quiet: bool,
+
+    /// Coloring
+    #[clap(long, value_name = "WHEN", possible_values = ["auto", "always", "never"])]
+    color: Option<ColorWhen>,
+
+    /// Run without accessing the network
+    #[clap(long)]
+    offline: bool,
+
+    /// Require `Cargo.lock` to be up to date
+    #[clap(long)]
+    locked: bool,
+
+    /// Require `Cargo.lock` and the registry cache to be up to date
+    #[clap(long)]
+    frozen: bool,
+
+    /// Override a configuration value
+    #[clap(long, value_name = "KEY=VALUE")]
+    config: Vec<String>,
+
+    /// Unstable (nightly-only) flags
+    #[clap(short = 'Z', value_name = "FLAG", global = true, arg_enum)]
+    unstable_features: Vec<UnstableOptions>,
+}
+
+impl SetArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum UnstableOptions {}
+
+/// Main processing function. Allows us to return a `Result` so that `main` can print pretty error
+/// messages.
+fn exec(args: SetArgs) -> CargoResult<()> {
+    let mut output = OutputConfig::from_env()?;
+    if let Some(color) = args.color {
+        output = output.set_color(color);
+    }
+    set_output_config(output);
+
+    let cargo_flags = args
+        .config
+        .iter()
+        .fold(CargoFlags::new(), |flags, config| flags.add_config(config))
+        .set_offline(args.offline)
+        .set_locked(args.locked)
+        .set_frozen(args.frozen);
+    set_cargo_flags(cargo_flags)?;
+    let write_options = WriteOptions::new()
+        .set_allow_vendored(args.force_vendored)
+        .set_follow_symlinks(!args.no_follow_symlinks)
+        .set_backup_dir(backup_dir());
+    set_hook_command(Some("profile"));
+
+    let value = args.key.parse_value(&args.value)?;
+    let mut manifest =
+        LocalManifest::try_new(&workspace_root_manifest(args.manifest_path.as_deref())?)?;
+    manifest.set_profile_setting(&args.key, value.clone())?;
+    if !args.quiet {
+        shell_status(
+            "Setting",
+            &format!("`{}` to {}", args.key, value.to_string().trim()),
+        )?;
+    }
+
+    if args.dry_run {
+        shell_warn("aborting set due to dry run")?;
+    } else {
+        manifest.write_unchecked_with(&write_options)?;
+    }
+    Ok(())
+}
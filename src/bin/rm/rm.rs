@@ -1,10 +1,16 @@
+use cargo_edit::shell_note;
 use cargo_edit::shell_status;
 use cargo_edit::shell_warn;
+use cargo_edit::shell_write_stderr;
 use cargo_edit::CargoResult;
-use cargo_edit::{manifest_from_pkgid, LocalManifest};
+use cargo_edit::{
+    backup_dir, resolve_manifests, set_cargo_flags, set_hook_command, set_output_config,
+    unified_diff, workspace_root_manifest, CargoFlags, ColorWhen, LocalManifest, OutputConfig,
+    WriteOptions,
+};
 use clap::Args;
-use std::borrow::Cow;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use termcolor::ColorSpec;
 
 /// Remove a dependency from a Cargo.toml manifest file.
 #[derive(Debug, Args)]
@@ -32,20 +38,61 @@ pub struct RmArgs {
     manifest_path: Option<PathBuf>,
 
     /// Package to remove from
-    #[clap(long = "package", short = 'p', value_name = "PKGID")]
-    pkgid: Option<String>,
+    #[clap(
+        long = "package",
+        short = 'p',
+        value_name = "PKGID",
+        conflicts_with = "workspace"
+    )]
+    pkgid: Vec<String>,
+
+    /// Remove from all workspace members and `[workspace.dependencies]`
+    #[clap(long, conflicts_with = "pkgid")]
+    workspace: bool,
+
+    /// Also remove `[workspace.dependencies]` entries no member uses anymore
+    #[clap(long)]
+    gc_workspace_deps: bool,
 
     /// Unstable (nightly-only) flags
     #[clap(short = 'Z', value_name = "FLAG", global = true, arg_enum)]
     unstable_features: Vec<UnstableOptions>,
 
-    /// Don't actually write the manifest
+    /// Show what would change, without writing the manifest
     #[clap(long)]
     dry_run: bool,
 
+    /// Fail on symlinked manifests instead of writing to their target
+    #[clap(long)]
+    no_follow_symlinks: bool,
+
+    /// Edit vendored crates, which invalidates their checksums
+    #[clap(long)]
+    force_vendored: bool,
+
     /// Do not print any output in case of success
     #[clap(long, short)]
     quiet: bool,
+
+    /// Coloring
+    #[clap(long, value_name = "WHEN", possible_values = ["auto", "always", "never"])]
+    color: Option<ColorWhen>,
+
+    /// Run without accessing the network
+    #[clap(long)]
+    offline: bool,
+
+    /// Require `Cargo.lock` to be up to date
+    #[clap(long)]
+    locked: bool,
+
+    /// Require `Cargo.lock` and the registry cache to be up to date
+    #[clap(long)]
+    frozen: bool,
+
+    /// Override a configuration value
+    #[clap(long, value_name = "KEY=VALUE")]
+    config: Vec<String>,
 }
 
 impl RmArgs {
@@ -53,6 +100,14 @@ impl RmArgs {
         exec(self)
     }
 
+    /// How the edited manifests are written
+    fn write_options(&self) -> WriteOptions {
+        WriteOptions::new()
+            .set_allow_vendored(self.force_vendored)
+            .set_follow_symlinks(!self.no_follow_symlinks)
+            .set_backup_dir(backup_dir())
+    }
+
     /// Get dependency section
     pub fn get_section(&self) -> Vec<String> {
         let section_name = if self.dev {
@@ -74,46 +129,255 @@ impl RmArgs {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
-enum UnstableOptions {}
+enum UnstableOptions {
+    // Artifact dependencies, which cargo needs to read manifests using them
+    Bindeps,
+}
+
+impl UnstableOptions {
+    /// The flag cargo takes for it, after `-Z`
+    fn cargo_flag(self) -> &'static str {
+        match self {
+            Self::Bindeps => "bindeps",
+        }
+    }
+}
 
 fn exec(args: &RmArgs) -> CargoResult<()> {
-    let manifest_path = if let Some(ref pkgid) = args.pkgid {
-        let pkg = manifest_from_pkgid(args.manifest_path.as_deref(), pkgid)?;
-        Cow::Owned(Some(pkg.manifest_path.into_std_path_buf()))
-    } else {
-        Cow::Borrowed(&args.manifest_path)
-    };
-    let mut manifest = LocalManifest::find(manifest_path.as_deref())?;
-    let deps = &args.crates;
+    let mut output = OutputConfig::from_env()?;
+    if let Some(color) = args.color {
+        output = output.set_color(color);
+    }
+    set_output_config(output);
 
-    deps.iter()
-        .map(|dep| {
-            if !args.quiet {
-                let section = args.get_section();
-                let section = if section.len() >= 3 {
-                    format!("{} for target `{}`", &section[2], &section[1])
-                } else {
-                    section[0].clone()
-                };
-                shell_status("Removing", &format!("{dep} from {section}",))?;
+    let cargo_flags = args
+        .config
+        .iter()
+        .fold(CargoFlags::new(), |flags, config| flags.add_config(config))
+        .set_offline(args.offline)
+        .set_locked(args.locked)
+        .set_frozen(args.frozen);
+    let cargo_flags = args
+        .unstable_features
+        .iter()
+        .fold(cargo_flags, |flags, feature| {
+            flags.add_unstable(feature.cargo_flag())
+        });
+    set_cargo_flags(cargo_flags)?;
+    set_hook_command(Some("rm"));
+
+    let section = args.get_section();
+    if !args.workspace && args.pkgid.is_empty() && !args.gc_workspace_deps {
+        let mut manifest = LocalManifest::find(args.manifest_path.as_deref())?;
+        let original = manifest.to_string();
+        for dep in &args.crates {
+            remove_dep(args, &mut manifest, &section, dep, None)?;
+        }
+        let label = manifest
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        return finish(args, vec![(label, original, manifest)], None);
+    }
+
+    let packages = resolve_manifests(
+        args.manifest_path.as_deref(),
+        args.workspace,
+        args.pkgid.iter().map(|s| s.as_str()).collect(),
+    )?;
+    let root_path = workspace_root_manifest(args.manifest_path.as_deref())?;
+    let root_dir = root_path.parent().expect("manifests are files").to_owned();
+    let mut manifests = Vec::new();
+    for package in &packages {
+        let manifest = LocalManifest::try_new(package.manifest_path.as_std_path())?;
+        manifests.push((package.name.clone(), manifest.to_string(), manifest));
+    }
+
+    // Nothing is written until every removal succeeded
+    let mut removed: Vec<&str> = Vec::new();
+    for (package, _, manifest) in &mut manifests {
+        for dep in &args.crates {
+            // Members not declaring it are left alone
+            if args.workspace && !manifest.dependency_usage(dep).tables.contains(&section) {
+                continue;
+            }
+            remove_dep(args, manifest, &section, dep, Some(package.as_str()))?;
+            removed.push(dep);
+        }
+    }
+
+    if !args.workspace {
+        // Whether an entry is still used depends on every member, not only the edited ones
+        for package in resolve_manifests(args.manifest_path.as_deref(), true, Vec::new())? {
+            let path = package.manifest_path.as_std_path();
+            if !manifests
+                .iter()
+                .any(|(_, _, manifest)| manifest.path == path)
+            {
+                let manifest = LocalManifest::try_new(path)?;
+                manifests.push((package.name.clone(), manifest.to_string(), manifest));
             }
-            let result = manifest
-                .remove_from_table(&args.get_section(), dep)
-                .map_err(Into::into);
+        }
+    }
 
-            // Now that we have removed the crate, if that was the last reference to that crate,
-            // then we need to drop any explicitly activated features on that crate.
-            manifest.gc_dep(dep);
+    let gc = args.workspace || args.gc_workspace_deps;
+    let root = match manifests.iter().position(|(_, _, m)| m.path == root_path) {
+        Some(root) => root,
+        None => {
+            let manifest = LocalManifest::try_new(&root_path)?;
+            manifests.push((String::new(), manifest.to_string(), manifest));
+            manifests.len() - 1
+        }
+    };
+    for dep in &args.crates {
+        let users: Vec<String> = manifests
+            .iter()
+            .filter(|(package, _, manifest)| {
+                !package.is_empty() && !manifest.dependency_usage(dep).tables.is_empty()
+            })
+            .map(|(package, _, _)| package.clone())
+            .collect();
+        let inherited = manifests[root]
+            .2
+            .data
+            .get("workspace")
+            .and_then(|w| w.get("dependencies"))
+            .and_then(|d| d.get(dep))
+            .is_some();
+        if !inherited {
+            if args.workspace && !removed.contains(&dep.as_str()) {
+                anyhow::bail!(
+                    "The dependency `{}` could not be found in `{}` of any workspace member.",
+                    dep,
+                    section.join(".")
+                );
+            }
+            continue;
+        }
+        if users.is_empty() && !gc {
+            if !args.quiet {
+                shell_note(&format!(
+                    "`{dep}` in workspace.dependencies is no longer used by any member, \
+                     remove it with `--gc-workspace-deps`"
+                ))?;
+            }
+        } else if users.is_empty() {
+            if !args.quiet {
+                shell_status("Removing", &format!("{dep} from workspace.dependencies"))?;
+            }
+            manifests[root]
+                .2
+                .remove_from_table(&["workspace".to_owned(), "dependencies".to_owned()], dep)?;
+        } else if gc && !args.quiet {
+            shell_note(&format!(
+                "`{dep}` is kept in workspace.dependencies, still declared by {}",
+                users.join(", ")
+            ))?;
+        }
+    }
 
-            result
+    let manifests = manifests
+        .into_iter()
+        .filter(|(_, original, manifest)| *original != manifest.to_string())
+        .map(|(_, original, manifest)| {
+            let label = manifest
+                .path
+                .strip_prefix(&root_dir)
+                .unwrap_or(&manifest.path)
+                .display()
+                .to_string();
+            (label, original, manifest)
         })
-        .collect::<CargoResult<Vec<_>>>()?;
+        .collect();
+    finish(args, manifests, Some(&root_path))
+}
+
+/// Remove `dep` from `section` of `manifest`, reporting it unless quiet
+fn remove_dep(
+    args: &RmArgs,
+    manifest: &mut LocalManifest,
+    section: &[String],
+    dep: &str,
+    package: Option<&str>,
+) -> CargoResult<()> {
+    if !args.quiet {
+        let mut target = section_name(section);
+        if let Some(package) = package {
+            target = format!("{target} of {package}");
+        }
+        shell_status("Removing", &format!("{dep} from {target}"))?;
+        if args.dry_run {
+            report_usage(manifest, section, dep)?;
+        }
+    }
+    let result = manifest.remove_from_table(section, dep);
 
+    // Now that we have removed the crate, if that was the last reference to that crate,
+    // then we need to drop any explicitly activated features on that crate.
+    manifest.gc_dep(dep);
+
+    result
+}
+
+/// Write the edited manifests, or print their diffs in a dry run
+///
+/// Each manifest is `(label, original, edited)`.  The workspace root at `root_path` may be a
+/// virtual manifest, which only has `[workspace.dependencies]` to remove from.
+fn finish(
+    args: &RmArgs,
+    manifests: Vec<(String, String, LocalManifest)>,
+    root_path: Option<&Path>,
+) -> CargoResult<()> {
     if args.dry_run {
+        if !args.quiet {
+            for (label, original, manifest) in &manifests {
+                shell_write_stderr(
+                    unified_diff(original, &manifest.to_string(), label, 3),
+                    &ColorSpec::new(),
+                )?;
+            }
+        }
         shell_warn("aborting rm due to dry run")?;
     } else {
-        manifest.write()?;
+        let write_options = args.write_options();
+        for (_, _, manifest) in manifests {
+            if Some(manifest.path.as_path()) == root_path {
+                manifest.write_unchecked_with(&write_options)?;
+            } else {
+                manifest.write_with(&write_options)?;
+            }
+        }
     }
 
     Ok(())
 }
+
+fn section_name(section: &[String]) -> String {
+    if section.len() >= 3 {
+        format!("{} for target `{}`", &section[2], &section[1])
+    } else {
+        section[0].clone()
+    }
+}
+
+/// Report what else in the manifest refers to `dep`, before it is removed from `section`
+fn report_usage(manifest: &LocalManifest, section: &[String], dep: &str) -> CargoResult<()> {
+    let usage = manifest.dependency_usage(dep);
+    for (feature, activation) in &usage.features {
+        shell_note(&format!("feature `{feature}` activates `{activation}`"))?;
+    }
+    for (kind, name) in &usage.targets {
+        shell_note(&format!(
+            "{} `{name}` requires `{dep}` through its `required-features`",
+            kind.kind_table()
+        ))?;
+    }
+    for table in usage.tables.iter().filter(|table| *table != section) {
+        shell_note(&format!(
+            "`{dep}` is still declared in {}",
+            section_name(table)
+        ))?;
+    }
+    Ok(())
+}
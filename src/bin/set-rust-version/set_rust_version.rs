@@ -0,0 +1,204 @@
+use std::path::PathBuf;
+
+use cargo_edit::{
+    backup_dir, dependency_rust_versions, installed_rust_version, resolve_manifests,
+    rust_version_exceeds, set_cargo_flags, set_hook_command, set_output_config, shell_status,
+    shell_warn, workspace_root_manifest, CargoFlags, CargoResult, ColorWhen, Context,
+    LocalManifest, OutputConfig, PackageField, WriteOptions,
+};
+use clap::Args;
+
+/// Change the `rust-version` of packages, checking their dependencies support it
+#[derive(Debug, Args)]
+#[clap(version)]
+#[clap(group = clap::ArgGroup::new("ver").multiple(false))]
+#[clap(after_help = "\
+Members inheriting `rust-version` from the workspace have it changed in `[workspace.package]`. \
+Dependencies in the lockfile declaring a newer `rust-version` are reported.")]
+pub struct RustVersionArgs {
+    /// Rust version to require, like `1.60`
+    #[clap(value_name = "RUST_VERSION", group = "ver")]
+    rust_version: Option<String>,
+
+    /// Require the version of the installed toolchain
+    #[clap(long, group = "ver")]
+    to_installed: bool,
+
+    /// Path to the manifest to change
+    #[clap(long, value_name = "PATH", action)]
+    manifest_path: Option<PathBuf>,
+
+    /// Package id of the crate to change
+    #[clap(
+        long = "package",
+        short = 'p',
+        value_name = "PKGID",
+        conflicts_with = "workspace"
+    )]
+    pkgid: Vec<String>,
+
+    /// Modify all packages in the workspace.
+    #[clap(long, conflicts_with = "pkgid")]
+    workspace: bool,
+
+    /// Crates to exclude and not modify.
+    #[clap(long)]
+    exclude: Vec<String>,
+
+    /// Print changes to be made without making them.
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Fail on symlinked manifests instead of writing to their target
+    #[clap(long)]
+    no_follow_symlinks: bool,
+
+    /// Edit vendored crates, which invalidates their checksums
+    #[clap(long)]
+    force_vendored: bool,
+
+    /// Coloring
+    #[clap(long, value_name = "WHEN", possible_values = ["auto", "always", "never"])]
+    color: Option<ColorWhen>,
+
+    /// Run without accessing the network
+    #[clap(long)]
+    offline: bool,
+
+    /// Require `Cargo.lock` to be up to date
+    #[clap(long)]
+    locked: bool,
+
+    /// Require `Cargo.lock` and the registry cache to be up to date
+    #[clap(long)]
+    frozen: bool,
+
+    /// Override a configuration value
+    #[clap(long, value_name = "KEY=VALUE")]
+    config: Vec<String>,
+
+    /// Unstable (nightly-only) flags
+    #[clap(short = 'Z', value_name = "FLAG", global = true, arg_enum)]
+    unstable_features: Vec<UnstableOptions>,
+}
+
+impl RustVersionArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum UnstableOptions {}
+
+/// Main processing function. Allows us to return a `Result` so that `main` can print pretty error
+/// messages.
+fn exec(args: RustVersionArgs) -> CargoResult<()> {
+    let mut output = OutputConfig::from_env()?;
+    if let Some(color) = args.color {
+        output = output.set_color(color);
+    }
+    set_output_config(output);
+
+    let cargo_flags = args
+        .config
+        .iter()
+        .fold(CargoFlags::new(), |flags, config| flags.add_config(config))
+        .set_offline(args.offline)
+        .set_locked(args.locked)
+        .set_frozen(args.frozen);
+    set_cargo_flags(cargo_flags)?;
+    let write_options = WriteOptions::new()
+        .set_allow_vendored(args.force_vendored)
+        .set_follow_symlinks(!args.no_follow_symlinks)
+        .set_backup_dir(backup_dir());
+    set_hook_command(Some("set-rust-version"));
+
+    let rust_version = match (args.rust_version, args.to_installed) {
+        (Some(rust_version), _) => rust_version,
+        (None, true) => installed_rust_version()?,
+        (None, false) => anyhow::bail!("Pass the Rust version to require, or `--to-installed`"),
+    };
+    let value = PackageField::RustVersion.parse_value(&[rust_version.clone()])?;
+
+    let manifest_path = args.manifest_path.as_deref();
+    let packages = resolve_manifests(
+        manifest_path,
+        args.workspace,
+        args.pkgid.iter().map(String::as_str).collect(),
+    )?;
+
+    let mut inheriting = Vec::new();
+    for package in packages {
+        if args.exclude.contains(&package.name) {
+            continue;
+        }
+        let mut manifest = LocalManifest::try_new(package.manifest_path.as_std_path())?;
+        let current = manifest
+            .data
+            .get("package")
+            .and_then(|p| p.get("rust-version"));
+        if current.and_then(|v| v.get("workspace")).is_some() {
+            inheriting.push(package.name);
+            continue;
+        }
+        match current.and_then(|v| v.as_str()) {
+            Some(current) if current == rust_version => continue,
+            Some(current) => shell_status(
+                "Upgrading",
+                &format!(
+                    "{} rust-version from {} to {}",
+                    package.name, current, rust_version
+                ),
+            )?,
+            None => shell_status(
+                "Setting",
+                &format!("{} rust-version to {}", package.name, rust_version),
+            )?,
+        }
+        manifest.set_package_field(PackageField::RustVersion, value.clone())?;
+        if !args.dry_run {
+            manifest.write_with(&write_options)?;
+        }
+    }
+
+    let root_path = workspace_root_manifest(manifest_path)?;
+    if !inheriting.is_empty() {
+        let mut root = LocalManifest::try_new(&root_path)?;
+        root.set_workspace_package_field(PackageField::RustVersion, value)
+            .with_context(|| format!("{} inherit `rust-version`", inheriting.join(", ")))?;
+        shell_status(
+            "Setting",
+            &format!(
+                "workspace.package rust-version to {}, for {}",
+                rust_version,
+                inheriting.join(", ")
+            ),
+        )?;
+        if !args.dry_run {
+            root.write_unchecked_with(&write_options)?;
+        }
+    }
+
+    match dependency_rust_versions(&root_path) {
+        Ok(dependencies) => {
+            for dependency in dependencies {
+                if rust_version_exceeds(&dependency.rust_version, &rust_version) {
+                    shell_warn(&format!(
+                        "{} {} requires Rust {}, newer than {}",
+                        dependency.name, dependency.version, dependency.rust_version, rust_version
+                    ))?;
+                }
+            }
+        }
+        Err(err) => shell_warn(&format!(
+            "couldn't check the rust-version of dependencies: {:#}",
+            err
+        ))?,
+    }
+
+    if args.dry_run {
+        shell_warn("aborting set-rust-version due to dry run")?;
+    }
+    Ok(())
+}
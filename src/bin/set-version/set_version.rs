@@ -1,12 +1,14 @@
-use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
 
 use cargo_edit::{
-    colorize_stderr, resolve_manifests, upgrade_requirement, workspace_members, LocalManifest,
+    backup_dir, get_dep_version, resolve_manifests, set_cargo_flags, set_dep_version,
+    set_hook_command, set_output_config, shell_status, shell_write_stderr, upgrade_requirement,
+    workspace_members, workspace_root_manifest, CargoFlags, ColorWhen, LocalManifest, OutputConfig,
+    WriteOptions,
 };
 use clap::Args;
-use termcolor::{BufferWriter, Color, ColorSpec, WriteColor};
+use termcolor::{Color, ColorSpec};
 
 use crate::errors::*;
 use crate::version::BumpLevel;
@@ -60,10 +62,42 @@ pub struct VersionArgs {
     #[clap(long)]
     dry_run: bool,
 
+    /// Fail on symlinked manifests instead of writing to their target
+    #[clap(long)]
+    no_follow_symlinks: bool,
+
+    /// Edit vendored crates, which invalidates their checksums
+    #[clap(long)]
+    force_vendored: bool,
+
     /// Crates to exclude and not modify.
     #[clap(long)]
     exclude: Vec<String>,
 
+    /// Update requirements on the changed packages in this manifest too
+    #[clap(long, value_name = "PATH")]
+    propagate_to: Vec<PathBuf>,
+
+    /// Coloring
+    #[clap(long, value_name = "WHEN", possible_values = ["auto", "always", "never"])]
+    color: Option<ColorWhen>,
+
+    /// Run without accessing the network
+    #[clap(long)]
+    offline: bool,
+
+    /// Require `Cargo.lock` to be up to date
+    #[clap(long)]
+    locked: bool,
+
+    /// Require `Cargo.lock` and the registry cache to be up to date
+    #[clap(long)]
+    frozen: bool,
+
+    /// Override a configuration value
+    #[clap(long, value_name = "KEY=VALUE")]
+    config: Vec<String>,
+
     /// Unstable (nightly-only) flags
     #[clap(short = 'Z', value_name = "FLAG", global = true, arg_enum)]
     unstable_features: Vec<UnstableOptions>,
@@ -89,11 +123,38 @@ fn exec(args: VersionArgs) -> CargoResult<()> {
         pkgid,
         all,
         dry_run,
+        no_follow_symlinks,
+        force_vendored,
         workspace,
         exclude,
+        propagate_to,
+        color,
+        offline,
+        locked,
+        frozen,
+        config,
         unstable_features: _,
     } = args;
 
+    let mut output = OutputConfig::from_env()?;
+    if let Some(color) = color {
+        output = output.set_color(color);
+    }
+    set_output_config(output);
+
+    let cargo_flags = config
+        .iter()
+        .fold(CargoFlags::new(), |flags, config| flags.add_config(config))
+        .set_offline(offline)
+        .set_locked(locked)
+        .set_frozen(frozen);
+    set_cargo_flags(cargo_flags)?;
+    let write_options = WriteOptions::new()
+        .set_allow_vendored(force_vendored)
+        .set_follow_symlinks(!no_follow_symlinks)
+        .set_backup_dir(backup_dir());
+    set_hook_command(Some("set-version"));
+
     let target = match (target, bump) {
         (None, None) => TargetVersion::Relative(BumpLevel::Release),
         (None, Some(level)) => TargetVersion::Relative(level),
@@ -116,6 +177,7 @@ fn exec(args: VersionArgs) -> CargoResult<()> {
     }
 
     let workspace_members = workspace_members(manifest_path.as_deref())?;
+    let workspace_root = workspace_root_manifest(manifest_path.as_deref())?;
 
     for package in manifests.0 {
         if exclude.contains(&package.name) {
@@ -130,7 +192,7 @@ fn exec(args: VersionArgs) -> CargoResult<()> {
 
                 upgrade_message(package.name.as_str(), current, &next)?;
                 if !dry_run {
-                    manifest.write()?;
+                    manifest.write_with(&write_options)?;
                 }
             }
 
@@ -138,118 +200,269 @@ fn exec(args: VersionArgs) -> CargoResult<()> {
                 dunce::canonicalize(package.manifest_path.parent().expect("at least a parent"))?;
             for member in workspace_members.iter() {
                 let mut dep_manifest = LocalManifest::try_new(member.manifest_path.as_std_path())?;
-                let mut changed = false;
                 let dep_crate_root = dep_manifest
                     .path
                     .parent()
                     .expect("at least a parent")
                     .to_owned();
-                for dep in dep_manifest
-                    .get_dependency_tables_mut()
-                    .flat_map(|t| t.iter_mut().filter_map(|(_, d)| d.as_table_like_mut()))
-                    .filter(|d| {
-                        if !d.contains_key("version") {
-                            return false;
-                        }
-                        match d.get("path").and_then(|i| i.as_str()).and_then(|relpath| {
-                            dunce::canonicalize(dep_crate_root.join(relpath)).ok()
-                        }) {
-                            Some(dep_path) => dep_path == crate_root.as_path(),
-                            None => false,
-                        }
-                    })
-                {
-                    let old_req = dep
-                        .get("version")
-                        .expect("filter ensures this")
-                        .as_str()
-                        .unwrap_or("*");
-                    if let Some(new_req) = upgrade_requirement(old_req, &next)? {
-                        upgrade_dependent_message(member.name.as_str(), old_req, &new_req)?;
-                        dep.insert("version", toml_edit::value(new_req));
-                        changed = true;
-                    }
-                }
+                let changed = update_path_dependents(
+                    member.name.as_str(),
+                    dep_manifest.get_dependency_tables_mut(),
+                    &dep_crate_root,
+                    &crate_root,
+                    &next,
+                )?;
                 if changed && !dry_run {
-                    dep_manifest.write()?;
+                    dep_manifest.write_with(&write_options)?;
                 }
             }
+
+            let mut root_manifest = LocalManifest::try_new(&workspace_root)?;
+            let root = root_manifest
+                .path
+                .parent()
+                .expect("at least a parent")
+                .to_owned();
+            let changed = update_path_dependents(
+                "workspace.dependencies",
+                root_manifest
+                    .get_workspace_dependency_table_mut()
+                    .into_iter(),
+                &root,
+                &crate_root,
+                &next,
+            )?;
+            if changed && !dry_run {
+                root_manifest.write_unchecked_with(&write_options)?;
+            }
+
+            for path in &propagate_to {
+                propagate(
+                    path,
+                    &package.name,
+                    &crate_root,
+                    &next,
+                    dry_run,
+                    &write_options,
+                )?;
+            }
         }
     }
 
     Ok(())
 }
 
+/// Update the manifest at `path`, and its workspace members, outside of the workspace
+///
+/// Besides dependencies on `crate_root` by `path`, requirements on `name` are updated when the
+/// manifest patches it with `crate_root`, as they'd otherwise stop matching the patch.
+fn propagate(
+    path: &Path,
+    name: &str,
+    crate_root: &Path,
+    next: &semver::Version,
+    dry_run: bool,
+    write_options: &WriteOptions,
+) -> CargoResult<()> {
+    let mut root = LocalManifest::find(Some(path))?;
+    let root_path = root.path.clone();
+    let root_dir = root_path.parent().expect("at least a parent").to_owned();
+
+    let patched = root.get_patch_tables_mut().any(|table| {
+        table.iter().any(|(_, dep)| {
+            dep.get("path")
+                .and_then(|i| i.as_str())
+                .map_or(false, |relpath| points_to(relpath, &root_dir, crate_root))
+        })
+    });
+    let label = manifest_label(&root);
+    let mut root_changed = update_path_dependents(
+        &label,
+        root.get_patch_tables_mut(),
+        &root_dir,
+        crate_root,
+        next,
+    )?;
+    root_changed |= update_path_dependents(
+        &label,
+        root.get_workspace_dependency_table_mut().into_iter(),
+        &root_dir,
+        crate_root,
+        next,
+    )?;
+    if patched {
+        root_changed |= update_patched_dependents(
+            &label,
+            root.get_workspace_dependency_table_mut().into_iter(),
+            name,
+            next,
+        )?;
+    }
+
+    let mut manifests = vec![root];
+    if manifests[0].data.contains_key("workspace") {
+        for member in workspace_members(Some(&root_path))? {
+            if member.manifest_path != root_path {
+                manifests.push(LocalManifest::try_new(member.manifest_path.as_std_path())?);
+            }
+        }
+    }
+    for (i, mut manifest) in manifests.into_iter().enumerate() {
+        let is_root = i == 0;
+        let label = manifest_label(&manifest);
+        let dir = manifest
+            .path
+            .parent()
+            .expect("at least a parent")
+            .to_owned();
+        let mut changed = is_root && root_changed;
+        changed |= update_path_dependents(
+            &label,
+            manifest.get_dependency_tables_mut(),
+            &dir,
+            crate_root,
+            next,
+        )?;
+        if patched {
+            changed |= update_patched_dependents(
+                &label,
+                manifest.get_dependency_tables_mut(),
+                name,
+                next,
+            )?;
+        }
+        if changed && !dry_run {
+            if is_root {
+                manifest.write_unchecked_with(write_options)?;
+            } else {
+                manifest.write_with(write_options)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The package name of `manifest`, or its path for virtual manifests
+fn manifest_label(manifest: &LocalManifest) -> String {
+    match manifest
+        .data
+        .get("package")
+        .and_then(|p| p.get("name"))
+        .and_then(|n| n.as_str())
+    {
+        Some(name) => name.to_owned(),
+        None => manifest.path.display().to_string(),
+    }
+}
+
+/// Whether `relpath`, relative to `dep_crate_root`, refers to `crate_root`
+fn points_to(relpath: &str, dep_crate_root: &Path, crate_root: &Path) -> bool {
+    match dunce::canonicalize(dep_crate_root.join(relpath)) {
+        Ok(dep_path) => dep_path.as_path() == crate_root,
+        Err(_) => false,
+    }
+}
+
+/// Update the registry requirements on `name`, which a `[patch]` replaces with the package
+fn update_patched_dependents<'t>(
+    dependent: &str,
+    dep_tables: impl Iterator<Item = &'t mut dyn toml_edit::TableLike>,
+    name: &str,
+    next: &semver::Version,
+) -> CargoResult<bool> {
+    let mut changed = false;
+    for table in dep_tables {
+        for (key, dep) in table.iter_mut() {
+            let package = dep
+                .get("package")
+                .and_then(|p| p.as_str())
+                .unwrap_or_else(|| key.get());
+            let from_registry = ["path", "git", "workspace"]
+                .iter()
+                .all(|k| dep.get(k).is_none());
+            if package != name || !from_registry {
+                continue;
+            }
+            let old_req = match get_dep_version(dep) {
+                Ok(old_req) => old_req.to_owned(),
+                Err(_) => continue,
+            };
+            if let Some(new_req) = upgrade_requirement(&old_req, next)? {
+                upgrade_dependent_message(dependent, &old_req, &new_req)?;
+                set_dep_version(dep, &new_req)?;
+                changed = true;
+            }
+        }
+    }
+    Ok(changed)
+}
+
+/// Update the version requirement of dependencies that refer to `crate_root` by `path`
+fn update_path_dependents<'t>(
+    dependent: &str,
+    dep_tables: impl Iterator<Item = &'t mut dyn toml_edit::TableLike>,
+    dep_crate_root: &Path,
+    crate_root: &Path,
+    next: &semver::Version,
+) -> CargoResult<bool> {
+    let mut changed = false;
+    for dep in dep_tables
+        .flat_map(|t| t.iter_mut().filter_map(|(_, d)| d.as_table_like_mut()))
+        .filter(|d| {
+            if !d.contains_key("version") {
+                return false;
+            }
+            d.get("path")
+                .and_then(|i| i.as_str())
+                .map_or(false, |relpath| {
+                    points_to(relpath, dep_crate_root, crate_root)
+                })
+        })
+    {
+        let old_req = dep
+            .get("version")
+            .expect("filter ensures this")
+            .as_str()
+            .unwrap_or("*");
+        if let Some(new_req) = upgrade_requirement(old_req, next)? {
+            upgrade_dependent_message(dependent, old_req, &new_req)?;
+            dep.insert("version", toml_edit::value(new_req));
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
 /// A collection of manifests.
 struct Manifests(Vec<cargo_metadata::Package>);
 
 fn dry_run_message() -> CargoResult<()> {
-    let colorchoice = colorize_stderr();
-    let bufwtr = BufferWriter::stderr(colorchoice);
-    let mut buffer = bufwtr.buffer();
-    buffer
-        .set_color(ColorSpec::new().set_fg(Some(Color::Cyan)).set_bold(true))
-        .with_context(|| "Failed to set output colour")?;
-    write!(&mut buffer, "Starting dry run. ").with_context(|| "Failed to write dry run message")?;
-    buffer
-        .set_color(&ColorSpec::new())
-        .with_context(|| "Failed to clear output colour")?;
-    writeln!(&mut buffer, "Changes will not be saved.")
-        .with_context(|| "Failed to write dry run message")?;
-    bufwtr
-        .print(&buffer)
-        .with_context(|| "Failed to print dry run message")
+    shell_write_stderr(
+        "Starting dry run. ",
+        ColorSpec::new().set_fg(Some(Color::Cyan)).set_bold(true),
+    )
+    .with_context(|| "Failed to write dry run message")?;
+    shell_write_stderr("Changes will not be saved.\n", &ColorSpec::new())
+        .with_context(|| "Failed to write dry run message")
 }
 
 fn deprecated_message(message: &str) -> CargoResult<()> {
-    let colorchoice = colorize_stderr();
-    let bufwtr = BufferWriter::stderr(colorchoice);
-    let mut buffer = bufwtr.buffer();
-    buffer
-        .set_color(ColorSpec::new().set_fg(Some(Color::Red)).set_bold(true))
-        .with_context(|| "Failed to set output colour")?;
-    writeln!(&mut buffer, "{}", message).with_context(|| "Failed to write dry run message")?;
-    buffer
-        .set_color(&ColorSpec::new())
-        .with_context(|| "Failed to clear output colour")?;
-    bufwtr
-        .print(&buffer)
-        .with_context(|| "Failed to print dry run message")
+    shell_write_stderr(
+        message,
+        ColorSpec::new().set_fg(Some(Color::Red)).set_bold(true),
+    )
+    .with_context(|| "Failed to write deprecated message")?;
+    shell_write_stderr("\n", &ColorSpec::new())
+        .with_context(|| "Failed to write deprecated message")
 }
 
 fn upgrade_message(name: &str, from: &semver::Version, to: &semver::Version) -> CargoResult<()> {
-    let colorchoice = colorize_stderr();
-    let bufwtr = BufferWriter::stderr(colorchoice);
-    let mut buffer = bufwtr.buffer();
-    buffer
-        .set_color(ColorSpec::new().set_fg(Some(Color::Green)).set_bold(true))
-        .with_context(|| "Failed to print dry run message")?;
-    write!(&mut buffer, "{:>12}", "Upgraded").with_context(|| "Failed to print dry run message")?;
-    buffer
-        .reset()
-        .with_context(|| "Failed to print dry run message")?;
-    writeln!(&mut buffer, " {} from {} to {}", name, from, to)
-        .with_context(|| "Failed to print dry run message")?;
-    bufwtr
-        .print(&buffer)
-        .with_context(|| "Failed to print dry run message")
+    shell_status("Upgraded", &format!("{} from {} to {}", name, from, to))
 }
 
 fn upgrade_dependent_message(name: &str, old_req: &str, new_req: &str) -> CargoResult<()> {
-    let colorchoice = colorize_stderr();
-    let bufwtr = BufferWriter::stderr(colorchoice);
-    let mut buffer = bufwtr.buffer();
-    buffer
-        .set_color(ColorSpec::new().set_fg(Some(Color::Green)).set_bold(true))
-        .with_context(|| "Failed to print dry run message")?;
-    write!(&mut buffer, "{:>16}", "Updated dependency")
-        .with_context(|| "Failed to print dry run message")?;
-    buffer
-        .reset()
-        .with_context(|| "Failed to print dry run message")?;
-    writeln!(&mut buffer, " {} from {} to {}", name, old_req, new_req)
-        .with_context(|| "Failed to print dry run message")?;
-    bufwtr
-        .print(&buffer)
-        .with_context(|| "Failed to print dry run message")
+    shell_status(
+        "Updated dependency",
+        &format!("{} from {} to {}", name, old_req, new_req),
+    )
 }
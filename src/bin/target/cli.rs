@@ -0,0 +1,35 @@
+use cargo_edit::CargoResult;
+use clap::{Parser, Subcommand};
+
+#[derive(Debug, Parser)]
+#[clap(bin_name = "cargo")]
+pub enum Command {
+    /// Edit the `[[bin]]`, `[[example]]`, `[[test]]` and `[[bench]]` targets of a manifest
+    #[clap(subcommand)]
+    Target(TargetCommand),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TargetCommand {
+    AddBin(crate::add_bin::AddBinArgs),
+    Rename(crate::rename::RenameArgs),
+    SetRequiredFeatures(crate::required_features::RequiredFeaturesArgs),
+    Remove(crate::remove::RemoveArgs),
+}
+
+impl Command {
+    pub fn exec(self) -> CargoResult<()> {
+        match self {
+            Self::Target(TargetCommand::AddBin(add)) => add.exec(),
+            Self::Target(TargetCommand::Rename(rename)) => rename.exec(),
+            Self::Target(TargetCommand::SetRequiredFeatures(set)) => set.exec(),
+            Self::Target(TargetCommand::Remove(remove)) => remove.exec(),
+        }
+    }
+}
+
+#[test]
+fn verify_app() {
+    use clap::CommandFactory;
+    Command::command().debug_assert()
+}
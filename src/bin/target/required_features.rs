@@ -0,0 +1,146 @@
+use std::path::PathBuf;
+
+use cargo_edit::{
+    backup_dir, find, manifest_from_pkgid, set_cargo_flags, set_hook_command, set_output_config,
+    shell_status, shell_warn, CargoFlags, CargoResult, ColorWhen, LocalManifest, OutputConfig,
+    TargetKind, WriteOptions,
+};
+use clap::Args;
+
+/// Replace the features a target is only built with
+#[derive(Debug, Args)]
+#[clap(version)]
+#[clap(after_help = "\
+Without features, `required-features` is removed. A target without a table of its own, that cargo \
+finds from its source, gets one.")]
+pub struct RequiredFeaturesArgs {
+    /// Name of the target
+    #[clap(value_name = "NAME")]
+    name: String,
+
+    /// Features to require, none to remove `required-features`
+    #[clap(value_name = "FEATURE")]
+    features: Vec<String>,
+
+    /// Kind of target
+    #[clap(
+        long,
+        value_name = "KIND",
+        default_value = "bin",
+        possible_values = ["bin", "example", "test", "bench"]
+    )]
+    kind: TargetKind,
+
+    /// Path to the manifest to change
+    #[clap(long, value_name = "PATH", action)]
+    manifest_path: Option<PathBuf>,
+
+    /// Package id of the crate to change
+    #[clap(long = "package", short = 'p', value_name = "PKGID")]
+    pkgid: Option<String>,
+
+    /// Print changes to be made without making them.
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Fail on symlinked manifests instead of writing to their target
+    #[clap(long)]
+    no_follow_symlinks: bool,
+
+    /// Edit vendored crates, which invalidates their checksums
+    #[clap(long)]
+    force_vendored: bool,
+
+    /// Do not print any output in case of success
+    #[clap(long, short)]
+    quiet: bool,
+
+    /// Coloring
+    #[clap(long, value_name = "WHEN", possible_values = ["auto", "always", "never"])]
+    color: Option<ColorWhen>,
+
+    /// Run without accessing the network
+    #[clap(long)]
+    offline: bool,
+
+    /// Require `Cargo.lock` to be up to date
+    #[clap(long)]
+    locked: bool,
+
+    /// Require `Cargo.lock` and the registry cache to be up to date
+    #[clap(long)]
+    frozen: bool,
+
+    /// Override a configuration value
+    #[clap(long, value_name = "KEY=VALUE")]
+    config: Vec<String>,
+
+    /// Unstable (nightly-only) flags
+    #[clap(short = 'Z', value_name = "FLAG", global = true, arg_enum)]
+    unstable_features: Vec<UnstableOptions>,
+}
+
+impl RequiredFeaturesArgs {
+    pub fn exec(self) -> CargoResult<()> {
+        exec(self)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum UnstableOptions {}
+
+/// Main processing function. Allows us to return a `Result` so that `main` can print pretty error
+/// messages.
+fn exec(args: RequiredFeaturesArgs) -> CargoResult<()> {
+    let mut output = OutputConfig::from_env()?;
+    if let Some(color) = args.color {
+        output = output.set_color(color);
+    }
+    set_output_config(output);
+
+    let cargo_flags = args
+        .config
+        .iter()
+        .fold(CargoFlags::new(), |flags, config| flags.add_config(config))
+        .set_offline(args.offline)
+        .set_locked(args.locked)
+        .set_frozen(args.frozen);
+    set_cargo_flags(cargo_flags)?;
+    let write_options = WriteOptions::new()
+        .set_allow_vendored(args.force_vendored)
+        .set_follow_symlinks(!args.no_follow_symlinks)
+        .set_backup_dir(backup_dir());
+    set_hook_command(Some("target"));
+
+    let manifest_path = match &args.pkgid {
+        Some(pkgid) => manifest_from_pkgid(args.manifest_path.as_deref(), pkgid)?
+            .manifest_path
+            .into_std_path_buf(),
+        None => find(args.manifest_path.as_deref())?,
+    };
+    let mut manifest = LocalManifest::try_new(&manifest_path)?;
+    manifest.set_required_features(args.kind, &args.name, &args.features)?;
+    if !args.quiet {
+        let features = if args.features.is_empty() {
+            "no features".to_owned()
+        } else {
+            args.features.join(", ")
+        };
+        shell_status(
+            "Setting",
+            &format!(
+                "{} `{}` to require {}",
+                args.kind.kind_table(),
+                args.name,
+                features
+            ),
+        )?;
+    }
+
+    if args.dry_run {
+        shell_warn("aborting set-required-features due to dry run")?;
+    } else {
+        manifest.write_with(&write_options)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,181 @@
+//! `cargo upgrade --github-output`: report a run to the GitHub Actions workflow running it
+
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+use cargo_edit::{CargoResult, Context};
+use semver::VersionReq;
+
+/// Upgrades of a run, written to `$GITHUB_STEP_SUMMARY` and `$GITHUB_OUTPUT`
+#[derive(Debug, Default)]
+pub struct GithubReport {
+    upgrades: Vec<Upgrade>,
+    changed_files: Vec<PathBuf>,
+}
+
+#[derive(Debug)]
+struct Upgrade {
+    package: String,
+    name: String,
+    old_req: String,
+    new_req: String,
+    breaking: bool,
+}
+
+impl GithubReport {
+    /// Fail early outside of GitHub Actions, rather than after upgrading
+    pub fn new() -> CargoResult<Self> {
+        if std::env::var_os("GITHUB_STEP_SUMMARY").is_none()
+            && std::env::var_os("GITHUB_OUTPUT").is_none()
+        {
+            anyhow::bail!(
+                "`--github-output` writes to `$GITHUB_STEP_SUMMARY` and `$GITHUB_OUTPUT`, which \
+                 GitHub Actions sets, and neither is set"
+            );
+        }
+        Ok(Self::default())
+    }
+
+    /// Record that `package`'s requirement on `name` changed
+    pub fn add_upgrade(&mut self, package: &str, name: &str, old_req: &str, new_req: &str) {
+        self.upgrades.push(Upgrade {
+            package: package.to_owned(),
+            name: name.to_owned(),
+            old_req: old_req.to_owned(),
+            new_req: new_req.to_owned(),
+            breaking: is_breaking(old_req, new_req),
+        });
+    }
+
+    /// Record that the run wrote `path`
+    pub fn add_changed_file(&mut self, path: &Path) {
+        if !self.changed_files.iter().any(|p| p == path) {
+            self.changed_files.push(path.to_owned());
+        }
+    }
+
+    /// Append the Markdown summary and the step outputs to the files GitHub Actions reads them
+    /// from
+    ///
+    /// The outputs are `upgraded`, the number of changed requirements, `breaking`, whether any
+    /// allows no version the old one did, `changed-files`, one path per line relative to the
+    /// current directory, and `summary`, the Markdown summary, for a pull request's body.
+    pub fn write(&self, title: &str) -> CargoResult<()> {
+        let summary = self.summary(title);
+        if let Some(path) = std::env::var_os("GITHUB_STEP_SUMMARY") {
+            append(Path::new(&path), &summary)?;
+        }
+        if let Some(path) = std::env::var_os("GITHUB_OUTPUT") {
+            let cwd = std::env::current_dir()?;
+            let changed_files = self
+                .changed_files
+                .iter()
+                .map(|path| {
+                    pathdiff::diff_paths(path, &cwd)
+                        .unwrap_or_else(|| path.clone())
+                        .display()
+                        .to_string()
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            let mut outputs = format!(
+                "upgraded={}\nbreaking={}\n",
+                self.upgrades.len(),
+                self.upgrades.iter().any(|u| u.breaking)
+            );
+            outputs.push_str(&multiline_output("changed-files", &changed_files));
+            outputs.push_str(&multiline_output("summary", summary.trim_end()));
+            append(Path::new(&path), &outputs)?;
+        }
+        Ok(())
+    }
+
+    fn summary(&self, title: &str) -> String {
+        let mut summary = format!("## {}\n\n", title);
+        if self.upgrades.is_empty() {
+            summary.push_str("All dependencies are up to date.\n");
+            return summary;
+        }
+        let mut packages: Vec<&str> = Vec::new();
+        for upgrade in &self.upgrades {
+            if !packages.contains(&upgrade.package.as_str()) {
+                packages.push(&upgrade.package);
+            }
+        }
+        for package in packages {
+            summary.push_str(&format!(
+                "### {}\n\n| name | old req | new req | note |\n| --- | --- | --- | --- |\n",
+                package
+            ));
+            for upgrade in self.upgrades.iter().filter(|u| u.package == package) {
+                summary.push_str(&format!(
+                    "| {} | {} | {} | {} |\n",
+                    upgrade.name,
+                    upgrade.old_req,
+                    upgrade.new_req,
+                    if upgrade.breaking { "breaking" } else { "" }
+                ));
+            }
+            summary.push('\n');
+        }
+        summary
+    }
+}
+
+/// Whether the version `new_req` was raised to isn't one `old_req` allows
+fn is_breaking(old_req: &str, new_req: &str) -> bool {
+    let (old_req, new_req) = match (VersionReq::parse(old_req), VersionReq::parse(new_req)) {
+        (Ok(old_req), Ok(new_req)) => (old_req, new_req),
+        _ => return false,
+    };
+    new_req.comparators.first().map_or(false, |c| {
+        let version = semver::Version {
+            major: c.major,
+            minor: c.minor.unwrap_or(0),
+            patch: c.patch.unwrap_or(0),
+            pre: c.pre.clone(),
+            build: Default::default(),
+        };
+        !old_req.matches(&version)
+    })
+}
+
+/// A `name<<DELIMITER` output, with a delimiter that isn't in `value`
+fn multiline_output(name: &str, value: &str) -> String {
+    let mut delimiter = format!("CARGO_UPGRADE_{}", std::process::id());
+    while value.contains(&delimiter) {
+        delimiter.push('_');
+    }
+    format!("{}<<{}\n{}\n{}\n", name, delimiter, value, delimiter)
+}
+
+fn append(path: &Path, contents: &str) -> CargoResult<()> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .with_context(|| format!("Failed to write `{}`", path.display()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn breaking_upgrades() {
+        assert!(is_breaking("0.1.1", "0.2"));
+        assert!(is_breaking("1.0", "2.0.0"));
+        assert!(!is_breaking("1.0", "1.4"));
+        assert!(!is_breaking("=1.0.0", "=1.0.0"));
+        assert!(is_breaking("~1.0", "~1.1"));
+    }
+
+    #[test]
+    fn delimiters_are_unique() {
+        let delimiter = format!("CARGO_UPGRADE_{}", std::process::id());
+        let output = multiline_output("summary", &delimiter);
+        assert!(output.starts_with(&format!("summary<<{}_\n", delimiter)));
+        assert!(output.ends_with(&format!("\n{}_\n", delimiter)));
+    }
+}
@@ -12,7 +12,11 @@
 )]
 
 mod cli;
+mod github;
+mod notify;
+mod stats;
 mod upgrade;
+mod watch;
 
 use std::process;
 
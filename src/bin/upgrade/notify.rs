@@ -0,0 +1,77 @@
+//! `cargo upgrade --notify`: summarize the run's report for a webhook or the desktop
+
+use std::path::PathBuf;
+
+use cargo_edit::{
+    send_notification, set_output_sink, shell_warn, CargoResult, NotifyTarget, OutputSink,
+    RecordedEvents, RecordingSink,
+};
+
+/// Sends what the events of a run report to the `--notify` targets
+#[derive(Clone, Debug)]
+pub struct Notifier {
+    targets: Vec<NotifyTarget>,
+    events: RecordedEvents,
+    manifest_path: PathBuf,
+}
+
+impl Notifier {
+    /// Install `sink`, recording its events to notify `targets` about, through the proxy
+    /// configured for `manifest_path`
+    pub fn install(
+        sink: Box<dyn OutputSink>,
+        targets: Vec<NotifyTarget>,
+        manifest_path: PathBuf,
+    ) -> Self {
+        let sink = RecordingSink::new(sink);
+        let events = sink.events();
+        set_output_sink(Box::new(sink));
+        Self {
+            targets,
+            events,
+            manifest_path,
+        }
+    }
+
+    /// Notify about the dependencies reported since the last call, if any
+    ///
+    /// A target that can't be reached is warned about, as the run itself succeeded.
+    pub fn send(&self, title: &str) -> CargoResult<()> {
+        let lines: Vec<_> = self
+            .events
+            .take()
+            .iter()
+            .filter_map(|(kind, data)| describe(kind, data))
+            .collect();
+        if lines.is_empty() {
+            return Ok(());
+        }
+        for target in &self.targets {
+            if let Err(err) = send_notification(target, title, &lines, &self.manifest_path) {
+                shell_warn(&format!("couldn't send a notification: {:#}", err))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn describe(kind: &str, data: &serde_json::Value) -> Option<String> {
+    let field = |name| data.get(name).and_then(|v| v.as_str());
+    match kind {
+        "upgraded-dependency" => Some(format!(
+            "{}: {} {} → {}",
+            field("package")?,
+            field("name")?,
+            field("old_req")?,
+            field("new_req")?
+        )),
+        "outdated-dependency" => Some(format!(
+            "{}: {} {} (latest: {})",
+            field("package")?,
+            field("name")?,
+            field("version_req")?,
+            field("latest")?
+        )),
+        _ => None,
+    }
+}
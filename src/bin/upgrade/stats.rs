@@ -0,0 +1,152 @@
+//! `cargo upgrade --stats`: how far behind their latest versions dependencies are, in aggregate
+
+use std::path::Path;
+
+use cargo_edit::{
+    get_version_lag, shell_event, shell_warn, shell_write_stderr, CargoResult, VersionLag,
+};
+use termcolor::ColorSpec;
+
+/// Upper bounds, in days, of the buckets dependencies are counted in
+const BUCKETS: [u64; 3] = [30, 90, 365];
+
+/// Dependencies counted by how long ago a newer version came out, without naming them
+#[derive(Debug, Default)]
+pub struct UpdateStats {
+    up_to_date: usize,
+    /// One count per bucket, and one for those older than the last
+    behind: [usize; BUCKETS.len() + 1],
+    /// Dependencies whose lag couldn't be measured, like those from other registries
+    unknown: usize,
+    total_days: u64,
+    total_releases: usize,
+}
+
+impl UpdateStats {
+    /// Count a crates.io dependency at `current`, whose newest version is `latest`
+    ///
+    /// Publish dates are looked up on crates.io, so a failed lookup is warned about and counted
+    /// as unknown rather than failing the run.
+    pub fn add(
+        &mut self,
+        crate_name: &str,
+        current: Option<&semver::Version>,
+        latest: &semver::Version,
+        manifest_path: &Path,
+    ) -> CargoResult<()> {
+        let current = match current {
+            Some(current) => current,
+            None => {
+                self.unknown += 1;
+                return Ok(());
+            }
+        };
+        if current >= latest {
+            self.up_to_date += 1;
+            return Ok(());
+        }
+        match get_version_lag(crate_name, current, latest, manifest_path) {
+            Ok(Some(lag)) => self.add_lag(lag),
+            Ok(None) => self.unknown += 1,
+            Err(err) => {
+                shell_warn(&format!(
+                    "couldn't look up when {} was published: {:#}",
+                    crate_name, err
+                ))?;
+                self.unknown += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Count a dependency whose lag can't be measured
+    pub fn add_unknown(&mut self) {
+        self.unknown += 1;
+    }
+
+    fn add_lag(&mut self, lag: VersionLag) {
+        let bucket = BUCKETS
+            .iter()
+            .position(|max| lag.days <= *max)
+            .unwrap_or(BUCKETS.len());
+        self.behind[bucket] += 1;
+        self.total_days += lag.days;
+        self.total_releases += lag.releases;
+    }
+
+    fn outdated(&self) -> usize {
+        self.behind.iter().sum()
+    }
+
+    /// Report the counts, as an `update-stats` event and a table
+    pub fn print(&self) -> CargoResult<()> {
+        let outdated = self.outdated();
+        let (average_days, average_releases) = if outdated == 0 {
+            (0.0, 0.0)
+        } else {
+            (
+                self.total_days as f64 / outdated as f64,
+                self.total_releases as f64 / outdated as f64,
+            )
+        };
+        shell_event(
+            "update-stats",
+            &serde_json::json!({
+                "up_to_date": self.up_to_date,
+                "within_30_days": self.behind[0],
+                "within_90_days": self.behind[1],
+                "within_365_days": self.behind[2],
+                "older": self.behind[3],
+                "unknown": self.unknown,
+                "average_days_behind": average_days,
+                "average_releases_behind": average_releases,
+            }),
+        )?;
+
+        let mut rows = vec![("up to date".to_owned(), self.up_to_date)];
+        rows.extend(
+            BUCKETS
+                .iter()
+                .zip(self.behind)
+                .map(|(max, count)| (format!("≤ {} days behind", max), count)),
+        );
+        rows.push((
+            format!("> {} days behind", BUCKETS[BUCKETS.len() - 1]),
+            self.behind[BUCKETS.len()],
+        ));
+        rows.push(("unknown".to_owned(), self.unknown));
+        let width = rows
+            .iter()
+            .map(|(label, _)| label.chars().count())
+            .max()
+            .unwrap_or_default();
+        let mut table = String::new();
+        for (label, count) in rows {
+            let padding = width - label.chars().count();
+            table.push_str(&format!("{}{} {:>5}\n", label, " ".repeat(padding), count));
+        }
+        if outdated != 0 {
+            table.push_str(&format!(
+                "outdated dependencies are {:.1} releases and {:.0} days behind, on average\n",
+                average_releases, average_days
+            ));
+        }
+        shell_write_stderr(table, &ColorSpec::new())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lags_are_bucketed() {
+        let mut stats = UpdateStats::default();
+        for days in [0, 30, 31, 365, 366] {
+            stats.add_lag(VersionLag { days, releases: 2 });
+        }
+        assert_eq!(stats.behind, [2, 1, 1, 1]);
+        assert_eq!(stats.outdated(), 5);
+        assert_eq!(stats.total_releases, 10);
+    }
+}
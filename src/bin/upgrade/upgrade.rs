@@ -1,17 +1,36 @@
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
-use std::io::Write;
-use std::path::PathBuf;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use cargo_edit::{
-    colorize_stderr, find, get_latest_dependency, registry_url, resolve_manifests, set_dep_version,
-    shell_note, shell_status, shell_warn, shell_write_stderr, update_registry_index, CargoResult,
-    Context, CrateSpec, Dependency, LocalManifest,
+    backup_dir, blocked_crate, cargo_flags, find, find_replacement, find_template_placeholder,
+    get_advisories, get_compatible_dependency, get_latest_dependency,
+    get_latest_published_dependency, get_selected_dependency, is_pinned_req, is_yanked_version,
+    lookup_source, merge_duplicate_dependencies, prefetch_registry_crates, registry_url,
+    resolve_manifests, set_cargo_flags, set_dep_default_features, set_dep_registry,
+    set_dep_version, set_hook_command, set_output_config, set_output_sink, set_timings_enabled,
+    shell_event, shell_note, shell_status, shell_warn, shell_write_stderr, split_build_metadata,
+    take_timings, update_registry_index, update_registry_indexes, verify_upstream,
+    workspace_root_manifest, write_manifest_file, Advisory, BlockLevel, CargoFlags, CargoResult,
+    ColorWhen, Context, CrateSpec, Dependency, IfYanked, JsonLinesSink, Latest, LatestOnChannel,
+    LatestStable, LocalManifest, LookupOptions, MinimalVersion, NotifyTarget, OutputConfig,
+    OutputSink, PackageField, PatchedVersion, PlannedUpgrade, PublishWindow, Source, SupportedBy,
+    TerminalSink, Timing, UpgradePlan, UpgradePolicy, VersionSelector, WriteOptions,
+    DEFAULT_BACKUP_DIR, JSON_SCHEMA,
 };
+#[cfg(feature = "git")]
+use cargo_edit::{git_package_version, latest_git_rev, latest_git_tag};
 use clap::Args;
 use indexmap::IndexMap;
 use semver::{Op, VersionReq};
-use termcolor::{Color, ColorSpec, StandardStream, WriteColor};
+use termcolor::{Color, ColorSpec};
+
+use crate::github::GithubReport;
+use crate::notify::Notifier;
+use crate::stats::UpdateStats;
+use crate::watch::{watch, WatchOptions};
 
 /// Upgrade dependency version requirements in Cargo.toml manifest files
 #[derive(Debug, Args)]
@@ -32,6 +51,14 @@ pub struct UpgradeArgs {
     #[clap(long, value_name = "PATH", action)]
     manifest_path: Option<PathBuf>,
 
+    /// Output format for messages
+    #[clap(long, value_name = "FMT", arg_enum, default_value = "human")]
+    message_format: MessageFormat,
+
+    /// Print the JSON Schema of `--message-format json` output, and exit
+    #[clap(long)]
+    schema: bool,
+
     /// Package id of the crate to add this dependency to.
     #[clap(
         long = "package",
@@ -59,30 +86,218 @@ pub struct UpgradeArgs {
     #[clap(long)]
     dry_run: bool,
 
+    /// Fail on symlinked manifests instead of writing to their target
+    #[clap(long)]
+    no_follow_symlinks: bool,
+
+    /// Edit vendored crates, which invalidates their checksums
+    #[clap(long)]
+    force_vendored: bool,
+
+    /// Copy each manifest to this directory, or `.cargo-edit-backups`, before changing it
+    #[clap(
+        long,
+        value_name = "DIR",
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = DEFAULT_BACKUP_DIR
+    )]
+    backup_dir: Option<PathBuf>,
+
+    /// Ask before changing each version requirement.
+    #[clap(long, conflicts_with_all = &["dry_run", "watch"])]
+    confirm: bool,
+
     /// Upgrade dependencies pinned in the manifest.
     #[clap(long)]
     pinned: bool,
 
+    /// Upgrade pre-releases to the newest on their channel, like `-rc`
+    #[clap(long, conflicts_with_all = &["to_lockfile", "minimal", "since", "before", "min_age"])]
+    pre_release_channel: bool,
+
+    /// Upgrade to versions needing a newer Rust than the package's `rust-version`
+    #[clap(long)]
+    ignore_rust_version: bool,
+
+    /// Upgrade to versions on a newer edition than the package's
+    #[clap(long)]
+    ignore_edition: bool,
+
+    /// Registry lookups to run at once, 4 per CPU up to 16 by default
+    #[clap(long, short = 'j', value_name = "N")]
+    jobs: Option<NonZeroUsize>,
+
+    /// Clone git registry indexes without their history, using `git`
+    #[clap(long)]
+    shallow_index: bool,
+
     /// Run without accessing the network
     #[clap(long)]
     offline: bool,
 
+    /// Require `Cargo.lock` and the registry cache to be up to date
+    #[clap(long)]
+    frozen: bool,
+
+    /// Override a configuration value
+    #[clap(long, value_name = "KEY=VALUE")]
+    config: Vec<String>,
+
     /// Upgrade all packages to the version in the lockfile.
     #[clap(long)]
     to_lockfile: bool,
 
+    /// Only upgrade requirements holding back duplicates in the lockfile.
+    #[clap(long, conflicts_with = "to_lockfile")]
+    dedupe: bool,
+
+    /// Lower requirements to the oldest versions they allow, or any version with `all`
+    #[clap(
+        long,
+        value_name = "SCOPE",
+        arg_enum,
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "compatible",
+        conflicts_with_all = &["to_lockfile", "dedupe", "since", "before", "min_age"]
+    )]
+    minimal: Option<MinimalScope>,
+
+    /// Only upgrade dependencies with RustSec advisories, to the oldest version without any
+    #[clap(
+        long,
+        conflicts_with_all = &[
+            "to_lockfile",
+            "dedupe",
+            "minimal",
+            "pre_release_channel",
+            "since",
+            "before",
+            "min_age"
+        ]
+    )]
+    only_direct_security: bool,
+
     /// Crates to exclude and not upgrade.
     #[clap(long)]
     exclude: Vec<String>,
 
+    /// Drop enabled features that the new version no longer has.
+    #[clap(long)]
+    fix_removed_features: bool,
+
+    /// Merge dependencies declared twice in the manifest, which cargo refuses to load.
+    #[clap(long)]
+    fix_duplicates: bool,
+
+    /// Drop build metadata, like `+mycorp.1`, from upgraded requirements.
+    #[clap(long)]
+    strip_build_metadata: bool,
+
+    /// Suggest replacements for deprecated crates.
+    #[clap(long)]
+    suggest_replacements: bool,
+
+    /// Swap deprecated crates for their replacement, if there is only one.
+    #[clap(long)]
+    apply_replacements: bool,
+
+    /// Only consider versions published since this date (YYYY-MM-DD)
+    #[clap(long, value_name = "DATE")]
+    since: Option<String>,
+
+    /// Only consider versions published before this date (YYYY-MM-DD)
+    #[clap(long, value_name = "DATE")]
+    before: Option<String>,
+
+    /// Only consider versions published at least this long ago, like `7d`, `2w` or `12h`
+    #[clap(long, value_name = "AGE")]
+    min_age: Option<String>,
+
+    /// How to handle yanked newest versions
+    #[clap(long, value_name = "MODE", arg_enum, default_value = "next-unyanked")]
+    if_yanked: YankedMode,
+
+    /// Only look crates up by their name, not other `-`/`_` spellings
+    #[clap(long, visible_alias = "no-fuzzy")]
+    exact: bool,
+
+    /// Check versions picked from a crates.io mirror against crates.io
+    #[clap(long, value_name = "MODE", arg_enum)]
+    verify_upstream: Option<VerifyMode>,
+
+    /// Move a crate to another registry, like `foo=internal`
+    #[clap(
+        long,
+        value_name = "DEP=REG",
+        conflicts_with_all = &["watch", "dedupe"]
+    )]
+    registry_for: Vec<RegistryFor>,
+
+    /// Keep compatible requirements whose locked version is yanked
+    #[clap(long)]
+    allow_yanked_current: bool,
+
+    /// Leave dependencies containing template placeholders, like `{{version}}`, alone
+    #[clap(long)]
+    skip_templated: bool,
+
+    /// Move forks pinned by `rev` or `tag` in `[patch]` to their newest commit or tag
+    #[clap(long)]
+    update_patches: bool,
+
     /// Require `Cargo.toml` to be up to date
     #[clap(long)]
     locked: bool,
 
+    /// Coloring
+    #[clap(long, value_name = "WHEN", possible_values = ["auto", "always", "never"])]
+    color: Option<ColorWhen>,
+
     /// Use verbose output
     #[clap(short, long)]
     verbose: bool,
 
+    /// Only print the per-package summary, not each dependency.
+    #[clap(long, conflicts_with = "verbose")]
+    summary_only: bool,
+
+    /// List the dependencies that were skipped and why.
+    #[clap(long)]
+    show_skipped: bool,
+
+    /// Summarize how far behind their latest versions dependencies are
+    #[clap(long, conflicts_with_all = &["watch", "dedupe"])]
+    stats: bool,
+
+    /// Report how long index updates, lookups, and writes took
+    #[clap(long)]
+    timings: bool,
+
+    /// Keep reporting dependencies with newer versions, instead of upgrading them
+    #[clap(
+        long,
+        conflicts_with_all = &["dry_run", "to_lockfile", "dedupe", "minimal", "dependency"]
+    )]
+    watch: bool,
+
+    /// Seconds between registry updates with `--watch`
+    #[clap(long, value_name = "SECS", default_value = "3600", requires = "watch")]
+    interval: u64,
+
+    /// Write the outdated dependencies to this JSON file with `--watch`
+    #[clap(long, value_name = "PATH", requires = "watch")]
+    status_file: Option<PathBuf>,
+
+    /// Send a summary to a webhook URL, or `desktop`
+    #[clap(long, value_name = "TARGET")]
+    notify: Vec<NotifyTarget>,
+
+    /// Write a summary and step outputs for GitHub Actions
+    #[clap(long, conflicts_with_all = &["watch", "dedupe"])]
+    github_output: bool,
+
     /// Unstable (nightly-only) flags
     #[clap(short = 'Z', value_name = "FLAG", global = true, arg_enum)]
     unstable_features: Vec<UnstableOptions>,
@@ -90,13 +305,62 @@ pub struct UpgradeArgs {
 
 impl UpgradeArgs {
     pub fn exec(self) -> CargoResult<()> {
-        exec(self)
+        let timings = self.timings;
+        set_timings_enabled(timings);
+        let result = exec(self);
+        if timings {
+            print_timings(&take_timings())?;
+        }
+        result
     }
 
     fn workspace(&self) -> bool {
         self.all || self.workspace
     }
 
+    /// How the upgraded manifests are written
+    fn write_options(&self) -> WriteOptions {
+        WriteOptions::new()
+            .set_allow_vendored(self.force_vendored)
+            .set_follow_symlinks(!self.no_follow_symlinks)
+            .set_backup_dir(self.backup_dir.clone().or_else(backup_dir))
+    }
+
+    /// How the versions to upgrade to are looked up
+    fn lookup_options(&self) -> LookupOptions {
+        LookupOptions::new()
+            .set_fuzzy(!self.exact)
+            .set_if_yanked(self.if_yanked.into())
+    }
+
+    /// How registry indexes are updated and read ahead of the lookups
+    ///
+    /// Offline, lookups only read local files, so one at a time is enough.
+    fn index_options(&self) -> LookupOptions {
+        let jobs = if self.offline {
+            NonZeroUsize::new(1)
+        } else {
+            self.jobs
+        };
+        LookupOptions::new()
+            .set_jobs(jobs)
+            .set_shallow_index(self.shallow_index)
+    }
+
+    fn publish_window(&self) -> CargoResult<PublishWindow> {
+        let mut window = PublishWindow::new();
+        if let Some(since) = &self.since {
+            window = window.set_since(since)?;
+        }
+        if let Some(before) = &self.before {
+            window = window.set_before(before)?;
+        }
+        if let Some(min_age) = &self.min_age {
+            window = window.set_min_age(min_age)?;
+        }
+        Ok(window)
+    }
+
     fn resolve_targets(&self) -> CargoResult<Vec<cargo_metadata::Package>> {
         resolve_manifests(
             self.manifest_path.as_deref(),
@@ -117,247 +381,1726 @@ impl UpgradeArgs {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
-enum UnstableOptions {}
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum UnstableOptions {
+    // Artifact dependencies, which cargo needs to read manifests using them
+    Bindeps,
+}
+
+impl UnstableOptions {
+    /// The flag cargo takes for it, after `-Z`
+    fn cargo_flag(self) -> &'static str {
+        match self {
+            Self::Bindeps => "bindeps",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum MinimalScope {
+    Compatible,
+    All,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum YankedMode {
+    Error,
+    Warn,
+    NextUnyanked,
+}
+
+impl From<YankedMode> for IfYanked {
+    fn from(mode: YankedMode) -> Self {
+        match mode {
+            YankedMode::Error => Self::Error,
+            YankedMode::Warn => Self::Warn,
+            YankedMode::NextUnyanked => Self::NextUnyanked,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum VerifyMode {
+    Warn,
+    Error,
+}
+
+/// A `--registry-for` mapping of a dependency to the registry it moves to
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct RegistryFor {
+    dependency: String,
+    /// `None` for crates.io
+    registry: Option<String>,
+}
+
+impl std::str::FromStr for RegistryFor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> CargoResult<Self> {
+        let (dependency, registry) = s
+            .split_once('=')
+            .filter(|(d, r)| !d.is_empty() && !r.is_empty())
+            .with_context(|| format!("Expected `DEPENDENCY=REGISTRY`, got `{}`", s))?;
+        Ok(Self {
+            dependency: dependency.to_owned(),
+            registry: Some(registry)
+                .filter(|r| *r != "crates-io")
+                .map(str::to_owned),
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum MessageFormat {
+    Human,
+    Json,
+}
+
+/// Main processing function. Allows us to return a `Result` so that `main` can print pretty error
+/// messages.
+fn exec(mut args: UpgradeArgs) -> CargoResult<()> {
+    if args.schema {
+        print!("{}", JSON_SCHEMA);
+        return Ok(());
+    }
+
+    let mut output = OutputConfig::from_env()?;
+    if let Some(color) = args.color {
+        output = output.set_color(color);
+    }
+    set_output_config(output);
+
+    let cargo_flags = args
+        .config
+        .iter()
+        .fold(CargoFlags::new(), |flags, config| flags.add_config(config))
+        .set_offline(args.offline)
+        .set_locked(args.locked)
+        .set_frozen(args.frozen);
+    let cargo_flags = args
+        .unstable_features
+        .iter()
+        .fold(cargo_flags, |flags, feature| {
+            flags.add_unstable(feature.cargo_flag())
+        });
+    set_cargo_flags(cargo_flags.clone())?;
+    let write_options = args.write_options();
+    set_hook_command(Some("upgrade"));
+    // `--frozen` and `net.offline` config imply the flags checked from here on
+    let manifest_path = find(args.manifest_path.as_deref())?;
+    args.offline = cargo_flags.offline(&manifest_path)?;
+    args.locked = cargo_flags.locked();
+    let index_options = args.index_options();
+    let lookup_options = args.lookup_options();
+
+    let sink: Box<dyn OutputSink> = match args.message_format {
+        MessageFormat::Human => Box::new(TerminalSink),
+        MessageFormat::Json => Box::new(JsonLinesSink::default()),
+    };
+    // Notifications summarize the same events `--message-format json` reports
+    let notifier = if args.notify.is_empty() {
+        set_output_sink(sink);
+        None
+    } else {
+        Some(Notifier::install(
+            sink,
+            args.notify.clone(),
+            manifest_path.clone(),
+        ))
+    };
+
+    let mut github = if args.github_output {
+        Some(GithubReport::new()?)
+    } else {
+        None
+    };
+
+    if args.all {
+        deprecated_message("The flag `--all` has been deprecated in favor of `--workspace`")?;
+    }
+
+    if args.fix_duplicates {
+        let manifest_path = find(args.manifest_path.as_deref())?;
+        if fix_duplicates(&manifest_path, args.dry_run, &write_options)? && args.dry_run {
+            // The rest needs the merged manifest
+            shell_warn("aborting upgrade due to dry run")?;
+            return Ok(());
+        }
+    }
+
+    if args.update_patches {
+        if args.offline {
+            anyhow::bail!(
+                "`--update-patches` looks up git repositories, which `--offline` prevents"
+            );
+        }
+        update_patches(args.manifest_path.as_deref(), args.dry_run, &write_options)?;
+    }
+
+    // `--watch` updates the indexes it needs itself
+    if !args.offline && !args.to_lockfile && !args.watch {
+        let url = registry_url(&manifest_path, None)?;
+        update_registry_index(&url, &index_options, &manifest_path, false)?;
+    }
+
+    if args.offline && args.verify_upstream.is_some() {
+        anyhow::bail!(
+            "`--verify-upstream` looks versions up on crates.io, which `--offline` prevents"
+        );
+    }
+    let publish_window = args.publish_window()?;
+    if args.offline && !publish_window.is_unbounded() {
+        anyhow::bail!("`--since`, `--before` and `--min-age` look up publish dates on crates.io, which `--offline` prevents");
+    }
+    if args.offline && args.only_direct_security {
+        anyhow::bail!(
+            "`--only-direct-security` looks advisories up on osv.dev, which `--offline` prevents"
+        );
+    }
+    if args.offline && args.stats {
+        anyhow::bail!("`--stats` looks up publish dates on crates.io, which `--offline` prevents");
+    }
+    let stats = if args.stats {
+        Some(UpdateStats::default())
+    } else {
+        None
+    };
+    let manifests = args.resolve_targets()?;
+    if args.watch {
+        return watch(
+            &manifests,
+            &WatchOptions {
+                interval: Duration::from_secs(args.interval),
+                status_file: args.status_file.clone(),
+                offline: args.offline,
+                exclude: args.exclude.clone(),
+                notifier,
+                manifest_path: manifest_path.clone(),
+                index_options,
+            },
+        );
+    }
+    if !args.skip_templated {
+        check_templated(&manifests)?;
+    }
+    check_blocked(&manifests)?;
+    let locked = load_lockfile(&manifests).unwrap_or_default();
+    if args.dedupe {
+        return dedupe(&args, &manifests, &locked);
+    }
+    let patched = patched_dependencies(args.manifest_path.as_deref())?;
+    // Looking for pre-releases costs an extra index query per dependency, so only do it when
+    // someone will see the result
+    let report_skipped = args.show_skipped || args.message_format == MessageFormat::Json;
+
+    let selected_dependencies = args
+        .dependency
+        .iter()
+        .map(|name| {
+            let spec = CrateSpec::resolve(name)?;
+            Ok((spec.name, spec.version_req))
+        })
+        .collect::<CargoResult<IndexMap<_, _>>>()?;
+    let mut processed_keys = BTreeSet::new();
+
+    // Unknown registries fail before anything is looked up
+    let manifest_path = find(args.manifest_path.as_deref())?;
+    let moved_registries = args
+        .registry_for
+        .iter()
+        .filter_map(|r| r.registry.as_deref())
+        .map(|registry| registry_url(&manifest_path, Some(registry)))
+        .collect::<CargoResult<Vec<_>>>()?;
+    let mut updated_registries = BTreeSet::new();
+    // Update every alternative registry once, together, rather than as each comes up
+    if !args.offline && !args.to_lockfile {
+        let mut registries = alternative_registries(&manifests)?;
+        for url in moved_registries {
+            if !registries.contains(&url) {
+                registries.push(url);
+            }
+        }
+        update_registry_indexes(&registries, &index_options, &manifest_path, false)?;
+        updated_registries.extend(registries);
+    }
+    // Reading each registry once is much faster than a lookup per dependency in large workspaces
+    for (registry, crate_names) in registry_crates(&manifests)? {
+        prefetch_registry_crates(&registry, &crate_names, &index_options, &manifest_path);
+    }
+    let mut any_crate_modified = false;
+    let mut upgrader = Upgrader {
+        args: &args,
+        lookup_options,
+        index_options,
+        publish_window,
+        selected_dependencies: &selected_dependencies,
+        patched: &patched,
+        confirmation: if args.confirm {
+            Confirmation::Ask
+        } else {
+            Confirmation::AcceptAll
+        },
+        updated_registries,
+        stats,
+        compatible_present: false,
+        pinned_present: false,
+    };
+    let show_summary = args.summary_only || 1 < manifests.len();
+    let workspace_root =
+        LocalManifest::try_new(&workspace_root_manifest(args.manifest_path.as_deref())?)?;
+    for package in &manifests {
+        let mut manifest = LocalManifest::try_new(package.manifest_path.as_std_path())?;
+        let manifest_path = manifest.path.clone();
+        let context = PackageContext {
+            manifest_path: manifest_path.clone(),
+            policy: manifest.upgrade_policy()?,
+            // Candidates the package can't build with are skipped, like cargo does
+            rust_version: manifest
+                .package_field(PackageField::RustVersion, &workspace_root)
+                .filter(|_| !args.ignore_rust_version)
+                .map(str::to_owned),
+            edition: manifest
+                .package_field(PackageField::Edition, &workspace_root)
+                .filter(|_| !args.ignore_edition)
+                .map(str::to_owned),
+        };
+        let public_deps_enabled = manifest.has_cargo_feature("public-dependency");
+        let bindeps_enabled = cargo_flags.unstable(&manifest.path, "bindeps")?;
+        let mut replaced = Vec::new();
+        let mut moved = Vec::new();
+        let mut crate_modified = false;
+        let mut table = Vec::new();
+        let mut summary = Summary::default();
+        let mut skipped = Vec::new();
+        // Requirement changes are planned here, and applied like `plan_upgrade`'s before writing
+        let mut plan = UpgradePlan {
+            manifest_path: manifest_path.clone(),
+            upgrades: Vec::new(),
+            skipped: Vec::new(),
+        };
+        shell_status("Checking", &format!("{}'s dependencies", package.name))?;
+        for (dep_table_path, dep_table) in manifest.get_dependency_tables_with_path_mut() {
+            let dep_kind = dep_table_path
+                .last()
+                .expect("dependency tables have a kind");
+            let mut renamed_keys = Vec::new();
+            for (dep_key, dep_item) in dep_table.iter_mut() {
+                let dep_key = dep_key.get();
+                processed_keys.insert(dep_key.to_owned());
+                if let Some(reason) = upgrader.skip_reason(&context.policy, dep_key, dep_item)? {
+                    summary.skipped += 1;
+                    skipped.push(Skipped::new(dep_key, reason));
+                    continue;
+                }
+                let dependency = match Dependency::from_toml(&manifest_path, dep_key, dep_item) {
+                    Ok(dependency) => dependency,
+                    Err(err) => {
+                        shell_warn(&format!("ignoring {}, unsupported entry: {}", dep_key, err))?;
+                        summary.errored += 1;
+                        skipped.push(Skipped::new(dep_key, SkipReason::Unsupported));
+                        continue;
+                    }
+                };
+                if dependency.public().is_some() && !public_deps_enabled {
+                    shell_warn(&format!(
+                        "{} sets `public`, which requires `cargo-features = [\"public-dependency\"]`",
+                        dependency.toml_key()
+                    ))?;
+                }
+                if dependency.artifact().is_some() && !bindeps_enabled {
+                    shell_warn(&format!(
+                        "{} sets `artifact`, which requires nightly cargo with `-Z bindeps`",
+                        dependency.toml_key()
+                    ))?;
+                }
+                let old_version_req = match upgrader.version_req(&dependency)? {
+                    Ok(version_req) => version_req,
+                    Err(reason) => {
+                        summary.skipped += 1;
+                        skipped.push(Skipped::new(dep_key, reason));
+                        continue;
+                    }
+                };
+
+                if let Some((new_name, new_version_req)) =
+                    upgrader.replacement(&dependency, &manifest_path)?
+                {
+                    set_dep_version(dep_item, &new_version_req)?;
+                    if dependency.rename.is_some() {
+                        if let Some(table) = dep_item.as_table_like_mut() {
+                            table.insert("package", toml_edit::value(new_name));
+                        }
+                    } else {
+                        renamed_keys.push((dep_key.to_owned(), new_name));
+                    }
+                    replaced.push((dependency, new_name, new_version_req));
+                    crate_modified = true;
+                    any_crate_modified = true;
+                    summary.upgraded += 1;
+                    continue;
+                }
+
+                let locked_version =
+                    find_locked_version(&dependency.name, &old_version_req, &locked);
+
+                let is_registry = dependency
+                    .source
+                    .as_ref()
+                    .and_then(|s| s.as_registry())
+                    .is_some();
+                let current_registry = dependency.registry().filter(|r| *r != "crates-io");
+                let moved_to = args
+                    .registry_for
+                    .iter()
+                    .find(|r| r.dependency == dep_key)
+                    .map(|r| r.registry.as_deref())
+                    .filter(|registry| *registry != current_registry);
+                if moved_to.is_some() && !is_registry {
+                    shell_warn(&format!(
+                        "not moving {}, it isn't from a registry",
+                        dependency.toml_key()
+                    ))?;
+                }
+                let moved_to = moved_to.filter(|_| is_registry);
+
+                let versions = if is_registry {
+                    // Moved crates are looked up where they're moving to
+                    upgrader.look_up(
+                        &context,
+                        &dependency,
+                        &old_version_req,
+                        locked_version,
+                        moved_to.unwrap_or(current_registry),
+                    )?
+                } else {
+                    Versions {
+                        locked: locked_version,
+                        ..Versions::default()
+                    }
+                };
+                let lookup_failed = versions.lookup_failed;
+                let (dep, outcome) = upgrader.decide(
+                    &context,
+                    &dep_table_path,
+                    dep_key,
+                    &dependency,
+                    old_version_req,
+                    versions,
+                )?;
+                match outcome {
+                    Outcome::Upgrade(upgrade) => {
+                        if let Some(enabled) = context.policy.default_features(dep_kind) {
+                            if set_dep_default_features(dep_item, enabled) {
+                                shell_note(&format!(
+                                    "{} default features in {}, following the `default-features` policy",
+                                    if enabled { "enabling" } else { "disabling" },
+                                    dep_key
+                                ))?;
+                            }
+                        }
+                        check_removed_features(
+                            &dependency,
+                            dep_item,
+                            &upgrade.new_req,
+                            &manifest_path,
+                            args.fix_removed_features,
+                        )?;
+                        plan.upgrades.push(upgrade);
+                        crate_modified = true;
+                        any_crate_modified = true;
+                        summary.upgraded += 1;
+                    }
+                    Outcome::Latest => {
+                        summary.latest += 1;
+                        if report_skipped
+                            && has_newer_prerelease(
+                                &dependency,
+                                &dep.old_version_req,
+                                dep.latest_version.as_deref(),
+                                &manifest_path,
+                            )
+                        {
+                            skipped.push(Skipped::new(dep_key, SkipReason::PrereleaseOnly));
+                        }
+                    }
+                    Outcome::Skip(SkipReason::RegistryError) => {
+                        summary.errored += 1;
+                        skipped.push(Skipped::new(dep_key, SkipReason::RegistryError));
+                    }
+                    Outcome::Skip(reason) => {
+                        summary.skipped += 1;
+                        skipped.push(Skipped::new(dep_key, reason));
+                    }
+                }
+                // A crate missing from its new registry stays where it is
+                if let Some(registry) = moved_to.filter(|_| !lookup_failed) {
+                    if set_dep_registry(dep_item, registry) {
+                        moved.push((
+                            dependency.toml_key().to_owned(),
+                            current_registry.unwrap_or("crates-io").to_owned(),
+                            registry.unwrap_or("crates-io").to_owned(),
+                        ));
+                        crate_modified = true;
+                        any_crate_modified = true;
+                    }
+                }
+                table.push(dep);
+            }
+            for (old_key, new_key) in renamed_keys {
+                if let Some(item) = dep_table.remove(&old_key) {
+                    dep_table.insert(new_key, item);
+                }
+            }
+        }
+        for dep in table.iter().filter(|dep| dep.req_changed()) {
+            if let Some(github) = &mut github {
+                github.add_upgrade(
+                    &package.name,
+                    &dep.name,
+                    &dep.old_version_req,
+                    &dep.new_version_req,
+                );
+            }
+            shell_event(
+                "upgraded-dependency",
+                &serde_json::json!({
+                    "package": package.name,
+                    "name": dep.name,
+                    "old_req": dep.old_version_req,
+                    "new_req": dep.new_version_req,
+                }),
+            )?;
+        }
+        if !table.is_empty() && !args.summary_only {
+            print_upgrade(table, args.verbose)?;
+        }
+        for (dependency, new_name, new_version_req) in &replaced {
+            shell_status(
+                "Replacing",
+                &format!(
+                    "{} with {} {}",
+                    dependency.toml_key(),
+                    new_name,
+                    new_version_req
+                ),
+            )?;
+            if dependency
+                .features
+                .as_ref()
+                .map_or(false, |f| !f.is_empty())
+            {
+                shell_note(&format!(
+                    "{}'s features were kept from {}, check they still apply",
+                    new_name, dependency.name
+                ))?;
+            }
+        }
+        for (name, from, to) in &moved {
+            shell_status("Moving", &format!("{} from {} to {}", name, from, to))?;
+            shell_event(
+                "moved-dependency",
+                &serde_json::json!({
+                    "package": package.name,
+                    "name": name,
+                    "old_registry": from,
+                    "new_registry": to,
+                }),
+            )?;
+        }
+        if show_summary {
+            summary.print(&package.name)?;
+        }
+        for dep in &skipped {
+            shell_event(
+                "skipped-dependency",
+                &serde_json::json!({
+                    "package": package.name,
+                    "name": dep.name,
+                    "reason": dep.reason.as_str(),
+                }),
+            )?;
+        }
+        if args.show_skipped && !skipped.is_empty() {
+            print_skipped(&package.name, skipped)?;
+        }
+        if !args.dry_run && !args.locked && crate_modified {
+            plan.apply_to(&mut manifest)?;
+            manifest.write_with(&write_options)?;
+            if let Some(github) = &mut github {
+                github.add_changed_file(&manifest.path);
+            }
+        }
+    }
+
+    if any_crate_modified {
+        if args.locked {
+            anyhow::bail!("cannot upgrade due to `--locked`");
+        } else {
+            load_lockfile(&manifests)?;
+        }
+        if let Some(github) = &mut github {
+            let lockfile = workspace_root.path.with_file_name("Cargo.lock");
+            if !args.dry_run && lockfile.exists() {
+                github.add_changed_file(&lockfile);
+            }
+        }
+    }
+
+    let unused = selected_dependencies
+        .keys()
+        .filter(|k| !processed_keys.contains(k.as_str()))
+        .map(|k| k.as_str())
+        .collect::<Vec<_>>();
+    for moved in &args.registry_for {
+        if !processed_keys.contains(&moved.dependency) {
+            shell_warn(&format!(
+                "not moving {}, no dependency by that name was found",
+                moved.dependency
+            ))?;
+        }
+    }
+    match unused.len() {
+        0 => {}
+        1 => anyhow::bail!("dependency {} doesn't exist", unused.join(", ")),
+        _ => anyhow::bail!("dependencies {} don't exist", unused.join(", ")),
+    }
+
+    if upgrader.pinned_present {
+        shell_note("Re-run with `--pinned` to upgrade pinned version requirements")?;
+    }
+    if upgrader.compatible_present {
+        shell_note("Re-run with `--to-lockfile` to upgrade compatible version requirements")?;
+    }
+
+    if args.dry_run {
+        shell_warn("aborting upgrade due to dry run")?;
+    }
+
+    let title = if args.dry_run {
+        "Dependencies to upgrade"
+    } else {
+        "Upgraded dependencies"
+    };
+    if let Some(stats) = &upgrader.stats {
+        stats.print()?;
+    }
+    if let Some(notifier) = &notifier {
+        notifier.send(title)?;
+    }
+    if let Some(github) = &github {
+        github.write(title)?;
+    }
+
+    Ok(())
+}
+
+/// Decides what each dependency's requirement becomes, and keeps what's learned along the way
+struct Upgrader<'a> {
+    args: &'a UpgradeArgs,
+    lookup_options: LookupOptions,
+    index_options: LookupOptions,
+    publish_window: PublishWindow,
+    selected_dependencies: &'a IndexMap<String, Option<String>>,
+    patched: &'a BTreeSet<String>,
+    confirmation: Confirmation,
+    /// Registries whose index is already up to date
+    updated_registries: BTreeSet<url::Url>,
+    stats: Option<UpdateStats>,
+    compatible_present: bool,
+    pinned_present: bool,
+}
+
+/// What the dependencies of one package are upgraded within
+struct PackageContext {
+    manifest_path: PathBuf,
+    policy: UpgradePolicy,
+    /// Oldest Rust candidates have to support, unless `--ignore-rust-version`
+    rust_version: Option<String>,
+    /// Edition candidates have to support, unless `--ignore-edition`
+    edition: Option<String>,
+}
+
+/// Versions a dependency's new requirement is decided from
+#[derive(Default)]
+struct Versions {
+    locked: Option<String>,
+    /// Newest version that applies, when looked up in a registry
+    latest: Option<String>,
+    locked_yanked: bool,
+    lookup_failed: bool,
+}
+
+/// What becomes of a dependency's requirement
+enum Outcome {
+    /// It changes
+    Upgrade(PlannedUpgrade),
+    /// It stays, already admitting the newest version that applies
+    Latest,
+    /// It stays, for this reason
+    Skip(SkipReason),
+}
+
+impl Upgrader<'_> {
+    /// Why the entry at `dep_key` is left alone, before it's even read
+    fn skip_reason(
+        &self,
+        policy: &UpgradePolicy,
+        dep_key: &str,
+        dep_item: &toml_edit::Item,
+    ) -> CargoResult<Option<SkipReason>> {
+        let args = self.args;
+        let selected = self.selected_dependencies.contains_key(dep_key);
+        if (!self.selected_dependencies.is_empty() && !selected)
+            || args.exclude.contains(&dep_key.to_owned())
+        {
+            args.verbose(|| shell_warn(&format!("ignoring {}, excluded by user", dep_key)))?;
+            return Ok(Some(SkipReason::Excluded));
+        }
+        if policy.is_ignored(dep_key) && !selected {
+            args.verbose(|| {
+                shell_warn(&format!("ignoring {}, listed in `upgrade-ignore`", dep_key))
+            })?;
+            return Ok(Some(SkipReason::Ignored));
+        }
+        if let Some(placeholder) = find_template_placeholder(dep_item) {
+            args.verbose(|| {
+                shell_warn(&format!(
+                    "ignoring {}, templated with `{}`",
+                    dep_key, placeholder
+                ))
+            })?;
+            return Ok(Some(SkipReason::Templated));
+        }
+        Ok(None)
+    }
+
+    /// The requirement of `dependency`, or why it is left alone
+    fn version_req(&self, dependency: &Dependency) -> CargoResult<Result<String, SkipReason>> {
+        let old_version_req = match dependency.version() {
+            Some(version_req) => version_req.to_owned(),
+            None => {
+                self.args.verbose(|| {
+                    let source = dependency
+                        .source()
+                        .map(|s| s.to_string())
+                        .unwrap_or_else(|| "unknown".to_owned());
+                    shell_warn(&format!(
+                        "ignoring {}, source is {}",
+                        dependency.toml_key(),
+                        source,
+                    ))
+                })?;
+                let reason = match dependency.source() {
+                    Some(Source::Git(_)) => SkipReason::Git,
+                    Some(Source::Path(_)) => SkipReason::Path,
+                    Some(Source::Workspace(_)) => SkipReason::Workspace,
+                    _ => SkipReason::Unsupported,
+                };
+                return Ok(Err(reason));
+            }
+        };
+        if self.patched.contains(&dependency.name)
+            && !self
+                .selected_dependencies
+                .contains_key(dependency.toml_key())
+        {
+            // Unlike exclusions, nothing in the command line says why these don't move
+            shell_warn(&format!(
+                "ignoring {}, overridden by `[patch]`",
+                dependency.toml_key()
+            ))?;
+            return Ok(Err(SkipReason::Patched));
+        }
+        Ok(Ok(old_version_req))
+    }
+
+    /// The crate `--apply-replacements` swaps `dependency` for, and its requirement
+    ///
+    /// Superseded crates without a single replacement, or with `--suggest-replacements`, only get
+    /// their alternatives suggested.
+    fn replacement(
+        &self,
+        dependency: &Dependency,
+        manifest_path: &Path,
+    ) -> CargoResult<Option<(&'static str, String)>> {
+        let args = self.args;
+        if !args.suggest_replacements && !args.apply_replacements {
+            return Ok(None);
+        }
+        let replacement = match find_replacement(&dependency.name) {
+            Some(replacement) => replacement,
+            None => return Ok(None),
+        };
+        // Replacements are only known for crates.io
+        let swap_with = replacement
+            .unique_replacement()
+            .filter(|_| args.apply_replacements && dependency.registry().is_none());
+        if let Some(new_name) = swap_with {
+            if let Some(blocked) = blocked_crate(manifest_path, new_name)? {
+                blocked.enforce()?;
+            }
+            let new_version =
+                get_latest_dependency(new_name, &self.lookup_options, manifest_path, None)?
+                    .0
+                    .version()
+                    .expect("registry packages always have a version")
+                    .to_owned();
+            let new_version_req = requirement_like(dependency.version(), &new_version)?;
+            return Ok(Some((new_name, new_version_req)));
+        }
+        shell_warn(&format!(
+            "{} is {}, consider {}",
+            dependency.toml_key(),
+            replacement.reason,
+            replacement
+                .replacements
+                .iter()
+                .map(|r| format!("`{}`", r))
+                .collect::<Vec<_>>()
+                .join(" or "),
+        ))?;
+        Ok(None)
+    }
+
+    /// Look up the newest version of registry `dependency` that applies, in `registry` or the
+    /// default one
+    ///
+    /// A failed lookup is warned about and recorded rather than returned.
+    fn look_up(
+        &mut self,
+        context: &PackageContext,
+        dependency: &Dependency,
+        old_version_req: &str,
+        locked_version: Option<String>,
+        registry: Option<&str>,
+    ) -> CargoResult<Versions> {
+        let args = self.args;
+        let manifest_path = context.manifest_path.as_path();
+        // Update indices for any alternative registries, unless
+        // we're offline.
+        let registry_url = registry
+            .map(|registry| registry_url(manifest_path, Some(registry)))
+            .transpose()?;
+        if !args.offline {
+            if let Some(registry_url) = &registry_url {
+                if self.updated_registries.insert(registry_url.to_owned()) {
+                    update_registry_index(registry_url, &self.index_options, manifest_path, false)?;
+                }
+            }
+        }
+        let is_prerelease = old_version_req.contains('-');
+        let latest_version = if args.only_direct_security {
+            if registry.is_some() {
+                Err(anyhow::format_err!(
+                    "advisories are only available for crates.io"
+                ))
+            } else {
+                get_patched_dependency(
+                    &dependency.name,
+                    old_version_req,
+                    locked_version.as_deref(),
+                    &self.lookup_options,
+                    manifest_path,
+                    registry_url.as_ref(),
+                )
+            }
+        } else if let Some(scope) = args.minimal {
+            get_minimal_dependency(
+                &dependency.name,
+                old_version_req,
+                scope,
+                &self.lookup_options,
+                manifest_path,
+                registry_url.as_ref(),
+            )
+            .map(Some)
+        } else if args.pre_release_channel && is_prerelease {
+            get_channel_dependency(
+                &dependency.name,
+                old_version_req,
+                &self.lookup_options,
+                manifest_path,
+                registry_url.as_ref(),
+            )
+            .map(Some)
+        } else if self.publish_window.is_unbounded() {
+            let selector: &dyn VersionSelector = if is_prerelease {
+                &Latest
+            } else {
+                &LatestStable
+            };
+            get_selected_dependency(
+                &dependency.name,
+                &SupportedBy::new(selector)
+                    .set_rust_version(context.rust_version.as_deref())
+                    .set_edition(context.edition.as_deref()),
+                None,
+                &self.lookup_options,
+                manifest_path,
+                registry_url.as_ref(),
+            )
+            .map(Some)
+        } else if registry.is_some() {
+            Err(anyhow::format_err!(
+                "publish dates are only available for crates.io"
+            ))
+        } else {
+            get_latest_published_dependency(
+                &dependency.name,
+                is_prerelease,
+                &self.publish_window,
+                manifest_path,
+            )
+        }
+        .map(|d| {
+            d.map(|d| {
+                d.version()
+                    .expect("registry packages always have a version")
+                    .to_owned()
+            })
+        });
+        let mut locked_yanked = false;
+        let locked_semver = locked_version.as_deref().map(str::parse::<semver::Version>);
+        if let (Ok(_), Some(Ok(version))) = (&latest_version, locked_semver) {
+            locked_yanked = is_yanked_version(
+                &dependency.name,
+                &version,
+                manifest_path,
+                registry_url.as_ref(),
+            )?;
+            if locked_yanked {
+                shell_warn(&format!(
+                    "{} is locked at {}, which has been yanked",
+                    dependency.toml_key(),
+                    version
+                ))?;
+            }
+        }
+        let mut lookup_failed = false;
+        let latest_version = match latest_version {
+            Ok(latest_version) => latest_version,
+            Err(err) => {
+                shell_warn(&format!("ignoring {}, {}", dependency.toml_key(), err))?;
+                lookup_failed = true;
+                None
+            }
+        };
+        args.verbose(|| {
+            let registry = match &registry_url {
+                Some(registry) => registry.clone(),
+                None => cargo_edit::registry_url(manifest_path, None)?,
+            };
+            match lookup_source(&registry, &dependency.name) {
+                Some(source) => shell_note(&format!(
+                    "{} resolved from {}",
+                    dependency.toml_key(),
+                    source
+                )),
+                None => Ok(()),
+            }
+        })?;
+        if let (Some(mode), Some(version), None) = (args.verify_upstream, &latest_version, registry)
+        {
+            check_upstream(dependency, version, manifest_path, mode)?;
+        }
+        if let Some(stats) = &mut self.stats {
+            // Publish dates are only known for crates.io
+            match (&latest_version, registry) {
+                (Some(latest), None) => {
+                    let current = locked_version
+                        .as_deref()
+                        .and_then(|v| v.parse::<semver::Version>().ok());
+                    stats.add(
+                        &dependency.name,
+                        current.as_ref(),
+                        &latest.parse()?,
+                        manifest_path,
+                    )?;
+                }
+                _ => stats.add_unknown(),
+            }
+        }
+        Ok(Versions {
+            locked: locked_version,
+            latest: latest_version,
+            locked_yanked,
+            lookup_failed,
+        })
+    }
+
+    /// Decide what the requirement of `dependency`, at `dep_key` in `dep_table_path`, becomes
+    ///
+    /// Returns the row to report it with, and the planned change or why there is none.
+    fn decide(
+        &mut self,
+        context: &PackageContext,
+        dep_table_path: &[String],
+        dep_key: &str,
+        dependency: &Dependency,
+        old_version_req: String,
+        versions: Versions,
+    ) -> CargoResult<(Dep, Outcome)> {
+        let args = self.args;
+        let mut reason = None;
+        // Pins from the manifest's policy apply even with `--pinned`
+        let policy_pin = context
+            .policy
+            .pin
+            .get(dep_key)
+            .filter(|_| !self.selected_dependencies.contains_key(dep_key));
+        if policy_pin.is_some() {
+            reason = Some(Reason::Pinned);
+        } else if !args.pinned {
+            if dependency.rename.is_some() {
+                reason.get_or_insert(Reason::Pinned);
+                self.pinned_present = true;
+            }
+
+            if is_pinned_req(&old_version_req) {
+                reason.get_or_insert(Reason::Pinned);
+                self.pinned_present = true;
+            }
+        }
+
+        let new_version_req = if let Some(pin) = policy_pin {
+            pin.clone()
+        } else if reason.is_some() {
+            old_version_req.clone()
+        } else if let Some(Some(new_version_req)) =
+            self.selected_dependencies.get(dependency.toml_key())
+        {
+            new_version_req.to_owned()
+        } else {
+            self.upgraded_requirement(&old_version_req, &versions, &mut reason)?
+                .unwrap_or_else(|| old_version_req.clone())
+        };
+        let new_version_req = if args.strip_build_metadata && new_version_req != old_version_req {
+            split_build_metadata(&new_version_req).0
+        } else {
+            new_version_req
+        };
+        let new_version_req = if new_version_req == old_version_req
+            || self
+                .confirmation
+                .confirm(dep_key, &old_version_req, &new_version_req)?
+        {
+            new_version_req
+        } else {
+            reason.get_or_insert(Reason::Declined);
+            old_version_req.clone()
+        };
+        if new_version_req == old_version_req {
+            reason.get_or_insert(Reason::Unchanged);
+        }
+
+        let outcome = if new_version_req != old_version_req {
+            Outcome::Upgrade(PlannedUpgrade {
+                table: dep_table_path.to_vec(),
+                name: dep_key.to_owned(),
+                old_req: old_version_req.clone(),
+                new_req: new_version_req.clone(),
+                latest: versions
+                    .latest
+                    .clone()
+                    .unwrap_or_else(|| new_version_req.clone()),
+                breaking: versions.latest.as_deref().map_or(false, |latest| {
+                    !old_version_compatible(&old_version_req, latest)
+                }),
+            })
+        } else if versions.lookup_failed {
+            Outcome::Skip(SkipReason::RegistryError)
+        } else if reason == Some(Reason::Pinned) {
+            Outcome::Skip(SkipReason::Pinned)
+        } else if reason == Some(Reason::Declined) {
+            Outcome::Skip(SkipReason::Declined)
+        } else {
+            Outcome::Latest
+        };
+        let dep = Dep {
+            name: dependency.toml_key().to_owned(),
+            old_version_req,
+            locked_version: versions.locked,
+            latest_version: versions.latest,
+            new_version_req,
+            reason,
+        };
+        Ok((dep, outcome))
+    }
+
+    /// The requirement `old_version_req` moves to, for `--to-lockfile`, `--only-direct-security`
+    /// or the newest version, or `None` when it stays
+    fn upgraded_requirement(
+        &mut self,
+        old_version_req: &str,
+        versions: &Versions,
+        reason: &mut Option<Reason>,
+    ) -> CargoResult<Option<String>> {
+        let args = self.args;
+        if args.to_lockfile {
+            let locked_version = match &versions.locked {
+                Some(locked_version) => locked_version,
+                None => return Ok(None),
+            };
+            let new_version: semver::Version = locked_version.parse()?;
+            return Ok(
+                match cargo_edit::upgrade_requirement(old_version_req, &new_version) {
+                    Ok(Some(version_req)) => Some(version_req),
+                    Err(_) => Some(locked_version.clone()),
+                    _ => None,
+                },
+            );
+        }
+        if args.only_direct_security {
+            return versions
+                .latest
+                .as_deref()
+                .map(|version| patched_requirement(old_version_req, version))
+                .transpose();
+        }
+        let latest_version = match &versions.latest {
+            Some(latest_version) => latest_version,
+            None => return Ok(None),
+        };
+
+        let mut new_version_req = latest_version.clone();
+        let new_version: semver::Version = latest_version.parse()?;
+        // Builds against a yanked version break once the lockfile is regenerated
+        let escape_yanked = versions.locked_yanked
+            && args.minimal.is_none()
+            && !args.allow_yanked_current
+            && versions
+                .locked
+                .as_deref()
+                .and_then(|v| v.parse::<semver::Version>().ok())
+                .map_or(false, |locked| locked < new_version);
+        match cargo_edit::upgrade_requirement(old_version_req, &new_version) {
+            Ok(Some(version_req)) => {
+                new_version_req = version_req;
+            }
+            Err(_) => {}
+            // Raising the minimum is the only way past the yanked version
+            _ if escape_yanked => {}
+            _ => {
+                new_version_req = old_version_req.to_owned();
+            }
+        }
+        let compatible =
+            args.minimal.is_none() && old_version_compatible(old_version_req, latest_version);
+        if new_version_req == old_version_req {
+            // Requirements like `>0.2` already admit the latest version as they are
+            if compatible && has_open_lower_bound(old_version_req) {
+                reason.get_or_insert(Reason::Compatible);
+                self.compatible_present = true;
+            }
+            Ok(None)
+        } else if compatible && !escape_yanked {
+            reason.get_or_insert(Reason::Compatible);
+            self.compatible_present = true;
+            Ok(None)
+        } else {
+            Ok(Some(new_version_req))
+        }
+    }
+}
+
+/// Upgrade direct dependencies that hold back an older copy of a crate in the lockfile
+///
+/// Requirements are raised to the newest copy already in the lockfile, rather than the latest
+/// release, so the duplicates collapse.  Copies still required by other packages are reported.
+fn dedupe(
+    args: &UpgradeArgs,
+    manifests: &[cargo_metadata::Package],
+    locked: &[cargo_metadata::Package],
+) -> CargoResult<()> {
+    let duplicates = find_duplicates(locked);
+    if duplicates.is_empty() {
+        shell_note("No duplicate crates in the lockfile")?;
+        return Ok(());
+    }
+    let selected_dependencies = args
+        .dependency
+        .iter()
+        .map(|name| Ok(CrateSpec::resolve(name)?.name))
+        .collect::<CargoResult<BTreeSet<_>>>()?;
+
+    // (package, dependency) pairs that no longer require an older copy
+    let mut deduped = BTreeSet::new();
+    for package in manifests {
+        let mut manifest = LocalManifest::try_new(package.manifest_path.as_std_path())?;
+        let policy = manifest.upgrade_policy()?;
+        let manifest_path = manifest.path.clone();
+        let mut crate_modified = false;
+        shell_status("Checking", &format!("{}'s dependencies", package.name))?;
+        for dep_table in manifest.get_dependency_tables_mut() {
+            for (dep_key, dep_item) in dep_table.iter_mut() {
+                let dep_key = dep_key.get();
+                if (!selected_dependencies.is_empty() && !selected_dependencies.contains(dep_key))
+                    || args.exclude.iter().any(|e| e == dep_key)
+                {
+                    continue;
+                }
+                if (policy.is_ignored(dep_key) || policy.pin.contains_key(dep_key))
+                    && !selected_dependencies.contains(dep_key)
+                {
+                    continue;
+                }
+                if find_template_placeholder(dep_item).is_some() {
+                    continue;
+                }
+                let dependency = match Dependency::from_toml(&manifest_path, dep_key, dep_item) {
+                    Ok(dependency) => dependency,
+                    Err(_) => continue,
+                };
+                let newest = match duplicates.get(&dependency.name).and_then(|v| v.last()) {
+                    Some(newest) => newest,
+                    None => continue,
+                };
+                let old_version_req = match dependency.version() {
+                    Some(version_req) => version_req.to_owned(),
+                    None => continue,
+                };
+                if old_version_compatible(&old_version_req, &newest.to_string()) {
+                    continue;
+                }
+                if !args.pinned && (dependency.rename.is_some() || is_pinned_req(&old_version_req))
+                {
+                    continue;
+                }
+
+                let new_version_req =
+                    match cargo_edit::upgrade_requirement(&old_version_req, newest) {
+                        Ok(Some(version_req)) => version_req,
+                        _ => newest.to_string(),
+                    };
+                set_dep_version(dep_item, &new_version_req)?;
+                shell_status(
+                    "Deduping",
+                    &format!(
+                        "{} {} -> {}",
+                        dependency.toml_key(),
+                        old_version_req,
+                        new_version_req
+                    ),
+                )?;
+                deduped.insert((package.name.clone(), dependency.name.clone()));
+                crate_modified = true;
+            }
+        }
+        if !args.dry_run && !args.locked && crate_modified {
+            manifest.write_with(&args.write_options())?;
+        }
+    }
+
+    for (name, versions) in &duplicates {
+        let (_newest, older) = versions
+            .split_last()
+            .expect("duplicates have several versions");
+        for version in older {
+            let mut dependents = required_by(locked, name, version, versions)
+                .into_iter()
+                .filter(|p| !deduped.contains(&(p.name.clone(), name.clone())))
+                .map(|p| format!("{} {}", p.name, p.version))
+                .collect::<Vec<_>>();
+            if dependents.is_empty() {
+                continue;
+            }
+            dependents.sort();
+            shell_note(&format!(
+                "{} {} is still required by {}",
+                name,
+                version,
+                dependents.join(", ")
+            ))?;
+        }
+    }
+
+    if !deduped.is_empty() {
+        if args.locked {
+            anyhow::bail!("cannot upgrade due to `--locked`");
+        } else if !args.dry_run {
+            load_lockfile(manifests)?;
+        }
+    }
+    if args.dry_run {
+        shell_warn("aborting upgrade due to dry run")?;
+    }
+
+    Ok(())
+}
+
+/// Crates locked at several semver-incompatible versions, with the newest of each, oldest first
+fn find_duplicates(locked: &[cargo_metadata::Package]) -> BTreeMap<String, Vec<semver::Version>> {
+    let mut copies = BTreeMap::<_, BTreeMap<_, semver::Version>>::new();
+    for package in locked {
+        let mut version = package.version.clone();
+        version.build = semver::BuildMetadata::EMPTY;
+        let compatible = match (version.major, version.minor) {
+            (0, 0) => (0, 0, version.patch),
+            (0, minor) => (0, minor, 0),
+            (major, _) => (major, 0, 0),
+        };
+        let newest = copies
+            .entry(package.name.clone())
+            .or_default()
+            .entry(compatible)
+            .or_insert_with(|| version.clone());
+        if *newest < version {
+            *newest = version;
+        }
+    }
+    copies
+        .into_iter()
+        .filter(|(_, versions)| 1 < versions.len())
+        .map(|(name, versions)| (name, versions.into_values().collect()))
+        .collect()
+}
+
+/// Packages whose requirement on `name` resolves to the `version` copy
+fn required_by<'p>(
+    locked: &'p [cargo_metadata::Package],
+    name: &str,
+    version: &semver::Version,
+    versions: &[semver::Version],
+) -> Vec<&'p cargo_metadata::Package> {
+    locked
+        .iter()
+        .filter(|p| {
+            p.dependencies.iter().any(|dep| {
+                // Dev-dependencies of dependencies aren't part of the build
+                let is_built =
+                    dep.kind != cargo_metadata::DependencyKind::Development || p.source.is_none();
+                is_built
+                    && dep.name == name
+                    && versions.iter().rev().find(|v| dep.req.matches(v)) == Some(version)
+            })
+        })
+        .collect()
+}
 
-/// Main processing function. Allows us to return a `Result` so that `main` can print pretty error
-/// messages.
-fn exec(args: UpgradeArgs) -> CargoResult<()> {
-    if args.all {
-        deprecated_message("The flag `--all` has been deprecated in favor of `--workspace`")?;
+/// Write `version` with the precision and operator of `old_version_req`
+///
+/// For example, `0.3` and `3.2.0` make `3.2`.
+fn requirement_like(old_version_req: Option<&str>, version: &str) -> CargoResult<String> {
+    let old_version_req = match old_version_req {
+        Some(old_version_req) => old_version_req,
+        None => return Ok(version.to_owned()),
+    };
+    let parsed: semver::Version = version.parse()?;
+    match cargo_edit::upgrade_requirement(old_version_req, &parsed) {
+        Ok(Some(version_req)) => Ok(version_req),
+        Ok(None) => Ok(old_version_req.to_owned()),
+        Err(_) => Ok(version.to_owned()),
     }
+}
 
-    if !args.offline && !args.to_lockfile {
-        let url = registry_url(&find(args.manifest_path.as_deref())?, None)?;
-        update_registry_index(&url, false)?;
+/// Refuse to edit manifests with templated dependencies, which the edits could corrupt
+fn check_templated(manifests: &[cargo_metadata::Package]) -> CargoResult<()> {
+    for package in manifests {
+        let manifest = LocalManifest::try_new(package.manifest_path.as_std_path())?;
+        if let Some((dep_key, placeholder)) = manifest.templated_dependencies().into_iter().next() {
+            anyhow::bail!(
+                "`{}` in `{}` contains the template placeholder `{}`, pass `--skip-templated` to leave templated dependencies alone",
+                dep_key,
+                package.manifest_path,
+                placeholder
+            );
+        }
     }
+    Ok(())
+}
 
-    let manifests = args.resolve_targets()?;
-    let locked = load_lockfile(&manifests, args.locked, args.offline).unwrap_or_default();
-
-    let selected_dependencies = args
-        .dependency
-        .iter()
-        .map(|name| {
-            let spec = CrateSpec::resolve(name)?;
-            Ok((spec.name, spec.version_req))
-        })
-        .collect::<CargoResult<IndexMap<_, _>>>()?;
-    let mut processed_keys = BTreeSet::new();
-
-    let mut updated_registries = BTreeSet::new();
-    let mut any_crate_modified = false;
-    let mut compatible_present = false;
-    let mut pinned_present = false;
-    for package in &manifests {
+/// Refuse to upgrade manifests depending on crates denied by `[cargo-edit.blocked-crates]`, and
+/// warn about the rest of the crates listed there
+fn check_blocked(manifests: &[cargo_metadata::Package]) -> CargoResult<()> {
+    let mut denied = Vec::new();
+    for package in manifests {
         let mut manifest = LocalManifest::try_new(package.manifest_path.as_std_path())?;
-        let mut crate_modified = false;
-        let mut table = Vec::new();
         let manifest_path = manifest.path.clone();
-        shell_status("Checking", &format!("{}'s dependencies", package.name))?;
         for dep_table in manifest.get_dependency_tables_mut() {
-            for (dep_key, dep_item) in dep_table.iter_mut() {
-                let dep_key = dep_key.get();
-                processed_keys.insert(dep_key.to_owned());
-                if !selected_dependencies.is_empty() && !selected_dependencies.contains_key(dep_key)
-                {
-                    args.verbose(|| {
-                        shell_warn(&format!("ignoring {}, excluded by user", dep_key))
-                    })?;
-                    continue;
-                }
-                if args.exclude.contains(&dep_key.to_owned()) {
-                    args.verbose(|| {
-                        shell_warn(&format!("ignoring {}, excluded by user", dep_key))
-                    })?;
-                    continue;
-                }
+            for (dep_key, dep_item) in dep_table.iter() {
                 let dependency = match Dependency::from_toml(&manifest_path, dep_key, dep_item) {
                     Ok(dependency) => dependency,
-                    Err(err) => {
-                        shell_warn(&format!("ignoring {}, unsupported entry: {}", dep_key, err))?;
-                        continue;
-                    }
-                };
-                let old_version_req = match dependency.version() {
-                    Some(version_req) => version_req.to_owned(),
-                    None => {
-                        args.verbose(|| {
-                            let source = dependency
-                                .source()
-                                .map(|s| s.to_string())
-                                .unwrap_or_else(|| "unknown".to_owned());
-                            shell_warn(&format!(
-                                "ignoring {}, source is {}",
-                                dependency.toml_key(),
-                                source,
-                            ))
-                        })?;
-                        continue;
-                    }
+                    Err(_) => continue,
                 };
-
-                let mut reason = None;
-                if !args.pinned {
-                    if dependency.rename.is_some() {
-                        reason.get_or_insert(Reason::Pinned);
-                        pinned_present = true;
+                if let Some(blocked) = blocked_crate(&manifest_path, &dependency.name)? {
+                    let message = format!("{}: {}", package.name, blocked);
+                    match blocked.level {
+                        BlockLevel::Deny => denied.push(message),
+                        BlockLevel::Warn => shell_warn(&message)?,
                     }
+                }
+            }
+        }
+    }
+    if !denied.is_empty() {
+        anyhow::bail!("{}", denied.join("\n"));
+    }
+    Ok(())
+}
 
-                    if is_pinned_req(&old_version_req) {
-                        reason.get_or_insert(Reason::Pinned);
-                        pinned_present = true;
+/// Indexes of the alternative registries `manifests` depend on, in order of first use
+fn alternative_registries(manifests: &[cargo_metadata::Package]) -> CargoResult<Vec<url::Url>> {
+    let mut registries = Vec::new();
+    for package in manifests {
+        let mut manifest = LocalManifest::try_new(package.manifest_path.as_std_path())?;
+        let manifest_path = manifest.path.clone();
+        for dep_table in manifest.get_dependency_tables_mut() {
+            for (dep_key, dep_item) in dep_table.iter() {
+                let registry = Dependency::from_toml(&manifest_path, dep_key, dep_item)
+                    .ok()
+                    .and_then(|dependency| dependency.registry().map(|r| r.to_owned()));
+                if let Some(registry) = registry {
+                    let url = registry_url(&manifest_path, Some(&registry))?;
+                    if !registries.contains(&url) {
+                        registries.push(url);
                     }
                 }
+            }
+        }
+    }
+    Ok(registries)
+}
 
-                let locked_version =
-                    find_locked_version(&dependency.name, &old_version_req, &locked);
-
-                let latest_version = if dependency
+/// Names of the registry dependencies of `manifests`, by registry
+fn registry_crates(
+    manifests: &[cargo_metadata::Package],
+) -> CargoResult<Vec<(url::Url, Vec<String>)>> {
+    let mut registries: Vec<(url::Url, Vec<String>)> = Vec::new();
+    for package in manifests {
+        let mut manifest = LocalManifest::try_new(package.manifest_path.as_std_path())?;
+        let manifest_path = manifest.path.clone();
+        for dep_table in manifest.get_dependency_tables_mut() {
+            for (dep_key, dep_item) in dep_table.iter() {
+                let dependency = match Dependency::from_toml(&manifest_path, dep_key, dep_item) {
+                    Ok(dependency) => dependency,
+                    Err(_) => continue,
+                };
+                if dependency
                     .source
                     .as_ref()
                     .and_then(|s| s.as_registry())
-                    .is_some()
-                {
-                    // Update indices for any alternative registries, unless
-                    // we're offline.
-                    let registry_url = dependency
-                        .registry()
-                        .map(|registry| registry_url(&manifest_path, Some(registry)))
-                        .transpose()?;
-                    if !args.offline {
-                        if let Some(registry_url) = &registry_url {
-                            if updated_registries.insert(registry_url.to_owned()) {
-                                update_registry_index(registry_url, false)?;
-                            }
-                        }
-                    }
-                    let is_prerelease = old_version_req.contains('-');
-                    let latest_version = get_latest_dependency(
-                        &dependency.name,
-                        is_prerelease,
-                        &manifest_path,
-                        registry_url.as_ref(),
-                    )
-                    .map(|d| {
-                        d.version()
-                            .expect("registry packages always have a version")
-                            .to_owned()
-                    });
-                    latest_version.ok()
-                } else {
-                    None
-                };
-
-                let new_version_req = if reason.is_some() {
-                    old_version_req.clone()
-                } else if let Some(Some(new_version_req)) =
-                    selected_dependencies.get(dependency.toml_key())
+                    .is_none()
                 {
-                    new_version_req.to_owned()
-                } else {
-                    let new_version_req = if args.to_lockfile {
-                        if let Some(locked_version) = &locked_version {
-                            let new_version_req = locked_version.clone();
-                            let new_version: semver::Version = locked_version.parse()?;
-                            match cargo_edit::upgrade_requirement(&old_version_req, &new_version) {
-                                Ok(Some(version_req)) => Some(version_req),
-                                Err(_) => Some(new_version_req),
-                                _ => None,
-                            }
-                        } else {
-                            None
-                        }
-                    } else if let Some(latest_version) = &latest_version {
-                        let mut new_version_req = latest_version.clone();
-                        let new_version: semver::Version = latest_version.parse()?;
-                        match cargo_edit::upgrade_requirement(&old_version_req, &new_version) {
-                            Ok(Some(version_req)) => {
-                                new_version_req = version_req;
-                            }
-                            Err(_) => {}
-                            _ => {
-                                new_version_req = old_version_req.clone();
-                            }
-                        }
-                        if new_version_req == old_version_req {
-                            None
-                        } else if old_version_compatible(&old_version_req, latest_version) {
-                            reason.get_or_insert(Reason::Compatible);
-                            compatible_present = true;
-                            None
-                        } else {
-                            Some(new_version_req)
-                        }
-                    } else {
-                        None
-                    };
-                    new_version_req.unwrap_or_else(|| old_version_req.clone())
-                };
-                if new_version_req == old_version_req {
-                    reason.get_or_insert(Reason::Unchanged);
+                    continue;
                 }
-                if new_version_req != old_version_req {
-                    set_dep_version(dep_item, &new_version_req)?;
-                    crate_modified = true;
-                    any_crate_modified = true;
+                let url = registry_url(&manifest_path, dependency.registry())?;
+                match registries.iter_mut().find(|(r, _)| *r == url) {
+                    Some((_, names)) => names.push(dependency.name),
+                    None => registries.push((url, vec![dependency.name])),
                 }
-                table.push(Dep {
-                    name: dependency.toml_key().to_owned(),
-                    old_version_req,
-                    locked_version,
-                    latest_version,
-                    new_version_req,
-                    reason,
-                });
             }
         }
-        if !table.is_empty() {
-            print_upgrade(table, args.verbose)?;
-        }
-        if !args.dry_run && !args.locked && crate_modified {
-            manifest.write()?;
-        }
     }
+    Ok(registries)
+}
 
-    if any_crate_modified {
-        if args.locked {
-            anyhow::bail!("cannot upgrade due to `--locked`");
-        } else {
-            load_lockfile(&manifests, args.locked, args.offline)?;
-        }
+/// Look up the oldest version `old_version_req` allows, or any version with [`MinimalScope::All`]
+fn get_minimal_dependency(
+    name: &str,
+    old_version_req: &str,
+    scope: MinimalScope,
+    options: &LookupOptions,
+    manifest_path: &Path,
+    registry: Option<&url::Url>,
+) -> CargoResult<Dependency> {
+    let current = match scope {
+        MinimalScope::Compatible => Some(VersionReq::parse(old_version_req)?),
+        MinimalScope::All => None,
+    };
+    get_selected_dependency(
+        name,
+        &MinimalVersion,
+        current.as_ref(),
+        options,
+        manifest_path,
+        registry,
+    )
+}
+
+/// The oldest version fixing the RustSec advisories against the current version of a crate, if
+/// any affect it
+///
+/// The current version is the locked one, or else the oldest the requirement allows.
+/// Informational advisories, like unmaintained crates, are left out, as no version fixes them.
+fn get_patched_dependency(
+    name: &str,
+    old_version_req: &str,
+    locked_version: Option<&str>,
+    options: &LookupOptions,
+    manifest_path: &Path,
+    registry: Option<&url::Url>,
+) -> CargoResult<Option<Dependency>> {
+    let current: semver::Version = match locked_version {
+        Some(locked_version) => locked_version.parse()?,
+        None => get_minimal_dependency(
+            name,
+            old_version_req,
+            MinimalScope::Compatible,
+            options,
+            manifest_path,
+            registry,
+        )?
+        .version()
+        .expect("registry packages always have a version")
+        .parse()?,
+    };
+    let advisories: Vec<Advisory> = get_advisories(name, manifest_path)?
+        .into_iter()
+        .filter(|advisory| !advisory.informational)
+        .collect();
+    let mut affected = false;
+    for advisory in advisories.iter().filter(|a| a.affects(&current)) {
+        affected = true;
+        let summary = advisory
+            .summary
+            .as_deref()
+            .map(|summary| format!(": {}", summary))
+            .unwrap_or_default();
+        shell_warn(&format!(
+            "{} {} is affected by {}{}",
+            name, current, advisory.id, summary
+        ))?;
     }
+    if !affected {
+        return Ok(None);
+    }
+    get_selected_dependency(
+        name,
+        &PatchedVersion::new(&current, &advisories),
+        None,
+        options,
+        manifest_path,
+        registry,
+    )
+    .map(Some)
+}
 
-    let unused = selected_dependencies
-        .keys()
-        .filter(|k| !processed_keys.contains(k.as_str()))
-        .map(|k| k.as_str())
-        .collect::<Vec<_>>();
-    match unused.len() {
-        0 => {}
-        1 => anyhow::bail!("dependency {} doesn't exist", unused.join(", ")),
-        _ => anyhow::bail!("dependencies {} don't exist", unused.join(", ")),
+/// `old_version_req` moved to exactly `version`, keeping its operator
+///
+/// Ranges like `>=1.0, <2`, and single bounds like `>0.2` or `<0.2`, get `version` as their
+/// minimum and keep their upper bound, raised only when it doesn't admit `version`.
+fn patched_requirement(old_version_req: &str, version: &str) -> CargoResult<String> {
+    let (req, _) = split_build_metadata(old_version_req);
+    let req = VersionReq::parse(&req)?;
+    let op = match req.comparators.as_slice() {
+        [] => Some(""),
+        [comparator] => match comparator.op {
+            Op::Caret if old_version_req.trim_start().starts_with('^') => Some("^"),
+            Op::Caret | Op::Wildcard => Some(""),
+            Op::Tilde => Some("~"),
+            Op::Exact => Some("="),
+            _ => None,
+        },
+        _ => None,
+    };
+    if let Some(op) = op {
+        return Ok(format!("{}{}", op, version));
     }
 
-    if pinned_present {
-        shell_note("Re-run with `--pinned` to upgrade pinned version requirements")?;
+    let version: semver::Version = version.parse()?;
+    let req = if req.matches(&version) {
+        req
+    } else {
+        match cargo_edit::upgrade_requirement(old_version_req, &version)? {
+            Some(upgraded) => VersionReq::parse(&split_build_metadata(&upgraded).0)?,
+            None => req,
+        }
+    };
+    let minimum = semver::Comparator {
+        op: Op::GreaterEq,
+        major: version.major,
+        minor: Some(version.minor),
+        patch: Some(version.patch),
+        pre: version.pre.clone(),
+    };
+    let mut comparators: Vec<_> = req
+        .comparators
+        .into_iter()
+        .filter(|c| !matches!(c.op, Op::Greater | Op::GreaterEq))
+        .collect();
+    comparators.insert(0, minimum);
+    Ok(VersionReq { comparators }.to_string())
+}
+
+/// The newest version on the pre-release channel of `old_version_req`, like `rc`
+fn get_channel_dependency(
+    name: &str,
+    old_version_req: &str,
+    options: &LookupOptions,
+    manifest_path: &Path,
+    registry: Option<&url::Url>,
+) -> CargoResult<Dependency> {
+    let current = VersionReq::parse(old_version_req)?;
+    get_selected_dependency(
+        name,
+        &LatestOnChannel,
+        Some(&current),
+        options,
+        manifest_path,
+        registry,
+    )
+}
+
+/// Merge dependencies declared both as a key and as a table, returning whether there were any
+fn fix_duplicates(
+    manifest_path: &Path,
+    dry_run: bool,
+    write_options: &WriteOptions,
+) -> CargoResult<bool> {
+    let source = std::fs::read_to_string(manifest_path)
+        .with_context(|| format!("Failed to read `{}`", manifest_path.display()))?;
+    let (merged, duplicates) = merge_duplicate_dependencies(&source)?;
+    for duplicate in &duplicates {
+        let table = duplicate.table.join(".");
+        shell_status(
+            "Merging",
+            &format!("duplicate `{}` in `[{}]`", duplicate.name, table),
+        )?;
+        for field in &duplicate.conflicts {
+            shell_warn(&format!(
+                "dropping `{}` from the `{}` key, `[{}.{}]` sets it differently",
+                field, duplicate.name, table, duplicate.name
+            ))?;
+        }
     }
-    if compatible_present {
-        shell_note("Re-run with `--to-lockfile` to upgrade compatible version requirements")?;
+    if !duplicates.is_empty() && !dry_run {
+        write_manifest_file(manifest_path, merged.as_bytes(), write_options)?;
     }
+    Ok(!duplicates.is_empty())
+}
 
-    if args.dry_run {
-        shell_warn("aborting upgrade due to dry run")?;
+#[cfg(not(feature = "git"))]
+fn update_patches(
+    _manifest_path: Option<&Path>,
+    _dry_run: bool,
+    _write_options: &WriteOptions,
+) -> CargoResult<()> {
+    anyhow::bail!("`--update-patches` needs cargo-edit built with the `git` feature")
+}
+
+/// Move git dependencies in `[patch]` pinned by `rev` to the newest commit of the default branch,
+/// and those pinned by `tag` to the newest version tag
+#[cfg(feature = "git")]
+fn update_patches(
+    manifest_path: Option<&Path>,
+    dry_run: bool,
+    write_options: &WriteOptions,
+) -> CargoResult<()> {
+    let root_path = workspace_root_manifest(manifest_path)?;
+    let mut root = LocalManifest::try_new(&root_path)?;
+    let mut modified = false;
+    let sources = root
+        .data
+        .get_mut("patch")
+        .and_then(|p| p.as_table_like_mut());
+    for (_, source) in sources.into_iter().flat_map(|s| s.iter_mut()) {
+        let deps = match source.as_table_like_mut() {
+            Some(deps) => deps,
+            None => continue,
+        };
+        for (key, dep) in deps.iter_mut() {
+            let key = key.get();
+            let table = match dep.as_table_like_mut() {
+                Some(table) => table,
+                None => continue,
+            };
+            let field = |name: &str| table.get(name).and_then(|f| f.as_str()).map(String::from);
+            let url = match field("git") {
+                Some(git) => url::Url::parse(&git)
+                    .with_context(|| format!("Invalid git URL for the `{}` patch", key))?,
+                None => continue,
+            };
+            let crate_name = field("package").unwrap_or_else(|| key.to_owned());
+
+            let (pin, old, new, latest) = if let Some(old) = field("tag") {
+                let old_version = semver::Version::parse(old.strip_prefix('v').unwrap_or(&old));
+                let latest = match latest_git_tag(&url, &root_path)? {
+                    Some(latest) => latest,
+                    None => continue,
+                };
+                let new = latest.tag().expect("listed as a tag").to_owned();
+                let new_version = semver::Version::parse(new.strip_prefix('v').unwrap_or(&new));
+                match (old_version, new_version) {
+                    (Ok(old_version), Ok(new_version)) if old_version < new_version => {}
+                    _ => continue,
+                }
+                ("tag", old, new, latest)
+            } else if let Some(old) = field("rev") {
+                let latest = latest_git_rev(&url, None, &root_path)?;
+                if latest.rev.starts_with(&old) {
+                    continue;
+                }
+                // Keep abbreviated revisions abbreviated
+                let new = latest.rev[..old.len().clamp(7, latest.rev.len())].to_owned();
+                ("rev", old, new, latest)
+            } else {
+                continue;
+            };
+
+            shell_status(
+                "Updating",
+                &format!("`{}` patch from {} `{}` to `{}`", key, pin, old, new),
+            )?;
+            match git_package_version(&url, &latest.name, &crate_name, &root_path) {
+                Ok(Some(version)) => {
+                    shell_note(&format!("`{}` at `{}` is version {}", key, new, version))?
+                }
+                Ok(None) => {}
+                Err(err) => shell_warn(&format!(
+                    "couldn't read the version of `{}` at `{}`: {:#}",
+                    key, new, err
+                ))?,
+            }
+
+            let item = table.get_mut(pin).expect("read above");
+            let mut value = toml_edit::Value::from(new);
+            if let Some(existing) = item.as_value() {
+                *value.decor_mut() = existing.decor().clone();
+            }
+            *item = toml_edit::Item::Value(value);
+            modified = true;
+        }
     }
 
+    if modified && !dry_run {
+        root.write_with(write_options)?;
+    }
     Ok(())
 }
 
-fn load_lockfile(
-    targets: &[cargo_metadata::Package],
-    locked: bool,
-    offline: bool,
-) -> CargoResult<Vec<cargo_metadata::Package>> {
+/// Report how the crates.io mirror differs from crates.io on `version` of `dependency`
+fn check_upstream(
+    dependency: &Dependency,
+    version: &str,
+    manifest_path: &Path,
+    mode: VerifyMode,
+) -> CargoResult<()> {
+    let mismatches = match verify_upstream(&dependency.name, version, manifest_path) {
+        Ok(mismatches) => mismatches,
+        Err(err) if mode == VerifyMode::Warn => {
+            return shell_warn(&format!(
+                "couldn't check {} against crates.io: {:#}",
+                dependency.toml_key(),
+                err
+            ));
+        }
+        Err(err) => return Err(err),
+    };
+    for mismatch in mismatches {
+        let message = format!(
+            "{} differs on crates.io: {}",
+            dependency.toml_key(),
+            mismatch
+        );
+        match mode {
+            VerifyMode::Warn => shell_warn(&message)?,
+            VerifyMode::Error => anyhow::bail!(message),
+        }
+    }
+    Ok(())
+}
+
+fn load_lockfile(targets: &[cargo_metadata::Package]) -> CargoResult<Vec<cargo_metadata::Package>> {
     // Get locked dependencies. For workspaces with multiple Cargo.toml
     // files, there is only a single lockfile, so it suffices to get
     // metadata for any one of Cargo.toml files.
@@ -367,14 +2110,7 @@ fn load_lockfile(
     let mut cmd = cargo_metadata::MetadataCommand::new();
     cmd.manifest_path(package.manifest_path.clone());
     cmd.features(cargo_metadata::CargoOpt::AllFeatures);
-    let mut other = Vec::new();
-    if locked {
-        other.push("--locked".to_owned());
-    }
-    if offline {
-        other.push("--offline".to_owned());
-    }
-    cmd.other_options(other);
+    cmd.other_options(cargo_flags().cargo_args());
 
     let result = cmd.exec()?;
 
@@ -383,6 +2119,138 @@ fn load_lockfile(
     Ok(locked)
 }
 
+/// Names of crates overridden by `[patch]` in the workspace root
+fn patched_dependencies(manifest_path: Option<&Path>) -> CargoResult<BTreeSet<String>> {
+    let root = LocalManifest::try_new(&workspace_root_manifest(manifest_path)?)?;
+    let mut patched = BTreeSet::new();
+    let sources = root.data.get("patch").and_then(|p| p.as_table_like());
+    for (_, source) in sources.into_iter().flat_map(|s| s.iter()) {
+        let deps = match source.as_table_like() {
+            Some(deps) => deps,
+            None => continue,
+        };
+        for (key, dep) in deps.iter() {
+            let name = dep.get("package").and_then(|p| p.as_str()).unwrap_or(key);
+            patched.insert(name.to_owned());
+        }
+    }
+    Ok(patched)
+}
+
+/// Whether the only versions newer than `latest_version` are pre-releases
+fn has_newer_prerelease(
+    dependency: &Dependency,
+    old_version_req: &str,
+    latest_version: Option<&str>,
+    manifest_path: &Path,
+) -> bool {
+    if old_version_req.contains('-') {
+        // Pre-releases were already considered
+        return false;
+    }
+    let latest_version = match latest_version.and_then(|v| semver::Version::parse(v).ok()) {
+        Some(latest_version) => latest_version,
+        None => return false,
+    };
+    let registry_url = match dependency
+        .registry()
+        .map(|registry| registry_url(manifest_path, Some(registry)))
+        .transpose()
+    {
+        Ok(registry_url) => registry_url,
+        Err(_) => return false,
+    };
+    get_latest_dependency(
+        &dependency.name,
+        &LookupOptions::new().set_allow_prerelease(true),
+        manifest_path,
+        registry_url.as_ref(),
+    )
+    .ok()
+    .and_then(|(d, _)| d.version().and_then(|v| semver::Version::parse(v).ok()))
+    .map(|prerelease| latest_version < prerelease)
+    .unwrap_or(false)
+}
+
+/// Warn about enabled features that the upgraded-to version no longer has
+///
+/// With `fix`, they are removed from the dependency's `features` instead.
+fn check_removed_features(
+    dependency: &Dependency,
+    dep_item: &mut toml_edit::Item,
+    new_version_req: &str,
+    manifest_path: &Path,
+    fix: bool,
+) -> CargoResult<()> {
+    let features = match dependency.features.as_deref() {
+        Some(features) if !features.is_empty() => features,
+        _ => return Ok(()),
+    };
+    let new_version_req = match VersionReq::parse(new_version_req) {
+        Ok(new_version_req) => new_version_req,
+        Err(_) => return Ok(()),
+    };
+    let registry_url = dependency
+        .registry()
+        .map(|registry| registry_url(manifest_path, Some(registry)))
+        .transpose()?;
+    // Not being able to check features shouldn't stop the upgrade
+    let target = match get_compatible_dependency(
+        &dependency.name,
+        &new_version_req,
+        manifest_path,
+        registry_url.as_ref(),
+    ) {
+        Ok(target) => target,
+        Err(_) => return Ok(()),
+    };
+
+    let removed = features
+        .iter()
+        .filter(|f| !target.available_features.contains_key(f.as_str()))
+        .map(|f| f.as_str())
+        .collect::<Vec<_>>();
+    if removed.is_empty() {
+        return Ok(());
+    }
+    let target_version = target.version().unwrap_or_default();
+    let removed_list = removed
+        .iter()
+        .map(|f| format!("`{}`", f))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    if fix {
+        if let Some(activated) = dep_item.get_mut("features").and_then(|f| f.as_array_mut()) {
+            let remove_list = activated
+                .iter()
+                .enumerate()
+                .filter(|(_, f)| f.as_str().map(|f| removed.contains(&f)).unwrap_or(false))
+                .map(|(idx, _)| idx)
+                .collect::<Vec<_>>();
+            for idx in remove_list.into_iter().rev() {
+                activated.remove(idx);
+            }
+        }
+        shell_status(
+            "Removing",
+            &format!(
+                "{} from {}'s features, missing in {}",
+                removed_list,
+                dependency.toml_key(),
+                target_version
+            ),
+        )
+    } else {
+        shell_warn(&format!(
+            "{} {} does not have {}, re-run with `--fix-removed-features` to drop them",
+            dependency.toml_key(),
+            target_version,
+            removed_list
+        ))
+    }
+}
+
 fn find_locked_version(
     dep_name: &str,
     old_version: &str,
@@ -414,30 +2282,22 @@ fn old_version_compatible(old_version_req: &str, new_version: &str) -> bool {
     old_version_req.matches(&new_version)
 }
 
-fn is_pinned_req(old_version_req: &str) -> bool {
-    if let Ok(version_req) = VersionReq::parse(old_version_req) {
-        version_req.comparators.iter().any(|comparator| {
-            matches!(
-                comparator.op,
-                Op::Exact | Op::Less | Op::LessEq | Op::Wildcard
-            )
-        })
-    } else {
-        false
-    }
+/// Whether `req` has a `>` bound, which upgrades leave as it is
+fn has_open_lower_bound(req: &str) -> bool {
+    VersionReq::parse(req).map_or(false, |req| {
+        req.comparators
+            .iter()
+            .any(|comparator| comparator.op == semver::Op::Greater)
+    })
 }
 
 fn deprecated_message(message: &str) -> CargoResult<()> {
-    let colorchoice = colorize_stderr();
-    let mut output = StandardStream::stderr(colorchoice);
-    output
-        .set_color(ColorSpec::new().set_fg(Some(Color::Red)).set_bold(true))
-        .with_context(|| "Failed to set output colour")?;
-    writeln!(output, "{}", message).with_context(|| "Failed to write deprecated message")?;
-    output
-        .set_color(&ColorSpec::new())
-        .with_context(|| "Failed to clear output colour")?;
-    Ok(())
+    shell_write_stderr(
+        message,
+        ColorSpec::new().set_fg(Some(Color::Red)).set_bold(true),
+    )
+    .with_context(|| "Failed to write deprecated message")?;
+    shell_write_stderr("\n", &ColorSpec::new())
 }
 
 struct Dep {
@@ -555,11 +2415,126 @@ impl Dep {
     }
 }
 
+/// Answers to `--confirm` prompts
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Confirmation {
+    /// Ask about each change
+    Ask,
+    /// Make the remaining changes without asking
+    AcceptAll,
+    /// Drop the remaining changes without asking
+    DeclineAll,
+}
+
+impl Confirmation {
+    /// Whether to change `dep_key` from `old` to `new`, asking on stdin if needed
+    ///
+    /// Running out of input declines the remaining changes.
+    fn confirm(&mut self, dep_key: &str, old: &str, new: &str) -> CargoResult<bool> {
+        loop {
+            match self {
+                Self::Ask => {}
+                Self::AcceptAll => return Ok(true),
+                Self::DeclineAll => return Ok(false),
+            }
+            shell_write_stderr(
+                format!("{}: \"{}\" -> \"{}\" [y/n/a/q] ", dep_key, old, new),
+                &ColorSpec::new(),
+            )?;
+            let mut answer = String::new();
+            if std::io::stdin().read_line(&mut answer)? == 0 {
+                shell_write_stderr("\n", &ColorSpec::new())?;
+                *self = Self::DeclineAll;
+                continue;
+            }
+            match answer.trim().to_ascii_lowercase().as_str() {
+                "y" | "yes" => return Ok(true),
+                "n" | "no" => return Ok(false),
+                "a" | "all" => *self = Self::AcceptAll,
+                "q" | "quit" => *self = Self::DeclineAll,
+                _ => shell_warn("expected y(es), n(o), a(ll), or q(uit)")?,
+            }
+        }
+    }
+}
+
+/// Per-package tally of what happened to its dependencies
+#[derive(Default)]
+struct Summary {
+    upgraded: usize,
+    latest: usize,
+    skipped: usize,
+    errored: usize,
+}
+
+impl Summary {
+    fn print(&self, name: &str) -> CargoResult<()> {
+        shell_status(
+            "Summary",
+            &format!(
+                "{}: {} upgraded, {} already latest, {} skipped, {} errored",
+                name, self.upgraded, self.latest, self.skipped, self.errored
+            ),
+        )
+    }
+}
+
+/// A dependency that was left alone, and why
+struct Skipped {
+    name: String,
+    reason: SkipReason,
+}
+
+impl Skipped {
+    fn new(name: &str, reason: SkipReason) -> Self {
+        Self {
+            name: name.to_owned(),
+            reason,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum SkipReason {
+    Excluded,
+    Ignored,
+    Git,
+    Path,
+    Workspace,
+    Patched,
+    Pinned,
+    Declined,
+    PrereleaseOnly,
+    RegistryError,
+    Templated,
+    Unsupported,
+}
+
+impl SkipReason {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Excluded => "excluded",
+            Self::Ignored => "ignored",
+            Self::Git => "git",
+            Self::Path => "path",
+            Self::Workspace => "workspace",
+            Self::Patched => "patched",
+            Self::Pinned => "pinned",
+            Self::Declined => "declined",
+            Self::PrereleaseOnly => "prerelease-only",
+            Self::RegistryError => "registry-error",
+            Self::Templated => "templated",
+            Self::Unsupported => "unsupported",
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 enum Reason {
     Unchanged,
     Compatible,
     Pinned,
+    Declined,
 }
 
 impl Reason {
@@ -568,6 +2543,7 @@ impl Reason {
             Self::Unchanged => "",
             Self::Compatible => "compatible",
             Self::Pinned => "pinned",
+            Self::Declined => "declined",
         }
     }
 
@@ -576,6 +2552,7 @@ impl Reason {
             Self::Unchanged => "unchanged",
             Self::Compatible => "compatible",
             Self::Pinned => "pinned",
+            Self::Declined => "declined",
         }
     }
 }
@@ -708,6 +2685,74 @@ fn print_upgrade(deps: Vec<Dep>, verbose: bool) -> CargoResult<()> {
     Ok(())
 }
 
+/// List skipped dependencies, grouped by why they were skipped
+fn print_skipped(package: &str, skipped: Vec<Skipped>) -> CargoResult<()> {
+    let mut categorize = BTreeMap::new();
+    for dep in skipped {
+        categorize
+            .entry(dep.reason)
+            .or_insert_with(BTreeSet::new)
+            .insert(dep.name);
+    }
+    let mut note = format!("Skipped dependencies of {}", package);
+    for (reason, deps) in categorize {
+        use std::fmt::Write;
+        write!(&mut note, "\n  {}: ", reason.as_str())?;
+        for (i, dep) in deps.into_iter().enumerate() {
+            if 0 < i {
+                note.push_str(", ");
+            }
+            note.push_str(&dep);
+        }
+    }
+    shell_note(&note)
+}
+
+/// Report where the time went, as a note and a `timings` event
+fn print_timings(timings: &[Timing]) -> CargoResult<()> {
+    let total: Duration = timings.iter().map(|t| t.duration).sum();
+    let lookups = timings.iter().filter(|t| t.step == "lookup").count();
+    let cache_hits = timings.iter().filter(|t| t.cached).count();
+    shell_event(
+        "timings",
+        &serde_json::json!({
+            "steps": timings.iter().map(|t| serde_json::json!({
+                "step": t.step,
+                "subject": t.subject,
+                "seconds": t.duration.as_secs_f64(),
+                "cached": t.cached,
+            })).collect::<Vec<_>>(),
+            "seconds": total.as_secs_f64(),
+            "lookups": lookups,
+            "cache_hits": cache_hits,
+        }),
+    )?;
+
+    let width = timings.iter().map(|t| t.step.len()).max().unwrap_or(0);
+    let mut note = "Timings".to_owned();
+    for timing in timings {
+        use std::fmt::Write;
+        write!(
+            &mut note,
+            "\n  {:width$}  {:>8.3}s  {}",
+            timing.step,
+            timing.duration.as_secs_f64(),
+            timing.subject,
+            width = width
+        )?;
+        if timing.cached {
+            note.push_str(" (cached)");
+        }
+    }
+    note.push_str(&format!(
+        "\n  {:.3}s in total, {} lookups with {} cache hits",
+        total.as_secs_f64(),
+        lookups,
+        cache_hits
+    ));
+    shell_note(&note)
+}
+
 fn write_cell(content: &str, width: usize, spec: &ColorSpec) -> CargoResult<()> {
     shell_write_stderr(content, spec)?;
     for _ in 0..(width - content.len()) {
@@ -720,6 +2765,24 @@ fn write_cell(content: &str, width: usize, spec: &ColorSpec) -> CargoResult<()>
 mod test {
     use super::*;
 
+    #[test]
+    fn patched_requirement_keeps_operator() {
+        let patched = |req, version| patched_requirement(req, version).unwrap();
+        assert_eq!(patched("1.2", "1.2.5"), "1.2.5");
+        assert_eq!(patched("^1.2", "1.2.5"), "^1.2.5");
+        assert_eq!(patched("~1.2.3", "1.2.5"), "~1.2.5");
+        assert_eq!(patched("=1.2.3+mycorp.1", "1.2.5"), "=1.2.5");
+    }
+
+    #[test]
+    fn patched_requirement_keeps_bounds() {
+        let patched = |req, version| patched_requirement(req, version).unwrap();
+        assert_eq!(patched(">=1.0, <2", "1.2.5"), ">=1.2.5, <2");
+        assert_eq!(patched(">=1.0, <1.2", "1.2.5"), ">=1.2.5, <1.3");
+        assert_eq!(patched(">0.2", "1.2.5"), ">=1.2.5");
+        assert_eq!(patched("<0.2", "0.1.5"), ">=0.1.5, <0.2");
+    }
+
     #[test]
     fn exact_is_pinned_req() {
         let req = "=3";
@@ -0,0 +1,227 @@
+//! `cargo upgrade --watch`: keep reporting dependencies with newer versions
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use cargo_edit::{
+    get_latest_dependency, registry_url, shell_event, shell_note, shell_status, shell_warn,
+    update_registry_indexes, CargoResult, Context, Dependency, LocalManifest, LookupOptions,
+};
+use semver::VersionReq;
+
+use crate::notify::Notifier;
+
+/// How often manifests are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug)]
+pub struct WatchOptions {
+    /// Time between registry index updates
+    pub interval: Duration,
+    /// Where to write the outdated dependencies after each check
+    pub status_file: Option<PathBuf>,
+    /// Only check against the indexes already on disk
+    pub offline: bool,
+    /// Dependencies to leave out
+    pub exclude: Vec<String>,
+    /// Where to send newly outdated dependencies
+    pub notifier: Option<Notifier>,
+    /// Manifest whose cargo config sets the proxy for the registries
+    pub manifest_path: PathBuf,
+    /// How the registry indexes are updated
+    pub index_options: LookupOptions,
+}
+
+/// A requirement that doesn't allow the latest version
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Outdated {
+    package: String,
+    name: String,
+    version_req: String,
+    latest: String,
+}
+
+/// Check `manifests` for outdated dependencies whenever one changes or the registries are
+/// updated, until interrupted
+pub fn watch(manifests: &[cargo_metadata::Package], options: &WatchOptions) -> CargoResult<()> {
+    let mut stamps = BTreeMap::new();
+    let mut last_update: Option<Instant> = None;
+    let mut reported = BTreeSet::new();
+    shell_note("Watching for newer versions, press Ctrl-C to stop")?;
+    loop {
+        let mut changed = false;
+        for package in manifests {
+            let path = package.manifest_path.as_std_path();
+            let stamp = modified(path);
+            if stamps.insert(path.to_owned(), stamp) != Some(stamp) {
+                changed = true;
+            }
+        }
+        let update_due = last_update.map_or(true, |last| options.interval <= last.elapsed());
+        if update_due {
+            if !options.offline {
+                let registries = registries(manifests)?;
+                update_registry_indexes(
+                    &registries,
+                    &options.index_options,
+                    &options.manifest_path,
+                    last_update.is_some(),
+                )?;
+            }
+            last_update = Some(Instant::now());
+        }
+
+        if changed || update_due {
+            let outdated = check(manifests, &options.exclude)?;
+            for dep in outdated.difference(&reported) {
+                shell_status(
+                    "Outdated",
+                    &format!(
+                        "{}'s {} {} (latest: {})",
+                        dep.package, dep.name, dep.version_req, dep.latest
+                    ),
+                )?;
+                shell_event(
+                    "outdated-dependency",
+                    &serde_json::json!({
+                        "package": dep.package,
+                        "name": dep.name,
+                        "version_req": dep.version_req,
+                        "latest": dep.latest,
+                    }),
+                )?;
+            }
+            if let Some(notifier) = &options.notifier {
+                notifier.send("Outdated dependencies")?;
+            }
+            if let Some(status_file) = &options.status_file {
+                write_status(status_file, &outdated)?;
+            }
+            reported = outdated;
+        }
+
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Indexes of every registry `manifests` depend on
+fn registries(manifests: &[cargo_metadata::Package]) -> CargoResult<Vec<url::Url>> {
+    let mut registries = Vec::new();
+    for package in manifests {
+        let path = package.manifest_path.as_std_path();
+        registries.push(registry_url(path, None)?);
+        for dependency in dependencies(path)? {
+            if let Some(registry) = dependency.registry() {
+                registries.push(registry_url(path, Some(registry))?);
+            }
+        }
+    }
+    Ok(registries)
+}
+
+/// Registry dependencies in the manifest at `path`, skipping entries that can't be read
+fn dependencies(path: &Path) -> CargoResult<Vec<Dependency>> {
+    let mut manifest = LocalManifest::try_new(path)?;
+    let manifest_path = manifest.path.clone();
+    let mut dependencies = Vec::new();
+    for dep_table in manifest.get_dependency_tables_mut() {
+        for (dep_key, dep_item) in dep_table.iter() {
+            if let Ok(dependency) = Dependency::from_toml(&manifest_path, dep_key, dep_item) {
+                let is_registry = dependency
+                    .source()
+                    .and_then(|source| source.as_registry())
+                    .is_some();
+                if is_registry {
+                    dependencies.push(dependency);
+                }
+            }
+        }
+    }
+    Ok(dependencies)
+}
+
+fn check(
+    manifests: &[cargo_metadata::Package],
+    exclude: &[String],
+) -> CargoResult<BTreeSet<Outdated>> {
+    let mut outdated = BTreeSet::new();
+    for package in manifests {
+        let path = package.manifest_path.as_std_path();
+        let policy = LocalManifest::try_new(path)?.upgrade_policy()?;
+        for dependency in dependencies(path)? {
+            let dep_key = dependency.toml_key().to_owned();
+            if exclude.contains(&dep_key) || policy.is_ignored(&dep_key) {
+                continue;
+            }
+            let version_req = dependency
+                .version()
+                .expect("registry dependencies always have a version")
+                .to_owned();
+            let registry = dependency
+                .registry()
+                .map(|registry| registry_url(path, Some(registry)))
+                .transpose()?;
+            let latest = get_latest_dependency(
+                &dependency.name,
+                &LookupOptions::new().set_allow_prerelease(version_req.contains('-')),
+                path,
+                registry.as_ref(),
+            );
+            let latest = match latest {
+                Ok((latest, _)) => latest
+                    .version()
+                    .expect("registry packages always have a version")
+                    .to_owned(),
+                Err(err) => {
+                    shell_warn(&format!("ignoring {}, {}", dep_key, err))?;
+                    continue;
+                }
+            };
+            let allows_latest = match (
+                VersionReq::parse(&version_req),
+                latest.parse::<semver::Version>(),
+            ) {
+                (Ok(req), Ok(version)) => req.matches(&version),
+                _ => true,
+            };
+            if !allows_latest {
+                outdated.insert(Outdated {
+                    package: package.name.clone(),
+                    name: dep_key,
+                    version_req,
+                    latest,
+                });
+            }
+        }
+    }
+    Ok(outdated)
+}
+
+/// Replace `path` with the outdated dependencies as JSON, for editors and status bars
+fn write_status(path: &Path, outdated: &BTreeSet<Outdated>) -> CargoResult<()> {
+    let checked = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let status = serde_json::json!({
+        "checked": checked,
+        "outdated": outdated.iter().map(|dep| serde_json::json!({
+            "package": dep.package,
+            "name": dep.name,
+            "version_req": dep.version_req,
+            "latest": dep.latest,
+        })).collect::<Vec<_>>(),
+    });
+    // Write then rename so readers never see a partial file
+    let partial = path.with_extension("partial");
+    std::fs::write(&partial, serde_json::to_string_pretty(&status)?)
+        .with_context(|| format!("Failed to write `{}`", partial.display()))?;
+    std::fs::rename(&partial, path)
+        .with_context(|| format!("Failed to write `{}`", path.display()))?;
+    Ok(())
+}
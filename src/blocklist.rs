@@ -0,0 +1,126 @@
+//! Crates a policy forbids depending on, from `[cargo-edit.blocked-crates]` in cargo config
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::errors::*;
+use super::registry::blocked_crate_config;
+use super::shell_warn;
+
+/// What happens when a blocked crate is depended on
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BlockLevel {
+    /// The command fails
+    #[default]
+    Deny,
+    /// The command warns and carries on
+    Warn,
+}
+
+impl BlockLevel {
+    /// The level as written in config
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Deny => "deny",
+            Self::Warn => "warn",
+        }
+    }
+}
+
+impl FromStr for BlockLevel {
+    type Err = Error;
+
+    fn from_str(level: &str) -> CargoResult<Self> {
+        match level {
+            "deny" => Ok(Self::Deny),
+            "warn" => Ok(Self::Warn),
+            _ => anyhow::bail!("Invalid block level `{}`, expected deny or warn", level),
+        }
+    }
+}
+
+/// A crate listed in `[cargo-edit.blocked-crates]`, like
+/// `openssl = { reason = "use rustls", alternatives = ["rustls"] }`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockedCrate {
+    /// Crate name, as published
+    pub name: String,
+    /// Why the crate is blocked
+    pub reason: Option<String>,
+    /// Crates to use instead
+    pub alternatives: Vec<String>,
+    /// Whether depending on the crate fails, or only warns
+    pub level: BlockLevel,
+}
+
+impl BlockedCrate {
+    /// Fail when the crate is denied, or warn
+    pub fn enforce(&self) -> CargoResult<()> {
+        match self.level {
+            BlockLevel::Deny => anyhow::bail!("{}", self),
+            BlockLevel::Warn => shell_warn(&self.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for BlockedCrate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` is blocked by policy", self.name)?;
+        if let Some(reason) = &self.reason {
+            write!(f, ": {}", reason)?;
+        }
+        if !self.alternatives.is_empty() {
+            let alternatives: Vec<_> = self
+                .alternatives
+                .iter()
+                .map(|a| format!("`{}`", a))
+                .collect();
+            write!(f, ", consider {}", alternatives.join(" or "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether the crate `name` is blocked for the manifest at `manifest_path`
+///
+/// Blocked crates are listed in `[cargo-edit.blocked-crates]` of the cargo config from the
+/// manifest's directory up, so a policy in a shared parent directory or `$CARGO_HOME` covers
+/// every project under it.  The nearest config listing the crate wins.
+pub fn blocked_crate(manifest_path: &Path, name: &str) -> CargoResult<Option<BlockedCrate>> {
+    let work_dir = manifest_path
+        .parent()
+        .expect("there must be a parent directory");
+    blocked_crate_config(work_dir, name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn blocked_crates_from_config() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let config_dir = temp.path().join(".cargo");
+        std::fs::create_dir(&config_dir).unwrap();
+        std::fs::write(
+            config_dir.join("config.toml"),
+            r#"[cargo-edit.blocked-crates]
+openssl = { reason = "we standardize on rustls", alternatives = ["rustls"] }
+failure = { level = "warn" }
+"#,
+        )
+        .unwrap();
+        let manifest_path = temp.path().join("Cargo.toml");
+
+        let openssl = blocked_crate(&manifest_path, "openssl").unwrap().unwrap();
+        assert_eq!(openssl.level, BlockLevel::Deny);
+        assert_eq!(
+            openssl.enforce().unwrap_err().to_string(),
+            "`openssl` is blocked by policy: we standardize on rustls, consider `rustls`"
+        );
+        let failure = blocked_crate(&manifest_path, "failure").unwrap().unwrap();
+        assert_eq!(failure.level, BlockLevel::Warn);
+        assert_eq!(failure.reason, None);
+        assert_eq!(blocked_crate(&manifest_path, "rustls").unwrap(), None);
+    }
+}
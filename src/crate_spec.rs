@@ -6,7 +6,9 @@ use super::errors::*;
 /// This can be a
 /// - Name (e.g. `docopt`)
 /// - Name and a version req (e.g. `docopt@^0.8`)
-/// - Path
+///
+/// Features are not part of the spec, as `+` also introduces build metadata in a version (e.g.
+/// `foo@1.0.0+build5`).  Build metadata is dropped from the version req, as cargo ignores it.
 #[derive(Debug)]
 pub struct CrateSpec {
     /// Crate name
@@ -18,32 +20,45 @@ pub struct CrateSpec {
 impl CrateSpec {
     /// Convert a string to a `Crate`
     pub fn resolve(pkg_id: &str) -> CargoResult<Self> {
-        let (name, version) = pkg_id
-            .split_once('@')
-            .map(|(n, v)| (n, Some(v)))
-            .unwrap_or((pkg_id, None));
-
-        let invalid: Vec<_> = name
-            .chars()
-            .filter(|c| !is_name_char(*c))
-            .map(|c| c.to_string())
-            .collect();
-        if !invalid.is_empty() {
-            return Err(anyhow::format_err!(
-                "Invalid name `{}`: {}",
+        let (name, version) = match pkg_id.split('@').collect::<Vec<_>>()[..] {
+            [name] => (name, None),
+            [name, version] => (name, Some(version)),
+            _ => anyhow::bail!(
+                "Invalid crate spec `{}`: expected `<name>[@<version-req>]`, found more than one `@`",
+                pkg_id
+            ),
+        };
+
+        if name.is_empty() {
+            anyhow::bail!("Invalid crate spec `{}`: missing crate name", pkg_id);
+        }
+        if let Some((name, features)) = name.split_once('+') {
+            anyhow::bail!(
+                "Invalid crate spec `{}`: features can't be selected in a crate spec, drop `+{}` and activate them with `--features {}/{}`",
+                pkg_id,
+                features,
                 name,
-                invalid.join(", ")
-            ));
+                features.replace('+', &format!(",{}/", name))
+            );
         }
+        validate_name(name)?;
 
-        if let Some(version) = version {
-            semver::VersionReq::parse(version)
-                .with_context(|| format!("Invalid version requirement `{}`", version))?;
-        }
+        let version_req = match version {
+            Some("") => anyhow::bail!(
+                "Invalid crate spec `{}`: missing version requirement after `@`",
+                pkg_id
+            ),
+            Some(version) => {
+                semver::VersionReq::parse(version)
+                    .with_context(|| format!("Invalid version requirement `{}`", version))?;
+                Some(strip_build_metadata(version))
+            }
+            None => None,
+        };
 
         Ok(Self {
             name: name.to_owned(),
-            version_req: version.map(|s| s.to_owned()),
+            version_req,
         })
     }
 }
@@ -56,6 +71,180 @@ impl std::str::FromStr for CrateSpec {
     }
 }
 
+/// Longest crate name crates.io accepts
+const MAX_NAME_LENGTH: usize = 64;
+
+/// Check `name` is one any registry could hold
+fn validate_name(name: &str) -> CargoResult<()> {
+    let mut invalid: Vec<_> = name.chars().filter(|c| !is_name_char(*c)).collect();
+    invalid.dedup();
+    if !invalid.is_empty() {
+        let invalid: Vec<_> = invalid.iter().map(|c| format!("`{}`", c)).collect();
+        anyhow::bail!(
+            "Invalid name `{}`: {} can't be used, only letters, digits, `-` and `_` can",
+            name,
+            invalid.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Check `name` is one crates.io could have published, before looking it up there
+///
+/// Path, git and other registries' crates aren't held to these rules.  Case isn't checked:
+/// lookups find crates whatever the case, and warn when it differs.
+pub(crate) fn validate_crates_io_name(name: &str) -> CargoResult<()> {
+    let mut invalid: Vec<_> = name.chars().filter(|c| !c.is_ascii()).collect();
+    invalid.dedup();
+    if !invalid.is_empty() {
+        let invalid: Vec<_> = invalid.iter().map(|c| format!("`{}`", c)).collect();
+        anyhow::bail!(
+            "Invalid name `{}`: {} can't be used on crates.io, only ASCII letters, digits, `-` \
+             and `_` can",
+            name,
+            invalid.join(", ")
+        );
+    }
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        anyhow::bail!(
+            "Invalid name `{}`: crate names on crates.io start with a letter",
+            name
+        );
+    }
+    if name.len() > MAX_NAME_LENGTH {
+        anyhow::bail!(
+            "Invalid name `{}`: crate names on crates.io are at most {} characters, this one has {}",
+            name,
+            MAX_NAME_LENGTH,
+            name.len()
+        );
+    }
+    if is_windows_reserved(name) {
+        anyhow::bail!(
+            "Invalid name `{}`: it is reserved on Windows, so no crate on crates.io can use it",
+            name
+        );
+    }
+    Ok(())
+}
+
 fn is_name_char(c: char) -> bool {
     c.is_alphanumeric() || ['-', '_'].contains(&c)
 }
+
+/// Device names Windows won't create files for, whatever the case
+fn is_windows_reserved(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    match name.as_str() {
+        "con" | "prn" | "aux" | "nul" => true,
+        _ => name
+            .strip_prefix("com")
+            .or_else(|| name.strip_prefix("lpt"))
+            .map_or(false, |n| matches!(n.as_bytes(), [b'1'..=b'9'])),
+    }
+}
+
+/// Remove the `+build` suffix of each comparator in a (valid) version req
+fn strip_build_metadata(version_req: &str) -> String {
+    version_req
+        .split(',')
+        .map(|comparator| match comparator.split_once('+') {
+            Some((version, _build)) => version,
+            None => comparator,
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn name_only() {
+        let spec = CrateSpec::resolve("docopt").unwrap();
+        assert_eq!(spec.name, "docopt");
+        assert_eq!(spec.version_req, None);
+    }
+
+    #[test]
+    fn name_and_version_req() {
+        let spec = CrateSpec::resolve("docopt@^0.8").unwrap();
+        assert_eq!(spec.name, "docopt");
+        assert_eq!(spec.version_req.as_deref(), Some("^0.8"));
+    }
+
+    #[test]
+    fn build_metadata_is_dropped() {
+        let spec = CrateSpec::resolve("foo@1.0.0+build5").unwrap();
+        assert_eq!(spec.version_req.as_deref(), Some("1.0.0"));
+
+        let spec = CrateSpec::resolve("foo@>=1.0.0+a.b, <2").unwrap();
+        assert_eq!(spec.version_req.as_deref(), Some(">=1.0.0, <2"));
+    }
+
+    #[test]
+    fn multiple_at_signs() {
+        let err = CrateSpec::resolve("foo@1@2").unwrap_err();
+        assert!(err.to_string().contains("more than one `@`"), "{}", err);
+    }
+
+    #[test]
+    fn missing_parts() {
+        let err = CrateSpec::resolve("@1.0").unwrap_err();
+        assert!(err.to_string().contains("missing crate name"), "{}", err);
+
+        let err = CrateSpec::resolve("foo@").unwrap_err();
+        assert!(
+            err.to_string().contains("missing version requirement"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn invalid_names() {
+        let err = CrateSpec::resolve("foo.bar@1.0").unwrap_err();
+        assert!(err.to_string().contains("`.` can't be used"), "{}", err);
+
+        let spec = CrateSpec::resolve("café").unwrap();
+        assert_eq!(spec.name, "café");
+        CrateSpec::resolve("1password").unwrap();
+        CrateSpec::resolve(&"a".repeat(65)).unwrap();
+        CrateSpec::resolve("nul").unwrap();
+
+        let spec = CrateSpec::resolve("Serde_JSON").unwrap();
+        assert_eq!(spec.name, "Serde_JSON");
+    }
+
+    #[test]
+    fn invalid_crates_io_names() {
+        let err = validate_crates_io_name("café").unwrap_err();
+        assert!(err.to_string().contains("`é` can't be used"), "{}", err);
+
+        let err = validate_crates_io_name("1password").unwrap_err();
+        assert!(err.to_string().contains("start with a letter"), "{}", err);
+
+        let err = validate_crates_io_name(&"a".repeat(65)).unwrap_err();
+        assert!(err.to_string().contains("at most 64"), "{}", err);
+        validate_crates_io_name(&"a".repeat(64)).unwrap();
+
+        for reserved in ["nul", "COM1", "lpt9"] {
+            let err = validate_crates_io_name(reserved).unwrap_err();
+            assert!(err.to_string().contains("reserved on Windows"), "{}", err);
+        }
+        validate_crates_io_name("com10").unwrap();
+        validate_crates_io_name("console").unwrap();
+        validate_crates_io_name("Serde_JSON").unwrap();
+    }
+
+    #[test]
+    fn features_are_rejected() {
+        let err = CrateSpec::resolve("serde+derive+rc@1.0").unwrap_err();
+        assert!(
+            err.to_string().contains("--features serde/derive,serde/rc"),
+            "{}",
+            err
+        );
+    }
+}
@@ -0,0 +1,720 @@
+//! Queries against the crates.io web API
+//!
+//! Registry indexes only carry what cargo needs for resolution, so anything else (like when a
+//! version was published) has to come from crates.io itself.
+//!
+//! Following the crates.io crawler policy, requests are at least a second apart within a run.
+//! Responses are cached under `$CARGO_HOME/cargo-edit/api-cache`, and revalidated with their
+//! `ETag`, so a crate looked up again only costs a `304 Not Modified`.  Requests go through the
+//! proxy cargo config sets for the manifest they are made for.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use url::Url;
+
+use super::errors::*;
+use super::http::http_agent;
+use super::registry::cargo_home;
+use super::timings::time;
+use super::{Dependency, RegistrySource, VersionExt};
+
+const CRATES_IO_API: &str = "https://crates.io/api/v1";
+/// Rate-limited requests are retried this many times, if the wait is short enough
+const MAX_RETRIES: u32 = 2;
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(60);
+/// Time between requests the crates.io crawler policy asks for
+const MIN_REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+
+/// When the last request of this run was sent
+static LAST_REQUEST: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Range of publish dates to select versions from
+///
+/// Dates are `YYYY-MM-DD`, in UTC.  `since` is inclusive while `before` is exclusive, so
+/// consecutive windows don't overlap.  A minimum age is an exclusive bound too.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PublishWindow {
+    since: Option<String>,
+    before: Option<String>,
+}
+
+impl PublishWindow {
+    /// Accept versions published at any time
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only accept versions published on or after `date`
+    pub fn set_since(mut self, date: &str) -> CargoResult<Self> {
+        self.since = Some(parse_date(date)?);
+        Ok(self)
+    }
+
+    /// Only accept versions published before `date`
+    pub fn set_before(mut self, date: &str) -> CargoResult<Self> {
+        self.before = Some(parse_date(date)?);
+        Ok(self)
+    }
+
+    /// Only accept versions published at least `age` ago, like `7d`, on top of any other bound
+    ///
+    /// Ages are a number of days (`d`), weeks (`w`) or hours (`h`).
+    pub fn set_min_age(self, age: &str) -> CargoResult<Self> {
+        let age = parse_age(age)?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Ok(self.set_cutoff(now.saturating_sub(age)))
+    }
+
+    /// Lower `before` to the time `since_epoch`, if it is earlier
+    fn set_cutoff(mut self, since_epoch: Duration) -> Self {
+        let cutoff = format_timestamp(since_epoch.as_secs());
+        if self
+            .before
+            .as_deref()
+            .map(|before| cutoff.as_str() < before)
+            .unwrap_or(true)
+        {
+            self.before = Some(cutoff);
+        }
+        self
+    }
+
+    /// Whether any publish dates are excluded
+    pub fn is_unbounded(&self) -> bool {
+        self.since.is_none() && self.before.is_none()
+    }
+
+    /// Check an RFC 3339 timestamp, like crates.io's `created_at`, against the window
+    fn contains(&self, timestamp: &str) -> bool {
+        // RFC 3339 timestamps in UTC sort lexicographically, and a date sorts before any time
+        // on that day
+        let after_since = self
+            .since
+            .as_deref()
+            .map(|since| since <= timestamp)
+            .unwrap_or(true);
+        let before_before = self
+            .before
+            .as_deref()
+            .map(|before| timestamp < before)
+            .unwrap_or(true);
+        after_since && before_before
+    }
+}
+
+fn parse_date(date: &str) -> CargoResult<String> {
+    let is_date = date.len() == 10
+        && date.bytes().enumerate().all(|(i, b)| match i {
+            4 | 7 => b == b'-',
+            _ => b.is_ascii_digit(),
+        });
+    if is_date {
+        Ok(date.to_owned())
+    } else {
+        anyhow::bail!("Invalid date `{}`, expected `YYYY-MM-DD`", date)
+    }
+}
+
+fn parse_age(age: &str) -> CargoResult<Duration> {
+    let invalid = || {
+        anyhow::format_err!(
+            "Invalid age `{}`, expected a number of days, like `7d`",
+            age
+        )
+    };
+    let unit = match age.chars().last().ok_or_else(invalid)? {
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        'w' => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    let count: u64 = age[..age.len() - 1].parse().map_err(|_| invalid())?;
+    Ok(Duration::from_secs(count.saturating_mul(unit)))
+}
+
+/// An RFC 3339 timestamp in UTC, like `2022-03-01T10:00:00Z`, for seconds since 1970-01-01
+fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let secs = secs % 86_400;
+    // Howard Hinnant's `civil_from_days`, the inverse of `days_from_civil`
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Query the latest version of a crates.io crate published within `window`
+///
+/// Returns `None` when no version (that isn't yanked) was published in that time.
+pub fn get_latest_published_dependency(
+    crate_name: &str,
+    flag_allow_prerelease: bool,
+    window: &PublishWindow,
+    manifest_path: &Path,
+) -> CargoResult<Option<Dependency>> {
+    let url = format!("{}/crates/{}/versions", CRATES_IO_API, crate_name);
+    let versions: ApiVersions = time("api-request", crate_name, || get_json(&url, manifest_path))?;
+    read_latest_published(crate_name, versions, flag_allow_prerelease, window)
+}
+
+fn read_latest_published(
+    crate_name: &str,
+    versions: ApiVersions,
+    flag_allow_prerelease: bool,
+    window: &PublishWindow,
+) -> CargoResult<Option<Dependency>> {
+    let mut latest: Option<(semver::Version, ApiVersion)> = None;
+    for version in versions.versions {
+        if version.yanked || !window.contains(&version.created_at) {
+            continue;
+        }
+        let num: semver::Version = version.num.parse()?;
+        if num.is_prerelease() && !flag_allow_prerelease {
+            continue;
+        }
+        if latest.as_ref().map(|(l, _)| *l < num).unwrap_or(true) {
+            latest = Some((num, version));
+        }
+    }
+
+    Ok(latest.map(|(num, version)| {
+        Dependency::new(crate_name)
+            .set_source(RegistrySource::new(num.to_string()))
+            .set_available_features(version.features)
+    }))
+}
+
+/// How far a version of a crate is behind a newer one
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct VersionLag {
+    /// Days between their publish dates
+    pub days: u64,
+    /// Stable releases after the older version, up to and including the newer one, that weren't
+    /// yanked
+    pub releases: usize,
+}
+
+/// Measure how far `current` of a crates.io crate is behind `latest`
+///
+/// Returns `None` when crates.io doesn't list one of them.
+pub fn get_version_lag(
+    crate_name: &str,
+    current: &semver::Version,
+    latest: &semver::Version,
+    manifest_path: &Path,
+) -> CargoResult<Option<VersionLag>> {
+    let url = format!("{}/crates/{}/versions", CRATES_IO_API, crate_name);
+    let versions: ApiVersions = time("api-request", crate_name, || get_json(&url, manifest_path))?;
+    read_version_lag(versions, current, latest)
+}
+
+fn read_version_lag(
+    versions: ApiVersions,
+    current: &semver::Version,
+    latest: &semver::Version,
+) -> CargoResult<Option<VersionLag>> {
+    let mut current_day = None;
+    let mut latest_day = None;
+    let mut releases = 0;
+    for version in versions.versions {
+        let num: semver::Version = version.num.parse()?;
+        if num == *current {
+            current_day = days_since_epoch(&version.created_at);
+        }
+        if num == *latest {
+            latest_day = days_since_epoch(&version.created_at);
+        }
+        if *current < num && num <= *latest && !num.is_prerelease() && !version.yanked {
+            releases += 1;
+        }
+    }
+    Ok(match (current_day, latest_day) {
+        (Some(current_day), Some(latest_day)) => Some(VersionLag {
+            days: latest_day.saturating_sub(current_day),
+            releases,
+        }),
+        _ => None,
+    })
+}
+
+/// Days from 1970-01-01 to the date of an RFC 3339 timestamp, like `2022-03-01T10:00:00Z`
+fn days_since_epoch(timestamp: &str) -> Option<u64> {
+    let date = timestamp.get(..10)?;
+    parse_date(date).ok()?;
+    let year: i64 = date[..4].parse().ok()?;
+    let month: i64 = date[5..7].parse().ok()?;
+    let day: i64 = date[8..10].parse().ok()?;
+    // Howard Hinnant's `days_from_civil`, with years starting in March
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    u64::try_from(era * 146_097 + day_of_era - 719_468).ok()
+}
+
+/// Query the license expression crates.io lists for `version` of a crate
+pub fn get_published_license(
+    crate_name: &str,
+    version: &str,
+    manifest_path: &Path,
+) -> CargoResult<Option<String>> {
+    let url = format!("{}/crates/{}/{}", CRATES_IO_API, crate_name, version);
+    let response: ApiVersionResponse =
+        time("api-request", crate_name, || get_json(&url, manifest_path))?;
+    Ok(response.version.license)
+}
+
+/// The targets a published version of a crate builds
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PublishedTargets {
+    /// Whether it has a library target, or `None` for versions published before crates.io
+    /// recorded it
+    pub has_lib: Option<bool>,
+    /// Names of its binary targets
+    pub bin_names: Vec<String>,
+}
+
+impl PublishedTargets {
+    /// Whether it only publishes binaries, which can be installed but not depended on
+    pub fn is_bin_only(&self) -> bool {
+        self.has_lib == Some(false)
+    }
+
+    /// Explain that `crate_name` can't be depended on, suggesting to install it instead
+    pub fn bin_only_message(&self, crate_name: &str) -> Option<String> {
+        if !self.is_bin_only() {
+            return None;
+        }
+        let binaries = match self.bin_names.as_slice() {
+            [] => String::new(),
+            [bin] => format!(", only the `{}` binary", bin),
+            bins => format!(", only the `{}` binaries", bins.join("`, `")),
+        };
+        Some(format!(
+            "`{}` has no library{}; to use it as a command, run `cargo install {}`",
+            crate_name, binaries, crate_name
+        ))
+    }
+}
+
+/// Query which targets crates.io lists for `version` of a crate
+pub fn get_published_targets(
+    crate_name: &str,
+    version: &str,
+    manifest_path: &Path,
+) -> CargoResult<PublishedTargets> {
+    let url = format!("{}/crates/{}/{}", CRATES_IO_API, crate_name, version);
+    let response: ApiVersionResponse =
+        time("api-request", crate_name, || get_json(&url, manifest_path))?;
+    Ok(response.version.targets())
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiVersionResponse {
+    version: ApiVersionDetails,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiVersionDetails {
+    license: Option<String>,
+    has_lib: Option<bool>,
+    #[serde(default)]
+    bin_names: Vec<String>,
+}
+
+impl ApiVersionDetails {
+    fn targets(self) -> PublishedTargets {
+        PublishedTargets {
+            has_lib: self.has_lib,
+            bin_names: self.bin_names,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiVersions {
+    versions: Vec<ApiVersion>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiVersion {
+    num: String,
+    yanked: bool,
+    created_at: String,
+    #[serde(default)]
+    features: BTreeMap<String, Vec<String>>,
+}
+
+/// Fetch and parse a JSON document, with the shared [`http_agent`] for `manifest_path`
+///
+/// Redirects are followed, and rate-limited requests are retried when the server says how soon
+/// to, like GitHub does with `X-RateLimit-Reset`.  A cached response is sent back when the server
+/// says it's still current.
+fn get_json<T: serde::de::DeserializeOwned>(url: &str, manifest_path: &Path) -> CargoResult<T> {
+    let work_dir = manifest_path
+        .parent()
+        .expect("there must be a parent directory");
+    let agent = http_agent(&Url::parse(url)?, work_dir)?;
+    let cache_path = cargo_home()
+        .ok()
+        .and_then(|home| api_cache_path(&home, url));
+    let cached = cache_path.as_deref().and_then(read_cached);
+
+    let mut retries = 0;
+    loop {
+        wait_for_turn();
+        let mut request = agent.get(url);
+        if let Some(cached) = &cached {
+            request = request.set("If-None-Match", &cached.etag);
+        }
+        let response = match request.call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(status @ (403 | 429), response))
+                if status == 429 || response.header("x-ratelimit-remaining") == Some("0") =>
+            {
+                let wait = rate_limit_wait(|name| response.header(name), unix_now());
+                match wait {
+                    Some(wait) if retries < MAX_RETRIES && wait <= MAX_RETRY_WAIT => {
+                        retries += 1;
+                        std::thread::sleep(wait);
+                        continue;
+                    }
+                    Some(wait) => anyhow::bail!(
+                        "`{}` is rate limited (HTTP {}), try again in {}s",
+                        url,
+                        status,
+                        wait.as_secs()
+                    ),
+                    None => anyhow::bail!(
+                        "`{}` is rate limited (HTTP {}), try again later",
+                        url,
+                        status
+                    ),
+                }
+            }
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to fetch `{}`", url));
+            }
+        };
+        let body = match (response.status(), cached) {
+            (304, Some(cached)) => cached.body,
+            (_, _) => {
+                let etag = response.header("etag").map(str::to_owned);
+                let body = response
+                    .into_string()
+                    .with_context(|| format!("Invalid response from `{}`", url))?;
+                if let (Some(path), Some(etag)) = (&cache_path, etag) {
+                    write_cached(path, &etag, &body);
+                }
+                body
+            }
+        };
+        return serde_json::from_str(&body)
+            .with_context(|| format!("Invalid response from `{}`", url));
+    }
+}
+
+/// Sleep until a second has passed since the last request of the run
+fn wait_for_turn() {
+    let mut last = LAST_REQUEST.lock().expect("not poisoned");
+    let now = Instant::now();
+    let wait = turn_wait(*last, now);
+    if !wait.is_zero() {
+        std::thread::sleep(wait);
+    }
+    *last = Some(now + wait);
+}
+
+fn turn_wait(last: Option<Instant>, now: Instant) -> Duration {
+    last.map_or(Duration::ZERO, |last| {
+        (last + MIN_REQUEST_INTERVAL).saturating_duration_since(now)
+    })
+}
+
+/// A response body kept for revalidation with its `ETag`
+#[derive(Debug, Deserialize)]
+struct CachedResponse {
+    etag: String,
+    body: String,
+}
+
+/// Where the response to `url` is cached, mirroring its host and path
+fn api_cache_path(cargo_home: &Path, url: &str) -> Option<PathBuf> {
+    let url = Url::parse(url).ok()?;
+    let mut path = cargo_home.join("cargo-edit").join("api-cache");
+    path.push(url.host_str()?);
+    for segment in url.path_segments()? {
+        if segment.is_empty() || segment == ".." {
+            return None;
+        }
+        path.push(segment);
+    }
+    let mut path = path.into_os_string();
+    path.push(".json");
+    Some(path.into())
+}
+
+fn read_cached(path: &Path) -> Option<CachedResponse> {
+    let data = std::fs::read(path).ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+/// Cache a response; a cache that can't be written only makes the next run slower
+fn write_cached(path: &Path, etag: &str, body: &str) {
+    let entry = serde_json::json!({ "etag": etag, "body": body });
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let _ = std::fs::write(path, entry.to_string());
+}
+
+/// How long a rate-limited response asks to wait, from `Retry-After` in seconds or GitHub's
+/// `X-RateLimit-Reset` timestamp
+fn rate_limit_wait<'r>(header: impl Fn(&str) -> Option<&'r str>, now: u64) -> Option<Duration> {
+    if let Some(seconds) = header("retry-after").and_then(|s| s.trim().parse().ok()) {
+        return Some(Duration::from_secs(seconds));
+    }
+    let reset: u64 = header("x-ratelimit-reset")?.trim().parse().ok()?;
+    Some(Duration::from_secs(reset.saturating_sub(now)))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn versions() -> ApiVersions {
+        serde_json::from_str(
+            r#"{"versions": [
+                {"num": "1.2.0", "yanked": false, "created_at": "2022-03-01T10:00:00.000000+00:00"},
+                {"num": "1.2.0-rc.1", "yanked": false, "created_at": "2022-02-20T10:00:00.000000+00:00"},
+                {"num": "1.1.1", "yanked": true, "created_at": "2022-02-10T10:00:00.000000+00:00"},
+                {"num": "1.1.0", "yanked": false, "created_at": "2022-01-01T10:00:00.000000+00:00", "features": {"std": []}}
+            ]}"#,
+        )
+        .unwrap()
+    }
+
+    fn latest(window: &PublishWindow, flag_allow_prerelease: bool) -> Option<String> {
+        read_latest_published("foo", versions(), flag_allow_prerelease, window)
+            .unwrap()
+            .map(|d| d.version().unwrap().to_owned())
+    }
+
+    #[test]
+    fn unbounded_picks_latest() {
+        assert_eq!(
+            latest(&PublishWindow::new(), false).as_deref(),
+            Some("1.2.0")
+        );
+    }
+
+    #[test]
+    fn before_skips_newer_and_yanked() {
+        let window = PublishWindow::new().set_before("2022-03-01").unwrap();
+        assert_eq!(latest(&window, false).as_deref(), Some("1.1.0"));
+        assert_eq!(latest(&window, true).as_deref(), Some("1.2.0-rc.1"));
+    }
+
+    #[test]
+    fn since_is_inclusive() {
+        let window = PublishWindow::new()
+            .set_since("2022-03-01")
+            .unwrap()
+            .set_before("2022-04-01")
+            .unwrap();
+        assert_eq!(latest(&window, false).as_deref(), Some("1.2.0"));
+        let window = PublishWindow::new().set_since("2022-03-02").unwrap();
+        assert_eq!(latest(&window, false), None);
+    }
+
+    #[test]
+    fn requests_are_spaced() {
+        let now = Instant::now();
+        assert_eq!(turn_wait(None, now), Duration::ZERO);
+        assert_eq!(turn_wait(Some(now), now), MIN_REQUEST_INTERVAL);
+        assert_eq!(
+            turn_wait(Some(now), now + Duration::from_millis(400)),
+            Duration::from_millis(600)
+        );
+        assert_eq!(
+            turn_wait(Some(now), now + Duration::from_secs(5)),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn responses_are_cached_by_url() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let path = api_cache_path(
+            temp.path(),
+            "https://crates.io/api/v1/crates/serde/versions",
+        )
+        .unwrap();
+        assert_eq!(
+            path,
+            temp.path()
+                .join("cargo-edit/api-cache/crates.io/api/v1/crates/serde/versions.json")
+        );
+        assert_eq!(
+            api_cache_path(
+                temp.path(),
+                "https://crates.io/api/v1/crates/a%2F..%2F..b/1.0.0"
+            ),
+            None
+        );
+
+        assert!(read_cached(&path).is_none());
+        write_cached(&path, "\"abc\"", "{}");
+        let cached = read_cached(&path).unwrap();
+        assert_eq!(cached.etag, "\"abc\"");
+        assert_eq!(cached.body, "{}");
+    }
+
+    #[test]
+    fn rate_limit_waits() {
+        let headers = |retry_after: Option<&'static str>, reset: Option<&'static str>| {
+            move |name: &str| match name {
+                "retry-after" => retry_after,
+                "x-ratelimit-reset" => reset,
+                _ => None,
+            }
+        };
+        assert_eq!(
+            rate_limit_wait(headers(Some("5"), Some("1000")), 900),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            rate_limit_wait(headers(None, Some("1000")), 900),
+            Some(Duration::from_secs(100))
+        );
+        assert_eq!(
+            rate_limit_wait(headers(None, Some("1000")), 1100),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(rate_limit_wait(headers(None, None), 900), None);
+    }
+
+    #[test]
+    fn version_lags() {
+        let lag = |current: &str, latest: &str| {
+            read_version_lag(
+                versions(),
+                &current.parse().unwrap(),
+                &latest.parse().unwrap(),
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            lag("1.1.0", "1.2.0"),
+            Some(VersionLag {
+                days: 59,
+                releases: 1
+            })
+        );
+        assert_eq!(
+            lag("1.2.0", "1.2.0"),
+            Some(VersionLag {
+                days: 0,
+                releases: 0
+            })
+        );
+        assert_eq!(lag("1.0.0", "1.2.0"), None);
+    }
+
+    #[test]
+    fn epoch_days() {
+        assert_eq!(days_since_epoch("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(days_since_epoch("2000-03-01T00:00:00Z"), Some(11017));
+        assert_eq!(
+            days_since_epoch("2022-03-01T10:00:00.000000+00:00"),
+            Some(19052)
+        );
+        assert_eq!(days_since_epoch("March 1st"), None);
+    }
+
+    #[test]
+    fn bin_only_crates() {
+        let response: ApiVersionResponse = serde_json::from_str(
+            r#"{"version": {"num": "13.0.0", "license": "Unlicense OR MIT", "has_lib": false, "bin_names": ["rg"]}}"#,
+        )
+        .unwrap();
+        let targets = response.version.targets();
+        assert!(targets.is_bin_only());
+        assert_eq!(
+            targets.bin_only_message("ripgrep").unwrap(),
+            "`ripgrep` has no library, only the `rg` binary; to use it as a command, run `cargo install ripgrep`"
+        );
+
+        let response: ApiVersionResponse =
+            serde_json::from_str(r#"{"version": {"num": "0.1.0", "license": null}}"#).unwrap();
+        let targets = response.version.targets();
+        assert!(!targets.is_bin_only());
+        assert_eq!(targets.bin_only_message("old"), None);
+    }
+
+    #[test]
+    fn min_age_lowers_before() {
+        let day = |date| Duration::from_secs(days_since_epoch(date).unwrap() * 86_400);
+        let window = PublishWindow::new().set_cutoff(day("2022-03-01T00:00:00Z"));
+        assert_eq!(latest(&window, false).as_deref(), Some("1.1.0"));
+        let window = PublishWindow::new()
+            .set_cutoff(day("2022-03-01T00:00:00Z") + Duration::from_secs(11 * 3600));
+        assert_eq!(latest(&window, false).as_deref(), Some("1.2.0"));
+        let window = PublishWindow::new()
+            .set_before("2022-02-01")
+            .unwrap()
+            .set_cutoff(day("2022-03-02T00:00:00Z"));
+        assert_eq!(latest(&window, true).as_deref(), Some("1.1.0"));
+
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            format_timestamp(19052 * 86_400 + 36_000 + 61),
+            "2022-03-01T10:01:01Z"
+        );
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(parse_age("7d").unwrap(), Duration::from_secs(7 * 86_400));
+        assert_eq!(parse_age("2w").unwrap(), Duration::from_secs(14 * 86_400));
+        assert_eq!(parse_age("12h").unwrap(), Duration::from_secs(12 * 3600));
+        assert!(parse_age("7").is_err());
+        assert!(parse_age("d").is_err());
+        assert!(parse_age("a week").is_err());
+    }
+
+    #[test]
+    fn reject_invalid_dates() {
+        assert!(PublishWindow::new().set_before("2022-3-1").is_err());
+        assert!(PublishWindow::new().set_before("yesterday").is_err());
+    }
+}
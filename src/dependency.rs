@@ -1,11 +1,12 @@
 use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 use indexmap::IndexSet;
 use toml_edit::KeyMut;
 
 use super::manifest::str_or_1_len_table;
-use crate::CargoResult;
+use crate::{CargoResult, Error};
 
 /// A dependency handled by Cargo
 ///
@@ -17,6 +18,17 @@ pub struct Dependency {
     pub name: String,
     /// Whether the dependency is opted-in with a feature flag
     pub optional: Option<bool>,
+    /// Whether the dependency is part of the public API, with the unstable
+    /// `public-dependency` cargo feature
+    pub public: Option<bool>,
+
+    /// Kinds of artifacts to depend on, like `bin` or `cdylib:foo`, with the unstable `bindeps`
+    /// cargo feature
+    pub artifact: Option<Vec<String>>,
+    /// Platform to build the artifacts for, or `target` for the one being built for
+    pub target: Option<String>,
+    /// Whether the library is depended on alongside the artifacts
+    pub lib: Option<bool>,
 
     /// List of features to add (or None to keep features unchanged).
     pub features: Option<Vec<String>>,
@@ -44,6 +56,10 @@ impl Dependency {
         Self {
             name: name.into(),
             optional: None,
+            public: None,
+            artifact: None,
+            target: None,
+            lib: None,
             features: None,
             default_features: None,
             inherited_features: None,
@@ -76,6 +92,30 @@ impl Dependency {
         self
     }
 
+    /// Set whether the dependency is exposed in the public API
+    pub fn set_public(mut self, public: bool) -> Self {
+        self.public = Some(public);
+        self
+    }
+
+    /// Set the kinds of artifacts to depend on, like `bin` or `cdylib:foo`
+    pub fn set_artifact(mut self, artifact: Vec<String>) -> Self {
+        self.artifact = Some(artifact);
+        self
+    }
+
+    /// Set the platform to build the artifacts for
+    pub fn set_target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    /// Set whether the library is depended on alongside the artifacts
+    pub fn set_lib(mut self, lib: bool) -> Self {
+        self.lib = Some(lib);
+        self
+    }
+
     /// Set features as an array of string (does some basic parsing)
     #[allow(dead_code)]
     pub fn set_features(mut self, features: Vec<String>) -> Self {
@@ -115,6 +155,90 @@ impl Dependency {
         self
     }
 
+    /// Keep the git source of an existing entry for this dependency
+    ///
+    /// Adding features to a git dependency shouldn't quietly turn it into a registry dependency.
+    /// If `existing` comes from git and this dependency would come from the registry, the git
+    /// source is carried over so only the other fields (like features) get merged.  Callers that
+    /// want to convert the entry to the registry can skip this.
+    pub fn preserve_git_source(mut self, existing: &Dependency) -> Self {
+        if let (Some(Source::Git(src)), Some(Source::Registry(_)) | None) =
+            (existing.source(), self.source())
+        {
+            self.source = Some(Source::Git(src.clone()));
+            self.registry = None;
+        }
+        self
+    }
+
+    /// Keys `existing` customizes that writing this dependency over it would change or drop
+    ///
+    /// Customizations are the keys besides the source and version requirement, like `features`
+    /// or `optional`, as replacing those is the point of adding a dependency again.  Features are
+    /// only dropped when this dependency sets none, as they are otherwise added to the entry's.
+    pub fn overwritten_keys(&self, existing: &Dependency) -> Vec<&'static str> {
+        fn changed<T: PartialEq>(existing: &Option<T>, new: &Option<T>) -> bool {
+            existing.is_some() && existing != new
+        }
+
+        let mut keys = Vec::new();
+        let has_features = existing.features.as_ref().map_or(false, |f| !f.is_empty());
+        if has_features && self.features.is_none() {
+            keys.push("features");
+        }
+        if changed(&existing.default_features, &self.default_features) {
+            keys.push("default-features");
+        }
+        if changed(&existing.optional, &self.optional) {
+            keys.push("optional");
+        }
+        if changed(&existing.rename, &self.rename) {
+            keys.push("package");
+        }
+        if changed(&existing.public, &self.public) {
+            keys.push("public");
+        }
+        if changed(&existing.artifact, &self.artifact) {
+            keys.push("artifact");
+        }
+        if changed(&existing.target, &self.target) {
+            keys.push("target");
+        }
+        if changed(&existing.lib, &self.lib) {
+            keys.push("lib");
+        }
+        keys
+    }
+
+    /// Keep the customizations of `existing` this dependency doesn't set itself
+    ///
+    /// Features are combined, with those of `existing` first.
+    pub fn merge_customizations(mut self, existing: &Dependency) -> Self {
+        if let Some(existing_features) = &existing.features {
+            let mut features = existing_features.clone();
+            for feature in self.features.take().unwrap_or_default() {
+                if !features.contains(&feature) {
+                    features.push(feature);
+                }
+            }
+            self.features = Some(features);
+        }
+        self.default_features = self.default_features.or(existing.default_features);
+        self.optional = self.optional.or(existing.optional);
+        self.public = self.public.or(existing.public);
+        self.lib = self.lib.or(existing.lib);
+        if self.rename.is_none() {
+            self.rename = existing.rename.clone();
+        }
+        if self.artifact.is_none() {
+            self.artifact = existing.artifact.clone();
+        }
+        if self.target.is_none() {
+            self.target = existing.target.clone();
+        }
+        self
+    }
+
     /// Get the dependency source
     pub fn source(&self) -> Option<&Source> {
         self.source.as_ref()
@@ -149,6 +273,26 @@ impl Dependency {
     pub fn optional(&self) -> Option<bool> {
         self.optional
     }
+
+    /// Get whether the dep is exposed in the public API
+    pub fn public(&self) -> Option<bool> {
+        self.public
+    }
+
+    /// Get the kinds of artifacts depended on
+    pub fn artifact(&self) -> Option<&[String]> {
+        self.artifact.as_deref()
+    }
+
+    /// Get the platform the artifacts are built for
+    pub fn target(&self) -> Option<&str> {
+        self.target.as_deref()
+    }
+
+    /// Get whether the library is depended on alongside the artifacts
+    pub fn lib(&self) -> Option<bool> {
+        self.lib
+    }
 }
 
 impl Dependency {
@@ -198,7 +342,8 @@ impl Dependency {
                     }
                     src.into()
                 } else if let Some(path) = table.get("path") {
-                    let path = crate_root
+                    // Verbatim paths don't resolve `..`
+                    let path = dunce::simplified(crate_root)
                         .join(path.as_str().ok_or_else(|| {
                             invalid_type(key, "path", path.type_name(), "string")
                         })?);
@@ -262,6 +407,41 @@ impl Dependency {
             let available_features = BTreeMap::default();
 
             let optional = table.get("optional").and_then(|v| v.as_bool());
+            let public = table.get("public").and_then(|v| v.as_bool());
+
+            let artifact = if let Some(value) = table.get("artifact") {
+                if let Some(kind) = value.as_str() {
+                    Some(vec![kind.to_owned()])
+                } else {
+                    Some(
+                        value
+                            .as_array()
+                            .ok_or_else(|| {
+                                invalid_type(key, "artifact", value.type_name(), "string or array")
+                            })?
+                            .iter()
+                            .map(|v| {
+                                v.as_str().map(|s| s.to_owned()).ok_or_else(|| {
+                                    invalid_type(key, "artifact", v.type_name(), "string")
+                                })
+                            })
+                            .collect::<CargoResult<Vec<String>>>()?,
+                    )
+                }
+            } else {
+                None
+            };
+            let target = if let Some(value) = table.get("target") {
+                Some(
+                    value
+                        .as_str()
+                        .ok_or_else(|| invalid_type(key, "target", value.type_name(), "string"))?
+                        .to_owned(),
+                )
+            } else {
+                None
+            };
+            let lib = table.get("lib").and_then(|v| v.as_bool());
 
             let dep = Self {
                 name,
@@ -272,6 +452,10 @@ impl Dependency {
                 features,
                 available_features,
                 optional,
+                public,
+                artifact,
+                target,
+                lib,
                 inherited_features: None,
             };
             Ok(dep)
@@ -287,6 +471,10 @@ impl Dependency {
         self.rename().unwrap_or(&self.name)
     }
 
+    fn has_artifact_keys(&self) -> bool {
+        self.artifact.is_some() || self.target.is_some() || self.lib.is_some()
+    }
+
     /// Convert dependency to TOML
     ///
     /// Returns a tuple with the dependency's name and either the version as a `String`
@@ -304,7 +492,7 @@ impl Dependency {
             crate_root.display()
         );
         let table: toml_edit::Item = match (
-            self.optional.unwrap_or(false),
+            self.optional.unwrap_or(false) || self.public.is_some() || self.has_artifact_keys(),
             self.features.as_ref(),
             self.default_features.unwrap_or(true),
             self.source.as_ref(),
@@ -380,6 +568,18 @@ impl Dependency {
                 if let Some(v) = self.optional {
                     table.insert("optional", v.into());
                 }
+                if let Some(v) = self.public {
+                    table.insert("public", v.into());
+                }
+                if let Some(artifact) = self.artifact.as_deref() {
+                    table.insert("artifact", artifact_value(artifact));
+                }
+                if let Some(target) = self.target.as_deref() {
+                    table.insert("target", target.into());
+                }
+                if let Some(v) = self.lib {
+                    table.insert("lib", v.into());
+                }
 
                 toml_edit::value(toml_edit::Value::InlineTable(table))
             }
@@ -517,12 +717,99 @@ impl Dependency {
                     table.remove("optional");
                 }
             }
+            match self.public {
+                Some(v) => {
+                    table.set_dotted(false);
+                    overwrite_value(table, "public", v);
+                }
+                None => {
+                    table.remove("public");
+                }
+            }
+            match self.artifact.as_deref() {
+                Some(artifact) => {
+                    table.set_dotted(false);
+                    overwrite_value(table, "artifact", artifact_value(artifact));
+                }
+                None => {
+                    table.remove("artifact");
+                }
+            }
+            match self.target.as_deref() {
+                Some(target) => {
+                    table.set_dotted(false);
+                    overwrite_value(table, "target", target);
+                }
+                None => {
+                    table.remove("target");
+                }
+            }
+            match self.lib {
+                Some(v) => {
+                    table.set_dotted(false);
+                    overwrite_value(table, "lib", v);
+                }
+                None => {
+                    table.remove("lib");
+                }
+            }
         } else {
             unreachable!("Invalid dependency type: {}", item.type_name());
         }
     }
 }
 
+/// What to do when a dependency would be written over an entry customizing it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Keep the entry's customizations the new dependency doesn't set, and its git source
+    Merge,
+    /// Write the new dependency as is, dropping the entry's customizations and turning a git
+    /// entry into a registry one
+    Replace,
+    /// Leave the entry alone
+    Skip,
+}
+
+impl MergeStrategy {
+    /// The dependency to write over `existing`, or `None` to leave it alone
+    pub fn apply(self, dependency: Dependency, existing: &Dependency) -> Option<Dependency> {
+        match self {
+            Self::Merge => Some(
+                dependency
+                    .preserve_git_source(existing)
+                    .merge_customizations(existing),
+            ),
+            Self::Replace => Some(dependency),
+            Self::Skip => None,
+        }
+    }
+}
+
+impl FromStr for MergeStrategy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> CargoResult<Self> {
+        match s {
+            "merge" => Ok(Self::Merge),
+            "replace" => Ok(Self::Replace),
+            "skip" => Ok(Self::Skip),
+            _ => anyhow::bail!(
+                "Invalid merge strategy `{}`, expected `merge`, `replace`, or `skip`",
+                s
+            ),
+        }
+    }
+}
+
+/// A single artifact kind is written as a string, like cargo's documentation does
+fn artifact_value(artifact: &[String]) -> toml_edit::Value {
+    match artifact {
+        [kind] => kind.as_str().into(),
+        _ => artifact.iter().cloned().collect(),
+    }
+}
+
 impl Default for WorkspaceSource {
     fn default() -> Self {
         Self::new()
@@ -563,9 +850,25 @@ impl std::fmt::Display for Dependency {
 }
 
 fn path_field(crate_root: &Path, abs_path: &Path) -> String {
+    // Canonicalized paths may be verbatim (`\\?\C:\...`), which can't be diffed against
+    // non-verbatim ones
+    let crate_root = dunce::simplified(crate_root);
+    let abs_path = dunce::simplified(abs_path);
     let relpath = pathdiff::diff_paths(abs_path, crate_root).expect("both paths are absolute");
-    let relpath = relpath.to_str().unwrap().replace('\\', "/");
-    relpath
+    normalize_path_field(relpath.to_str().unwrap())
+}
+
+/// Render a path the way it should be written to a manifest
+///
+/// Any verbatim prefix left (e.g. when the dependency is on another drive) is dropped, UNC
+/// shares are written as `//server/share`, and `/` is always used as the separator.
+fn normalize_path_field(path: &str) -> String {
+    let path = if let Some(share) = path.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{}", share)
+    } else {
+        path.strip_prefix(r"\\?\").unwrap_or(path).to_owned()
+    };
+    path.replace('\\', "/")
 }
 
 /// Primary location of a dependency
@@ -750,6 +1053,24 @@ impl GitSource {
         }
     }
 
+    /// Expand a `gh:owner/repo` or `gl:group/repo` shorthand to a GitHub or GitLab repo
+    ///
+    /// GitLab repos can be in nested groups, like `gl:group/subgroup/repo`, and on a self-hosted
+    /// instance, like `gl:gitlab.example.com:group/repo`.  Returns `None` for anything else,
+    /// including full URLs.
+    pub fn from_shorthand(spec: &str) -> Option<Self> {
+        let repo = match spec.split_once(':')? {
+            ("gh", path) => RepoPath::parse("github.com", path, false)?,
+            ("gl", rest) => match rest.split_once(':') {
+                Some((host, path)) if is_host(host) => RepoPath::parse(host, path, true)?,
+                Some(_) => return None,
+                None => RepoPath::parse("gitlab.com", rest, true)?,
+            },
+            _ => return None,
+        };
+        Some(Self::new(repo.url()))
+    }
+
     /// Specify an optional branch
     pub fn set_branch(mut self, branch: impl Into<String>) -> Self {
         self.branch = Some(branch.into());
@@ -785,6 +1106,57 @@ impl GitSource {
     }
 }
 
+/// Where a repo is on a forge, like `gitlab.com`, `["group", "subgroup"]` and `repo`
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct RepoPath {
+    host: String,
+    namespace: Vec<String>,
+    name: String,
+}
+
+impl RepoPath {
+    /// Parse `owner/repo`, or with `nested`, `group/subgroup/.../repo`
+    fn parse(host: &str, path: &str, nested: bool) -> Option<Self> {
+        let path = path.strip_suffix(".git").unwrap_or(path);
+        let mut segments: Vec<&str> = path.split('/').collect();
+        if !segments.iter().all(|segment| is_repo_segment(segment)) {
+            return None;
+        }
+        let name = segments.pop()?;
+        if segments.is_empty() || (!nested && segments.len() > 1) {
+            return None;
+        }
+        Some(Self {
+            host: host.to_owned(),
+            namespace: segments.into_iter().map(str::to_owned).collect(),
+            name: name.to_owned(),
+        })
+    }
+
+    fn url(&self) -> String {
+        format!(
+            "https://{}/{}/{}",
+            self.host,
+            self.namespace.join("/"),
+            self.name
+        )
+    }
+}
+
+fn is_host(host: &str) -> bool {
+    host.contains('.')
+        && host.split('.').all(|label| {
+            !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+fn is_repo_segment(segment: &str) -> bool {
+    !segment.is_empty()
+        && segment
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || ['-', '_', '.'].contains(&c))
+}
+
 impl std::fmt::Display for GitSource {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.git)?;
@@ -822,6 +1194,48 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn overwritten_customizations() {
+        let existing = Dependency::new("dep")
+            .set_source(RegistrySource::new("1.0"))
+            .set_features(vec!["derive".to_owned()])
+            .set_optional(true);
+        let plain = Dependency::new("dep").set_source(RegistrySource::new("2.0"));
+        assert_eq!(plain.overwritten_keys(&existing), ["features", "optional"]);
+        let with_features = plain.clone().set_features(vec!["rc".to_owned()]);
+        assert_eq!(with_features.overwritten_keys(&existing), ["optional"]);
+        assert!(existing.overwritten_keys(&plain).is_empty());
+
+        let merged = MergeStrategy::Merge
+            .apply(with_features.clone(), &existing)
+            .unwrap();
+        assert_eq!(merged.version(), Some("2.0"));
+        assert_eq!(
+            merged.features.as_deref(),
+            Some(&["derive".to_owned(), "rc".to_owned()][..])
+        );
+        assert_eq!(merged.optional(), Some(true));
+        assert!(merged.overwritten_keys(&existing).is_empty());
+        assert_eq!(
+            MergeStrategy::Replace.apply(plain.clone(), &existing),
+            Some(plain.clone())
+        );
+        assert_eq!(MergeStrategy::Skip.apply(plain.clone(), &existing), None);
+        assert!("overwrite".parse::<MergeStrategy>().is_err());
+
+        let git = Dependency::new("dep")
+            .set_source(GitSource::new("https://github.com/user/dep"))
+            .set_features(vec!["derive".to_owned()]);
+        let merged = MergeStrategy::Merge.apply(with_features, &git).unwrap();
+        assert_eq!(merged.source(), git.source());
+        assert_eq!(
+            merged.features.as_deref(),
+            Some(&["derive".to_owned(), "rc".to_owned()][..])
+        );
+        let replaced = MergeStrategy::Replace.apply(plain.clone(), &git).unwrap();
+        assert_eq!(replaced.source(), plain.source());
+    }
+
     #[test]
     fn to_toml_simple_dep() {
         let crate_root =
@@ -871,6 +1285,25 @@ mod tests {
         verify_roundtrip(&crate_root, key, &item);
     }
 
+    #[test]
+    fn to_toml_public_dep() {
+        let crate_root =
+            dunce::canonicalize(&std::env::current_dir().unwrap().join(Path::new("/")))
+                .expect("root exists");
+        let dep = Dependency::new("dep")
+            .set_source(RegistrySource::new("1.0"))
+            .set_public(false);
+        let key = dep.toml_key();
+        let item = dep.to_toml(&crate_root);
+
+        assert!(item.is_inline_table());
+
+        let dep = item.as_inline_table().unwrap();
+        assert_eq!(dep.get("public").unwrap().as_bool(), Some(false));
+
+        verify_roundtrip(&crate_root, key, &item);
+    }
+
     #[test]
     fn to_toml_dep_without_default_features() {
         let crate_root =
@@ -993,6 +1426,48 @@ mod tests {
         verify_roundtrip(&crate_root, key, &item);
     }
 
+    #[test]
+    fn to_toml_artifact_dep() {
+        let crate_root =
+            dunce::canonicalize(&std::env::current_dir().unwrap().join(Path::new("/")))
+                .expect("root exists");
+        let dep = Dependency::new("dep")
+            .set_source(RegistrySource::new("1.0"))
+            .set_artifact(vec!["bin".to_owned()])
+            .set_target("wasm32-unknown-unknown")
+            .set_lib(true);
+        let key = dep.toml_key();
+        let item = dep.to_toml(&crate_root);
+
+        assert_eq!(key, "dep".to_owned());
+        assert!(item.is_inline_table());
+
+        let dep = item.as_inline_table().unwrap();
+        assert_eq!(dep.get("artifact").unwrap().as_str(), Some("bin"));
+        assert_eq!(
+            dep.get("target").unwrap().as_str(),
+            Some("wasm32-unknown-unknown")
+        );
+        assert_eq!(dep.get("lib").unwrap().as_bool(), Some(true));
+
+        verify_roundtrip(&crate_root, key, &item);
+
+        let dep = Dependency::new("dep")
+            .set_source(RegistrySource::new("1.0"))
+            .set_artifact(vec!["bin:tool".to_owned(), "cdylib".to_owned()]);
+        let item = dep.to_toml(&crate_root);
+        let artifact = item.as_inline_table().unwrap().get("artifact").unwrap();
+        let artifact: Vec<_> = artifact
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v.as_str())
+            .collect();
+        assert_eq!(artifact, [Some("bin:tool"), Some("cdylib")]);
+
+        verify_roundtrip(&crate_root, "dep", &item);
+    }
+
     #[test]
     fn paths_with_forward_slashes_are_left_as_is() {
         let crate_root =
@@ -1030,6 +1505,129 @@ mod tests {
         verify_roundtrip(&crate_root, key, &item);
     }
 
+    #[test]
+    fn preserve_git_source_when_adding_features() {
+        let crate_root =
+            dunce::canonicalize(&std::env::current_dir().unwrap().join(Path::new("/")))
+                .expect("root exists");
+        let existing = Dependency::new("dep")
+            .set_source(GitSource::new("https://github.com/foo/bar.git").set_branch("main"))
+            .set_features(vec!["a".to_owned()]);
+        let mut item = existing.to_toml(&crate_root);
+        let existing = Dependency::from_toml(&crate_root, "dep", &item).unwrap();
+
+        let dep = Dependency::new("dep")
+            .set_source(RegistrySource::new("1.0"))
+            .set_features(vec!["b".to_owned()])
+            .preserve_git_source(&existing);
+        assert_eq!(dep.source(), existing.source());
+
+        let mut doc = toml_edit::Document::new();
+        doc.insert("dep", std::mem::take(&mut item));
+        let (mut key, item) = doc.as_table_mut().iter_mut().next().unwrap();
+        dep.update_toml(&crate_root, &mut key, item);
+        let table = item.as_inline_table().unwrap();
+        assert_eq!(
+            table.get("git").unwrap().as_str(),
+            Some("https://github.com/foo/bar.git")
+        );
+        assert_eq!(table.get("branch").unwrap().as_str(), Some("main"));
+        assert!(table.get("version").is_none());
+        let features = table
+            .get("features")
+            .and_then(|f| f.as_array())
+            .unwrap()
+            .iter()
+            .map(|f| f.as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(features, ["a", "b"]);
+    }
+
+    #[test]
+    fn preserve_git_source_ignores_registry_entries() {
+        let existing = Dependency::new("dep").set_source(RegistrySource::new("0.1"));
+        let dep = Dependency::new("dep")
+            .set_source(RegistrySource::new("1.0"))
+            .preserve_git_source(&existing);
+        assert_eq!(dep.version(), Some("1.0"));
+    }
+
+    #[test]
+    fn git_shorthands() {
+        assert_eq!(
+            GitSource::from_shorthand("gh:killercup/cargo-edit").map(|s| s.git),
+            Some("https://github.com/killercup/cargo-edit".to_owned())
+        );
+        assert_eq!(
+            GitSource::from_shorthand("gl:gitlab-org/gitlab.git").map(|s| s.git),
+            Some("https://gitlab.com/gitlab-org/gitlab".to_owned())
+        );
+        assert_eq!(
+            GitSource::from_shorthand("gl:group/subgroup/project").map(|s| s.git),
+            Some("https://gitlab.com/group/subgroup/project".to_owned())
+        );
+        assert_eq!(
+            GitSource::from_shorthand("gl:gitlab.example.com:group/sub/project.git").map(|s| s.git),
+            Some("https://gitlab.example.com/group/sub/project".to_owned())
+        );
+        assert_eq!(GitSource::from_shorthand("gh:killercup"), None);
+        assert_eq!(GitSource::from_shorthand("gh:a/b/c"), None);
+        assert_eq!(GitSource::from_shorthand("gl:group//project"), None);
+        assert_eq!(
+            GitSource::from_shorthand("gl:localhost:group/project"),
+            None
+        );
+        assert_eq!(
+            GitSource::from_shorthand("https://github.com/killercup/cargo-edit"),
+            None
+        );
+    }
+
+    #[test]
+    fn normalise_verbatim_drive_paths() {
+        assert_eq!(
+            normalize_path_field(r"\\?\D:\shared\crate"),
+            "D:/shared/crate"
+        );
+    }
+
+    #[test]
+    fn normalise_verbatim_unc_paths() {
+        assert_eq!(
+            normalize_path_field(r"\\?\UNC\server\share\crate"),
+            "//server/share/crate"
+        );
+        assert_eq!(
+            normalize_path_field(r"\\server\share\crate"),
+            "//server/share/crate"
+        );
+    }
+
+    #[test]
+    fn normalise_relative_windows_paths() {
+        assert_eq!(
+            normalize_path_field(r"..\sibling\crate"),
+            "../sibling/crate"
+        );
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn verbatim_crate_root_writes_relative_paths() {
+        let crate_root = Path::new(r"\\?\C:\work\crate");
+        let dep = Dependency::new("dep").set_source(PathSource::new(r"C:\work\sibling"));
+        let key = dep.toml_key();
+        let item = dep.to_toml(crate_root);
+
+        let table = item.as_inline_table().unwrap();
+        let got = table.get("path").unwrap().as_str().unwrap();
+        assert_eq!(got, "../sibling");
+
+        let roundtrip = Dependency::from_toml(crate_root, key, &item).unwrap();
+        let path = &roundtrip.source().unwrap().as_path().unwrap().path;
+        assert_eq!(path, Path::new(r"C:\work\crate\..\sibling"));
+    }
+
     #[track_caller]
     fn verify_roundtrip(crate_root: &Path, key: &str, item: &toml_edit::Item) {
         let roundtrip = Dependency::from_toml(crate_root, key, item).unwrap();
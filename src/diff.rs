@@ -0,0 +1,376 @@
+//! Compare two manifests
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use super::errors::*;
+use super::{Dependency, Manifest, Source};
+
+/// How a dependency differs between two manifests
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChangeKind {
+    /// Only in the new manifest
+    Added,
+    /// Only in the old manifest
+    Removed,
+    /// In both manifests, with a different version requirement or source
+    Changed,
+}
+
+impl ChangeKind {
+    /// Lowercase name, for reports
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Removed => "removed",
+            Self::Changed => "changed",
+        }
+    }
+}
+
+/// A dependency that differs between two manifests
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DependencyChange {
+    /// Dependency table, like `dependencies` or `target.'cfg(unix)'.dev-dependencies`
+    pub table: String,
+    /// Key of the dependency in its table
+    pub name: String,
+    /// What happened to the dependency
+    pub kind: ChangeKind,
+    /// Version requirement or source in the old manifest
+    pub old: Option<String>,
+    /// Version requirement or source in the new manifest
+    pub new: Option<String>,
+}
+
+/// List the dependencies added, removed, or changed from `old` to `new`
+///
+/// Path dependencies are described relative to `crate_root`.  Changes are sorted by table, then
+/// by name.
+pub fn diff_dependencies(
+    crate_root: &Path,
+    old: &Manifest,
+    new: &Manifest,
+) -> CargoResult<Vec<DependencyChange>> {
+    let old = describe_dependencies(crate_root, old)?;
+    let mut new = describe_dependencies(crate_root, new)?;
+
+    let mut changes = Vec::new();
+    for ((table, name), old) in old {
+        let (kind, new) = match new.remove(&(table.clone(), name.clone())) {
+            Some(new) if new == old => continue,
+            Some(new) => (ChangeKind::Changed, Some(new)),
+            None => (ChangeKind::Removed, None),
+        };
+        changes.push(DependencyChange {
+            table,
+            name,
+            kind,
+            old: Some(old),
+            new,
+        });
+    }
+    changes.extend(
+        new.into_iter()
+            .map(|((table, name), new)| DependencyChange {
+                table,
+                name,
+                kind: ChangeKind::Added,
+                old: None,
+                new: Some(new),
+            }),
+    );
+    changes.sort_by(|a, b| (&a.table, &a.name).cmp(&(&b.table, &b.name)));
+    Ok(changes)
+}
+
+fn describe_dependencies(
+    crate_root: &Path,
+    manifest: &Manifest,
+) -> CargoResult<BTreeMap<(String, String), String>> {
+    let mut described = BTreeMap::new();
+    for (table, item) in manifest.get_sections() {
+        let table_name = match table.target() {
+            Some(target) => format!("target.{}.{}", key_repr(target), table.kind_table()),
+            None => table.kind_table().to_owned(),
+        };
+        let deps = item.as_table_like().expect("sections are table-like");
+        for (key, dep_item) in deps.iter() {
+            let description = match Dependency::from_toml(crate_root, key, dep_item) {
+                Ok(dependency) => describe(crate_root, &dependency),
+                // Still report unsupported entries, as written
+                Err(_) => dep_item.to_string().trim().to_owned(),
+            };
+            described.insert((table_name.clone(), key.to_owned()), description);
+        }
+    }
+    Ok(described)
+}
+
+/// Quote `key` as it would be written in a dotted key
+fn key_repr(key: &str) -> String {
+    if key
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        key.to_owned()
+    } else if !key.contains('\'') {
+        format!("'{}'", key)
+    } else {
+        format!("{:?}", key)
+    }
+}
+
+fn describe(crate_root: &Path, dependency: &Dependency) -> String {
+    let source = match dependency.source() {
+        Some(Source::Registry(registry)) => registry.version.clone(),
+        Some(Source::Path(path)) => {
+            let relative = path
+                .path
+                .strip_prefix(dunce::simplified(crate_root))
+                .unwrap_or(&path.path);
+            format!("path {}", relative.display())
+        }
+        Some(Source::Git(git)) => format!("git {}", git),
+        Some(Source::Workspace(_)) => "workspace".to_owned(),
+        None => "*".to_owned(),
+    };
+    match (dependency.rename(), dependency.registry()) {
+        (Some(_), Some(registry)) => {
+            format!("{} {} (registry {})", dependency.name, source, registry)
+        }
+        (Some(_), None) => format!("{} {}", dependency.name, source),
+        (None, Some(registry)) => format!("{} (registry {})", source, registry),
+        (None, None) => source,
+    }
+}
+
+/// Render the changes from `old` to `new` as a unified diff of their lines
+///
+/// Each hunk shows up to `context` unchanged lines around its changes.  Identical inputs render
+/// as an empty string.
+pub fn unified_diff(old: &str, new: &str, label: &str, context: usize) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let lines = diff_lines(&old, &new);
+
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for (i, _) in lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, DiffLine::Same(_)))
+    {
+        let start = i.saturating_sub(context);
+        let end = (i + context + 1).min(lines.len());
+        match hunks.last_mut() {
+            Some(hunk) if start <= hunk.1 => hunk.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+    if hunks.is_empty() {
+        return String::new();
+    }
+
+    let mut diff = format!("--- {}\n+++ {}\n", label, label);
+    for (start, end) in hunks {
+        let before = &lines[..start];
+        let old_start = before
+            .iter()
+            .filter(|l| !matches!(l, DiffLine::Added(_)))
+            .count();
+        let new_start = before
+            .iter()
+            .filter(|l| !matches!(l, DiffLine::Removed(_)))
+            .count();
+        let hunk = &lines[start..end];
+        let old_len = hunk
+            .iter()
+            .filter(|l| !matches!(l, DiffLine::Added(_)))
+            .count();
+        let new_len = hunk
+            .iter()
+            .filter(|l| !matches!(l, DiffLine::Removed(_)))
+            .count();
+        diff.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_start, old_len),
+            hunk_range(new_start, new_len)
+        ));
+        for line in hunk {
+            let (prefix, text) = match line {
+                DiffLine::Same(text) => (' ', text),
+                DiffLine::Removed(text) => ('-', text),
+                DiffLine::Added(text) => ('+', text),
+            };
+            diff.push(prefix);
+            diff.push_str(text);
+            diff.push('\n');
+        }
+    }
+    diff
+}
+
+enum DiffLine<'s> {
+    Same(&'s str),
+    Removed(&'s str),
+    Added(&'s str),
+}
+
+/// Line-by-line edit script from `old` to `new`, through their longest common subsequence
+fn diff_lines<'s>(old: &[&'s str], new: &[&'s str]) -> Vec<DiffLine<'s>> {
+    // `common[i][j]` is the length of the longest common subsequence of `old[i..]` and `new[j..]`
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(DiffLine::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            lines.push(DiffLine::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new[j]));
+            j += 1;
+        }
+    }
+    lines.extend(old[i..].iter().copied().map(DiffLine::Removed));
+    lines.extend(new[j..].iter().copied().map(DiffLine::Added));
+    lines
+}
+
+/// `start,len` of a hunk, 1-based unless the hunk is empty on this side
+fn hunk_range(before: usize, len: usize) -> String {
+    if len == 0 {
+        format!("{},0", before)
+    } else {
+        format!("{},{}", before + 1, len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn diff(old: &str, new: &str) -> Vec<DependencyChange> {
+        diff_dependencies(
+            Path::new("/project"),
+            &old.parse().unwrap(),
+            &new.parse().unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn added_removed_changed() {
+        let changes = diff(
+            r#"
+[dependencies]
+failure = "0.1"
+regex = "1.5"
+serde = "1.0"
+"#,
+            r#"
+[dependencies]
+anyhow = "1.0"
+regex = "1.6"
+serde = { version = "1.0", features = ["derive"] }
+"#,
+        );
+        assert_eq!(
+            changes,
+            vec![
+                DependencyChange {
+                    table: "dependencies".to_owned(),
+                    name: "anyhow".to_owned(),
+                    kind: ChangeKind::Added,
+                    old: None,
+                    new: Some("1.0".to_owned()),
+                },
+                DependencyChange {
+                    table: "dependencies".to_owned(),
+                    name: "failure".to_owned(),
+                    kind: ChangeKind::Removed,
+                    old: Some("0.1".to_owned()),
+                    new: None,
+                },
+                DependencyChange {
+                    table: "dependencies".to_owned(),
+                    name: "regex".to_owned(),
+                    kind: ChangeKind::Changed,
+                    old: Some("1.5".to_owned()),
+                    new: Some("1.6".to_owned()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn tables_are_distinct() {
+        let changes = diff(
+            r#"
+[dev-dependencies]
+tempfile = "3"
+"#,
+            r#"
+[target.'cfg(unix)'.dev-dependencies]
+tempfile = "3"
+"#,
+        );
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].table, "dev-dependencies");
+        assert_eq!(changes[0].kind, ChangeKind::Removed);
+        assert_eq!(changes[1].table, "target.'cfg(unix)'.dev-dependencies");
+        assert_eq!(changes[1].kind, ChangeKind::Added);
+    }
+
+    #[test]
+    fn source_changes() {
+        let changes = diff(
+            r#"
+[dependencies]
+local = { path = "../local" }
+"#,
+            r#"
+[dependencies]
+local = { git = "https://github.com/example/local", branch = "main" }
+"#,
+        );
+        assert_eq!(changes[0].old.as_deref(), Some("path ../local"));
+        assert_eq!(
+            changes[0].new.as_deref(),
+            Some("git https://github.com/example/local?branch=main")
+        );
+    }
+
+    #[test]
+    fn unified_diff_hunks() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\n";
+        let new = "a\nc\nd\ne\nf\ng\nh\ni\n";
+        assert_eq!(
+            unified_diff(old, new, "Cargo.toml", 1),
+            "\
+--- Cargo.toml
++++ Cargo.toml
+@@ -1,3 +1,2 @@
+ a
+-b
+ c
+@@ -8,1 +7,2 @@
+ h
++i
+"
+        );
+        assert_eq!(unified_diff(old, old, "Cargo.toml", 3), "");
+    }
+}
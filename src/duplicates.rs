@@ -0,0 +1,316 @@
+//! Repair dependencies declared twice in hand-edited manifests
+//!
+//! TOML forbids defining a key twice, so a manifest with both `foo = "1"` under `[dependencies]`
+//! and a `[dependencies.foo]` table can't be loaded, let alone edited, until the two are merged.
+use std::collections::BTreeMap;
+
+use super::errors::*;
+
+/// A dependency declared both as a key of its table and as a table of its own
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateDependency {
+    /// Path to the dependency table, like `["target", "cfg(unix)", "dependencies"]`
+    pub table: Vec<String>,
+    /// Key of the dependency in its table
+    pub name: String,
+    /// Fields set differently by the key, whose values were dropped in favor of the table's
+    pub conflicts: Vec<String>,
+}
+
+/// Find the dependencies declared twice in the manifest `source`
+pub fn find_duplicate_dependencies(source: &str) -> Vec<DuplicateDependency> {
+    merge_duplicate_dependencies(source)
+        .map(|(_, duplicates)| duplicates)
+        .unwrap_or_default()
+}
+
+/// Merge dependencies declared both as a key, like `foo = "1"`, and as a `[dependencies.foo]`
+/// table
+///
+/// The merged dependency is the table, with the fields only the key sets added to it.  Where both
+/// set a field, the table's value is kept and the field is listed in
+/// [`DuplicateDependency::conflicts`].  The rest of the manifest is left as written.
+///
+/// Returns the repaired manifest, and the dependencies that were merged.
+pub fn merge_duplicate_dependencies(
+    source: &str,
+) -> CargoResult<(String, Vec<DuplicateDependency>)> {
+    if source.parse::<toml_edit::Document>().is_ok() {
+        return Ok((source.to_owned(), Vec::new()));
+    }
+
+    let (bom, body) = match source.strip_prefix('\u{feff}') {
+        Some(body) => ("\u{feff}", body),
+        None => ("", source),
+    };
+    let sections = split_sections(body);
+    let headers: BTreeMap<&[String], usize> = sections
+        .iter()
+        .enumerate()
+        .filter_map(|(i, section)| Some((section.path.as_deref()?, i)))
+        .collect();
+
+    let mut documents: Vec<Option<toml_edit::Document>> = vec![None; sections.len()];
+    let mut duplicates = Vec::new();
+    for (i, section) in sections.iter().enumerate() {
+        let table_path = match &section.path {
+            Some(path) if is_dependency_path(path) => path,
+            _ => continue,
+        };
+        let keys: Vec<String> = match parse_table(section.text, table_path) {
+            Some(table) => table.iter().map(|(key, _)| key.to_owned()).collect(),
+            None => continue,
+        };
+        for key in keys {
+            let mut entry_path = table_path.clone();
+            entry_path.push(key.clone());
+            let entry = match headers.get(entry_path.as_slice()) {
+                Some(entry) => *entry,
+                None => continue,
+            };
+
+            let document = documents[i]
+                .get_or_insert_with(|| section.text.parse().expect("checked by `parse_table`"));
+            let item = table_mut(document, table_path)
+                .expect("checked by `parse_table`")
+                .remove(&key)
+                .expect("listed above");
+
+            if documents[entry].is_none() {
+                let document: toml_edit::Document = sections[entry]
+                    .text
+                    .parse()
+                    .with_context(|| format!("Unable to parse `[{}]`", entry_path.join(".")))?;
+                documents[entry] = Some(document);
+            }
+            let document = documents[entry].as_mut().expect("parsed above");
+            let table = table_mut(document, &entry_path).ok_or_else(|| {
+                anyhow::format_err!("Expected `{}` to be a table", entry_path.join("."))
+            })?;
+            let conflicts = merge_into(table, item);
+
+            duplicates.push(DuplicateDependency {
+                table: table_path.clone(),
+                name: key,
+                conflicts,
+            });
+        }
+    }
+
+    let mut merged = bom.to_owned();
+    for (section, document) in sections.iter().zip(&documents) {
+        match document {
+            Some(document) => merged.push_str(&document.to_string()),
+            None => merged.push_str(section.text),
+        }
+    }
+    Ok((merged, duplicates))
+}
+
+/// A table header and the lines up to the next one
+struct Section<'s> {
+    /// Path of the table, or `None` before the first header and for arrays of tables
+    path: Option<Vec<String>>,
+    text: &'s str,
+}
+
+fn split_sections(source: &str) -> Vec<Section<'_>> {
+    let mut sections = Vec::new();
+    let mut start = 0;
+    let mut path = None;
+    let mut offset = 0;
+    for line in source.split_inclusive('\n') {
+        if let Some(header) = header(line) {
+            sections.push(Section {
+                path,
+                text: &source[start..offset],
+            });
+            start = offset;
+            path = header;
+        }
+        offset += line.len();
+    }
+    sections.push(Section {
+        path,
+        text: &source[start..],
+    });
+    sections
+}
+
+/// The table a header line opens, `Some(None)` for an array of tables, `None` if not a header
+fn header(line: &str) -> Option<Option<Vec<String>>> {
+    if !line.trim_start().starts_with('[') {
+        return None;
+    }
+    let document: toml_edit::Document = line.parse().ok()?;
+    let mut path = Vec::new();
+    let mut item = document.as_item();
+    while let Some(table) = item.as_table() {
+        let mut entries = table.iter();
+        match (entries.next(), entries.next()) {
+            (Some((key, inner)), None) => {
+                path.push(key.to_owned());
+                item = inner;
+            }
+            _ => break,
+        }
+    }
+    match item {
+        toml_edit::Item::Table(_) if !path.is_empty() => Some(Some(path)),
+        toml_edit::Item::ArrayOfTables(_) => Some(None),
+        _ => None,
+    }
+}
+
+fn is_dependency_path(path: &[String]) -> bool {
+    let is_kind = |kind: &str| {
+        matches!(
+            kind,
+            "dependencies" | "dev-dependencies" | "build-dependencies"
+        )
+    };
+    match path {
+        [kind] => is_kind(kind),
+        [target, _, kind] => target == "target" && is_kind(kind),
+        [workspace, dependencies] => workspace == "workspace" && dependencies == "dependencies",
+        _ => false,
+    }
+}
+
+fn parse_table(text: &str, path: &[String]) -> Option<toml_edit::Table> {
+    let mut document: toml_edit::Document = text.parse().ok()?;
+    table_mut(&mut document, path).map(std::mem::take)
+}
+
+fn table_mut<'d>(
+    document: &'d mut toml_edit::Document,
+    path: &[String],
+) -> Option<&'d mut toml_edit::Table> {
+    path.iter().try_fold(document.as_table_mut(), |table, key| {
+        table.get_mut(key)?.as_table_mut()
+    })
+}
+
+/// Add the fields of the dependency `item` missing from `table`, returning the fields both set
+/// differently
+fn merge_into(table: &mut toml_edit::Table, item: toml_edit::Item) -> Vec<String> {
+    let fields: Vec<(String, toml_edit::Item)> = match &item {
+        toml_edit::Item::Value(toml_edit::Value::String(_)) => {
+            vec![("version".to_owned(), item.clone())]
+        }
+        _ => item
+            .as_table_like()
+            .into_iter()
+            .flat_map(|fields| fields.iter())
+            .map(|(key, value)| (key.to_owned(), value.clone()))
+            .collect(),
+    };
+
+    let mut conflicts = Vec::new();
+    for (key, value) in fields {
+        let value = match value {
+            toml_edit::Item::Value(value) => toml_edit::Item::Value(value.decorated(" ", "")),
+            value => value,
+        };
+        match table.get(&key) {
+            Some(existing) if same_value(existing, &value) => {}
+            Some(_) => conflicts.push(key),
+            None => {
+                table.insert(&key, value);
+            }
+        }
+    }
+    conflicts
+}
+
+fn same_value(a: &toml_edit::Item, b: &toml_edit::Item) -> bool {
+    match (a.as_value(), b.as_value()) {
+        (Some(a), Some(b)) => {
+            a.clone().decorated("", "").to_string() == b.clone().decorated("", "").to_string()
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn merges_key_into_table() {
+        let (merged, duplicates) = merge_duplicate_dependencies(
+            r#"[package]
+name = "foo"
+
+[dependencies]
+regex = "1.5"
+serde = "1.0"
+
+[dependencies.serde]
+features = ["derive"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            merged,
+            r#"[package]
+name = "foo"
+
+[dependencies]
+regex = "1.5"
+
+[dependencies.serde]
+features = ["derive"]
+version = "1.0"
+"#
+        );
+        assert_eq!(
+            duplicates,
+            vec![DuplicateDependency {
+                table: vec!["dependencies".to_owned()],
+                name: "serde".to_owned(),
+                conflicts: Vec::new(),
+            }]
+        );
+        assert!(merged.parse::<toml_edit::Document>().is_ok());
+    }
+
+    #[test]
+    fn table_wins_conflicts() {
+        let (merged, duplicates) = merge_duplicate_dependencies(
+            r#"[target.'cfg(unix)'.dev-dependencies]
+nix = { version = "0.24", optional = true }
+
+[target.'cfg(unix)'.dev-dependencies.nix]
+version = "0.25"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            merged,
+            r#"[target.'cfg(unix)'.dev-dependencies]
+
+[target.'cfg(unix)'.dev-dependencies.nix]
+version = "0.25"
+optional = true
+"#
+        );
+        assert_eq!(
+            duplicates[0].table,
+            ["target", "cfg(unix)", "dev-dependencies"]
+        );
+        assert_eq!(duplicates[0].conflicts, ["version"]);
+    }
+
+    #[test]
+    fn valid_manifests_are_untouched() {
+        let source = r#"[dependencies]
+serde = "1.0"
+
+[dependencies.regex]
+version = "1.5"
+"#;
+        assert!(find_duplicate_dependencies(source).is_empty());
+        assert_eq!(merge_duplicate_dependencies(source).unwrap().0, source);
+    }
+}
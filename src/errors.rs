@@ -65,8 +65,124 @@ impl From<std::io::Error> for CliError {
     }
 }
 
+/// A crate missing from the registries it was looked up in
+#[derive(Debug)]
+pub struct CrateNotFound {
+    message: String,
+}
+
+impl Display for CrateNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CrateNotFound {}
+
+/// What an error comes down to, for scripts to tell failures apart
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Something the user can fix, like a bad argument or manifest
+    User,
+    /// A crate isn't in the registry
+    NotFound,
+    /// A registry or repository couldn't be reached
+    Network,
+}
+
+impl ErrorKind {
+    /// Classify `err` by the causes in its chain, defaulting to [`ErrorKind::User`]
+    pub fn of(err: &Error) -> Self {
+        for cause in err.chain() {
+            if cause.is::<CrateNotFound>() {
+                return Self::NotFound;
+            }
+            if let Some(err) = cause.downcast_ref::<ureq::Error>() {
+                return match err {
+                    ureq::Error::Status(404 | 410, _) => Self::NotFound,
+                    ureq::Error::Status(400..=499, _) => Self::User,
+                    _ => Self::Network,
+                };
+            }
+            if let Some(err) = cause.downcast_ref::<std::io::Error>() {
+                if matches!(
+                    err.kind(),
+                    std::io::ErrorKind::ConnectionRefused
+                        | std::io::ErrorKind::ConnectionReset
+                        | std::io::ErrorKind::ConnectionAborted
+                        | std::io::ErrorKind::TimedOut
+                ) {
+                    return Self::Network;
+                }
+            }
+            #[cfg(feature = "git")]
+            if let Some(err) = cause.downcast_ref::<git2::Error>() {
+                if matches!(
+                    err.class(),
+                    git2::ErrorClass::Net | git2::ErrorClass::Http | git2::ErrorClass::Ssl
+                ) {
+                    return Self::Network;
+                }
+            }
+        }
+        Self::User
+    }
+
+    /// Exit code for the kind, distinct from the `101` of a panic
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Self::User => 1,
+            Self::NotFound => 2,
+            Self::Network => 3,
+        }
+    }
+
+    /// Name of the kind in JSON errors
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::User => "user",
+            Self::NotFound => "not-found",
+            Self::Network => "network",
+        }
+    }
+}
+
+/// `err` as a JSON object for scripts, with its kind, message, and causes
+pub fn error_json(err: &Error) -> serde_json::Value {
+    serde_json::json!({
+        "kind": ErrorKind::of(err).as_str(),
+        "message": err.to_string(),
+        "causes": err.chain().skip(1).map(|c| c.to_string()).collect::<Vec<_>>(),
+    })
+}
+
 pub(crate) fn no_crate_err(name: impl Display) -> Error {
-    anyhow::format_err!("The crate `{}` could not be found in registry index.", name)
+    CrateNotFound {
+        message: format!("The crate `{}` could not be found in registry index.", name),
+    }
+    .into()
+}
+
+pub(crate) fn ambiguous_crate_err<'m>(
+    name: impl Display,
+    matches: impl IntoIterator<Item = &'m str>,
+) -> Error {
+    anyhow::format_err!(
+        "The crate `{}` could not be found in registry index but several similarly named crates were: {}",
+        name,
+        matches.into_iter().collect::<Vec<_>>().join(", ")
+    )
+}
+
+pub(crate) fn no_crate_in_registries_err(name: impl Display, registries: &[&str]) -> Error {
+    CrateNotFound {
+        message: format!(
+            "The crate `{}` could not be found in any of the registries: {}",
+            name,
+            registries.join(", ")
+        ),
+    }
+    .into()
 }
 
 pub(crate) fn non_existent_table_err(table: impl Display) -> Error {
@@ -92,3 +208,34 @@ pub(crate) fn unsupported_version_req(req: impl Display) -> Error {
 pub(crate) fn invalid_release_level(actual: impl Display, version: impl Display) -> Error {
     anyhow::format_err!("Cannot increment the {} field for {}", actual, version)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn error_kinds() {
+        let not_found = no_crate_err("foo").context("Failed to add `foo`");
+        assert_eq!(ErrorKind::of(&not_found), ErrorKind::NotFound);
+        assert_eq!(ErrorKind::of(&not_found).exit_code(), 2);
+
+        let offline: Error = std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out").into();
+        assert_eq!(ErrorKind::of(&offline), ErrorKind::Network);
+
+        let user = anyhow::format_err!("Invalid version requirement");
+        assert_eq!(ErrorKind::of(&user), ErrorKind::User);
+    }
+
+    #[test]
+    fn json_errors() {
+        let err = no_crate_err("foo").context("Failed to add `foo`");
+        assert_eq!(
+            error_json(&err),
+            serde_json::json!({
+                "kind": "not-found",
+                "message": "Failed to add `foo`",
+                "causes": ["The crate `foo` could not be found in registry index."],
+            })
+        );
+    }
+}
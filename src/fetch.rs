@@ -1,13 +1,32 @@
-use std::collections::BTreeMap;
-use std::env;
+use std::collections::{BTreeMap, HashMap};
+use std::num::NonZeroUsize;
 use std::path::Path;
+#[cfg(feature = "git")]
+use std::path::PathBuf;
+use std::sync::Mutex;
+#[cfg(feature = "git")]
 use std::time::Duration;
+use std::time::Instant;
 
 use url::Url;
 
+use super::crate_spec::validate_crates_io_name;
 use super::errors::*;
-use super::registry::registry_url;
+#[cfg(feature = "git")]
+use super::proxy::{git_proxy_options, proxy_for_url};
+#[cfg(feature = "git")]
+use super::registry::cargo_home;
+use super::registry::{registry_url, CRATES_IO_INDEX, CRATES_IO_REGISTRY};
+use super::select::{Latest, LatestCompatible, LatestStable, VersionSelector};
+use super::shell_event;
 use super::shell_status;
+use super::shell_warn;
+use super::sparse::{cached_crate_versions, fetch_crate_versions, is_sparse};
+#[cfg(any(test, feature = "test-support"))]
+use super::testing::mock_registry;
+use super::timings::record_timing;
+#[cfg(feature = "git")]
+use super::timings::time;
 use super::Dependency;
 use super::RegistrySource;
 use super::VersionExt;
@@ -17,112 +36,702 @@ use super::VersionExt;
 /// The registry argument must be specified for crates
 /// from alternative registries.
 ///
-/// The latest version will be returned as a `Dependency`. This will fail, when
+/// `options` decide whether pre-releases, or a crate whose name only differs by `-` and `_`, are
+/// accepted, and what happens when the newest versions have been yanked.
+///
+/// The latest version will be returned as a `Dependency`, along with warnings for the caller to
+/// [`report`](LookupWarning::report). This will fail, when
 ///
 /// - there is no Internet connection and offline is false.
 /// - summaries in registry index with an incorrect format.
 /// - a crate with the given name does not exist on the registry.
+/// - the newest versions are yanked and [`LookupOptions::set_if_yanked`] is [`IfYanked::Error`].
 pub fn get_latest_dependency(
     crate_name: &str,
-    flag_allow_prerelease: bool,
+    options: &LookupOptions,
+    manifest_path: &Path,
+    registry: Option<&Url>,
+) -> CargoResult<(Dependency, Vec<LookupWarning>)> {
+    if crate_name.is_empty() {
+        anyhow::bail!("Found empty crate name");
+    }
+
+    let registry = match registry {
+        Some(url) => url.clone(),
+        None => registry_url(manifest_path, None)?,
+    };
+
+    let crate_versions =
+        fuzzy_query_registry_index(crate_name, options.fuzzy, &registry, manifest_path)?;
+
+    let dep = read_latest_version(&crate_versions, options.allow_prerelease, options.if_yanked)?;
+    let warnings = LookupWarning::substituted(crate_name, &dep.name)
+        .into_iter()
+        .collect();
+
+    Ok((dep, warnings))
+}
+
+/// Query the newest version matching `version_req` from a registry index
+///
+/// Like [`get_latest_dependency`], the returned `Dependency` includes the features that version
+/// exposes, allowing a target version to be checked before upgrading to it.
+pub fn get_compatible_dependency(
+    crate_name: &str,
+    version_req: &semver::VersionReq,
     manifest_path: &Path,
     registry: Option<&Url>,
 ) -> CargoResult<Dependency> {
-    if env::var("CARGO_IS_TEST").is_ok() {
-        // We are in a simulated reality. Nothing is real here.
-        // FIXME: Use actual test handling code.
-        let new_version = if flag_allow_prerelease {
-            format!("99999.0.0-alpha.1+{}", crate_name)
-        } else {
-            match crate_name {
-                "test_breaking" => "0.2.0".to_string(),
-                "test_nonbreaking" => "0.1.1".to_string(),
-                other => format!("99999.0.0+{}", other),
-            }
-        };
+    let registry = match registry {
+        Some(url) => url.clone(),
+        None => registry_url(manifest_path, None)?,
+    };
 
-        let features = if crate_name == "your-face" {
-            [
-                ("nose".to_string(), vec![]),
-                ("mouth".to_string(), vec![]),
-                ("eyes".to_string(), vec![]),
-                ("ears".to_string(), vec![]),
-            ]
-            .into_iter()
-            .collect::<BTreeMap<_, _>>()
-        } else {
-            BTreeMap::default()
-        };
+    let crate_versions = fuzzy_query_registry_index(crate_name, false, &registry, manifest_path)?;
+    read_compatible_version(&crate_versions, version_req)
+}
 
-        return Ok(Dependency::new(crate_name)
-            .set_source(RegistrySource::new(&new_version))
-            .set_available_features(features));
+/// Query latest version from the first registry that has the crate
+///
+/// `registries` are names from cargo config, searched in order, with `crates-io` standing for
+/// the default registry.  This is for setups that mirror some crates in a private registry and
+/// get the rest from crates.io.  The returned `Dependency` records the registry it was found in,
+/// unless that is the default one.  Like [`get_latest_dependency`], warnings are returned for the
+/// caller to report.
+pub fn get_latest_dependency_from_registries(
+    crate_name: &str,
+    options: &LookupOptions,
+    manifest_path: &Path,
+    registries: &[&str],
+) -> CargoResult<(Dependency, Vec<LookupWarning>)> {
+    for &registry in registries {
+        let is_default = registry == CRATES_IO_REGISTRY;
+        let registry_url = registry_url(manifest_path, (!is_default).then(|| registry))?;
+        let found = find_registry_crate(crate_name, options.fuzzy, &registry_url, manifest_path)?
+            .map(|versions| {
+                read_latest_version(&versions, options.allow_prerelease, options.if_yanked)
+            })
+            .transpose()?;
+        if let Some(dep) = found {
+            let warnings = LookupWarning::substituted(crate_name, &dep.name)
+                .into_iter()
+                .collect();
+            let dep = if is_default {
+                dep
+            } else {
+                dep.set_registry(registry)
+            };
+            return Ok((dep, warnings));
+        }
     }
 
-    if crate_name.is_empty() {
-        anyhow::bail!("Found empty crate name");
+    Err(no_crate_in_registries_err(crate_name, registries))
+}
+
+/// Something a registry lookup noticed that the caller should pass on
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LookupWarning {
+    /// Fuzzy matching found the crate under another spelling
+    Substituted {
+        /// Name that was looked up
+        requested: String,
+        /// Name of the crate that was found
+        found: String,
+    },
+}
+
+impl LookupWarning {
+    fn substituted(requested: &str, found: &str) -> Option<Self> {
+        (requested != found).then(|| Self::Substituted {
+            requested: requested.to_owned(),
+            found: found.to_owned(),
+        })
+    }
+
+    /// Print the warning, and report it as a `lookup-warning` event
+    pub fn report(&self) -> CargoResult<()> {
+        let data = match self {
+            Self::Substituted { requested, found } => serde_json::json!({
+                "kind": "substituted",
+                "requested": requested,
+                "found": found,
+            }),
+        };
+        shell_event("lookup-warning", &data)?;
+        shell_warn(&self.to_string())
+    }
+}
+
+impl std::fmt::Display for LookupWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Substituted { requested, found } => {
+                write!(f, "using `{}` instead of `{}`", found, requested)
+            }
+        }
     }
+}
 
+/// Query the version `selector` picks from a registry index
+///
+/// `current` is the requirement the manifest has for the crate, if any.  Like
+/// [`get_latest_dependency`], `options` decide whether other `-`/`_` spellings of the name are
+/// tried, and what happens when yanked versions would otherwise have been picked; `selector`
+/// decides about pre-releases.
+pub fn get_selected_dependency(
+    crate_name: &str,
+    selector: &dyn VersionSelector,
+    current: Option<&semver::VersionReq>,
+    options: &LookupOptions,
+    manifest_path: &Path,
+    registry: Option<&Url>,
+) -> CargoResult<Dependency> {
+    let registry = match registry {
+        Some(url) => url.clone(),
+        None => registry_url(manifest_path, None)?,
+    };
+
+    let crate_versions =
+        fuzzy_query_registry_index(crate_name, options.fuzzy, &registry, manifest_path)?;
+    read_selected_version(&crate_versions, selector, current, options.if_yanked)
+}
+
+/// Check whether `version` of a crate has been yanked from its registry index
+///
+/// Build metadata is ignored when matching `version`, and versions the index doesn't list aren't
+/// reported as yanked.
+pub fn is_yanked_version(
+    crate_name: &str,
+    version: &semver::Version,
+    manifest_path: &Path,
+    registry: Option<&Url>,
+) -> CargoResult<bool> {
+    let registry = match registry {
+        Some(url) => url.clone(),
+        None => registry_url(manifest_path, None)?,
+    };
+
+    let crate_versions = fuzzy_query_registry_index(crate_name, false, &registry, manifest_path)?;
+    let same_version = |v: &semver::Version| {
+        (v.major, v.minor, v.patch, &v.pre)
+            == (version.major, version.minor, version.patch, &version.pre)
+    };
+    Ok(crate_versions
+        .iter()
+        .any(|v| v.yanked && same_version(&v.version)))
+}
+
+/// How the registry index lists `version` of `crate_name`, if it lists it at all
+///
+/// Build metadata is ignored when matching `version`, like [`is_yanked_version`] does.
+pub fn get_crate_version(
+    crate_name: &str,
+    version: &semver::Version,
+    manifest_path: &Path,
+    registry: Option<&Url>,
+) -> CargoResult<Option<CrateVersion>> {
     let registry = match registry {
         Some(url) => url.clone(),
         None => registry_url(manifest_path, None)?,
     };
 
-    let crate_versions = fuzzy_query_registry_index(crate_name, &registry)?;
+    let crate_versions =
+        find_registry_crate(crate_name, false, &registry, manifest_path)?.unwrap_or_default();
+    Ok(crate_versions.into_iter().find(|v| {
+        (
+            v.version.major,
+            v.version.minor,
+            v.version.patch,
+            &v.version.pre,
+        ) == (version.major, version.minor, version.patch, &version.pre)
+    }))
+}
 
-    let dep = read_latest_version(&crate_versions, flag_allow_prerelease)?;
+/// What to do when the newest versions of a crate have been yanked
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IfYanked {
+    /// Fail the lookup
+    Error,
+    /// Use the newest version that isn't yanked, with a warning
+    Warn,
+    /// Quietly use the newest version that isn't yanked
+    NextUnyanked,
+}
 
-    if dep.name != crate_name {
-        eprintln!("WARN: Added `{}` instead of `{}`", dep.name, crate_name);
+impl Default for IfYanked {
+    fn default() -> Self {
+        Self::NextUnyanked
     }
+}
 
-    Ok(dep)
+/// How [`get_latest_dependency`] and its variants pick the latest version
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LookupOptions {
+    allow_prerelease: bool,
+    fuzzy: bool,
+    if_yanked: IfYanked,
+    jobs: Option<NonZeroUsize>,
+    shallow_index: bool,
 }
 
-#[derive(Debug)]
-struct CrateVersion {
-    name: String,
-    version: semver::Version,
-    yanked: bool,
-    available_features: BTreeMap<String, Vec<String>>,
+impl LookupOptions {
+    /// Pick the newest stable version of the crate named exactly, skipping yanked versions
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also pick pre-releases
+    pub fn set_allow_prerelease(mut self, allow_prerelease: bool) -> Self {
+        self.allow_prerelease = allow_prerelease;
+        self
+    }
+
+    /// Accept a crate whose name only differs by `-` and `_`, reported as a
+    /// [`LookupWarning::Substituted`]
+    pub fn set_fuzzy(mut self, fuzzy: bool) -> Self {
+        self.fuzzy = fuzzy;
+        self
+    }
+
+    /// What happens when the newest versions have been yanked
+    pub fn set_if_yanked(mut self, if_yanked: IfYanked) -> Self {
+        self.if_yanked = if_yanked;
+        self
+    }
+
+    /// How many registry lookups and index updates run at once, or `None` for the default
+    ///
+    /// The default scales with the CPUs available, and is 1 when only one is.
+    pub fn set_jobs(mut self, jobs: Option<NonZeroUsize>) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Whether git registry indexes cloned from now on only get their latest commit
+    ///
+    /// Shallow clones are made with the `git` command, as libgit2 can't, and kept up to date with
+    /// it.  They take seconds to set up instead of downloading the full history of crates.io's
+    /// index, for fresh CI containers.  They are kept in `$CARGO_HOME/cargo-edit/index`, apart
+    /// from cargo's clones, and indexes cargo already cloned in full are used as they are.
+    pub fn set_shallow_index(mut self, shallow: bool) -> Self {
+        self.shallow_index = shallow;
+        self
+    }
+
+    /// How many registry lookups and index updates run at once
+    pub fn jobs(&self) -> usize {
+        match self.jobs {
+            Some(jobs) => jobs.get(),
+            None => default_jobs(
+                std::thread::available_parallelism()
+                    .ok()
+                    .map(NonZeroUsize::get),
+            ),
+        }
+    }
 }
 
-/// Fuzzy query crate from registry index
-fn fuzzy_query_registry_index(
+/// A published version of a crate, as listed in a registry index
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CrateVersion {
+    /// Name of the crate
+    pub name: String,
+    /// Semver version
+    pub version: semver::Version,
+    /// Whether the version was yanked
+    pub yanked: bool,
+    /// Features and what they activate, including optional dependencies
+    pub available_features: BTreeMap<String, Vec<String>>,
+    /// Oldest Rust the version builds with, its `rust-version`, if the registry records it
+    pub rust_version: Option<String>,
+    /// Edition the version is written in, if the registry records it
+    pub edition: Option<String>,
+    /// SHA-256 of the `.crate` file, in hex, if the registry records it
+    pub checksum: Option<String>,
+}
+
+/// Query crate from registry index
+///
+/// When `fuzzy` is set and the name isn't found as written, spellings with `-` and `_` swapped
+/// are tried until one is found.  Registries like crates.io reject names that only differ by
+/// separators, so the first match is the crate.
+pub(crate) fn fuzzy_query_registry_index(
     crate_name: impl Into<String>,
+    fuzzy: bool,
     registry: &Url,
+    manifest_path: &Path,
 ) -> CargoResult<Vec<CrateVersion>> {
-    let index = crates_index::Index::from_url(registry.as_str())?;
-
     let crate_name = crate_name.into();
-    let mut names = gen_fuzzy_crate_names(crate_name.clone())?;
-    if let Some(index) = names.iter().position(|x| *x == crate_name) {
-        // ref: https://github.com/killercup/cargo-edit/pull/317#discussion_r307365704
-        names.swap(index, 0);
+    find_registry_crate(&crate_name, fuzzy, registry, manifest_path)?
+        .ok_or_else(|| no_crate_err(crate_name))
+}
+
+/// Like [`fuzzy_query_registry_index`] but a missing crate is `None` rather than an error
+///
+/// With the `test-support` feature, a [`MockRegistry`](crate::testing::MockRegistry), when
+/// installed, stands in for `registry`.
+/// Until the index is updated, crates cargo cached from a sparse index are read from that cache
+/// first.  Sparse indexes are otherwise fetched over HTTP, and so is crates.io without the `git`
+/// feature.  Both go through the proxy cargo config sets for `manifest_path`.
+fn find_registry_crate(
+    crate_name: &str,
+    fuzzy: bool,
+    registry: &Url,
+    manifest_path: &Path,
+) -> CargoResult<Option<Vec<CrateVersion>>> {
+    if is_crates_io_index(registry) {
+        validate_crates_io_name(crate_name)?;
+    }
+    #[cfg(any(test, feature = "test-support"))]
+    if let Some(mock) = mock_registry()? {
+        return find_crate_by(crate_name, fuzzy, registry, |name| {
+            mock.crate_versions(name)
+        });
+    }
+    let sparse_lookup = |name: &str| {
+        let versions = cached_crate_versions(registry, name)?;
+        if versions.is_some() {
+            record_source(registry, name, IndexProtocol::Sparse, true);
+        }
+        Ok(versions)
+    };
+    if !was_updated(registry) {
+        if let Some(versions) = find_crate_by(crate_name, fuzzy, registry, sparse_lookup)? {
+            return Ok(Some(versions));
+        }
     }
 
-    for the_name in names {
-        let crate_ = match index.crate_(&the_name) {
-            Some(crate_) => crate_,
-            None => continue,
-        };
-        return crate_
-            .versions()
+    let work_dir = manifest_path
+        .parent()
+        .expect("there must be a parent directory");
+    if is_sparse(registry) {
+        return sparse_index_lookup(crate_name, fuzzy, registry, work_dir);
+    }
+    index_lookup(crate_name, fuzzy, registry, work_dir)
+}
+
+/// Fetch the crate from the sparse index of `registry` over HTTP
+fn sparse_index_lookup(
+    crate_name: &str,
+    fuzzy: bool,
+    registry: &Url,
+    work_dir: &Path,
+) -> CargoResult<Option<Vec<CrateVersion>>> {
+    find_crate_by(crate_name, fuzzy, registry, |name| {
+        cached_lookup(registry, name, (IndexProtocol::Sparse, false), || {
+            fetch_crate_versions(registry, name, work_dir)
+        })
+    })
+}
+
+#[cfg(feature = "git")]
+fn index_lookup(
+    crate_name: &str,
+    fuzzy: bool,
+    registry: &Url,
+    work_dir: &Path,
+) -> CargoResult<Option<Vec<CrateVersion>>> {
+    let index = open_index(registry, work_dir)?;
+    let source = (IndexProtocol::Git, !was_updated(registry));
+    find_crate_by(crate_name, fuzzy, registry, |name| {
+        cached_lookup(registry, name, source, || {
+            index
+                .crate_(name)
+                .map(|crate_| crate_versions(&crate_))
+                .transpose()
+        })
+    })
+}
+
+#[cfg(not(feature = "git"))]
+fn index_lookup(
+    crate_name: &str,
+    fuzzy: bool,
+    registry: &Url,
+    work_dir: &Path,
+) -> CargoResult<Option<Vec<CrateVersion>>> {
+    sparse_index_lookup(crate_name, fuzzy, registry, work_dir)
+}
+
+/// Most registry lookups and index updates running at once, unless set with
+/// [`LookupOptions::set_jobs`]
+const MAX_DEFAULT_JOBS: usize = 16;
+/// Registry requests in flight per CPU by default, as they mostly wait on the network
+const JOBS_PER_CPU: usize = 4;
+
+fn default_jobs(cpus: Option<usize>) -> usize {
+    match cpus {
+        None | Some(0 | 1) => 1,
+        Some(cpus) => (cpus * JOBS_PER_CPU).min(MAX_DEFAULT_JOBS),
+    }
+}
+
+/// Read `crate_names` from the `registry` index in one pass, so looking them up afterwards is
+/// answered from memory
+///
+/// A git index is opened once for all of them rather than once per crate, and a sparse index's
+/// files are fetched concurrently.  Crates already read, or that cargo cached from a sparse index,
+/// are skipped.  This is best-effort: a crate that can't be read here is left for its lookup to
+/// report.  Like lookups, `manifest_path` is the manifest depending on the crates, and `options`
+/// bound how many sparse index files are fetched at once.
+pub fn prefetch_registry_crates(
+    registry: &Url,
+    crate_names: &[String],
+    options: &LookupOptions,
+    manifest_path: &Path,
+) {
+    if !matches!(mock_registry(), Ok(None)) {
+        return;
+    }
+    let updated = was_updated(registry);
+    let mut pending: Vec<&str> = {
+        let cache = INDEX_CACHE.lock().expect("not poisoned");
+        crate_names
             .iter()
-            .map(|v| {
-                Ok(CrateVersion {
-                    name: v.name().to_owned(),
-                    version: v.version().parse()?,
-                    yanked: v.is_yanked(),
-                    available_features: registry_features(v),
-                })
+            .filter(|name| {
+                let key = (registry.as_str().to_owned(), name.to_string());
+                !cache.as_ref().map_or(false, |c| c.contains_key(&key))
             })
-            .collect();
+            .map(String::as_str)
+            .collect()
+    };
+    pending.sort_unstable();
+    pending.dedup();
+    if !updated {
+        pending.retain(|name| !matches!(cached_crate_versions(registry, name), Ok(Some(_))));
+    }
+    if !pending.is_empty() {
+        let work_dir = manifest_path
+            .parent()
+            .expect("there must be a parent directory");
+        prefetch(registry, &pending, options.jobs(), work_dir);
     }
-    Err(no_crate_err(crate_name))
 }
 
-/// Generate all similar crate names
+#[cfg(feature = "git")]
+fn prefetch(registry: &Url, crate_names: &[&str], jobs: usize, work_dir: &Path) {
+    if is_sparse(registry) {
+        return prefetch_sparse(registry, crate_names, jobs, work_dir);
+    }
+    let index = match open_index(registry, work_dir) {
+        Ok(index) => index,
+        Err(_) => return,
+    };
+    let source = (IndexProtocol::Git, !was_updated(registry));
+    for name in crate_names {
+        let _ = cached_lookup(registry, name, source, || {
+            index
+                .crate_(name)
+                .map(|crate_| crate_versions(&crate_))
+                .transpose()
+        });
+    }
+}
+
+#[cfg(not(feature = "git"))]
+fn prefetch(registry: &Url, crate_names: &[&str], jobs: usize, work_dir: &Path) {
+    prefetch_sparse(registry, crate_names, jobs, work_dir)
+}
+
+/// Fetch `crate_names` from the sparse index of `registry`, `jobs` at a time
+fn prefetch_sparse(registry: &Url, crate_names: &[&str], jobs: usize, work_dir: &Path) {
+    let queue = Mutex::new(crate_names.iter());
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.min(crate_names.len()) {
+            scope.spawn(|| loop {
+                let name = match queue.lock().expect("not poisoned").next() {
+                    Some(name) => *name,
+                    None => break,
+                };
+                let _ = cached_lookup(registry, name, (IndexProtocol::Sparse, false), || {
+                    fetch_crate_versions(registry, name, work_dir)
+                });
+            });
+        }
+    });
+}
+
+type IndexCache = HashMap<(String, String), Option<Vec<CrateVersion>>>;
+
+/// Crates read from each registry index, until that index is updated
+///
+/// Upgrading a workspace looks the same crates up for every member, and again for pre-releases
+/// and compatible versions.
+static INDEX_CACHE: Mutex<Option<IndexCache>> = Mutex::new(None);
+
+/// Indexes updated by this process, which are fresher than cargo's cache of them
+static UPDATED_INDEXES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Whether `registry` was updated by this process
+fn was_updated(registry: &Url) -> bool {
+    UPDATED_INDEXES
+        .lock()
+        .expect("not poisoned")
+        .iter()
+        .any(|u| u == registry.as_str())
+}
+
+/// Where the versions of a crate came from, for `--verbose` output
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LookupSource {
+    /// The registry index, after source replacement
+    pub registry: Url,
+    /// How the index was read
+    pub protocol: IndexProtocol,
+    /// Whether the versions were read without fetching them in this run, from cargo's cache or a
+    /// git index that wasn't updated
+    pub cached: bool,
+}
+
+/// How a registry index is read
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IndexProtocol {
+    /// A file per crate, over HTTP
+    Sparse,
+    /// A clone of a git repository
+    Git,
+}
+
+impl std::fmt::Display for LookupSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let protocol = match self.protocol {
+            IndexProtocol::Sparse => "sparse",
+            IndexProtocol::Git => "git",
+        };
+        let cached = if self.cached { "cached" } else { "fetched" };
+        write!(f, "`{}` ({} index, {})", self.registry, protocol, cached)
+    }
+}
+
+type LookupSources = HashMap<(String, String), LookupSource>;
+
+/// Where each crate looked up was read from, by registry and name
+static LOOKUP_SOURCES: Mutex<Option<LookupSources>> = Mutex::new(None);
+
+fn record_source(registry: &Url, crate_name: &str, protocol: IndexProtocol, cached: bool) {
+    LOOKUP_SOURCES
+        .lock()
+        .expect("not poisoned")
+        .get_or_insert_with(HashMap::new)
+        .insert(
+            (registry.as_str().to_owned(), crate_name.to_owned()),
+            LookupSource {
+                registry: registry.clone(),
+                protocol,
+                cached,
+            },
+        );
+}
+
+/// Where the versions of `crate_name` were last read from `registry`, if they were
+///
+/// Lookups against a [mock registry](crate::testing) aren't recorded.
+pub fn lookup_source(registry: &Url, crate_name: &str) -> Option<LookupSource> {
+    LOOKUP_SOURCES
+        .lock()
+        .expect("not poisoned")
+        .as_ref()?
+        .get(&(registry.as_str().to_owned(), crate_name.to_owned()))
+        .cloned()
+}
+
+/// Look `crate_name` up with `lookup`, unless it was already, recording the index it was read
+/// from as `source`
+fn cached_lookup(
+    registry: &Url,
+    crate_name: &str,
+    source: (IndexProtocol, bool),
+    lookup: impl FnOnce() -> CargoResult<Option<Vec<CrateVersion>>>,
+) -> CargoResult<Option<Vec<CrateVersion>>> {
+    let key = (registry.as_str().to_owned(), crate_name.to_owned());
+    let start = Instant::now();
+    let cached = INDEX_CACHE
+        .lock()
+        .expect("not poisoned")
+        .as_ref()
+        .and_then(|cache| cache.get(&key).cloned());
+    if let Some(versions) = cached {
+        record_timing("lookup", crate_name, start.elapsed(), true);
+        return Ok(versions);
+    }
+
+    let versions = lookup()?;
+    record_timing("lookup", crate_name, start.elapsed(), false);
+    if versions.is_some() {
+        record_source(registry, crate_name, source.0, source.1);
+    }
+    INDEX_CACHE
+        .lock()
+        .expect("not poisoned")
+        .get_or_insert_with(HashMap::new)
+        .insert(key, versions.clone());
+    Ok(versions)
+}
+
+/// Look `crate_name` up with `lookup`, trying its other `-`/`_` spellings when `fuzzy`
+///
+/// crates.io treats `-` and `_` alike, so only one spelling can exist there and the search stops
+/// at the first found.  Other registries may have several, which is an error listing them all,
+/// unless the name as given is one of them.
+fn find_crate_by(
+    crate_name: &str,
+    fuzzy: bool,
+    registry: &Url,
+    lookup: impl Fn(&str) -> CargoResult<Option<Vec<CrateVersion>>>,
+) -> CargoResult<Option<Vec<CrateVersion>>> {
+    // ref: https://github.com/killercup/cargo-edit/pull/317#discussion_r307365704
+    if let Some(versions) = lookup(crate_name)? {
+        return Ok(Some(versions));
+    }
+    if !fuzzy {
+        return Ok(None);
+    }
+
+    let unique = is_crates_io_index(registry);
+    let mut found = Vec::new();
+    for name in gen_fuzzy_crate_names(crate_name).skip(1) {
+        if let Some(versions) = lookup(&name)? {
+            if unique {
+                return Ok(Some(versions));
+            }
+            found.push((name, versions));
+        }
+    }
+    if 1 < found.len() {
+        return Err(ambiguous_crate_err(
+            crate_name,
+            found.iter().map(|(name, _)| name.as_str()),
+        ));
+    }
+    Ok(found.pop().map(|(_, versions)| versions))
+}
+
+/// Whether `registry` is crates.io's index, over git or sparse
+fn is_crates_io_index(registry: &Url) -> bool {
+    let registry = registry.as_str().trim_end_matches('/');
+    registry == CRATES_IO_INDEX || registry == "sparse+https://index.crates.io"
+}
+
+#[cfg(feature = "git")]
+fn crate_versions(crate_: &crates_index::Crate) -> CargoResult<Vec<CrateVersion>> {
+    crate_
+        .versions()
+        .iter()
+        .map(|v| {
+            Ok(CrateVersion {
+                name: v.name().to_owned(),
+                version: v.version().parse()?,
+                yanked: v.is_yanked(),
+                available_features: registry_features(v),
+                rust_version: None,
+                edition: None,
+                checksum: Some(hex::encode(v.checksum())),
+            })
+        })
+        .collect()
+}
+
+/// Generate all similar crate names, most likely first
 ///
 /// Examples:
 ///
@@ -130,67 +739,187 @@ fn fuzzy_query_registry_index(
 /// | ----- | ------ |
 /// | cargo | cargo  |
 /// | cargo-edit | cargo-edit, cargo_edit |
-/// | parking_lot_core | parking_lot_core, parking_lot-core, parking-lot_core, parking-lot-core |
-fn gen_fuzzy_crate_names(crate_name: String) -> CargoResult<Vec<String>> {
-    const PATTERN: [u8; 2] = [b'-', b'_'];
-
-    let wildcard_indexs = crate_name
-        .bytes()
-        .enumerate()
-        .filter(|(_, item)| PATTERN.contains(item))
-        .map(|(index, _)| index)
-        .take(10)
-        .collect::<Vec<usize>>();
-    if wildcard_indexs.is_empty() {
-        return Ok(vec![crate_name]);
-    }
-
-    let mut result = vec![];
-    let mut bytes = crate_name.into_bytes();
-    for mask in 0..2u128.pow(wildcard_indexs.len() as u32) {
-        for (mask_index, wildcard_index) in wildcard_indexs.iter().enumerate() {
-            let mask_value = (mask >> mask_index) & 1 == 1;
-            if mask_value {
-                bytes[*wildcard_index] = b'-';
-            } else {
-                bytes[*wildcard_index] = b'_';
-            }
+/// | parking_lot-core | parking_lot-core, parking-lot-core, parking_lot_core, parking-lot_core |
+fn gen_fuzzy_crate_names(crate_name: &str) -> FuzzyCrateNames {
+    let separators = &['-', '_'][..];
+    let mut prioritized = vec![crate_name.to_owned()];
+    for separator in ["-", "_"] {
+        let name = crate_name.replace(separators, separator);
+        if !prioritized.contains(&name) {
+            prioritized.push(name);
         }
-        result.push(String::from_utf8(bytes.clone()).unwrap());
     }
-    Ok(result)
+    let wildcards = crate_name
+        .char_indices()
+        .filter(|(_, c)| separators.contains(c))
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    FuzzyCrateNames {
+        bytes: crate_name.as_bytes().to_vec(),
+        combination: (!wildcards.is_empty()).then(|| vec![false; wildcards.len()]),
+        wildcards,
+        prioritized,
+        position: 0,
+    }
 }
 
-// Checks whether a version object is a stable release
-fn version_is_stable(version: &CrateVersion) -> bool {
-    !version.version.is_prerelease()
+/// Spellings of a crate name with each `-` and `_` swapped for the other, generated lazily
+///
+/// The name as given comes first, then the spellings using only `-` or only `_`, then every other
+/// combination.
+struct FuzzyCrateNames {
+    bytes: Vec<u8>,
+    wildcards: Vec<usize>,
+    prioritized: Vec<String>,
+    position: usize,
+    /// Which wildcards are `-` in the next combination, `None` once all were generated
+    combination: Option<Vec<bool>>,
+}
+
+impl Iterator for FuzzyCrateNames {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if let Some(name) = self.prioritized.get(self.position) {
+            self.position += 1;
+            return Some(name.clone());
+        }
+        loop {
+            let dashes = self.combination.as_mut()?;
+            let mut bytes = self.bytes.clone();
+            for (&index, &dash) in self.wildcards.iter().zip(dashes.iter()) {
+                bytes[index] = if dash { b'-' } else { b'_' };
+            }
+            // Count up in binary, finishing when every wildcard has been both
+            let mut carry = true;
+            for dash in dashes.iter_mut() {
+                *dash = !*dash;
+                if *dash {
+                    carry = false;
+                    break;
+                }
+            }
+            if carry {
+                self.combination = None;
+            }
+
+            let name = String::from_utf8(bytes).expect("only ASCII separators were swapped");
+            if !self.prioritized.contains(&name) {
+                return Some(name);
+            }
+        }
+    }
 }
 
 /// Read latest version from Versions structure
 fn read_latest_version(
     versions: &[CrateVersion],
     flag_allow_prerelease: bool,
+    if_yanked: IfYanked,
 ) -> CargoResult<Dependency> {
-    let latest = versions
-        .iter()
-        .filter(|&v| flag_allow_prerelease || version_is_stable(v))
-        .filter(|&v| !v.yanked)
-        .max_by_key(|&v| v.version.clone())
-        .ok_or_else(|| {
-            anyhow::format_err!(
-                "No available versions exist. Either all were yanked \
-                         or only prerelease versions exist. Trying with the \
-                         --allow-prerelease flag might solve the issue."
-            )
-        })?;
+    let selector: &dyn VersionSelector = if flag_allow_prerelease {
+        &Latest
+    } else {
+        &LatestStable
+    };
+    read_selected_version(versions, selector, None, if_yanked)
+}
+
+/// Read the version `selector` picks
+///
+/// Yanked versions `selector` would have picked over that one are reported as a
+/// `yanked-skipped` event.
+fn read_selected_version(
+    versions: &[CrateVersion],
+    selector: &dyn VersionSelector,
+    current: Option<&semver::VersionReq>,
+    if_yanked: IfYanked,
+) -> CargoResult<Dependency> {
+    let latest = selector.select(versions, current).ok_or_else(|| {
+        anyhow::format_err!(
+            "No available versions exist. Either all were yanked \
+                     or only prerelease versions exist. Trying with the \
+                     --allow-prerelease flag might solve the issue."
+        )
+    })?;
 
     let name = &latest.name;
     let version = latest.version.to_string();
+
+    // What would have been picked if nothing was yanked
+    let unyanked = versions
+        .iter()
+        .map(|v| CrateVersion {
+            yanked: false,
+            ..v.clone()
+        })
+        .collect::<Vec<_>>();
+    let preferred = selector
+        .select(&unyanked, current)
+        .map(|v| v.version.clone())
+        .unwrap_or_else(|| latest.version.clone());
+    let mut yanked = unyanked
+        .iter()
+        .zip(versions)
+        .filter(|(unyanked, v)| {
+            v.yanked
+                && latest.version < v.version
+                && v.version <= preferred
+                && selector
+                    .select(std::slice::from_ref(unyanked), current)
+                    .is_some()
+        })
+        .map(|(_, v)| &v.version)
+        .collect::<Vec<_>>();
+    if !yanked.is_empty() {
+        yanked.sort();
+        let yanked = yanked.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        shell_event(
+            "yanked-skipped",
+            &serde_json::json!({
+                "name": name,
+                "yanked": yanked,
+                "selected": version,
+            }),
+        )?;
+        match if_yanked {
+            IfYanked::Error => anyhow::bail!(
+                "The newest versions of `{}` are yanked: {}",
+                name,
+                yanked.join(", ")
+            ),
+            IfYanked::Warn => shell_warn(&format!(
+                "skipping yanked {} {}, using {}",
+                name,
+                yanked.join(", "),
+                version
+            ))?,
+            IfYanked::NextUnyanked => {}
+        }
+    }
+
     Ok(Dependency::new(name)
         .set_source(RegistrySource::new(&version))
         .set_available_features(latest.available_features.clone()))
 }
 
+/// Read the newest version matching `version_req`
+fn read_compatible_version(
+    versions: &[CrateVersion],
+    version_req: &semver::VersionReq,
+) -> CargoResult<Dependency> {
+    let compatible = LatestCompatible
+        .select(versions, Some(version_req))
+        .ok_or_else(|| anyhow::format_err!("No available versions match `{}`", version_req))?;
+
+    let name = &compatible.name;
+    let version = compatible.version.to_string();
+    Ok(Dependency::new(name)
+        .set_source(RegistrySource::new(&version))
+        .set_available_features(compatible.available_features.clone()))
+}
+
+#[cfg(feature = "git")]
 fn registry_features(v: &crates_index::Version) -> BTreeMap<String, Vec<String>> {
     let mut features: BTreeMap<_, _> = v
         .features()
@@ -206,43 +935,356 @@ fn registry_features(v: &crates_index::Version) -> BTreeMap<String, Vec<String>>
     features
 }
 
+/// Without the `test-support` feature, there's no mock registry to stand in for the real ones
+#[cfg(not(any(test, feature = "test-support")))]
+fn mock_registry() -> CargoResult<Option<std::convert::Infallible>> {
+    Ok(None)
+}
+
 /// update registry index for given project
-pub fn update_registry_index(registry: &Url, quiet: bool) -> CargoResult<()> {
-    let mut index = crates_index::Index::from_url(registry.as_str())?;
+///
+/// The index is fetched through the proxy cargo config sets for `manifest_path`, shallowly if
+/// `options` say so.
+pub fn update_registry_index(
+    registry: &Url,
+    options: &LookupOptions,
+    manifest_path: &Path,
+    quiet: bool,
+) -> CargoResult<()> {
+    if mock_registry()?.is_some() {
+        return Ok(());
+    }
     if !quiet {
         shell_status("Updating", &format!("'{}' index", registry))?;
     }
+    let work_dir = manifest_path
+        .parent()
+        .expect("there must be a parent directory");
+    update_index(registry, options, work_dir)
+}
 
-    while need_retry(index.update())? {
-        shell_status("Blocking", "waiting for lock on registry index")?;
-        std::thread::sleep(REGISTRY_BACKOFF);
+/// Update several registry indexes
+///
+/// Each index is updated once, however often it is listed.  The updates run in parallel, as many
+/// at a time as `options` allow, each through the proxy cargo config sets for `manifest_path` and
+/// its registry.
+pub fn update_registry_indexes(
+    registries: &[Url],
+    options: &LookupOptions,
+    manifest_path: &Path,
+    quiet: bool,
+) -> CargoResult<()> {
+    if mock_registry()?.is_some() {
+        return Ok(());
+    }
+    let mut unique: Vec<&Url> = Vec::new();
+    for registry in registries {
+        if !unique.contains(&registry) {
+            unique.push(registry);
+        }
     }
 
+    if !quiet {
+        for registry in &unique {
+            shell_status("Updating", &format!("'{}' index", registry))?;
+        }
+    }
+    let work_dir = manifest_path
+        .parent()
+        .expect("there must be a parent directory");
+    for batch in unique.chunks(options.jobs()) {
+        std::thread::scope(|scope| {
+            let updates = batch
+                .iter()
+                .map(|registry| scope.spawn(move || update_index(registry, options, work_dir)))
+                .collect::<Vec<_>>();
+            updates
+                .into_iter()
+                .map(|update| update.join().expect("index updates don't panic"))
+                .collect::<CargoResult<()>>()
+        })?;
+    }
+    Ok(())
+}
+
+/// Fetch the latest `registry` index, through the proxy configured for `work_dir`
+///
+/// Sparse indexes are fetched a crate at a time, so once `registry` is marked as updated, lookups
+/// skip cargo's cache and fetch the crate instead.
+fn update_index(registry: &Url, options: &LookupOptions, work_dir: &Path) -> CargoResult<()> {
+    if !is_sparse(registry) {
+        fetch_index(registry, options.shallow_index, work_dir)?;
+    }
+    if let Some(cache) = INDEX_CACHE.lock().expect("not poisoned").as_mut() {
+        cache.retain(|(cached_registry, _), _| cached_registry != registry.as_str());
+    }
+    let mut updated = UPDATED_INDEXES.lock().expect("not poisoned");
+    if !updated.iter().any(|updated| updated == registry.as_str()) {
+        updated.push(registry.as_str().to_owned());
+    }
+    Ok(())
+}
+
+/// Fetch the git `registry` index, waiting for cargo to release its lock
+///
+/// When the index's history was rewritten, like when crates.io squashes it, the local clone can't
+/// be fast-forwarded and is cloned again, once, beside it before being swapped in.  Other
+/// fast-forward failures are errors.
+#[cfg(feature = "git")]
+fn fetch_index(registry: &Url, shallow: bool, work_dir: &Path) -> CargoResult<()> {
+    if time("index-update", registry, || {
+        fetch_shallow_index(registry, shallow, work_dir)
+    })? {
+        return Ok(());
+    }
+    let (path, url) = crates_index::local_path_and_canonical_url(registry.as_str(), None)?;
+    let mut refreshed = false;
+    time("index-update", registry, || -> CargoResult<()> {
+        loop {
+            let proxy = git_proxy_options(registry, work_dir)?;
+            match need_retry(fetch_git_index(&path, &url, proxy))? {
+                None => return Ok(()),
+                Some(Retry::Locked) => {
+                    shell_status("Blocking", "waiting for lock on registry index")?;
+                    std::thread::sleep(REGISTRY_BACKOFF);
+                }
+                Some(Retry::NotFastForward)
+                    if !refreshed
+                        && history_rewritten(
+                            &path,
+                            &url,
+                            git_proxy_options(registry, work_dir)?,
+                        )? =>
+                {
+                    shell_status(
+                        "Refreshing",
+                        &format!("'{}' index (history rewritten)", registry),
+                    )?;
+                    reclone_index(&path, &url, git_proxy_options(registry, work_dir)?)?;
+                    refreshed = true;
+                }
+                Some(Retry::NotFastForward) => anyhow::bail!(
+                    "The '{}' index can't be fast-forwarded{}",
+                    registry,
+                    if refreshed {
+                        ", even after cloning it again"
+                    } else {
+                        ""
+                    }
+                ),
+            }
+        }
+    })
+}
+
+/// Open the git `registry` index, cloning it first if it's missing
+///
+/// A shallow clone made by cargo-edit is opened instead of cargo's clone when there is one.
+#[cfg(feature = "git")]
+fn open_index(registry: &Url, work_dir: &Path) -> CargoResult<crates_index::Index> {
+    let (path, url) = crates_index::local_path_and_canonical_url(registry.as_str(), None)?;
+    let shallow_path = shallow_index_path(&path)?;
+    if shallow_path.exists() {
+        return Ok(crates_index::Index::with_path(shallow_path, url)?);
+    }
+    if !path.exists() {
+        fetch_git_index(&path, &url, git_proxy_options(registry, work_dir)?)?;
+    }
+    Ok(crates_index::Index::with_path(path, url)?)
+}
+
+/// Fetch the latest commit of the git index at `url` into `path`, like crates-index does, but
+/// through `proxy`
+///
+/// The repository is created first if it doesn't exist.
+#[cfg(feature = "git")]
+fn fetch_git_index(
+    path: &Path,
+    url: &str,
+    proxy: git2::ProxyOptions<'_>,
+) -> Result<(), git2::Error> {
+    let repo = match git2::Repository::open(path) {
+        Ok(repo) => repo,
+        Err(_) => {
+            let mut options = git2::RepositoryInitOptions::new();
+            options.external_template(false);
+            git2::Repository::init_opts(path, &options)?
+        }
+    };
+    let mut remote = repo
+        .find_remote("origin")
+        .or_else(|_| repo.remote_anonymous(url))?;
+    let mut options = git2::FetchOptions::new();
+    options.proxy_options(proxy);
+    remote.fetch(&["HEAD:refs/remotes/origin/HEAD"], Some(&mut options), None)
+}
+
+/// Clone or update the `registry` index with only its latest commit, returning whether it did
+///
+/// Shallow clones are kept in cargo-edit's own directory, as cargo and libgit2 expect full ones,
+/// and are used instead of cargo's clone once made.  A new one is only made when `shallow` is set
+/// and cargo has no clone yet, and falls back to a full one when the server or the `git` command
+/// can't make shallow ones.
+#[cfg(feature = "git")]
+fn fetch_shallow_index(registry: &Url, shallow: bool, work_dir: &Path) -> CargoResult<bool> {
+    let (cargo_path, url) = crates_index::local_path_and_canonical_url(registry.as_str(), None)?;
+    let path = shallow_index_path(&cargo_path)?;
+    let fresh = !path.exists();
+    if fresh && !(shallow && !cargo_path.exists()) {
+        return Ok(false);
+    }
+
+    if fresh {
+        std::fs::create_dir_all(&path)
+            .with_context(|| format!("Failed to create `{}`", path.display()))?;
+    }
+    let proxy = proxy_for_url(registry, work_dir)?;
+    let fetched = run_git(&path, &["init", "--quiet"], None).and_then(|()| {
+        run_git(
+            &path,
+            &[
+                "fetch",
+                "--quiet",
+                "--depth=1",
+                &url,
+                "+HEAD:refs/remotes/origin/HEAD",
+            ],
+            proxy.as_deref(),
+        )
+    });
+    match fetched {
+        Ok(()) => Ok(true),
+        Err(err) if fresh => {
+            let _ = std::fs::remove_dir_all(&path);
+            shell_warn(&format!("{:#}, cloning the full index instead", err))?;
+            Ok(false)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Where cargo-edit keeps the shallow clone of the index cargo clones to `cargo_path`
+#[cfg(feature = "git")]
+fn shallow_index_path(cargo_path: &Path) -> CargoResult<PathBuf> {
+    let name = cargo_path.file_name().expect("indexes are in a directory");
+    Ok(cargo_home()?.join("cargo-edit").join("index").join(name))
+}
+
+/// Run the `git` command in `dir`, through `proxy` if it's set
+#[cfg(feature = "git")]
+fn run_git(dir: &Path, args: &[&str], proxy: Option<&str>) -> CargoResult<()> {
+    let mut command = std::process::Command::new("git");
+    if let Some(proxy) = proxy {
+        command.arg("-c").arg(format!("http.proxy={}", proxy));
+    }
+    let output = command
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .context("Failed to run `git`, needed for shallow index clones")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "`git {}` failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Whether the remote HEAD of the git index at `url` no longer descends from the local one at
+/// `path`, like after a squash
+///
+/// The remote HEAD is fetched to a scratch ref, which is removed again.
+#[cfg(feature = "git")]
+fn history_rewritten(
+    path: &Path,
+    url: &str,
+    proxy: git2::ProxyOptions<'_>,
+) -> Result<bool, git2::Error> {
+    const FETCHED: &str = "refs/cargo-edit/remote-head";
+    let repo = git2::Repository::open(path)?;
+    let local = repo.refname_to_id("refs/remotes/origin/HEAD")?;
+    let mut remote = repo.remote_anonymous(url)?;
+    let mut options = git2::FetchOptions::new();
+    options.proxy_options(proxy);
+    remote.fetch(&[format!("+HEAD:{}", FETCHED)], Some(&mut options), None)?;
+    let mut fetched = repo.find_reference(FETCHED)?;
+    let remote_head = fetched.target().expect("fetched refs are direct");
+    fetched.delete()?;
+    Ok(remote_head != local && !repo.graph_descendant_of(remote_head, local)?)
+}
+
+/// Clone the git index at `url` again next to `path`, then swap the clone in for the stale one
+///
+/// The stale clone is only moved away once the new one is complete, so a failed clone leaves
+/// it as it was, and cargo never sees a missing or half-cloned index.
+#[cfg(feature = "git")]
+fn reclone_index(path: &Path, url: &str, proxy: git2::ProxyOptions<'_>) -> CargoResult<()> {
+    let parent = path.parent().expect("indexes are in a directory");
+    let temp_dir = |prefix: &str| {
+        tempfile::Builder::new()
+            .prefix(prefix)
+            .tempdir_in(parent)
+            .with_context(|| format!("Failed to create a directory in `{}`", parent.display()))
+    };
+    let fresh = temp_dir(".cargo-edit-index-")?;
+    fetch_git_index(fresh.path(), url, proxy)
+        .with_context(|| format!("Failed to clone `{}` again", url))?;
+    let stale = temp_dir(".cargo-edit-stale-")?;
+    let stale_index = stale.path().join("index");
+    std::fs::rename(path, &stale_index)
+        .with_context(|| format!("Failed to move the stale index at {}", path.display()))?;
+    if let Err(err) = std::fs::rename(fresh.path(), path) {
+        let _ = std::fs::rename(&stale_index, path);
+        return Err(err)
+            .with_context(|| format!("Failed to replace the stale index at {}", path.display()));
+    }
+    // Moved into place, so there's nothing left to clean up
+    let _ = fresh.into_path();
+    Ok(())
+}
+
+/// crates.io is read through its sparse index without the `git` feature, like other sparse
+/// indexes
+#[cfg(not(feature = "git"))]
+fn fetch_index(_registry: &Url, _shallow: bool, _work_dir: &Path) -> CargoResult<()> {
     Ok(())
 }
 
 /// Time between retries for retrieving the registry.
+#[cfg(feature = "git")]
 const REGISTRY_BACKOFF: Duration = Duration::from_secs(1);
 
+/// Why retrieving the Index should be retried
+#[cfg(feature = "git")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Retry {
+    /// Another process holds the lock on the index
+    Locked,
+    /// The remote HEAD isn't a fast-forward of the local one, like after a squash
+    NotFastForward,
+}
+
 /// Check if we need to retry retrieving the Index.
-fn need_retry(res: Result<(), crates_index::Error>) -> CargoResult<bool> {
+#[cfg(feature = "git")]
+fn need_retry(res: Result<(), git2::Error>) -> CargoResult<Option<Retry>> {
     match res {
-        Ok(()) => Ok(false),
-        Err(crates_index::Error::Git(err)) => {
-            if err.class() == git2::ErrorClass::Index && err.code() == git2::ErrorCode::Locked {
-                Ok(true)
-            } else {
-                Err(crates_index::Error::Git(err).into())
+        Ok(()) => Ok(None),
+        Err(err) => match (err.class(), err.code()) {
+            (git2::ErrorClass::Index, git2::ErrorCode::Locked) => Ok(Some(Retry::Locked)),
+            (git2::ErrorClass::Reference, git2::ErrorCode::NotFastForward) => {
+                Ok(Some(Retry::NotFastForward))
             }
-        }
-        Err(err) => Err(err.into()),
+            _ => Err(err.into()),
+        },
     }
 }
 
 #[test]
 fn test_gen_fuzzy_crate_names() {
     fn test_helper(input: &str, expect: &[&str]) {
-        let mut actual = gen_fuzzy_crate_names(input.to_string()).unwrap();
+        let mut actual = gen_fuzzy_crate_names(input).collect::<Vec<_>>();
         actual.sort();
 
         let mut expect = expect.iter().map(|x| x.to_string()).collect::<Vec<_>>();
@@ -261,6 +1303,64 @@ fn test_gen_fuzzy_crate_names() {
     );
 }
 
+#[test]
+fn fuzzy_crate_names_are_prioritized() {
+    let names = gen_fuzzy_crate_names("parking_lot-core").collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            "parking_lot-core",
+            "parking-lot-core",
+            "parking_lot_core",
+            "parking-lot_core"
+        ]
+    );
+
+    let long_name = "a-b-c-d-e-f-g-h-i-j-k-l";
+    let mut names = gen_fuzzy_crate_names(long_name);
+    assert_eq!(names.next().unwrap(), long_name);
+    assert_eq!(names.next().unwrap(), long_name.replace('-', "_"));
+    assert_eq!(names.count(), 2usize.pow(11) - 2);
+}
+
+#[test]
+fn ambiguous_spellings_are_errors_outside_crates_io() {
+    let lookup = |name: &str| {
+        Ok(["foo-bar_baz", "foo_bar-baz"].contains(&name).then(|| {
+            vec![CrateVersion {
+                name: name.to_owned(),
+                version: "1.0.0".parse().unwrap(),
+                yanked: false,
+                available_features: BTreeMap::new(),
+                rust_version: None,
+                edition: None,
+                checksum: None,
+            }]
+        }))
+    };
+    let crates_io = Url::parse(CRATES_IO_INDEX).unwrap();
+    let alternative = Url::parse("sparse+https://registry.example.com/index/").unwrap();
+
+    let found = find_crate_by("foo-bar-baz", true, &crates_io, lookup).unwrap();
+    assert_eq!(found.unwrap()[0].name, "foo-bar_baz");
+    let err = find_crate_by("foo-bar-baz", true, &alternative, lookup).unwrap_err();
+    assert!(err
+        .to_string()
+        .ends_with("several similarly named crates were: foo-bar_baz, foo_bar-baz"));
+    let found = find_crate_by("foo_bar-baz", true, &alternative, lookup).unwrap();
+    assert_eq!(found.unwrap()[0].name, "foo_bar-baz");
+    assert!(find_crate_by("foo-bar-baz", false, &alternative, lookup)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn crates_io_names_are_checked_before_lookup() {
+    let crates_io = Url::parse(CRATES_IO_INDEX).unwrap();
+    let err = find_registry_crate("nul", false, &crates_io, Path::new("Cargo.toml")).unwrap_err();
+    assert!(err.to_string().contains("reserved on Windows"), "{}", err);
+}
+
 #[test]
 fn get_latest_stable_version() {
     let versions = vec![
@@ -269,16 +1369,22 @@ fn get_latest_stable_version() {
             version: "0.6.0-alpha".parse().unwrap(),
             yanked: false,
             available_features: BTreeMap::new(),
+            rust_version: None,
+            edition: None,
+            checksum: None,
         },
         CrateVersion {
             name: "foo".into(),
             version: "0.5.0".parse().unwrap(),
             yanked: false,
             available_features: BTreeMap::new(),
+            rust_version: None,
+            edition: None,
+            checksum: None,
         },
     ];
     assert_eq!(
-        read_latest_version(&versions, false)
+        read_latest_version(&versions, false, IfYanked::NextUnyanked)
             .unwrap()
             .version()
             .unwrap(),
@@ -294,16 +1400,22 @@ fn get_latest_unstable_or_stable_version() {
             version: "0.6.0-alpha".parse().unwrap(),
             yanked: false,
             available_features: BTreeMap::new(),
+            rust_version: None,
+            edition: None,
+            checksum: None,
         },
         CrateVersion {
             name: "foo".into(),
             version: "0.5.0".parse().unwrap(),
             yanked: false,
             available_features: BTreeMap::new(),
+            rust_version: None,
+            edition: None,
+            checksum: None,
         },
     ];
     assert_eq!(
-        read_latest_version(&versions, true)
+        read_latest_version(&versions, true, IfYanked::NextUnyanked)
             .unwrap()
             .version()
             .unwrap(),
@@ -319,16 +1431,54 @@ fn get_latest_version_with_yanked() {
             version: "0.3.1".parse().unwrap(),
             yanked: true,
             available_features: BTreeMap::new(),
+            rust_version: None,
+            edition: None,
+            checksum: None,
         },
         CrateVersion {
             name: "true".into(),
             version: "0.3.0".parse().unwrap(),
             yanked: false,
             available_features: BTreeMap::new(),
+            rust_version: None,
+            edition: None,
+            checksum: None,
+        },
+    ];
+    assert_eq!(
+        read_latest_version(&versions, false, IfYanked::NextUnyanked)
+            .unwrap()
+            .version()
+            .unwrap(),
+        "0.3.0"
+    );
+}
+
+#[test]
+fn error_on_yanked_latest_version() {
+    let versions = vec![
+        CrateVersion {
+            name: "treexml".into(),
+            version: "0.3.1".parse().unwrap(),
+            yanked: true,
+            available_features: BTreeMap::new(),
+            rust_version: None,
+            edition: None,
+            checksum: None,
+        },
+        CrateVersion {
+            name: "treexml".into(),
+            version: "0.3.0".parse().unwrap(),
+            yanked: false,
+            available_features: BTreeMap::new(),
+            rust_version: None,
+            edition: None,
+            checksum: None,
         },
     ];
+    assert!(read_latest_version(&versions, false, IfYanked::Error).is_err());
     assert_eq!(
-        read_latest_version(&versions, false)
+        read_latest_version(&versions, false, IfYanked::Warn)
             .unwrap()
             .version()
             .unwrap(),
@@ -336,6 +1486,37 @@ fn get_latest_version_with_yanked() {
     );
 }
 
+#[test]
+fn yanked_older_version_is_not_an_error() {
+    let versions = vec![
+        CrateVersion {
+            name: "treexml".into(),
+            version: "0.3.1".parse().unwrap(),
+            yanked: false,
+            available_features: BTreeMap::new(),
+            rust_version: None,
+            edition: None,
+            checksum: None,
+        },
+        CrateVersion {
+            name: "treexml".into(),
+            version: "0.3.0".parse().unwrap(),
+            yanked: true,
+            available_features: BTreeMap::new(),
+            rust_version: None,
+            edition: None,
+            checksum: None,
+        },
+    ];
+    assert_eq!(
+        read_latest_version(&versions, false, IfYanked::Error)
+            .unwrap()
+            .version()
+            .unwrap(),
+        "0.3.1"
+    );
+}
+
 #[test]
 fn get_no_latest_version_from_json_when_all_are_yanked() {
     let versions = vec![
@@ -344,13 +1525,104 @@ fn get_no_latest_version_from_json_when_all_are_yanked() {
             version: "0.3.1".parse().unwrap(),
             yanked: true,
             available_features: BTreeMap::new(),
+            rust_version: None,
+            edition: None,
+            checksum: None,
         },
         CrateVersion {
             name: "true".into(),
             version: "0.3.0".parse().unwrap(),
             yanked: true,
             available_features: BTreeMap::new(),
+            rust_version: None,
+            edition: None,
+            checksum: None,
+        },
+    ];
+    assert!(read_latest_version(&versions, false, IfYanked::NextUnyanked).is_err());
+}
+
+#[test]
+fn get_compatible_version() {
+    let versions = vec![
+        CrateVersion {
+            name: "foo".into(),
+            version: "2.0.0".parse().unwrap(),
+            yanked: false,
+            available_features: BTreeMap::new(),
+            rust_version: None,
+            edition: None,
+            checksum: None,
+        },
+        CrateVersion {
+            name: "foo".into(),
+            version: "1.2.0".parse().unwrap(),
+            yanked: true,
+            available_features: BTreeMap::new(),
+            rust_version: None,
+            edition: None,
+            checksum: None,
+        },
+        CrateVersion {
+            name: "foo".into(),
+            version: "1.1.0".parse().unwrap(),
+            yanked: false,
+            available_features: BTreeMap::new(),
+            rust_version: None,
+            edition: None,
+            checksum: None,
         },
     ];
-    assert!(read_latest_version(&versions, false).is_err());
+    let req = "1".parse().unwrap();
+    assert_eq!(
+        read_compatible_version(&versions, &req)
+            .unwrap()
+            .version()
+            .unwrap(),
+        "1.1.0"
+    );
+    let req = "3".parse().unwrap();
+    assert!(read_compatible_version(&versions, &req).is_err());
+}
+
+#[test]
+#[cfg(feature = "git")]
+fn only_locks_and_force_pushes_are_retried() {
+    let git_error = |code, class| Err(git2::Error::new(code, class, "test"));
+    assert_eq!(need_retry(Ok(())).unwrap(), None);
+    assert_eq!(
+        need_retry(git_error(git2::ErrorCode::Locked, git2::ErrorClass::Index)).unwrap(),
+        Some(Retry::Locked)
+    );
+    assert_eq!(
+        need_retry(git_error(
+            git2::ErrorCode::NotFastForward,
+            git2::ErrorClass::Reference
+        ))
+        .unwrap(),
+        Some(Retry::NotFastForward)
+    );
+    assert!(need_retry(git_error(git2::ErrorCode::NotFound, git2::ErrorClass::Odb)).is_err());
+    assert!(need_retry(git_error(git2::ErrorCode::Auth, git2::ErrorClass::Net)).is_err());
+}
+
+#[test]
+fn default_jobs_scale_with_cpus() {
+    assert_eq!(default_jobs(None), 1);
+    assert_eq!(default_jobs(Some(1)), 1);
+    assert_eq!(default_jobs(Some(2)), 2 * JOBS_PER_CPU);
+    assert_eq!(default_jobs(Some(64)), MAX_DEFAULT_JOBS);
+}
+
+#[test]
+fn lookup_sources_are_recorded() {
+    let registry = Url::parse("https://index.example.com/").unwrap();
+    assert_eq!(lookup_source(&registry, "recorded"), None);
+    record_source(&registry, "recorded", IndexProtocol::Sparse, true);
+    let source = lookup_source(&registry, "recorded").unwrap();
+    assert_eq!(
+        source.to_string(),
+        "`https://index.example.com/` (sparse index, cached)"
+    );
+    assert_eq!(lookup_source(&registry, "other"), None);
 }
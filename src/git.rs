@@ -0,0 +1,188 @@
+//! Look up git repositories, like forks pinned in `[patch]`, without a checkout
+//!
+//! Repositories are reached through the proxy cargo config sets for the manifest referring to
+//! them.
+use std::path::Path;
+
+use url::Url;
+
+use super::errors::*;
+use super::proxy::git_proxy_options;
+
+/// A commit a git reference points at
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GitRef {
+    /// Full name of the reference, like `refs/heads/main` or `refs/tags/v1.0.0`
+    pub name: String,
+    /// Full id of the commit
+    pub rev: String,
+}
+
+impl GitRef {
+    /// The tag name, for references under `refs/tags/`
+    pub fn tag(&self) -> Option<&str> {
+        self.name.strip_prefix("refs/tags/")
+    }
+}
+
+/// Where `branch`, or the default branch, of the repository at `url` points
+pub fn latest_git_rev(
+    url: &Url,
+    branch: Option<&str>,
+    manifest_path: &Path,
+) -> CargoResult<GitRef> {
+    let name = match branch {
+        Some(branch) => format!("refs/heads/{}", branch),
+        None => "HEAD".to_owned(),
+    };
+    list_refs(url, manifest_path)?
+        .into_iter()
+        .find(|r| r.name == name)
+        .ok_or_else(|| anyhow::format_err!("`{}` has no `{}`", url, name))
+}
+
+/// The tag of the repository at `url` with the highest version, like `v1.2.0`
+///
+/// Only tags that are stable versions, optionally prefixed with `v`, are considered.
+pub fn latest_git_tag(url: &Url, manifest_path: &Path) -> CargoResult<Option<GitRef>> {
+    Ok(newest_tag(list_refs(url, manifest_path)?))
+}
+
+/// Read the version of the package `crate_name` at the reference `name` of the repository at
+/// `url`
+///
+/// The reference is fetched into a temporary repository, and every `Cargo.toml` in the commit is
+/// searched for the package.  Returns `None` if it isn't found, or its version is inherited.
+pub fn git_package_version(
+    url: &Url,
+    name: &str,
+    crate_name: &str,
+    manifest_path: &Path,
+) -> CargoResult<Option<String>> {
+    let mut options = git2::FetchOptions::new();
+    options.proxy_options(git_proxy_options(url, work_dir(manifest_path))?);
+    let dir = tempfile::Builder::new()
+        .prefix("cargo-edit-git-")
+        .tempdir()
+        .context("Failed to create a temporary repository")?;
+    read_package_version(dir.path(), url, name, crate_name, options)
+        .with_context(|| format!("Failed to read `{}` from `{}`", crate_name, url))
+}
+
+fn read_package_version(
+    dir: &Path,
+    url: &Url,
+    name: &str,
+    crate_name: &str,
+    mut options: git2::FetchOptions<'_>,
+) -> CargoResult<Option<String>> {
+    const FETCHED: &str = "refs/cargo-edit/fetched";
+    let repo = git2::Repository::init_bare(dir)?;
+    repo.remote_anonymous(url.as_str())?.fetch(
+        &[format!("+{}:{}", name, FETCHED)],
+        Some(&mut options),
+        None,
+    )?;
+    let tree = repo.find_reference(FETCHED)?.peel_to_tree()?;
+
+    let mut manifests = Vec::new();
+    tree.walk(git2::TreeWalkMode::PreOrder, |_, entry| {
+        if entry.name() == Some("Cargo.toml") && entry.kind() == Some(git2::ObjectType::Blob) {
+            manifests.push(entry.id());
+        }
+        git2::TreeWalkResult::Ok
+    })?;
+    for id in manifests {
+        let blob = repo.find_blob(id)?;
+        let manifest: toml_edit::Document = match std::str::from_utf8(blob.content())
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            Some(manifest) => manifest,
+            None => continue,
+        };
+        let package = match manifest.get("package") {
+            Some(package) => package,
+            None => continue,
+        };
+        if package.get("name").and_then(|n| n.as_str()) == Some(crate_name) {
+            return Ok(package
+                .get("version")
+                .and_then(|v| v.as_str())
+                .map(ToOwned::to_owned));
+        }
+    }
+    Ok(None)
+}
+
+/// The references of the repository at `url`, like `git ls-remote`
+fn list_refs(url: &Url, manifest_path: &Path) -> CargoResult<Vec<GitRef>> {
+    let proxy = git_proxy_options(url, work_dir(manifest_path))?;
+    let mut remote = git2::Remote::create_detached(url.as_str())?;
+    let connection = remote
+        .connect_auth(git2::Direction::Fetch, None, Some(proxy))
+        .with_context(|| format!("Failed to connect to `{}`", url))?;
+    let mut refs: Vec<GitRef> = Vec::new();
+    for head in connection.list()? {
+        let rev = head.oid().to_string();
+        // Annotated tags are listed again, peeled to the commit they tag
+        match head.name().strip_suffix("^{}") {
+            Some(name) => match refs.iter_mut().find(|r| r.name == name) {
+                Some(tag) => tag.rev = rev,
+                None => refs.push(GitRef {
+                    name: name.to_owned(),
+                    rev,
+                }),
+            },
+            None => refs.push(GitRef {
+                name: head.name().to_owned(),
+                rev,
+            }),
+        }
+    }
+    Ok(refs)
+}
+
+fn work_dir(manifest_path: &Path) -> &Path {
+    manifest_path
+        .parent()
+        .expect("there must be a parent directory")
+}
+
+fn newest_tag(refs: Vec<GitRef>) -> Option<GitRef> {
+    refs.into_iter()
+        .filter_map(|r| {
+            let tag = r.tag()?;
+            let version: semver::Version = tag.strip_prefix('v').unwrap_or(tag).parse().ok()?;
+            version.pre.is_empty().then(|| (version, r))
+        })
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, r)| r)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn git_ref(name: &str) -> GitRef {
+        GitRef {
+            name: name.to_owned(),
+            rev: "0".repeat(40),
+        }
+    }
+
+    #[test]
+    fn newest_tag_by_version() {
+        let refs = vec![
+            git_ref("HEAD"),
+            git_ref("refs/heads/main"),
+            git_ref("refs/tags/v0.9.0"),
+            git_ref("refs/tags/v0.10.0"),
+            git_ref("refs/tags/0.10.1-rc.1"),
+            git_ref("refs/tags/release-2"),
+        ];
+        let newest = newest_tag(refs).unwrap();
+        assert_eq!(newest.tag(), Some("v0.10.0"));
+        assert_eq!(newest_tag(vec![git_ref("refs/tags/latest")]), None);
+    }
+}
@@ -0,0 +1,335 @@
+//! Commands run before and after manifests are written, like `post-upgrade = "cargo fmt"`
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::RwLock;
+
+use super::diff::{diff_dependencies, DependencyChange};
+use super::errors::*;
+use super::manifest::{LocalManifest, Manifest};
+use super::registry::{hook_config, metadata_hooks_allowed};
+use super::shell_status;
+
+static HOOK_COMMAND: RwLock<Option<String>> = RwLock::new(None);
+
+/// Run the hooks of `command`, like `upgrade`, around every manifest written, or stop with `None`
+///
+/// Without a call to this, no hooks run.  See [`find_hook`] for where hooks are configured.
+pub fn set_hook_command(command: Option<&str>) {
+    *HOOK_COMMAND.write().expect("not poisoned") = command.map(str::to_owned);
+}
+
+/// Whether a hook runs before or after a manifest is written
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HookStage {
+    /// Before the manifest is written; failing stops the write
+    Pre,
+    /// After the manifest is written
+    Post,
+}
+
+impl HookStage {
+    /// Prefix of the hooks of the stage, like `pre`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pre => "pre",
+            Self::Post => "post",
+        }
+    }
+}
+
+/// Find the command of hook `name`, like `post-upgrade`, for the manifest at `manifest_path`
+///
+/// Hooks are looked up in order in
+/// - `[package.metadata.cargo-edit.hooks]` and `[workspace.metadata.cargo-edit.hooks]` of the
+///   manifest
+/// - `[workspace.metadata.cargo-edit.hooks]` of the nearest workspace root above it
+/// - `[cargo-edit.hooks]` in `$CARGO_HOME/config.toml` or `--config`
+///
+/// Hooks in metadata are only looked up with `cargo-edit.allow-metadata-hooks = true` in
+/// `$CARGO_HOME/config.toml` or `--config`.  The `.cargo/config.toml` of the project is never
+/// read for hooks, so editing a checkout doesn't run commands it ships.
+pub fn find_hook(manifest_path: &Path, name: &str) -> CargoResult<Option<String>> {
+    if metadata_hooks_allowed()? {
+        if let Some(command) = find_metadata_hook(manifest_path, name)? {
+            return Ok(Some(command));
+        }
+    }
+    hook_config(name)
+}
+
+fn find_metadata_hook(manifest_path: &Path, name: &str) -> CargoResult<Option<String>> {
+    let manifest = LocalManifest::try_new(manifest_path)?;
+    if let Some(command) = metadata_hook(&manifest, name)? {
+        return Ok(Some(command));
+    }
+    let dir = manifest_path
+        .parent()
+        .expect("there must be a parent directory");
+    // Cargo metadata would be slow for every write, and workspace roots are always ancestors
+    for ancestor in dir.ancestors().skip(1) {
+        let root_path = ancestor.join("Cargo.toml");
+        if !root_path.is_file() {
+            continue;
+        }
+        let root = LocalManifest::try_new(&root_path)?;
+        if root.data.contains_key("workspace") {
+            return metadata_hook(&root, name);
+        }
+    }
+    Ok(None)
+}
+
+fn metadata_hook(manifest: &Manifest, name: &str) -> CargoResult<Option<String>> {
+    for table in ["package", "workspace"] {
+        let command = match manifest
+            .data
+            .get(table)
+            .and_then(|t| t.get("metadata"))
+            .and_then(|m| m.get("cargo-edit"))
+            .and_then(|c| c.get("hooks"))
+            .and_then(|h| h.get(name))
+        {
+            Some(command) => command,
+            None => continue,
+        };
+        let command = command.as_str().with_context(|| {
+            format!(
+                "Expected `{}.metadata.cargo-edit.hooks.{}` to be a command, like `\"cargo fmt\"`",
+                table, name
+            )
+        })?;
+        return Ok(Some(command.to_owned()));
+    }
+    Ok(None)
+}
+
+/// The hooks around writing a manifest, with the changes the write makes
+pub(crate) struct EditHooks {
+    command: String,
+    manifest_path: PathBuf,
+    hooks: Vec<(HookStage, String, String)>,
+    changes: Vec<DependencyChange>,
+}
+
+impl EditHooks {
+    /// Hooks for writing `new` over the manifest at `path`, unless no hook command is set
+    ///
+    /// Hooks are looked up before the manifest is written, so a change can't remove its own
+    /// checks.
+    pub(crate) fn new(path: &Path, new: &Manifest) -> CargoResult<Option<Self>> {
+        let command = match HOOK_COMMAND.read().expect("not poisoned").clone() {
+            Some(command) => command,
+            None => return Ok(None),
+        };
+        let mut hooks = Vec::new();
+        for stage in [HookStage::Pre, HookStage::Post] {
+            for suffix in ["edit", command.as_str()] {
+                let name = format!("{}-{}", stage.as_str(), suffix);
+                if let Some(script) = find_hook(path, &name)? {
+                    hooks.push((stage, name, script));
+                }
+            }
+        }
+        if hooks.is_empty() {
+            return Ok(None);
+        }
+        let old = LocalManifest::try_new(path)?;
+        let crate_root = path.parent().expect("manifests have a parent");
+        let changes = diff_dependencies(crate_root, &old, new)?;
+        Ok(Some(Self {
+            command,
+            manifest_path: path.to_owned(),
+            hooks,
+            changes,
+        }))
+    }
+
+    /// Run the `<stage>-edit` hook, then the `<stage>-<command>` hook
+    pub(crate) fn run(&self, stage: HookStage) -> CargoResult<()> {
+        for (hook_stage, name, script) in &self.hooks {
+            if *hook_stage == stage {
+                run_hook(
+                    stage,
+                    name,
+                    script,
+                    &self.command,
+                    &self.manifest_path,
+                    &self.changes,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Run `script` through the shell, in the manifest's directory
+///
+/// `{manifest}` in the script is replaced with the quoted manifest path.  The script gets
+/// - `CARGO_EDIT_HOOK`: the hook, like `pre-upgrade`
+/// - `CARGO_EDIT_COMMAND`: the command, like `upgrade`
+/// - `CARGO_EDIT_MANIFEST`: the manifest path
+/// - `CARGO_EDIT_CHANGES`: a line per dependency added, removed, or changed, with its kind, table,
+///   key, old and new requirement or source, separated by tabs, and `-` for none
+fn run_hook(
+    stage: HookStage,
+    name: &str,
+    script: &str,
+    command: &str,
+    manifest_path: &Path,
+    changes: &[DependencyChange],
+) -> CargoResult<()> {
+    let script = script.replace(
+        "{manifest}",
+        &shell_quote(&manifest_path.display().to_string()),
+    );
+    shell_status("Running", &format!("{} hook `{}`", name, script))?;
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(&script);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(&script);
+        cmd
+    };
+    let status = cmd
+        .current_dir(manifest_path.parent().expect("manifests have a parent"))
+        .env("CARGO_EDIT_HOOK", name)
+        .env("CARGO_EDIT_COMMAND", command)
+        .env("CARGO_EDIT_MANIFEST", manifest_path)
+        .env("CARGO_EDIT_CHANGES", format_changes(changes))
+        .status()
+        .with_context(|| format!("Failed to run `{}` hook `{}`", name, script))?;
+    if !status.success() {
+        if stage == HookStage::Pre {
+            anyhow::bail!(
+                "`{}` hook `{}` rejected the change to `{}` ({})",
+                name,
+                script,
+                manifest_path.display(),
+                status
+            );
+        } else {
+            anyhow::bail!(
+                "`{}` hook `{}` failed after `{}` was written ({})",
+                name,
+                script,
+                manifest_path.display(),
+                status
+            );
+        }
+    }
+    Ok(())
+}
+
+fn format_changes(changes: &[DependencyChange]) -> String {
+    changes
+        .iter()
+        .map(|change| {
+            format!(
+                "{}\t{}\t{}\t{}\t{}\n",
+                change.kind.as_str(),
+                change.table,
+                change.name,
+                change.old.as_deref().unwrap_or("-"),
+                change.new.as_deref().unwrap_or("-")
+            )
+        })
+        .collect()
+}
+
+/// Quote `arg` as a single word for the shell hooks run in
+fn shell_quote(arg: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", arg)
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hooks_are_found_in_metadata() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        std::fs::write(
+            temp.path().join("Cargo.toml"),
+            r#"[workspace]
+members = ["app"]
+
+[workspace.metadata.cargo-edit.hooks]
+pre-edit = "./check"
+post-upgrade = "cargo fmt"
+"#,
+        )
+        .unwrap();
+        let app = temp.path().join("app");
+        std::fs::create_dir(&app).unwrap();
+        let path = app.join("Cargo.toml");
+        std::fs::write(
+            &path,
+            r#"[package]
+name = "app"
+version = "0.1.0"
+
+[package.metadata.cargo-edit.hooks]
+post-upgrade = "cargo fmt --manifest-path {manifest}"
+post-rm = 1
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            find_metadata_hook(&path, "post-upgrade")
+                .unwrap()
+                .as_deref(),
+            Some("cargo fmt --manifest-path {manifest}")
+        );
+        assert_eq!(
+            find_metadata_hook(&path, "pre-edit").unwrap().as_deref(),
+            Some("./check")
+        );
+        assert!(find_metadata_hook(&path, "post-rm").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hooks_see_changes_and_veto_them() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let path = temp.path().join("it's").join("Cargo.toml");
+        std::fs::create_dir(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "[package]\nname = \"app\"\n").unwrap();
+        let changes = vec![DependencyChange {
+            table: "dependencies".to_owned(),
+            name: "serde".to_owned(),
+            kind: crate::ChangeKind::Added,
+            old: None,
+            new: Some("1.0".to_owned()),
+        }];
+
+        run_hook(
+            HookStage::Post,
+            "post-upgrade",
+            "printf '%s' \"$CARGO_EDIT_CHANGES\" > changes; test -f {manifest}",
+            "upgrade",
+            &path,
+            &changes,
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(path.with_file_name("changes")).unwrap(),
+            "added\tdependencies\tserde\t-\t1.0\n"
+        );
+        let err = run_hook(
+            HookStage::Pre,
+            "pre-upgrade",
+            "test \"$CARGO_EDIT_HOOK\" = pre-upgrade && exit 3",
+            "upgrade",
+            &path,
+            &changes,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("rejected the change"), "{}", err);
+    }
+}
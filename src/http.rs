@@ -0,0 +1,48 @@
+//! The HTTP client shared by every request of a run
+//!
+//! Agents keep connections alive between requests, so looking up a workspace's dependencies in
+//! the crates.io API or a sparse index reuses a handful of connections instead of opening one
+//! per crate.  There is one agent per proxy, as ureq configures the proxy per agent.
+//!
+//! ureq only speaks HTTP/1.1, so requests aren't multiplexed; parallel lookups each hold one of
+//! the pooled connections instead.
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use url::Url;
+
+use super::errors::*;
+use super::proxy::proxy_for_url;
+
+const USER_AGENT: &str = concat!("cargo-edit/", env!("CARGO_PKG_VERSION"));
+const TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_REDIRECTS: u32 = 5;
+/// Idle connections kept per host, enough for lookups running in parallel
+const MAX_IDLE_PER_HOST: usize = 8;
+
+/// Agents built so far, by the proxy they go through
+static AGENTS: Mutex<Vec<(Option<String>, ureq::Agent)>> = Mutex::new(Vec::new());
+
+/// The HTTP client for `url`, going through the proxy configured for `work_dir`
+///
+/// Requests through the same proxy, or without one, share an agent and its connection pool.
+pub(crate) fn http_agent(url: &Url, work_dir: &Path) -> CargoResult<ureq::Agent> {
+    let proxy = proxy_for_url(url, work_dir)?;
+    let mut agents = AGENTS.lock().expect("not poisoned");
+    if let Some((_, agent)) = agents.iter().find(|(p, _)| *p == proxy) {
+        return Ok(agent.clone());
+    }
+
+    let mut builder = ureq::AgentBuilder::new()
+        .timeout(TIMEOUT)
+        .redirects(MAX_REDIRECTS)
+        .user_agent(USER_AGENT)
+        .max_idle_connections_per_host(MAX_IDLE_PER_HOST);
+    if let Some(proxy) = &proxy {
+        builder = builder.proxy(ureq::Proxy::new(proxy)?);
+    }
+    let agent = builder.build();
+    agents.push((proxy, agent.clone()));
+    Ok(agent)
+}
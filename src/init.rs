@@ -0,0 +1,124 @@
+//! Create new manifests
+use std::path::Path;
+
+use super::errors::*;
+use super::{
+    get_latest_dependency, CrateSpec, Dependency, LocalManifest, LookupOptions, LookupWarning,
+    PresetEntry, RegistrySource,
+};
+
+/// Create a minimal `Cargo.toml` in `dir` and add `deps` to it
+///
+/// The package name is taken from the directory, with version `0.1.0` and the 2021 edition.
+/// Each dependency is a crate spec with optional features, like `serde`, `tokio@1` or
+/// `tokio@1+full`.  Ones without a version requirement are resolved to their latest release in
+/// the default registry, looked up as `options` say; the warnings of those lookups are returned
+/// for the caller to report.
+///
+/// Fails if `dir` already has a manifest.
+pub fn init_manifest(
+    dir: &Path,
+    deps: &[&str],
+    options: &LookupOptions,
+) -> CargoResult<(LocalManifest, Vec<LookupWarning>)> {
+    let path = dir.join("Cargo.toml");
+    if path.exists() {
+        anyhow::bail!("`{}` already exists", path.display());
+    }
+
+    let name = dir
+        .file_name()
+        .and_then(|n| n.to_str())
+        .with_context(|| format!("Cannot infer a package name from `{}`", dir.display()))?;
+    CrateSpec::resolve(name)
+        .with_context(|| format!("Cannot use the directory name `{}` as a package name", name))?;
+
+    // Valid package names never need escaping
+    let mut manifest = LocalManifest {
+        path,
+        manifest: format!(
+            "[package]
+name = \"{}\"
+version = \"0.1.0\"
+edition = \"2021\"
+
+[dependencies]
+",
+            name
+        )
+        .parse()?,
+    };
+
+    let mut warnings = Vec::new();
+    for dep in deps {
+        let spec: PresetEntry = dep.parse()?;
+        let (name, version) = match spec.version_req {
+            Some(version_req) => (spec.name, version_req),
+            None => {
+                let (latest, lookup_warnings) =
+                    get_latest_dependency(&spec.name, options, &manifest.path, None)?;
+                warnings.extend(lookup_warnings);
+                let version = latest
+                    .version()
+                    .expect("registry packages always have a version")
+                    .to_owned();
+                (latest.name, version)
+            }
+        };
+        let dependency = Dependency::new(&name).set_source(RegistrySource::new(version));
+        let dependency = if spec.features.is_empty() {
+            dependency
+        } else {
+            dependency.set_features(spec.features)
+        };
+        manifest.data["dependencies"][dependency.toml_key()] = dependency.to_toml(dir);
+    }
+
+    manifest.write()?;
+    Ok((manifest, warnings))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn init_with_version_reqs() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let dir = temp.path().join("my-crate");
+        std::fs::create_dir(&dir).unwrap();
+
+        let (manifest, warnings) = init_manifest(
+            &dir,
+            &["serde@1.0", "tokio@1+full+macros"],
+            &LookupOptions::new(),
+        )
+        .unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(manifest.data["package"]["name"].as_str(), Some("my-crate"));
+        assert_eq!(manifest.data["package"]["edition"].as_str(), Some("2021"));
+        assert_eq!(manifest.data["dependencies"]["serde"].as_str(), Some("1.0"));
+        let tokio = &manifest.data["dependencies"]["tokio"];
+        assert_eq!(tokio["version"].as_str(), Some("1"));
+        let features: Vec<_> = tokio["features"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| f.as_str().unwrap())
+            .collect();
+        assert_eq!(features, ["full", "macros"]);
+
+        let written = std::fs::read_to_string(dir.join("Cargo.toml")).unwrap();
+        assert_eq!(written, manifest.to_string());
+    }
+
+    #[test]
+    fn init_refuses_existing_manifest() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let dir = temp.path().join("existing");
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::write(dir.join("Cargo.toml"), "[package]\n").unwrap();
+
+        assert!(init_manifest(&dir, &[], &LookupOptions::new()).is_err());
+    }
+}
@@ -16,28 +16,124 @@
 #[macro_use]
 extern crate serde_derive;
 
+mod add;
+mod advisories;
+mod backup;
+mod blocklist;
 mod crate_spec;
+mod crates_io;
 mod dependency;
+mod diff;
+mod duplicates;
 mod errors;
 mod fetch;
+#[cfg(feature = "git")]
+mod git;
+mod hooks;
+mod http;
+mod init;
+mod license;
+mod lints;
+mod lockfile;
 mod manifest;
 mod metadata;
+#[cfg(feature = "async")]
+mod nonblocking;
+mod normalize;
+mod notify;
+mod package_field;
+mod plan;
+mod preset;
+mod profile;
+mod proxy;
+mod query;
 mod registry;
+mod replacements;
+mod rust_version;
+mod select;
+mod sparse;
+#[cfg(any(test, feature = "test-support"))]
+pub mod testing;
+mod timings;
+mod unification;
+mod upstream;
 mod util;
 mod version;
 
+pub use add::{add_dependency_to_manifests, DependencyAddition};
+pub use advisories::{get_advisories, Advisory, PatchedVersion};
+pub use backup::{
+    backup_dir, latest_backups, list_backups, ManifestBackup, BACKUP_DIR_ENV, DEFAULT_BACKUP_DIR,
+};
+pub use blocklist::{blocked_crate, BlockLevel, BlockedCrate};
 pub use crate_spec::CrateSpec;
+pub use crates_io::{
+    get_latest_published_dependency, get_published_license, get_published_targets, get_version_lag,
+    PublishWindow, PublishedTargets, VersionLag,
+};
 pub use dependency::Dependency;
+pub use dependency::GitSource;
+pub use dependency::MergeStrategy;
 pub use dependency::PathSource;
 pub use dependency::RegistrySource;
 pub use dependency::Source;
+pub use diff::{diff_dependencies, unified_diff, ChangeKind, DependencyChange};
+pub use duplicates::{
+    find_duplicate_dependencies, merge_duplicate_dependencies, DuplicateDependency,
+};
 pub use errors::*;
-pub use fetch::{get_latest_dependency, update_registry_index};
-pub use manifest::{find, get_dep_version, set_dep_version, LocalManifest, Manifest};
-pub use metadata::{manifest_from_pkgid, resolve_manifests, workspace_members};
-pub use registry::registry_url;
+pub use fetch::{
+    get_compatible_dependency, get_crate_version, get_latest_dependency,
+    get_latest_dependency_from_registries, get_selected_dependency, is_yanked_version,
+    lookup_source, prefetch_registry_crates, update_registry_index, update_registry_indexes,
+    CrateVersion, IfYanked, IndexProtocol, LookupOptions, LookupSource, LookupWarning,
+};
+#[cfg(feature = "git")]
+pub use git::{git_package_version, latest_git_rev, latest_git_tag, GitRef};
+pub use hooks::{find_hook, set_hook_command, HookStage};
+pub use init::init_manifest;
+pub use license::{dependency_license, LicensePolicy};
+pub use lints::{LintKey, LintLevel};
+pub use lockfile::{read_lockfile, LockedPackage};
+pub use manifest::{
+    find, find_template_placeholder, get_dep_version, set_dep_default_features, set_dep_registry,
+    set_dep_version, write_manifest_file, DependencyUsage, LocalManifest, Manifest,
+    RecordedChecksum, TargetKind, UpgradePolicy, WriteOptions,
+};
+pub use metadata::{
+    manifest_from_pkgid, resolve_manifests, workspace_members, workspace_root_manifest,
+};
+#[cfg(feature = "async")]
+pub use nonblocking::{get_latest_dependency_async, update_registry_index_async};
+pub use normalize::{normalize_dependencies, normalize_requirement, RequirementStyle};
+pub use notify::{send_notification, NotifyTarget};
+pub use package_field::PackageField;
+pub use plan::{
+    is_pinned_req, plan_upgrade, PlanSkipReason, PlannedSkip, PlannedUpgrade, UpgradeOptions,
+    UpgradePlan,
+};
+pub use preset::{add_preset, add_preset_to_manifests, find_preset, DependencyPreset, PresetEntry};
+pub use profile::{ProfileKey, ProfileScope};
+pub use proxy::proxy_for_url;
+pub use query::{DeclaredDependency, ManifestQuery};
+pub use registry::{cargo_flags, registry_url, set_cargo_flags, CargoFlags};
+pub use replacements::{find_replacement, Replacement};
+pub use rust_version::{
+    dependency_rust_versions, installed_rust_version, rust_version_exceeds, DependencyRustVersion,
+};
+pub use select::{
+    Latest, LatestCompatible, LatestOnChannel, LatestStable, MinimalVersion, SupportedBy,
+    VersionSelector,
+};
+pub use timings::{set_timings_enabled, take_timings, Timing};
+pub use unification::{feature_requests, unify_features, FeatureRequest};
+pub use upstream::{crates_io_mirror, verify_upstream, UpstreamMismatch};
 pub use util::{
-    colorize_stderr, shell_note, shell_print, shell_status, shell_warn, shell_write_stderr, Color,
-    ColorChoice,
+    colorize_stderr, output_config, set_output_config, set_output_sink, shell_event, shell_note,
+    shell_print, shell_status, shell_warn, shell_write_stderr, Color, ColorChoice, ColorWhen,
+    JsonLinesSink, OutputConfig, OutputSink, RecordedEvents, RecordingSink, SilentSink,
+    TerminalSink, JSON_SCHEMA, JSON_SCHEMA_VERSION,
+};
+pub use version::{
+    classify_version_change, split_build_metadata, upgrade_requirement, VersionChange, VersionExt,
 };
-pub use version::{upgrade_requirement, VersionExt};
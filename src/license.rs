@@ -0,0 +1,211 @@
+//! Look up the licenses of dependencies, and check them against a policy
+use std::iter::Peekable;
+use std::path::Path;
+
+use super::crates_io::get_published_license;
+use super::errors::*;
+use super::{split_build_metadata, Dependency, LocalManifest, Source};
+
+/// The license expression of `dependency`, like `MIT OR Apache-2.0`
+///
+/// Crates from crates.io are looked up in its web API, which needs the exact version, like
+/// [`get_latest_dependency`](crate::get_latest_dependency) returns.  Path dependencies are read
+/// from their manifest.  Returns `None` for git dependencies and alternative registries, and
+/// when the crate only has a `license-file`.  `manifest_path` is the manifest depending on it.
+pub fn dependency_license(
+    dependency: &Dependency,
+    manifest_path: &Path,
+) -> CargoResult<Option<String>> {
+    match dependency.source() {
+        Some(Source::Registry(source)) if dependency.registry.is_none() => {
+            let (version, _) = split_build_metadata(&source.version);
+            version.parse::<semver::Version>().with_context(|| {
+                format!(
+                    "Looking up the license of `{}` needs an exact version, not `{}`",
+                    dependency.name, source.version
+                )
+            })?;
+            get_published_license(&dependency.name, &version, manifest_path)
+        }
+        Some(Source::Path(source)) => {
+            let manifest = LocalManifest::try_new(&source.path.join("Cargo.toml"))?;
+            Ok(manifest.license().map(ToOwned::to_owned))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Licenses to allow or deny
+///
+/// An expression passes when the licenses it can be used under can all be chosen to pass: none
+/// are denied and, if any are allowed, all are allowed.  Identifiers are compared ignoring case,
+/// and `GPL-2.0+` matches `GPL-2.0` too.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LicensePolicy {
+    allow: Vec<String>,
+    deny: Vec<String>,
+}
+
+impl LicensePolicy {
+    /// Accept any license
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only accept the licenses allowed this way
+    pub fn allow(mut self, license: &str) -> Self {
+        self.allow.push(license.to_owned());
+        self
+    }
+
+    /// Reject `license`
+    pub fn deny(mut self, license: &str) -> Self {
+        self.deny.push(license.to_owned());
+        self
+    }
+
+    /// Whether every license is accepted
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Check the license expression `expression`, like `MIT OR Apache-2.0`
+    pub fn check(&self, expression: &str) -> CargoResult<()> {
+        let parsed = parse_expression(expression)
+            .with_context(|| format!("Invalid license expression `{}`", expression))?;
+        if self.permits(&parsed) {
+            Ok(())
+        } else {
+            anyhow::bail!("License `{}` is not allowed by the policy", expression)
+        }
+    }
+
+    fn permits(&self, expression: &Expression) -> bool {
+        match expression {
+            Expression::License(license) => {
+                let matches = |list: &[String]| {
+                    let base = license.trim_end_matches('+');
+                    list.iter()
+                        .any(|l| l.eq_ignore_ascii_case(license) || l.eq_ignore_ascii_case(base))
+                };
+                !matches(&self.deny) && (self.allow.is_empty() || matches(&self.allow))
+            }
+            Expression::And(lhs, rhs) => self.permits(lhs) && self.permits(rhs),
+            Expression::Or(lhs, rhs) => self.permits(lhs) || self.permits(rhs),
+        }
+    }
+}
+
+/// An SPDX license expression, with exceptions dropped
+#[derive(Debug)]
+enum Expression {
+    License(String),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+}
+
+fn parse_expression(expression: &str) -> CargoResult<Expression> {
+    let mut tokens = tokenize(expression).into_iter().peekable();
+    let parsed = parse_or(&mut tokens)?;
+    if let Some(token) = tokens.next() {
+        anyhow::bail!("Unexpected `{}`", token);
+    }
+    Ok(parsed)
+}
+
+/// Split into identifiers, operators and parentheses, reading the legacy `/` as `OR`
+fn tokenize(expression: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    for c in expression.chars() {
+        if c.is_whitespace() || matches!(c, '(' | ')' | '/') {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+            match c {
+                '/' => tokens.push("OR".to_owned()),
+                '(' | ')' => tokens.push(c.to_string()),
+                _ => {}
+            }
+        } else {
+            current.push(c);
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+fn is_operator(token: &str, operator: &str) -> bool {
+    token.eq_ignore_ascii_case(operator)
+}
+
+fn parse_or(tokens: &mut Peekable<impl Iterator<Item = String>>) -> CargoResult<Expression> {
+    let mut parsed = parse_and(tokens)?;
+    while tokens.next_if(|t| is_operator(t, "OR")).is_some() {
+        parsed = Expression::Or(Box::new(parsed), Box::new(parse_and(tokens)?));
+    }
+    Ok(parsed)
+}
+
+fn parse_and(tokens: &mut Peekable<impl Iterator<Item = String>>) -> CargoResult<Expression> {
+    let mut parsed = parse_license(tokens)?;
+    while tokens.next_if(|t| is_operator(t, "AND")).is_some() {
+        parsed = Expression::And(Box::new(parsed), Box::new(parse_license(tokens)?));
+    }
+    Ok(parsed)
+}
+
+fn parse_license(tokens: &mut Peekable<impl Iterator<Item = String>>) -> CargoResult<Expression> {
+    let token = tokens.next().context("Expected a license")?;
+    if token == "(" {
+        let parsed = parse_or(tokens)?;
+        if tokens.next().as_deref() != Some(")") {
+            anyhow::bail!("Expected `)`");
+        }
+        return Ok(parsed);
+    }
+    if token == ")" || ["AND", "OR", "WITH"].iter().any(|o| is_operator(&token, o)) {
+        anyhow::bail!("Expected a license, found `{}`", token);
+    }
+    if tokens.next_if(|t| is_operator(t, "WITH")).is_some() {
+        tokens.next().context("Expected an exception")?;
+    }
+    Ok(Expression::License(token))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deny_list() {
+        let policy = LicensePolicy::new().deny("GPL-3.0");
+        assert!(policy.check("MIT OR Apache-2.0").is_ok());
+        assert!(policy.check("MIT OR GPL-3.0").is_ok());
+        assert!(policy.check("MIT AND GPL-3.0").is_err());
+        assert!(policy.check("gpl-3.0+").is_err());
+    }
+
+    #[test]
+    fn allow_list() {
+        let policy = LicensePolicy::new().allow("MIT").allow("Apache-2.0");
+        assert!(policy.check("Apache-2.0/MIT").is_ok());
+        assert!(policy
+            .check("(Apache-2.0 WITH LLVM-exception) OR BSD-3-Clause")
+            .is_ok());
+        assert!(policy.check("MIT AND (Apache-2.0 OR Zlib)").is_ok());
+        assert!(policy.check("MIT AND Zlib").is_err());
+        assert!(policy.check("Zlib").is_err());
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        let policy = LicensePolicy::new();
+        assert!(policy.check("MIT OR").is_err());
+        assert!(policy.check("(MIT").is_err());
+        assert!(policy.check("MIT Apache-2.0").is_err());
+        assert!(policy.check("").is_err());
+    }
+}
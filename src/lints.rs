@@ -0,0 +1,127 @@
+//! Address `[lints]` entries, like `clippy.unwrap_used`
+use std::fmt;
+use std::str::FromStr;
+
+use super::errors::*;
+
+/// Tools cargo passes `[lints]` to
+const TOOLS: &[&str] = &["rust", "clippy", "rustdoc"];
+
+/// A lint in `[lints]` or `[workspace.lints]`, like `clippy.unwrap_used`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LintKey {
+    /// Tool the lint belongs to, like `clippy`
+    pub tool: String,
+    /// Name of the lint or lint group, like `unwrap_used`
+    pub name: String,
+}
+
+impl FromStr for LintKey {
+    type Err = Error;
+
+    fn from_str(key: &str) -> CargoResult<Self> {
+        let (tool, name) = key.split_once('.').with_context(|| {
+            format!(
+                "Invalid lint `{}`, expected `<TOOL>.<LINT>`, like `clippy.unwrap_used`",
+                key
+            )
+        })?;
+        if !TOOLS.contains(&tool) {
+            anyhow::bail!(
+                "Unknown lint tool `{}`, expected one of {}",
+                tool,
+                TOOLS.join(", ")
+            );
+        }
+        // Lints are snake case, but cargo also accepts them with dashes, like rustc does
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            anyhow::bail!("Invalid lint name `{}`", name);
+        }
+        Ok(Self {
+            tool: tool.to_owned(),
+            name: name.to_owned(),
+        })
+    }
+}
+
+impl fmt::Display for LintKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.tool, self.name)
+    }
+}
+
+/// How a lint is reported
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LintLevel {
+    /// Not reported
+    Allow,
+    /// Reported as a warning
+    Warn,
+    /// Reported as an error
+    Deny,
+    /// Reported as an error, which inner `#[allow]`s can't lower
+    Forbid,
+}
+
+impl LintLevel {
+    /// Levels, in the order they are listed in help
+    pub const VARIANTS: &'static [&'static str] = &["allow", "warn", "deny", "forbid"];
+
+    /// The level as written in a manifest
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Allow => "allow",
+            Self::Warn => "warn",
+            Self::Deny => "deny",
+            Self::Forbid => "forbid",
+        }
+    }
+}
+
+impl FromStr for LintLevel {
+    type Err = Error;
+
+    fn from_str(level: &str) -> CargoResult<Self> {
+        match level {
+            "allow" => Ok(Self::Allow),
+            "warn" => Ok(Self::Warn),
+            "deny" => Ok(Self::Deny),
+            "forbid" => Ok(Self::Forbid),
+            _ => anyhow::bail!(
+                "Invalid lint level `{}`, expected one of {}",
+                level,
+                Self::VARIANTS.join(", ")
+            ),
+        }
+    }
+}
+
+impl fmt::Display for LintLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_keys() {
+        let key: LintKey = "clippy.unwrap_used".parse().unwrap();
+        assert_eq!(key.tool, "clippy");
+        assert_eq!(key.name, "unwrap_used");
+        assert_eq!(key.to_string(), "clippy.unwrap_used");
+        assert!("rust.unsafe_code".parse::<LintKey>().is_ok());
+        assert!("unwrap_used".parse::<LintKey>().is_err());
+        assert!("clipy.unwrap_used".parse::<LintKey>().is_err());
+        assert!("clippy.".parse::<LintKey>().is_err());
+        assert!("clippy.a.b".parse::<LintKey>().is_err());
+        assert_eq!("deny".parse::<LintLevel>().unwrap(), LintLevel::Deny);
+        assert!("error".parse::<LintLevel>().is_err());
+    }
+}
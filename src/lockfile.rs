@@ -0,0 +1,128 @@
+//! Read `Cargo.lock` without resolving the workspace
+use std::path::Path;
+
+use super::errors::*;
+
+/// A package, as `Cargo.lock` records it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LockedPackage {
+    /// Package name
+    pub name: String,
+    /// Locked version
+    pub version: semver::Version,
+    /// Where it comes from, like `registry+https://github.com/rust-lang/crates.io-index`, or
+    /// `None` for path dependencies and workspace members
+    pub source: Option<String>,
+    /// Packages it depends on, as `(name, version)`
+    ///
+    /// The version is only recorded when several versions of the package are locked.
+    pub dependencies: Vec<(String, Option<semver::Version>)>,
+}
+
+impl LockedPackage {
+    /// The locked versions of `name` this package depends on
+    ///
+    /// Returns `None` when it doesn't depend on `name`, and an empty list when it does without
+    /// the lockfile saying which version.
+    pub fn dependency_versions(&self, name: &str) -> Option<Vec<&semver::Version>> {
+        let mut versions = Vec::new();
+        let mut depends = false;
+        for (dependency, version) in &self.dependencies {
+            if dependency == name {
+                depends = true;
+                versions.extend(version);
+            }
+        }
+        depends.then(|| versions)
+    }
+}
+
+/// Read the packages recorded in the lockfile at `path`
+pub fn read_lockfile(path: &Path) -> CargoResult<Vec<LockedPackage>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read `{}`", path.display()))?;
+    parse_lockfile(&contents).with_context(|| format!("Invalid lockfile `{}`", path.display()))
+}
+
+fn parse_lockfile(contents: &str) -> CargoResult<Vec<LockedPackage>> {
+    let document: toml_edit::Document = contents.parse()?;
+    let packages = match document.get("package") {
+        Some(packages) => packages
+            .as_array_of_tables()
+            .context("`package` should be an array of tables")?,
+        None => return Ok(Vec::new()),
+    };
+    let mut locked = Vec::new();
+    for package in packages.iter() {
+        let name = package
+            .get("name")
+            .and_then(|n| n.as_str())
+            .context("a package is missing its `name`")?;
+        let version = package
+            .get("version")
+            .and_then(|v| v.as_str())
+            .with_context(|| format!("`{}` is missing its `version`", name))?;
+        let mut dependencies = Vec::new();
+        let entries = package.get("dependencies").and_then(|d| d.as_array());
+        for entry in entries.into_iter().flat_map(|a| a.iter()) {
+            // Entries are `name`, `name version` or `name version (source)`
+            let mut parts = entry
+                .as_str()
+                .with_context(|| format!("`{}` lists a dependency that isn't a string", name))?
+                .split_whitespace();
+            let dependency = parts.next().unwrap_or_default().to_owned();
+            let version = parts.next().map(str::parse).transpose()?;
+            dependencies.push((dependency, version));
+        }
+        locked.push(LockedPackage {
+            name: name.to_owned(),
+            version: version.parse()?,
+            source: package
+                .get("source")
+                .and_then(|s| s.as_str())
+                .map(ToOwned::to_owned),
+            dependencies,
+        });
+    }
+    Ok(locked)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dependencies() {
+        let locked = parse_lockfile(
+            r#"version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "serde 1.0.100",
+ "serde 1.0.147 (registry+https://github.com/rust-lang/crates.io-index)",
+ "toml",
+]
+
+[[package]]
+name = "toml"
+version = "0.5.9"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#,
+        )
+        .unwrap();
+        assert_eq!(locked.len(), 2);
+        assert_eq!(locked[0].source, None);
+        let versions = locked[0].dependency_versions("serde").unwrap();
+        assert_eq!(
+            versions,
+            ["1.0.100".parse().unwrap(), "1.0.147".parse().unwrap()]
+                .iter()
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(locked[0].dependency_versions("toml").unwrap().len(), 0);
+        assert_eq!(locked[0].dependency_versions("regex"), None);
+        assert_eq!(locked[1].version, semver::Version::new(0, 5, 9));
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::fs;
 use std::ops::{Deref, DerefMut};
 use std::path::{Path, PathBuf};
@@ -5,8 +6,14 @@ use std::{env, str};
 
 use semver::Version;
 
+use super::duplicates::find_duplicate_dependencies;
 use super::errors::*;
+use super::hooks::{EditHooks, HookStage};
+use super::lints::{LintKey, LintLevel};
 use super::metadata::find_manifest_path;
+use super::package_field::PackageField;
+use super::profile::ProfileKey;
+use super::timings::time;
 
 #[derive(PartialEq, Eq, Hash, Ord, PartialOrd, Clone, Debug, Copy)]
 pub enum DepKind {
@@ -49,13 +56,26 @@ impl DepTable {
         self
     }
 
-    fn kind_table(&self) -> &str {
+    /// Platform the dependencies are for, if any
+    pub(crate) fn target(&self) -> Option<&str> {
+        self.target.as_deref()
+    }
+
+    pub(crate) fn kind_table(&self) -> &str {
         match self.kind {
             DepKind::Normal => "dependencies",
             DepKind::Development => "dev-dependencies",
             DepKind::Build => "build-dependencies",
         }
     }
+
+    /// Path to the table in the manifest
+    pub(crate) fn to_table(&self) -> Vec<&str> {
+        match &self.target {
+            Some(target) => vec!["target", target, self.kind_table()],
+            None => vec![self.kind_table()],
+        }
+    }
 }
 
 impl Default for DepTable {
@@ -70,6 +90,110 @@ impl From<DepKind> for DepTable {
     }
 }
 
+/// Build target sections (`[[bin]]`, `[[example]]`, etc) that can have `required-features`
+#[derive(PartialEq, Eq, Hash, Ord, PartialOrd, Clone, Debug, Copy)]
+pub enum TargetKind {
+    /// `[[bin]]`
+    Bin,
+    /// `[[example]]`
+    Example,
+    /// `[[test]]`
+    Test,
+    /// `[[bench]]`
+    Bench,
+}
+
+impl TargetKind {
+    const KINDS: &'static [Self] = &[Self::Bin, Self::Example, Self::Test, Self::Bench];
+
+    /// Name of the target's section, like `bin`
+    pub fn kind_table(&self) -> &'static str {
+        match self {
+            Self::Bin => "bin",
+            Self::Example => "example",
+            Self::Test => "test",
+            Self::Bench => "bench",
+        }
+    }
+
+    /// Sources cargo looks for a target at when it has no `path`, relative to the package
+    pub fn inferred_paths(&self, name: &str) -> [String; 2] {
+        let dir = match self {
+            Self::Bin => "src/bin",
+            Self::Example => "examples",
+            Self::Test => "tests",
+            Self::Bench => "benches",
+        };
+        [
+            format!("{}/{}.rs", dir, name),
+            format!("{}/{}/main.rs", dir, name),
+        ]
+    }
+}
+
+impl str::FromStr for TargetKind {
+    type Err = Error;
+
+    fn from_str(kind: &str) -> CargoResult<Self> {
+        Self::KINDS
+            .iter()
+            .find(|k| k.kind_table() == kind)
+            .copied()
+            .ok_or_else(|| {
+                anyhow::format_err!(
+                    "Unknown target kind `{}`, expected one of bin, example, test, bench",
+                    kind
+                )
+            })
+    }
+}
+
+/// Where a dependency is referenced in a manifest, see [`Manifest::dependency_usage`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DependencyUsage {
+    /// Dependency tables declaring it, as paths like `["target", "cfg(unix)", "dependencies"]`
+    pub tables: Vec<Vec<String>>,
+    /// Features activating it or one of its features, as `(feature, activation)`
+    pub features: Vec<(String, String)>,
+    /// Build targets with `required-features` that activate it
+    pub targets: Vec<(TargetKind, String)>,
+}
+
+/// Upgrade policy a package declares in `[package.metadata.cargo-edit]`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UpgradePolicy {
+    /// Dependencies to leave alone, from `upgrade-ignore`
+    pub ignore: Vec<String>,
+    /// Version requirements to hold dependencies at, from `pin`
+    pub pin: BTreeMap<String, String>,
+    /// Whether entries enable default features, by dependency table, from `default-features`
+    pub default_features: BTreeMap<String, bool>,
+}
+
+impl UpgradePolicy {
+    /// Whether the dependency `dep_key` is in `upgrade-ignore`
+    pub fn is_ignored(&self, dep_key: &str) -> bool {
+        self.ignore.iter().any(|i| i == dep_key)
+    }
+
+    /// Whether entries of the `table` dependency table, like `dev-dependencies`, should enable
+    /// default features, if the policy says
+    pub fn default_features(&self, table: &str) -> Option<bool> {
+        self.default_features.get(table).copied()
+    }
+}
+
+/// A checksum recorded in `[package.metadata.cargo-edit.checksums]`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordedChecksum {
+    /// Crate name, as published
+    pub name: String,
+    /// Version the checksum is for
+    pub version: String,
+    /// SHA-256 of the `.crate` file, in hex, as the registry index listed it
+    pub checksum: String,
+}
+
 /// A Cargo manifest
 #[derive(Debug, Clone)]
 pub struct Manifest {
@@ -89,6 +213,14 @@ impl Manifest {
         self.get_table_mut_internal(table_path, false)
     }
 
+    /// Get the specified table from the manifest, creating it and its parents as needed
+    pub(crate) fn get_or_insert_table_mut<'a>(
+        &'a mut self,
+        table_path: &[String],
+    ) -> CargoResult<&'a mut toml_edit::Item> {
+        self.get_table_mut_internal(table_path, true)
+    }
+
     /// Get all sections in the manifest that exist and might contain dependencies.
     /// The returned items are always `Table` or `InlineTable`.
     pub(crate) fn get_sections(&self) -> Vec<(DepTable, toml_edit::Item)> {
@@ -130,6 +262,258 @@ impl Manifest {
         sections
     }
 
+    /// Whether the unstable cargo `feature` is enabled with `cargo-features`
+    pub fn has_cargo_feature(&self, feature: &str) -> bool {
+        self.data
+            .get("cargo-features")
+            .and_then(|f| f.as_array())
+            .map(|f| f.iter().any(|f| f.as_str() == Some(feature)))
+            .unwrap_or(false)
+    }
+
+    /// The license expression in `[package]`, if not inherited from the workspace
+    pub fn license(&self) -> Option<&str> {
+        self.data
+            .get("package")
+            .and_then(|p| p.get("license"))
+            .and_then(|l| l.as_str())
+    }
+
+    /// A `[package]` field's string value, read from `root`'s `[workspace.package]` when inherited
+    pub fn package_field<'m>(&'m self, field: PackageField, root: &'m Self) -> Option<&'m str> {
+        let item = self.data.get("package")?.get(field.key())?;
+        if item.get("workspace").is_some() {
+            root.data
+                .get("workspace")?
+                .get("package")?
+                .get(field.key())?
+                .as_str()
+        } else {
+            item.as_str()
+        }
+    }
+
+    /// The value of a `[profile]` setting, if set
+    pub fn profile_setting(&self, key: &ProfileKey) -> Option<&toml_edit::Item> {
+        let mut item = self.data.as_item();
+        for segment in key.table_path() {
+            item = item.get(&segment)?;
+        }
+        item.get(&key.setting)
+    }
+
+    /// Read the upgrade policy from `[package.metadata.cargo-edit]`
+    pub fn upgrade_policy(&self) -> CargoResult<UpgradePolicy> {
+        let mut policy = UpgradePolicy::default();
+        let table = match self
+            .data
+            .get("package")
+            .and_then(|p| p.get("metadata"))
+            .and_then(|m| m.get("cargo-edit"))
+        {
+            Some(table) => table,
+            None => return Ok(policy),
+        };
+
+        if let Some(ignore) = table.get("upgrade-ignore") {
+            let invalid = || {
+                anyhow::format_err!(
+                    "Expected `package.metadata.cargo-edit.upgrade-ignore` to be an array of crate names"
+                )
+            };
+            for name in ignore.as_array().ok_or_else(invalid)? {
+                policy
+                    .ignore
+                    .push(name.as_str().ok_or_else(invalid)?.to_owned());
+            }
+        }
+
+        if let Some(pin) = table.get("pin") {
+            let pin = pin.as_table_like().ok_or_else(|| {
+                anyhow::format_err!(
+                    "Expected `package.metadata.cargo-edit.pin` to be a table of version requirements"
+                )
+            })?;
+            for (name, req) in pin.iter() {
+                let req = req.as_str().ok_or_else(|| {
+                    anyhow::format_err!(
+                        "Expected `package.metadata.cargo-edit.pin.{}` to be a version requirement",
+                        name
+                    )
+                })?;
+                semver::VersionReq::parse(req).with_context(|| {
+                    format!(
+                        "Invalid version requirement for `package.metadata.cargo-edit.pin.{}`",
+                        name
+                    )
+                })?;
+                policy.pin.insert(name.to_owned(), req.to_owned());
+            }
+        }
+
+        if let Some(default_features) = table.get("default-features") {
+            let invalid = || {
+                anyhow::format_err!(
+                    "Expected `package.metadata.cargo-edit.default-features` to map dependency tables, like `dev-dependencies`, to `true` or `false`"
+                )
+            };
+            for (kind, enabled) in default_features.as_table_like().ok_or_else(invalid)?.iter() {
+                if !is_dependency_table(kind) {
+                    return Err(invalid());
+                }
+                let enabled = enabled.as_bool().ok_or_else(invalid)?;
+                policy.default_features.insert(kind.to_owned(), enabled);
+            }
+        }
+
+        Ok(policy)
+    }
+
+    /// Checksums recorded in `[package.metadata.cargo-edit.checksums]`, by crate name
+    ///
+    /// Entries look like `serde = { version = "1.0.147", checksum = "d193d69b..." }`.
+    pub fn recorded_checksums(&self) -> CargoResult<Vec<RecordedChecksum>> {
+        let checksums = match self
+            .data
+            .get("package")
+            .and_then(|p| p.get("metadata"))
+            .and_then(|m| m.get("cargo-edit"))
+            .and_then(|c| c.get("checksums"))
+        {
+            Some(checksums) => checksums,
+            None => return Ok(Vec::new()),
+        };
+        let checksums = checksums.as_table_like().ok_or_else(|| {
+            anyhow::format_err!(
+                "Expected `package.metadata.cargo-edit.checksums` to be a table of crates"
+            )
+        })?;
+        checksums
+            .iter()
+            .map(|(name, entry)| {
+                let field = |key| entry.get(key).and_then(|v| v.as_str()).map(str::to_owned);
+                match (field("version"), field("checksum")) {
+                    (Some(version), Some(checksum)) => Ok(RecordedChecksum {
+                        name: name.to_owned(),
+                        version,
+                        checksum,
+                    }),
+                    _ => anyhow::bail!(
+                        "Expected `package.metadata.cargo-edit.checksums.{}` to have a `version` and a `checksum`",
+                        name
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    /// Record the index checksum of `version` of `name` in
+    /// `[package.metadata.cargo-edit.checksums]`, replacing any recorded for another version
+    pub fn record_checksum(
+        &mut self,
+        name: &str,
+        version: &str,
+        checksum: &str,
+    ) -> CargoResult<()> {
+        let table_path = ["package", "metadata", "cargo-edit", "checksums"];
+        let mut table = self.data.as_table_mut();
+        for (i, segment) in table_path.iter().enumerate() {
+            let created = !table.contains_key(segment);
+            let item = table.entry(segment).or_insert(toml_edit::table());
+            let path = || table_path[..=i].join(".");
+            let inner = item
+                .as_table_mut()
+                .ok_or_else(|| anyhow::format_err!("Expected `{}` to be a table", path()))?;
+            if created && i + 1 < table_path.len() {
+                inner.set_implicit(true);
+            }
+            table = inner;
+        }
+
+        let mut entry = toml_edit::InlineTable::new();
+        entry.insert("version", version.into());
+        entry.insert("checksum", checksum.into());
+        entry.fmt();
+        table[name] = toml_edit::value(entry);
+        Ok(())
+    }
+
+    /// Dependencies containing a template placeholder, as `(key, placeholder)`
+    ///
+    /// See [`find_template_placeholder`].  `[workspace.dependencies]` isn't included.
+    pub fn templated_dependencies(&self) -> Vec<(String, String)> {
+        let mut templated = Vec::new();
+        for (_, item) in self.get_sections() {
+            let table = item.as_table_like().expect("sections are table-like");
+            for (key, entry) in table.iter() {
+                if let Some(placeholder) = find_template_placeholder(entry) {
+                    templated.push((key.to_owned(), placeholder));
+                }
+            }
+        }
+        templated
+    }
+
+    /// Find everything in the manifest referring to the dependency `dep_key`
+    pub fn dependency_usage(&self, dep_key: &str) -> DependencyUsage {
+        let mut usage = DependencyUsage::default();
+        for (table, item) in self.get_sections() {
+            let declared = item
+                .as_table_like()
+                .map(|t| t.contains_key(dep_key))
+                .unwrap_or(false);
+            if declared {
+                usage
+                    .tables
+                    .push(table.to_table().into_iter().map(String::from).collect());
+            }
+        }
+
+        if let Some(features) = self.data.get("features").and_then(|f| f.as_table_like()) {
+            for (feature, activations) in features.iter() {
+                let activations = activations
+                    .as_array()
+                    .into_iter()
+                    .flat_map(|a| a.iter())
+                    .filter_map(|a| a.as_str());
+                for activation in activations {
+                    if activates_dep(activation, dep_key) {
+                        usage
+                            .features
+                            .push((feature.to_owned(), activation.to_owned()));
+                    }
+                }
+            }
+        }
+
+        // An optional dependency is also a feature of its own
+        let mut features: Vec<&str> = usage.features.iter().map(|(f, _)| f.as_str()).collect();
+        features.push(dep_key);
+        for kind in TargetKind::KINDS {
+            let targets = self
+                .data
+                .get(kind.kind_table())
+                .and_then(|t| t.as_array_of_tables())
+                .into_iter()
+                .flat_map(|t| t.iter());
+            for target in targets {
+                let required = target
+                    .get("required-features")
+                    .and_then(|r| r.as_array())
+                    .into_iter()
+                    .flat_map(|r| r.iter())
+                    .filter_map(|r| r.as_str())
+                    .any(|r| features.contains(&r));
+                if required {
+                    let name = target.get("name").and_then(|n| n.as_str()).unwrap_or("");
+                    usage.targets.push((*kind, name.to_owned()));
+                }
+            }
+        }
+
+        usage
+    }
+
     fn get_table_mut_internal<'a>(
         &'a mut self,
         table_path: &[String],
@@ -169,6 +553,7 @@ impl str::FromStr for Manifest {
 
     /// Read manifest data from string
     fn from_str(input: &str) -> ::std::result::Result<Self, Self::Err> {
+        let input = input.strip_prefix(BOM).unwrap_or(input);
         let d: toml_edit::Document = input.parse().context("Manifest not valid TOML")?;
 
         Ok(Manifest { data: d })
@@ -209,18 +594,58 @@ impl LocalManifest {
     /// Construct a `LocalManifest`. If no path is provided, make an educated guess as to which one
     /// the user means.
     pub fn find(path: Option<&Path>) -> CargoResult<Self> {
-        let path = dunce::canonicalize(find(path)?)?;
+        let path = find(path)?;
+        // Keep a symlinked manifest's own path, for `WriteOptions` to decide whether writing
+        // goes through it
+        let dir = path.parent().expect("files have a parent");
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        let path = dunce::canonicalize(dir)?.join(path.file_name().expect("files have a name"));
         Self::try_new(&path)
     }
 
     /// Construct the `LocalManifest` corresponding to the `Path` provided.
+    ///
+    /// For a cargo script (`.rs` file), this is the manifest embedded in its frontmatter.
     pub fn try_new(path: &Path) -> CargoResult<Self> {
         if !path.is_absolute() {
             anyhow::bail!("can only edit absolute paths, got {}", path.display());
         }
-        let data =
-            std::fs::read_to_string(&path).with_context(|| "Failed to read manifest contents")?;
-        let manifest = data.parse().context("Unable to parse Cargo.toml")?;
+        let data = read_manifest_source(path)?;
+        let (data, first_line) = if is_script(path) {
+            let frontmatter = find_frontmatter(&data)
+                .with_context(|| format!("Invalid frontmatter in `{}`", path.display()))?;
+            match frontmatter {
+                Some(f) => (&data[f.clone()], data[..f.start].matches('\n').count() + 1),
+                None => ("", 1),
+            }
+        } else {
+            (data.as_str(), 1)
+        };
+        let manifest = match data.parse::<toml_edit::Document>() {
+            Ok(data) => Manifest { data },
+            Err(err) => {
+                let err = syntax_error(path, data, first_line, &err);
+                // Editing either declaration would leave it ambiguous which one cargo sees
+                let context = match find_duplicate_dependencies(data).first() {
+                    Some(duplicate) => format!(
+                        "`{}` is declared twice in `[{}]` of `{}`, merge them first, like with `cargo upgrade --fix-duplicates`",
+                        duplicate.name,
+                        duplicate.table.join("."),
+                        path.display()
+                    ),
+                    None if data.contains("{{") || data.contains("{%") => format!(
+                        "Unable to parse `{}`, it looks like an unrendered template",
+                        path.display()
+                    ),
+                    None => return Err(err),
+                };
+                return Err(err.context(context));
+            }
+        };
         Ok(LocalManifest {
             manifest,
             path: path.to_owned(),
@@ -229,7 +654,14 @@ impl LocalManifest {
 
     /// Write changes back to the file
     pub fn write(&self) -> CargoResult<()> {
-        if !self.manifest.data.contains_key("package")
+        self.write_with(&WriteOptions::new())
+    }
+
+    /// Write changes back to the file, as `options` says
+    pub fn write_with(&self, options: &WriteOptions) -> CargoResult<()> {
+        // Scripts get their package information from the file
+        if !is_script(&self.path)
+            && !self.manifest.data.contains_key("package")
             && !self.manifest.data.contains_key("project")
         {
             if self.manifest.data.contains_key("workspace") {
@@ -246,10 +678,39 @@ impl LocalManifest {
             }
         }
 
-        let s = self.manifest.data.to_string();
+        self.write_unchecked_with(options)
+    }
+
+    /// Write changes back to the file, even if this is a virtual manifest
+    pub fn write_unchecked(&self) -> CargoResult<()> {
+        self.write_unchecked_with(&WriteOptions::new())
+    }
+
+    /// Write changes back to the file as `options` says, even if this is a virtual manifest
+    pub fn write_unchecked_with(&self, options: &WriteOptions) -> CargoResult<()> {
+        let mut s = self.manifest.data.to_string();
+        if is_script(&self.path) {
+            let source = read_manifest_source(&self.path)?;
+            s = replace_frontmatter(&source, &s)?;
+        }
+        // Keep the byte order mark for editors and tools that rely on it
+        if has_bom(&self.path) {
+            s.insert(0, BOM);
+        }
         let new_contents_bytes = s.as_bytes();
 
-        std::fs::write(&self.path, new_contents_bytes).context("Failed to write updated Cargo.toml")
+        let hooks = EditHooks::new(&self.path, &self.manifest)?;
+        if let Some(hooks) = &hooks {
+            hooks.run(HookStage::Pre)?;
+        }
+        time("write", self.path.display(), || {
+            write_manifest_file(&self.path, new_contents_bytes, options)
+        })
+        .context("Failed to write updated Cargo.toml")?;
+        if let Some(hooks) = &hooks {
+            hooks.run(HookStage::Post)?;
+        }
+        Ok(())
     }
 
     /// Remove entry from a Cargo.toml.
@@ -272,6 +733,7 @@ impl LocalManifest {
     ///   assert!(!manifest.data.contains_key("dependencies"));
     /// ```
     pub fn remove_from_table(&mut self, table_path: &[String], name: &str) -> CargoResult<()> {
+        let table_path = &self.resolve_target_path(table_path);
         let parent_table = self.get_table_mut(table_path)?;
 
         {
@@ -295,30 +757,43 @@ impl LocalManifest {
     pub fn get_dependency_tables_mut<'r>(
         &'r mut self,
     ) -> impl Iterator<Item = &mut dyn toml_edit::TableLike> + 'r {
+        self.get_dependency_tables_with_path_mut()
+            .map(|(_, table)| table)
+    }
+
+    /// Allow mutating depedencies, wherever they live, along with the path of their table, like
+    /// `["target", "cfg(unix)", "dev-dependencies"]`
+    pub fn get_dependency_tables_with_path_mut<'r>(
+        &'r mut self,
+    ) -> impl Iterator<Item = (Vec<String>, &mut dyn toml_edit::TableLike)> + 'r {
         let root = self.data.as_table_mut();
         root.iter_mut().flat_map(|(k, v)| {
-            if DepTable::KINDS
-                .iter()
-                .any(|kind| kind.kind_table() == k.get())
-            {
-                v.as_table_like_mut().into_iter().collect::<Vec<_>>()
+            if is_dependency_table(k.get()) {
+                let path = vec![k.get().to_owned()];
+                v.as_table_like_mut()
+                    .map(|table| (path, table))
+                    .into_iter()
+                    .collect::<Vec<_>>()
             } else if k == "target" {
                 v.as_table_like_mut()
-                    .unwrap()
-                    .iter_mut()
-                    .flat_map(|(_, v)| {
-                        v.as_table_like_mut().into_iter().flat_map(|v| {
-                            v.iter_mut().filter_map(|(k, v)| {
-                                if DepTable::KINDS
-                                    .iter()
-                                    .any(|kind| kind.kind_table() == k.get())
-                                {
-                                    v.as_table_like_mut()
-                                } else {
-                                    None
-                                }
+                    .into_iter()
+                    .flat_map(|targets| targets.iter_mut())
+                    .flat_map(|(platform, platform_table)| {
+                        let platform = platform.get().to_owned();
+                        platform_table
+                            .as_table_like_mut()
+                            .into_iter()
+                            .flat_map(|t| t.iter_mut())
+                            .filter(|(kind, _)| is_dependency_table(kind.get()))
+                            .filter_map(|(kind, table)| {
+                                let path = vec![
+                                    "target".to_owned(),
+                                    platform.clone(),
+                                    kind.get().to_owned(),
+                                ];
+                                table.as_table_like_mut().map(|table| (path, table))
                             })
-                        })
+                            .collect::<Vec<_>>()
                     })
                     .collect::<Vec<_>>()
             } else {
@@ -327,6 +802,504 @@ impl LocalManifest {
         })
     }
 
+    /// Iterate over the `[target.<platform>.*dependencies]` tables, with their platform
+    ///
+    /// Platforms are returned exactly as written, like `cfg(all(unix, target_arch = "x86_64"))`.
+    pub fn get_target_tables_mut(
+        &mut self,
+    ) -> impl Iterator<Item = (String, &mut dyn toml_edit::TableLike)> + '_ {
+        match self.data.as_table_mut().get_mut("target") {
+            Some(targets) => target_dependency_tables(targets),
+            None => Vec::new(),
+        }
+        .into_iter()
+    }
+
+    /// Match the platform of a `["target", <platform>, ..]` path to how the manifest spells it
+    fn resolve_target_path(&self, table_path: &[String]) -> Vec<String> {
+        let mut table_path = table_path.to_vec();
+        if let [target, platform, ..] = table_path.as_mut_slice() {
+            if *target == "target" {
+                let written = self
+                    .data
+                    .get("target")
+                    .and_then(|t| t.as_table_like())
+                    .into_iter()
+                    .flat_map(|t| t.iter())
+                    .map(|(written, _)| written)
+                    .find(|written| same_platform(written, platform));
+                if let Some(written) = written {
+                    *platform = written.to_owned();
+                }
+            }
+        }
+        table_path
+    }
+
+    /// Allow mutating `[workspace.dependencies]`, if present
+    pub fn get_workspace_dependency_table_mut(&mut self) -> Option<&mut dyn toml_edit::TableLike> {
+        self.data
+            .get_mut("workspace")?
+            .get_mut("dependencies")?
+            .as_table_like_mut()
+    }
+
+    /// Allow mutating the `[patch.<source>]` tables
+    pub fn get_patch_tables_mut(
+        &mut self,
+    ) -> impl Iterator<Item = &mut dyn toml_edit::TableLike> + '_ {
+        self.data
+            .get_mut("patch")
+            .and_then(|p| p.as_table_like_mut())
+            .into_iter()
+            .flat_map(|p| p.iter_mut().filter_map(|(_, t)| t.as_table_like_mut()))
+    }
+
+    /// Add `feature` to the `required-features` of a build target
+    ///
+    /// The target's `[[<kind>]]` entry is created if it doesn't exist yet.
+    pub fn add_required_feature(
+        &mut self,
+        kind: TargetKind,
+        name: &str,
+        feature: &str,
+    ) -> CargoResult<()> {
+        let target = self.target_or_insert(kind, name)?;
+        let required_features = target
+            .entry("required-features")
+            .or_insert(toml_edit::value(toml_edit::Array::new()))
+            .as_array_mut()
+            .ok_or_else(|| {
+                anyhow::format_err!("Expected `required-features` of `{}` to be an array", name)
+            })?;
+        if !required_features
+            .iter()
+            .any(|f| f.as_str() == Some(feature))
+        {
+            required_features.push(feature);
+        }
+
+        Ok(())
+    }
+
+    /// Replace the `required-features` of a build target, removing them when `features` is empty
+    ///
+    /// The target's `[[<kind>]]` entry is created if it doesn't exist yet.
+    pub fn set_required_features(
+        &mut self,
+        kind: TargetKind,
+        name: &str,
+        features: &[String],
+    ) -> CargoResult<()> {
+        let target = self.target_or_insert(kind, name)?;
+        if features.is_empty() {
+            target.remove("required-features");
+        } else {
+            let features: toml_edit::Array = features.iter().map(|f| f.as_str()).collect();
+            match target.get_mut("required-features") {
+                Some(item) => overwrite_value(item, features),
+                None => target["required-features"] = toml_edit::value(features),
+            }
+        }
+        Ok(())
+    }
+
+    /// Declare a build target in a new `[[<kind>]]` entry
+    ///
+    /// Without `path`, cargo looks for its source where it infers it from the name, like
+    /// `src/bin/<name>.rs`.
+    pub fn add_target(
+        &mut self,
+        kind: TargetKind,
+        name: &str,
+        path: Option<&str>,
+        required_features: &[String],
+    ) -> CargoResult<()> {
+        validate_target_name(name)?;
+        let targets = self.targets_mut(kind)?;
+        if find_target(targets, name).is_some() {
+            anyhow::bail!("`[[{}]]` `{}` is already declared", kind.kind_table(), name);
+        }
+        let mut target = toml_edit::Table::new();
+        target["name"] = toml_edit::value(name);
+        if let Some(path) = path {
+            target["path"] = toml_edit::value(path);
+        }
+        if !required_features.is_empty() {
+            let features: toml_edit::Array = required_features.iter().map(|f| f.as_str()).collect();
+            target["required-features"] = toml_edit::value(features);
+        }
+        targets.push(target);
+        Ok(())
+    }
+
+    /// Rename a declared build target, and `default-run` along with a binary
+    ///
+    /// A target without `path` gets one pointing to where cargo found it under its old name,
+    /// which is returned.
+    pub fn rename_target(
+        &mut self,
+        kind: TargetKind,
+        old: &str,
+        new: &str,
+    ) -> CargoResult<Option<String>> {
+        validate_target_name(new)?;
+        let package_root = self.path.parent().expect("manifests have a parent");
+        let inferred = kind
+            .inferred_paths(old)
+            .into_iter()
+            .find(|path| package_root.join(path).is_file());
+
+        let targets = self
+            .data
+            .get_mut(kind.kind_table())
+            .and_then(|t| t.as_array_of_tables_mut())
+            .ok_or_else(|| not_declared(kind, old))?;
+        if find_target(targets, new).is_some() {
+            anyhow::bail!("`[[{}]]` `{}` is already declared", kind.kind_table(), new);
+        }
+        let index = find_target(targets, old).ok_or_else(|| not_declared(kind, old))?;
+        let target = targets.get_mut(index).expect("index was just found");
+        overwrite_value(&mut target["name"], new);
+        let pinned = match inferred {
+            Some(path) if !target.contains_key("path") => {
+                target["path"] = toml_edit::value(path.as_str());
+                Some(path)
+            }
+            _ => None,
+        };
+
+        if kind == TargetKind::Bin && self.default_run() == Some(old) {
+            self.set_default_run(Some(new))?;
+        }
+        Ok(pinned)
+    }
+
+    /// Remove a declared build target, and `default-run` when it names this binary
+    ///
+    /// Returns whether `default-run` was removed.
+    pub fn remove_target(&mut self, kind: TargetKind, name: &str) -> CargoResult<bool> {
+        let targets = self
+            .data
+            .get_mut(kind.kind_table())
+            .and_then(|t| t.as_array_of_tables_mut())
+            .ok_or_else(|| not_declared(kind, name))?;
+        let index = find_target(targets, name).ok_or_else(|| not_declared(kind, name))?;
+        targets.remove(index);
+        if targets.is_empty() {
+            self.data.as_table_mut().remove(kind.kind_table());
+        }
+
+        let default_run = kind == TargetKind::Bin && self.default_run() == Some(name);
+        if default_run {
+            self.set_default_run(None)?;
+        }
+        Ok(default_run)
+    }
+
+    /// The binary `cargo run` picks, from `default-run` in `[package]`
+    pub fn default_run(&self) -> Option<&str> {
+        self.data
+            .get("package")
+            .and_then(|p| p.get("default-run"))
+            .and_then(|d| d.as_str())
+    }
+
+    /// Set the binary `cargo run` picks, or remove `default-run` with `None`
+    pub fn set_default_run(&mut self, name: Option<&str>) -> CargoResult<()> {
+        let package = self
+            .data
+            .get_mut("package")
+            .and_then(|p| p.as_table_mut())
+            .context("The manifest has no `[package]` table")?;
+        match (name, package.get_mut("default-run")) {
+            (Some(name), Some(item)) => overwrite_value(item, name),
+            (Some(name), None) => package["default-run"] = toml_edit::value(name),
+            (None, _) => {
+                package.remove("default-run");
+            }
+        }
+        Ok(())
+    }
+
+    fn targets_mut(&mut self, kind: TargetKind) -> CargoResult<&mut toml_edit::ArrayOfTables> {
+        let kind_table = kind.kind_table();
+        self.data
+            .as_table_mut()
+            .entry(kind_table)
+            .or_insert(toml_edit::Item::ArrayOfTables(
+                toml_edit::ArrayOfTables::new(),
+            ))
+            .as_array_of_tables_mut()
+            .ok_or_else(|| {
+                anyhow::format_err!("Expected `{}` to be an array of tables", kind_table)
+            })
+    }
+
+    fn target_or_insert(
+        &mut self,
+        kind: TargetKind,
+        name: &str,
+    ) -> CargoResult<&mut toml_edit::Table> {
+        let targets = self.targets_mut(kind)?;
+        let index = match find_target(targets, name) {
+            Some(index) => index,
+            None => {
+                let mut target = toml_edit::Table::new();
+                target["name"] = toml_edit::value(name);
+                targets.push(target);
+                targets.len() - 1
+            }
+        };
+        Ok(targets.get_mut(index).expect("index was just found"))
+    }
+
+    /// Record the version of the tool `name` in a metadata table, like
+    /// `["workspace", "metadata", "tools"]`
+    ///
+    /// Missing tables are created, without headers for the intermediate ones.  An existing entry
+    /// keeps its formatting, and its table form, like `{ version = "0.9", features = [..] }`.
+    pub fn set_tool_version(
+        &mut self,
+        table_path: &[String],
+        name: &str,
+        version: &str,
+    ) -> CargoResult<()> {
+        let mut table = self.data.as_table_mut();
+        for (i, segment) in table_path.iter().enumerate() {
+            let created = !table.contains_key(segment);
+            let item = table.entry(segment).or_insert(toml_edit::table());
+            let path = || table_path[..=i].join(".");
+            let inner = item
+                .as_table_mut()
+                .ok_or_else(|| anyhow::format_err!("Expected `{}` to be a table", path()))?;
+            if created && i + 1 < table_path.len() {
+                inner.set_implicit(true);
+            }
+            table = inner;
+        }
+
+        match table.get_mut(name) {
+            Some(entry) => set_dep_version(entry, version)
+                .with_context(|| format!("Invalid entry for `{}`", name))?,
+            None => table[name] = toml_edit::value(version),
+        }
+        Ok(())
+    }
+
+    /// Set a `[package]` field to `value`, like [`PackageField::parse_value`] returns
+    ///
+    /// An existing value keeps its comments.  Fields inherited from the workspace are an error.
+    pub fn set_package_field(
+        &mut self,
+        field: PackageField,
+        value: toml_edit::Value,
+    ) -> CargoResult<()> {
+        let package = self
+            .data
+            .get_mut("package")
+            .and_then(|p| p.as_table_mut())
+            .context("The manifest has no `[package]` table")?;
+        match package.get_mut(field.key()) {
+            Some(item) if item.get("workspace").is_some() => {
+                anyhow::bail!("`{}` is inherited from the workspace", field)
+            }
+            Some(item) => overwrite_value(item, value),
+            None => package[field.key()] = toml_edit::value(value),
+        }
+        Ok(())
+    }
+
+    /// Set a field of `[workspace.package]`, which members inherit with `field.workspace = true`
+    ///
+    /// The field must already be there, as members can't inherit it otherwise.
+    pub fn set_workspace_package_field(
+        &mut self,
+        field: PackageField,
+        value: toml_edit::Value,
+    ) -> CargoResult<()> {
+        let item = self
+            .data
+            .get_mut("workspace")
+            .and_then(|w| w.get_mut("package"))
+            .and_then(|p| p.get_mut(field.key()))
+            .with_context(|| {
+                format!("The workspace has no `{}` in `[workspace.package]`", field)
+            })?;
+        overwrite_value(item, value);
+        Ok(())
+    }
+
+    /// Set a `[profile]` setting, after checking the setting accepts `value`
+    ///
+    /// Missing tables are created, without headers for the intermediate ones.  An existing value
+    /// keeps its comments.
+    pub fn set_profile_setting(
+        &mut self,
+        key: &ProfileKey,
+        value: toml_edit::Value,
+    ) -> CargoResult<()> {
+        key.check_value(&value)?;
+        let table_path = key.table_path();
+        let mut table = self.data.as_table_mut();
+        for (i, segment) in table_path.iter().enumerate() {
+            let created = !table.contains_key(segment);
+            let item = table.entry(segment).or_insert(toml_edit::table());
+            let path = || table_path[..=i].join(".");
+            let inner = item
+                .as_table_mut()
+                .ok_or_else(|| anyhow::format_err!("Expected `{}` to be a table", path()))?;
+            if created && i + 1 < table_path.len() {
+                inner.set_implicit(true);
+            }
+            table = inner;
+        }
+
+        match table.get_mut(&key.setting) {
+            Some(item) => overwrite_value(item, value),
+            None => table[key.setting.as_str()] = toml_edit::value(value),
+        }
+        Ok(())
+    }
+
+    /// Whether the package takes its lints from `[workspace.lints]`, with `lints.workspace = true`
+    pub fn inherits_workspace_lints(&self) -> bool {
+        self.data
+            .get("lints")
+            .and_then(|lints| lints.get("workspace"))
+            .and_then(|inherit| inherit.as_bool())
+            .unwrap_or(false)
+    }
+
+    /// Set the level of a lint in `[lints]`, or `[workspace.lints]` with `workspace`
+    ///
+    /// A lint written as `{ level = "warn", priority = -1 }` keeps its priority, unless a new one
+    /// is given.  Missing tables are created, like `[lints.clippy]`.
+    pub fn set_lint(
+        &mut self,
+        key: &LintKey,
+        level: LintLevel,
+        priority: Option<i64>,
+        workspace: bool,
+    ) -> CargoResult<()> {
+        if !workspace && self.inherits_workspace_lints() {
+            anyhow::bail!(
+                "`[lints]` is inherited from the workspace, `{}` has to be set in \
+                 `[workspace.lints]`",
+                key
+            );
+        }
+        if workspace && !self.data.contains_key("workspace") {
+            anyhow::bail!("`{}` isn't a workspace root", self.path.display());
+        }
+        let mut table_path = if workspace {
+            vec!["workspace", "lints"]
+        } else {
+            vec!["lints"]
+        };
+        table_path.push(&key.tool);
+        let mut table = self.data.as_table_mut();
+        for (i, segment) in table_path.iter().enumerate() {
+            let created = !table.contains_key(segment);
+            let item = table.entry(segment).or_insert(toml_edit::table());
+            let path = || table_path[..=i].join(".");
+            let inner = item
+                .as_table_mut()
+                .ok_or_else(|| anyhow::format_err!("Expected `{}` to be a table", path()))?;
+            if created && i + 1 < table_path.len() {
+                inner.set_implicit(true);
+            }
+            table = inner;
+        }
+
+        match table.get_mut(&key.name) {
+            Some(item) if item.is_table_like() => {
+                let entry = item.as_table_like_mut().expect("checked");
+                let mut set = |field: &str, value: toml_edit::Value| match entry.get_mut(field) {
+                    Some(item) => overwrite_value(item, value),
+                    None => {
+                        entry.insert(field, toml_edit::value(value));
+                    }
+                };
+                set("level", level.as_str().into());
+                if let Some(priority) = priority {
+                    set("priority", priority.into());
+                }
+            }
+            Some(item) if priority.is_none() => overwrite_value(item, level.as_str()),
+            existing => {
+                let value = match priority {
+                    Some(priority) => {
+                        let mut entry = toml_edit::InlineTable::new();
+                        entry.insert("level", level.as_str().into());
+                        entry.insert("priority", priority.into());
+                        entry.fmt();
+                        toml_edit::Value::InlineTable(entry)
+                    }
+                    None => level.as_str().into(),
+                };
+                match existing {
+                    Some(item) => overwrite_value(item, value),
+                    None => table[key.name.as_str()] = toml_edit::value(value),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove a lint from `[lints]`, or `[workspace.lints]` with `workspace`, returning whether it
+    /// was set
+    ///
+    /// A tool table left empty, like `[lints.clippy]`, is removed with it.
+    pub fn remove_lint(&mut self, key: &LintKey, workspace: bool) -> CargoResult<bool> {
+        let lints = if workspace {
+            self.data
+                .get_mut("workspace")
+                .and_then(|w| w.get_mut("lints"))
+        } else {
+            self.data.get_mut("lints")
+        };
+        let lints = match lints.and_then(|l| l.as_table_like_mut()) {
+            Some(lints) => lints,
+            None => return Ok(false),
+        };
+        let tool = match lints.get_mut(&key.tool).and_then(|t| t.as_table_like_mut()) {
+            Some(tool) => tool,
+            None => return Ok(false),
+        };
+        let removed = tool.remove(&key.name).is_some();
+        if tool.is_empty() {
+            lints.remove(&key.tool);
+        }
+        Ok(removed)
+    }
+
+    /// Make the package take its lints from `[workspace.lints]`, returning whether it changed
+    ///
+    /// A package with lints of its own is left alone, as cargo doesn't allow overriding inherited
+    /// lints.
+    pub fn inherit_workspace_lints(&mut self) -> CargoResult<bool> {
+        if self.inherits_workspace_lints() {
+            return Ok(false);
+        }
+        if let Some(lints) = self.data.get("lints") {
+            let own = lints
+                .as_table_like()
+                .map_or(true, |lints| !lints.is_empty());
+            if own {
+                anyhow::bail!(
+                    "`{}` has lints of its own in `[lints]`, which can't be combined with \
+                     `[workspace.lints]`",
+                    self.path.display()
+                );
+            }
+        }
+        let mut lints = toml_edit::Table::new();
+        lints.insert("workspace", toml_edit::value(true));
+        self.data["lints"] = toml_edit::Item::Table(lints);
+        Ok(true)
+    }
+
     /// Override the manifest's version
     pub fn set_package_version(&mut self, version: &Version) {
         self.data["package"]["version"] = toml_edit::value(version.to_string());
@@ -349,6 +1322,99 @@ impl LocalManifest {
         }
     }
 
+    /// Make the dependency `dep_key` optional, enabled only by `feature`
+    ///
+    /// `dep:<dep_key>` is added to the feature, or `<dep_key>/<feature>` for each of
+    /// `dep_features`, creating the feature if needed.  Fails if another feature enables the
+    /// dependency too, or if it is only a dev-dependency, which can't be optional.
+    pub fn add_dep_to_feature(
+        &mut self,
+        dep_key: &str,
+        feature: &str,
+        dep_features: &[String],
+    ) -> CargoResult<()> {
+        let usage = self.dependency_usage(dep_key);
+        let tables: Vec<_> = usage
+            .tables
+            .into_iter()
+            .filter(|table| table.last().map(String::as_str) != Some("dev-dependencies"))
+            .collect();
+        if tables.is_empty() {
+            anyhow::bail!(
+                "`{}` isn't a dependency, or only a dev-dependency, which can't be optional",
+                dep_key
+            );
+        }
+        let others: Vec<_> = usage
+            .features
+            .iter()
+            .filter(|(other, _)| other != feature)
+            .map(|(other, activation)| format!("`{}` (`{}`)", other, activation))
+            .collect();
+        if !others.is_empty() {
+            anyhow::bail!(
+                "`{}` is also enabled by {}, not only by `{}`",
+                dep_key,
+                others.join(", "),
+                feature
+            );
+        }
+
+        for table in &tables {
+            let entry = self
+                .get_table_mut(table)?
+                .as_table_like_mut()
+                .and_then(|table| table.get_mut(dep_key))
+                .expect("found by `dependency_usage`");
+            if let Some(version) = entry.as_str() {
+                let mut optional = toml_edit::InlineTable::new();
+                optional.insert("version", version.into());
+                optional.insert("optional", true.into());
+                optional.fmt();
+                overwrite_value(entry, optional);
+            } else if let Some(entry) = entry.as_table_like_mut() {
+                match entry.get_mut("optional") {
+                    Some(optional) => overwrite_value(optional, true),
+                    None => {
+                        entry.insert("optional", toml_edit::value(true));
+                    }
+                }
+            } else {
+                anyhow::bail!("Invalid dependency type for `{}`", dep_key);
+            }
+        }
+
+        let features = self
+            .data
+            .as_table_mut()
+            .entry("features")
+            .or_insert(toml_edit::table())
+            .as_table_like_mut()
+            .context("Expected `features` to be a table")?;
+        let activations = features
+            .entry(feature)
+            .or_insert(toml_edit::value(toml_edit::Array::new()))
+            .as_array_mut()
+            .with_context(|| format!("Expected feature `{}` to be an array", feature))?;
+        let new_activations = if dep_features.is_empty() {
+            vec![format!("dep:{}", dep_key)]
+        } else {
+            dep_features
+                .iter()
+                .map(|dep_feature| format!("{}/{}", dep_key, dep_feature))
+                .collect()
+        };
+        for activation in new_activations {
+            if !activations
+                .iter()
+                .any(|a| a.as_str() == Some(activation.as_str()))
+            {
+                activations.push(activation);
+            }
+        }
+        Ok(())
+    }
+
     fn dep_feature(&self, dep_key: &str) -> FeatureStatus {
         let mut status = FeatureStatus::None;
         for (_, tbl) in self.get_sections() {
@@ -408,6 +1474,433 @@ fn remove_feature_activation(
     }
 }
 
+/// Whether the feature `activation` enables `dep`, or one of its features
+fn activates_dep(activation: &str, dep: &str) -> bool {
+    let activation = activation.strip_prefix("dep:").unwrap_or(activation);
+    let activated = activation
+        .split_once('/')
+        .map(|(dep, _)| dep.trim_end_matches('?'))
+        .unwrap_or(activation);
+    activated == dep
+}
+
+fn is_dependency_table(key: &str) -> bool {
+    DepTable::KINDS.iter().any(|kind| kind.kind_table() == key)
+}
+
+/// The `*dependencies` tables under `[target]`, with their platform as written
+fn target_dependency_tables(
+    targets: &mut toml_edit::Item,
+) -> Vec<(String, &mut dyn toml_edit::TableLike)> {
+    targets
+        .as_table_like_mut()
+        .into_iter()
+        .flat_map(|targets| targets.iter_mut())
+        .flat_map(|(platform, platform_table)| {
+            let platform = platform.get().to_owned();
+            platform_table
+                .as_table_like_mut()
+                .into_iter()
+                .flat_map(|t| t.iter_mut())
+                .filter(|(kind, _)| is_dependency_table(kind.get()))
+                .filter_map(|(_, table)| table.as_table_like_mut())
+                .map(|table| (platform.clone(), table))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Compare platforms, ignoring whitespace outside of the strings in `cfg(..)` expressions
+fn same_platform(a: &str, b: &str) -> bool {
+    fn normalize(platform: &str) -> String {
+        let mut in_string = false;
+        platform
+            .chars()
+            .filter(|c| {
+                if *c == '"' {
+                    in_string = !in_string;
+                }
+                in_string || !c.is_whitespace()
+            })
+            .collect()
+    }
+    normalize(a) == normalize(b)
+}
+
+/// UTF-8 byte order mark, which some Windows editors start files with
+const BOM: char = '\u{feff}';
+
+/// Read the manifest at `path`, without its byte order mark
+///
+/// Manifests must be UTF-8, so other encodings are reported with where decoding failed.
+pub(crate) fn read_manifest_source(path: &Path) -> CargoResult<String> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("Failed to read `{}`", path.display()))?;
+    if bytes.starts_with(&[0xff, 0xfe]) || bytes.starts_with(&[0xfe, 0xff]) {
+        anyhow::bail!(
+            "`{}` is UTF-16 encoded, manifests must be saved as UTF-8",
+            path.display()
+        );
+    }
+    // TOML forbids NUL, which is in most lines of UTF-16 and UTF-32 files without a BOM
+    if bytes.contains(&0) {
+        anyhow::bail!(
+            "`{}` contains NUL bytes, it looks UTF-16 or UTF-32 encoded, manifests must be saved \
+             as UTF-8",
+            path.display()
+        );
+    }
+    let data = String::from_utf8(bytes).map_err(|err| {
+        let valid = &err.as_bytes()[..err.utf8_error().valid_up_to()];
+        let valid = str::from_utf8(valid).expect("checked up to here");
+        let (line, text) = valid
+            .split('\n')
+            .enumerate()
+            .last()
+            .expect("always at least one line");
+        anyhow::format_err!(
+            "`{}` is not valid UTF-8 at line {}, column {}, re-save it as UTF-8",
+            path.display(),
+            line + 1,
+            text.chars().count() + 1
+        )
+    })?;
+    Ok(match data.strip_prefix(BOM) {
+        Some(data) => data.to_owned(),
+        None => data,
+    })
+}
+
+/// A syntax error in the manifest at `path`, pointing at where parsing stopped
+///
+/// `source` starts at line `first_line` of the file, as the manifest of a cargo script starts
+/// after its opening fence.  The location is given as `path:line:column`, followed by the line
+/// with a caret under the column, like rustc's diagnostics.
+fn syntax_error(path: &Path, source: &str, first_line: usize, err: &toml_edit::TomlError) -> Error {
+    let rendered = err.to_string();
+    let (line, column) = match parse_error_position(&rendered) {
+        Some(position) => position,
+        None => return anyhow::format_err!("`{}` is not valid TOML: {}", path.display(), rendered),
+    };
+    // The message follows the position, after the parser's own snippet of the line
+    let message: Vec<_> = rendered
+        .lines()
+        .skip(1)
+        .filter(|l| {
+            let l = l
+                .trim_start()
+                .trim_start_matches(|c: char| c.is_ascii_digit());
+            !l.trim_start().starts_with('|')
+        })
+        .collect();
+
+    let text = source.lines().nth(line - 1).unwrap_or_default();
+    let number = (first_line + line - 1).to_string();
+    let gutter = " ".repeat(number.len());
+    let caret: String = text
+        .chars()
+        .take(column - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let mut diagnostic = format!(
+        "`{path}` is not valid TOML\n{gutter}--> {path}:{number}:{column}\n{gutter} |\n{number} | {text}\n{gutter} | {caret}^",
+        path = path.display(),
+        gutter = gutter,
+        number = number,
+        column = column,
+        text = text,
+        caret = caret
+    );
+    for line in message {
+        diagnostic.push('\n');
+        diagnostic.push_str(line);
+    }
+    if let Some(help) = toml_1_1_help(text, column) {
+        diagnostic.push_str("\nhelp: ");
+        diagnostic.push_str(help);
+    }
+    anyhow::format_err!(diagnostic)
+}
+
+/// The 1-based line and column of a `TOML parse error at line 3, column 27` message
+fn parse_error_position(rendered: &str) -> Option<(usize, usize)> {
+    let position = rendered
+        .lines()
+        .next()?
+        .strip_prefix("TOML parse error at line ")?;
+    let (line, column) = position.split_once(", column ")?;
+    let (line, column) = (line.trim().parse().ok()?, column.trim().parse().ok()?);
+    (line > 0 && column > 0).then(|| (line, column))
+}
+
+/// Explain errors coming from syntax TOML 1.1 allows but cargo's TOML 1.0 parser doesn't
+fn toml_1_1_help(line: &str, column: usize) -> Option<&'static str> {
+    let before: String = line.chars().take(column - 1).collect();
+    let after: String = line.chars().skip(column - 1).collect();
+    let open = before.matches('{').count() > before.matches('}').count();
+    if open && after.trim_start().starts_with('}') && before.trim_end().ends_with(',') {
+        Some("inline tables can't end with a trailing comma until TOML 1.1, which cargo doesn't read yet; remove the `,`")
+    } else if open && before.trim_end().len() == line.trim_end().len() {
+        Some("inline tables must fit on one line until TOML 1.1, which cargo doesn't read yet; use a `[table]` instead")
+    } else {
+        None
+    }
+}
+
+/// Whether the file at `path` starts with a byte order mark
+fn has_bom(path: &Path) -> bool {
+    let mut start = [0; 3];
+    std::fs::File::open(path)
+        .and_then(|mut file| std::io::Read::read_exact(&mut file, &mut start))
+        .map(|_| start == [0xef, 0xbb, 0xbf])
+        .unwrap_or(false)
+}
+
+/// How a manifest is written, see [`LocalManifest::write_with`] and [`write_manifest_file`]
+#[derive(Clone, Debug)]
+pub struct WriteOptions {
+    allow_vendored: bool,
+    follow_symlinks: bool,
+    backup_dir: Option<PathBuf>,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            allow_vendored: true,
+            follow_symlinks: true,
+            backup_dir: None,
+        }
+    }
+}
+
+impl WriteOptions {
+    /// Write manifests of vendored crates and through symlinks, without backups
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether writing a symlinked manifest writes to its target, the default, or fails
+    pub fn set_follow_symlinks(mut self, follow: bool) -> Self {
+        self.follow_symlinks = follow;
+        self
+    }
+
+    /// Copy manifests to `dir` before they are written, or not with `None`, the default
+    ///
+    /// [`backup_dir`](crate::backup_dir) gives the directory set in the environment.
+    pub fn set_backup_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.backup_dir = dir;
+        self
+    }
+
+    /// Whether manifests of vendored crates can be written, the default, or are refused
+    ///
+    /// Editing a crate `cargo vendor` checksummed breaks the checksums cargo verifies.
+    pub fn set_allow_vendored(mut self, allow: bool) -> Self {
+        self.allow_vendored = allow;
+        self
+    }
+}
+
+/// Checksums `cargo vendor` writes next to a vendored crate's manifest, which cargo verifies
+const VENDOR_CHECKSUMS: &str = ".cargo-checksum.json";
+
+/// Whether the crate in `dir` was vendored, with checksums cargo verifies
+fn is_vendored(dir: &Path) -> bool {
+    dir.join(VENDOR_CHECKSUMS).is_file()
+}
+
+/// Replace the contents of the manifest at `path`
+///
+/// Manifests of vendored crates are refused if `options` says so, as changing them breaks the
+/// checksums cargo verifies.
+///
+/// A symlink is written through, leaving the link in place, unless `options` turned that off.  The new contents go to a temporary file next to the manifest that is then
+/// renamed over it, so an interrupted write never leaves a truncated manifest.  The manifest's
+/// permissions are kept; if it has another owner than the files we create, it is rewritten in
+/// place instead, as replacing it would change its owner.
+///
+/// The old contents are first copied to the backup directory, if `options` name one.
+pub fn write_manifest_file(
+    path: &Path,
+    contents: &[u8],
+    options: &WriteOptions,
+) -> CargoResult<()> {
+    write_file(path, contents, options, true)
+}
+
+/// Replace the contents of the file at `path` like [`write_manifest_file`], without a backup
+pub(crate) fn replace_file(
+    path: &Path,
+    contents: &[u8],
+    options: &WriteOptions,
+) -> CargoResult<()> {
+    write_file(path, contents, options, false)
+}
+
+fn write_file(
+    path: &Path,
+    contents: &[u8],
+    options: &WriteOptions,
+    backup: bool,
+) -> CargoResult<()> {
+    let link = fs::symlink_metadata(path)
+        .with_context(|| format!("Failed to read `{}`", path.display()))?;
+    let target = if link.file_type().is_symlink() {
+        if !options.follow_symlinks {
+            anyhow::bail!(
+                "`{}` is a symlink, not writing through it with `--no-follow-symlinks`",
+                path.display()
+            );
+        }
+        dunce::canonicalize(path)
+            .with_context(|| format!("`{}` is a broken symlink", path.display()))?
+    } else {
+        path.to_owned()
+    };
+    let metadata =
+        fs::metadata(&target).with_context(|| format!("Failed to read `{}`", target.display()))?;
+    if !metadata.is_file() {
+        anyhow::bail!("`{}` is not a regular file", target.display());
+    }
+    let dir = target.parent().expect("files have a parent");
+    if !options.allow_vendored && is_vendored(dir) {
+        let original = if dir.join("Cargo.toml.orig").is_file() {
+            ", the manifest as its authors wrote it is `Cargo.toml.orig`"
+        } else {
+            ""
+        };
+        anyhow::bail!(
+            "`{}` belongs to a vendored crate (found `{}`), editing it would break its \
+             checksums{}; edit the original crate instead, or pass `--force-vendored`",
+            target.display(),
+            VENDOR_CHECKSUMS,
+            original
+        );
+    }
+    if let (true, Some(backup_dir)) = (backup, &options.backup_dir) {
+        crate::backup::backup_manifest(backup_dir, &target)?;
+    }
+
+    let file_name = target.file_name().expect("files have a name");
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp = dir.join(temp_name);
+
+    let result = fs::write(&temp, contents)
+        .and_then(|()| fs::set_permissions(&temp, metadata.permissions()))
+        .and_then(|()| {
+            if same_owner(&metadata, &fs::metadata(&temp)?) {
+                fs::rename(&temp, &target)
+            } else {
+                fs::remove_file(&temp)?;
+                fs::write(&target, contents)
+            }
+        });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result.with_context(|| format!("Failed to write `{}`", target.display()))
+}
+
+#[cfg(unix)]
+fn same_owner(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    (a.uid(), a.gid()) == (b.uid(), b.gid())
+}
+
+#[cfg(not(unix))]
+fn same_owner(_a: &fs::Metadata, _b: &fs::Metadata) -> bool {
+    true
+}
+
+/// Whether `path` is a cargo script, with its manifest embedded in frontmatter
+fn is_script(path: &Path) -> bool {
+    path.extension().map(|ext| ext == "rs").unwrap_or(false)
+}
+
+/// Find the manifest embedded in a cargo script
+///
+/// The manifest is fenced by lines of three or more dashes at the top of the file, after any
+/// `#!` line, with the opening fence optionally tagged `cargo`:
+///
+/// ```text
+/// #!/usr/bin/env cargo
+/// ---cargo
+/// [dependencies]
+/// clap = "4"
+/// ---
+///
+/// fn main() {}
+/// ```
+///
+/// Returns the byte range of the manifest, without the fences.
+fn find_frontmatter(source: &str) -> CargoResult<Option<std::ops::Range<usize>>> {
+    let mut lines =
+        source_lines(source, skip_shebang(source)).skip_while(|(_, line)| line.trim().is_empty());
+    let (start, fence) = match lines.next() {
+        Some((offset, line)) if line.starts_with("---") => (offset + line.len(), line.trim_end()),
+        _ => return Ok(None),
+    };
+    let dashes = fence.len() - fence.trim_start_matches('-').len();
+    let info = fence[dashes..].trim();
+    if !info.is_empty() && info != "cargo" {
+        anyhow::bail!("Unsupported frontmatter `{}`, expected `cargo`", info);
+    }
+    let fence = &fence[..dashes];
+
+    for (offset, line) in lines {
+        if line.trim_end() == fence {
+            return Ok(Some(start..offset));
+        }
+    }
+    anyhow::bail!("Unterminated frontmatter, expected a closing `{}`", fence)
+}
+
+/// Replace the manifest embedded in a cargo script, adding frontmatter if needed
+fn replace_frontmatter(source: &str, manifest: &str) -> CargoResult<String> {
+    let mut manifest = manifest.to_owned();
+    if !manifest.is_empty() && !manifest.ends_with('\n') {
+        manifest.push('\n');
+    }
+    match find_frontmatter(source)? {
+        Some(range) => Ok(format!(
+            "{}{}{}",
+            &source[..range.start],
+            manifest,
+            &source[range.end..]
+        )),
+        None if manifest.is_empty() => Ok(source.to_owned()),
+        None => {
+            let (shebang, code) = source.split_at(skip_shebang(source));
+            Ok(format!("{}---cargo\n{}---\n{}", shebang, manifest, code))
+        }
+    }
+}
+
+/// Byte offset of the first line after a `#!` line, if any
+fn skip_shebang(source: &str) -> usize {
+    // `#![...]` is an inner attribute, not a shebang
+    if source.starts_with("#!") && !source.starts_with("#![") {
+        source.find('\n').map(|i| i + 1).unwrap_or(source.len())
+    } else {
+        0
+    }
+}
+
+/// Lines, including their terminator, with their byte offset in `source`
+fn source_lines(source: &str, start: usize) -> impl Iterator<Item = (usize, &str)> {
+    source[start..]
+        .split_inclusive('\n')
+        .scan(start, |offset, line| {
+            let line_start = *offset;
+            *offset += line.len();
+            Some((line_start, line))
+        })
+}
+
 /// If a manifest is specified, return that one, otherise perform a manifest search starting from
 /// the current directory.
 /// If a manifest is specified, return that one. If a path is specified, perform a manifest search
@@ -460,8 +1953,113 @@ pub fn set_dep_version(dep_item: &mut toml_edit::Item, new_version: &str) -> Car
     Ok(())
 }
 
+/// Make a dependency entry enable or disable default features, returning whether it changed
+///
+/// Entries only enabling them implicitly are left alone, while a bare version requirement becomes
+/// an inline table to disable them.
+pub fn set_dep_default_features(dep_item: &mut toml_edit::Item, enabled: bool) -> bool {
+    if let Some(version) = dep_item.as_str().filter(|_| !enabled) {
+        let mut table = toml_edit::InlineTable::new();
+        table.insert("version", version.into());
+        table.insert("default-features", false.into());
+        table.fmt();
+        overwrite_value(dep_item, table);
+        return true;
+    }
+    let table = match dep_item.as_table_like_mut() {
+        Some(table) => table,
+        None => return false,
+    };
+    let current = ["default-features", "default_features"]
+        .iter()
+        .find_map(|key| table.get(key).and_then(|v| v.as_bool()));
+    if current.unwrap_or(true) == enabled {
+        return false;
+    }
+    table.remove("default_features");
+    if enabled {
+        table.remove("default-features");
+    } else {
+        table.insert("default-features", toml_edit::value(false));
+    }
+    true
+}
+
+/// Make a dependency entry come from `registry`, or the default registry for `None`
+///
+/// A bare version requirement becomes an inline table to name the registry, and moving to the
+/// default registry drops `registry` and `registry-index`.  Returns whether the entry changed.
+pub fn set_dep_registry(dep_item: &mut toml_edit::Item, registry: Option<&str>) -> bool {
+    if let Some(version) = dep_item.as_str() {
+        let registry = match registry {
+            Some(registry) => registry,
+            None => return false,
+        };
+        let mut table = toml_edit::InlineTable::new();
+        table.insert("version", version.into());
+        table.insert("registry", registry.into());
+        table.fmt();
+        overwrite_value(dep_item, table);
+        return true;
+    }
+    let table = match dep_item.as_table_like_mut() {
+        Some(table) => table,
+        None => return false,
+    };
+    match registry {
+        Some(registry) => {
+            if table.get("registry").and_then(|r| r.as_str()) == Some(registry) {
+                return false;
+            }
+            table.remove("registry-index");
+            table.insert("registry", toml_edit::value(registry));
+            true
+        }
+        None => {
+            let removed = table.remove("registry").is_some();
+            table.remove("registry-index").is_some() || removed
+        }
+    }
+}
+
 /// Overwrite a value while preserving the original formatting
-fn overwrite_value(item: &mut toml_edit::Item, value: impl Into<toml_edit::Value>) {
+/// Index of the target named `name` in a `[[<kind>]]` array
+fn find_target(targets: &toml_edit::ArrayOfTables, name: &str) -> Option<usize> {
+    targets
+        .iter()
+        .position(|t| t.get("name").and_then(|n| n.as_str()) == Some(name))
+}
+
+fn not_declared(kind: TargetKind, name: &str) -> Error {
+    anyhow::format_err!(
+        "`[[{}]]` `{}` isn't declared, and targets cargo infers from their source can't be edited",
+        kind.kind_table(),
+        name
+    )
+}
+
+/// Check `name` is one cargo accepts for a build target
+fn validate_target_name(name: &str) -> CargoResult<()> {
+    if name.is_empty() {
+        anyhow::bail!("Target names can't be empty");
+    }
+    let mut invalid: Vec<_> = name
+        .chars()
+        .filter(|c| !(c.is_alphanumeric() || ['-', '_'].contains(c)))
+        .collect();
+    invalid.dedup();
+    if !invalid.is_empty() {
+        let invalid: Vec<_> = invalid.iter().map(|c| format!("`{}`", c)).collect();
+        anyhow::bail!(
+            "Invalid target name `{}`: {} can't be used",
+            name,
+            invalid.join(", ")
+        );
+    }
+    Ok(())
+}
+
+pub(crate) fn overwrite_value(item: &mut toml_edit::Item, value: impl Into<toml_edit::Value>) {
     let mut value = value.into();
 
     let existing_decor = item
@@ -474,6 +2072,745 @@ fn overwrite_value(item: &mut toml_edit::Item, value: impl Into<toml_edit::Value
     *item = toml_edit::Item::Value(value);
 }
 
+/// The first template placeholder in `item`, like `{{version}}`, `{% if x %}` or `${VERSION}`
+///
+/// Manifests generated from templates may still contain them, and editing the entries around
+/// them would corrupt the template.
+pub fn find_template_placeholder(item: &toml_edit::Item) -> Option<String> {
+    match item {
+        toml_edit::Item::Value(value) => value_placeholder(value),
+        toml_edit::Item::Table(table) => table_placeholder(table),
+        toml_edit::Item::ArrayOfTables(tables) => tables.iter().find_map(table_placeholder),
+        toml_edit::Item::None => None,
+    }
+}
+
+fn table_placeholder(table: &toml_edit::Table) -> Option<String> {
+    table
+        .iter()
+        .find_map(|(key, item)| str_placeholder(key).or_else(|| find_template_placeholder(item)))
+}
+
+fn value_placeholder(value: &toml_edit::Value) -> Option<String> {
+    match value {
+        toml_edit::Value::String(s) => str_placeholder(s.value()),
+        toml_edit::Value::Array(array) => array.iter().find_map(value_placeholder),
+        toml_edit::Value::InlineTable(table) => table
+            .iter()
+            .find_map(|(key, value)| str_placeholder(key).or_else(|| value_placeholder(value))),
+        _ => None,
+    }
+}
+
+fn str_placeholder(s: &str) -> Option<String> {
+    [("{{", "}}"), ("{%", "%}"), ("${", "}")]
+        .iter()
+        .filter_map(|(open, close)| {
+            let start = s.find(open)?;
+            let end = s[start + open.len()..].find(close)? + start + open.len() + close.len();
+            Some((start, &s[start..end]))
+        })
+        .min()
+        .map(|(_, placeholder)| placeholder.to_owned())
+}
+
 pub fn str_or_1_len_table(item: &toml_edit::Item) -> bool {
     item.is_str() || item.as_table_like().map(|t| t.len() == 1).unwrap_or(false)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local_manifest(data: &str) -> LocalManifest {
+        LocalManifest {
+            path: PathBuf::from("Cargo.toml"),
+            manifest: data.parse().unwrap(),
+        }
+    }
+
+    fn required_features(manifest: &LocalManifest, kind: &str, index: usize) -> Vec<&str> {
+        manifest.data[kind][index]["required-features"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| f.as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn find_script_frontmatter() {
+        let source =
+            "#!/usr/bin/env cargo\n---cargo\n[dependencies]\nclap = \"4\"\n---\n\nfn main() {}\n";
+        let range = find_frontmatter(source).unwrap().unwrap();
+        assert_eq!(&source[range], "[dependencies]\nclap = \"4\"\n");
+    }
+
+    #[test]
+    fn find_script_frontmatter_longer_fence() {
+        let source = "----\n[package]\nedition = \"2021\"\n----\nfn main() {}\n";
+        let range = find_frontmatter(source).unwrap().unwrap();
+        assert_eq!(&source[range], "[package]\nedition = \"2021\"\n");
+    }
+
+    #[test]
+    fn script_without_frontmatter() {
+        assert_eq!(
+            find_frontmatter("#![allow(unused)]\nfn main() {}\n").unwrap(),
+            None
+        );
+        assert!(find_frontmatter("---cargo\n[dependencies]\n").is_err());
+    }
+
+    #[test]
+    fn replace_script_frontmatter_keeps_code() {
+        let source = "#!/usr/bin/env cargo\n---\n[dependencies]\n---\nfn main() {}\n";
+        assert_eq!(
+            replace_frontmatter(source, "[dependencies]\nclap = \"4\"\n").unwrap(),
+            "#!/usr/bin/env cargo\n---\n[dependencies]\nclap = \"4\"\n---\nfn main() {}\n"
+        );
+    }
+
+    #[test]
+    fn replace_script_frontmatter_adds_fences() {
+        let source = "#!/usr/bin/env cargo\nfn main() {}\n";
+        assert_eq!(
+            replace_frontmatter(source, "[dependencies]\nclap = \"4\"").unwrap(),
+            "#!/usr/bin/env cargo\n---cargo\n[dependencies]\nclap = \"4\"\n---\nfn main() {}\n"
+        );
+        assert_eq!(replace_frontmatter(source, "").unwrap(), source);
+    }
+
+    #[test]
+    fn add_required_feature_creates_target() {
+        let mut manifest = local_manifest(
+            r#"[package]
+name = "foo"
+"#,
+        );
+        manifest
+            .add_required_feature(TargetKind::Example, "demo", "serde")
+            .unwrap();
+        assert_eq!(manifest.data["example"][0]["name"].as_str(), Some("demo"));
+        assert_eq!(required_features(&manifest, "example", 0), ["serde"]);
+    }
+
+    #[test]
+    fn add_required_feature_appends_once() {
+        let mut manifest = local_manifest(
+            r#"[[bench]]
+name = "parse"
+harness = false
+
+[[bench]]
+name = "render"
+required-features = ["html"]
+"#,
+        );
+        manifest
+            .add_required_feature(TargetKind::Bench, "render", "serde")
+            .unwrap();
+        manifest
+            .add_required_feature(TargetKind::Bench, "render", "serde")
+            .unwrap();
+        assert!(manifest.data["bench"][0].get("required-features").is_none());
+        assert_eq!(required_features(&manifest, "bench", 1), ["html", "serde"]);
+    }
+
+    #[test]
+    fn edit_targets() {
+        let mut manifest = local_manifest(
+            r#"[package]
+name = "foo"
+default-run = "cli"
+
+[[bin]]
+name = "cli"
+path = "src/main.rs"
+"#,
+        );
+        manifest
+            .add_target(TargetKind::Bin, "server", None, &["http".to_owned()])
+            .unwrap();
+        assert!(manifest
+            .add_target(TargetKind::Bin, "server", None, &[])
+            .is_err());
+        assert!(manifest
+            .add_target(TargetKind::Bin, "a/b", None, &[])
+            .is_err());
+        assert_eq!(required_features(&manifest, "bin", 1), ["http"]);
+
+        let pinned = manifest
+            .rename_target(TargetKind::Bin, "cli", "foo-cli")
+            .unwrap();
+        assert_eq!(pinned, None);
+        assert_eq!(manifest.default_run(), Some("foo-cli"));
+        assert!(manifest
+            .rename_target(TargetKind::Bin, "server", "foo-cli")
+            .is_err());
+        assert!(manifest
+            .rename_target(TargetKind::Example, "cli", "demo")
+            .is_err());
+        assert!(manifest.data.get("example").is_none());
+
+        manifest
+            .set_required_features(TargetKind::Bin, "server", &[])
+            .unwrap();
+        assert!(manifest.data["bin"][1].get("required-features").is_none());
+
+        assert!(manifest.remove_target(TargetKind::Bin, "foo-cli").unwrap());
+        assert!(!manifest.remove_target(TargetKind::Bin, "server").unwrap());
+        assert_eq!(
+            manifest.to_string(),
+            r#"[package]
+name = "foo"
+"#
+        );
+    }
+
+    #[test]
+    fn edit_lints() {
+        let key = |key: &str| key.parse::<LintKey>().unwrap();
+        let mut manifest = local_manifest(
+            r#"[package]
+name = "foo"
+"#,
+        );
+        manifest
+            .set_lint(&key("clippy.unwrap_used"), LintLevel::Warn, None, false)
+            .unwrap();
+        assert_eq!(
+            manifest.to_string(),
+            r#"[package]
+name = "foo"
+
+[lints.clippy]
+unwrap_used = "warn"
+"#
+        );
+        manifest
+            .set_lint(&key("clippy.unwrap_used"), LintLevel::Deny, Some(-1), false)
+            .unwrap();
+        manifest
+            .set_lint(&key("clippy.unwrap_used"), LintLevel::Forbid, None, false)
+            .unwrap();
+        let entry = &manifest.data["lints"]["clippy"]["unwrap_used"];
+        assert_eq!(entry["level"].as_str(), Some("forbid"));
+        assert_eq!(entry["priority"].as_integer(), Some(-1));
+        assert!(manifest
+            .set_lint(&key("rust.unsafe_code"), LintLevel::Deny, None, true)
+            .is_err());
+        assert!(manifest.inherit_workspace_lints().is_err());
+
+        assert!(manifest
+            .remove_lint(&key("clippy.unwrap_used"), false)
+            .unwrap());
+        assert!(!manifest
+            .remove_lint(&key("clippy.unwrap_used"), false)
+            .unwrap());
+        assert!(manifest.data["lints"].get("clippy").is_none());
+        assert!(manifest.inherit_workspace_lints().unwrap());
+        assert!(manifest.inherits_workspace_lints());
+        assert!(manifest
+            .set_lint(&key("rust.unsafe_code"), LintLevel::Deny, None, false)
+            .is_err());
+
+        let mut root = local_manifest(
+            r#"[workspace]
+members = ["crates/*"]
+"#,
+        );
+        root.set_lint(&key("rust.unsafe_code"), LintLevel::Forbid, None, true)
+            .unwrap();
+        assert_eq!(
+            root.data["workspace"]["lints"]["rust"]["unsafe_code"].as_str(),
+            Some("forbid")
+        );
+    }
+
+    #[test]
+    fn gate_dep_behind_feature() {
+        let mut manifest = local_manifest(
+            r#"[package]
+name = "foo"
+
+[dependencies]
+reqwest = "0.11"
+serde = { version = "1.0", features = ["derive"] }
+
+[dev-dependencies]
+insta = "1.0"
+
+[features]
+default = ["serde/std"]
+"#,
+        );
+        manifest.add_dep_to_feature("reqwest", "net", &[]).unwrap();
+        manifest
+            .add_dep_to_feature("reqwest", "net", &["json".to_owned()])
+            .unwrap();
+        assert!(manifest.add_dep_to_feature("serde", "serde", &[]).is_err());
+        assert!(manifest
+            .add_dep_to_feature("insta", "snapshots", &[])
+            .is_err());
+        assert_eq!(
+            manifest.to_string(),
+            r#"[package]
+name = "foo"
+
+[dependencies]
+reqwest = { version = "0.11", optional = true }
+serde = { version = "1.0", features = ["derive"] }
+
+[dev-dependencies]
+insta = "1.0"
+
+[features]
+default = ["serde/std"]
+net = ["dep:reqwest", "reqwest/json"]
+"#
+        );
+    }
+
+    #[test]
+    fn set_tool_version_creates_tables() {
+        let mut manifest = local_manifest(
+            r#"[workspace]
+members = ["crates/*"]
+"#,
+        );
+        let tools = ["workspace", "metadata", "tools"].map(String::from);
+        manifest
+            .set_tool_version(&tools, "cargo-nextest", "0.9.40")
+            .unwrap();
+        assert_eq!(
+            manifest.to_string(),
+            r#"[workspace]
+members = ["crates/*"]
+
+[workspace.metadata.tools]
+cargo-nextest = "0.9.40"
+"#
+        );
+    }
+
+    #[test]
+    fn set_tool_version_keeps_entry_form() {
+        let mut manifest = local_manifest(
+            r#"[workspace.metadata.tools]
+cargo-nextest = { version = "0.9.30", features = ["default-no-update"] }
+cargo-deny = "0.12" # policy checks
+"#,
+        );
+        let tools = ["workspace", "metadata", "tools"].map(String::from);
+        manifest
+            .set_tool_version(&tools, "cargo-nextest", "0.9.40")
+            .unwrap();
+        manifest
+            .set_tool_version(&tools, "cargo-deny", "0.13.1")
+            .unwrap();
+        assert_eq!(
+            manifest.to_string(),
+            r#"[workspace.metadata.tools]
+cargo-nextest = { version = "0.9.40", features = ["default-no-update"] }
+cargo-deny = "0.13.1" # policy checks
+"#
+        );
+    }
+
+    #[test]
+    fn dependency_usage_across_sections() {
+        let manifest: Manifest = r#"[dependencies]
+serde = { version = "1.0", optional = true }
+
+[target.'cfg(unix)'.dev-dependencies]
+serde = "1.0"
+
+[features]
+derive = ["serde?/derive"]
+json = ["dep:serde", "dep:serde_json"]
+serde_json = ["dep:serde_json"]
+
+[[bin]]
+name = "cli"
+required-features = ["json"]
+
+[[example]]
+name = "plain"
+"#
+        .parse()
+        .unwrap();
+        let usage = manifest.dependency_usage("serde");
+        assert_eq!(
+            usage.tables,
+            [
+                vec!["dependencies".to_owned()],
+                vec![
+                    "target".to_owned(),
+                    "cfg(unix)".to_owned(),
+                    "dev-dependencies".to_owned()
+                ],
+            ]
+        );
+        assert_eq!(
+            usage.features,
+            [
+                ("derive".to_owned(), "serde?/derive".to_owned()),
+                ("json".to_owned(), "dep:serde".to_owned()),
+            ]
+        );
+        assert_eq!(usage.targets, [(TargetKind::Bin, "cli".to_owned())]);
+    }
+
+    #[test]
+    fn bom_is_preserved() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let path = temp.path().join("Cargo.toml");
+        fs::write(&path, "\u{feff}[package]\nname = \"bom\"\n").unwrap();
+
+        let mut manifest = LocalManifest::try_new(&path).unwrap();
+        assert_eq!(manifest.data["package"]["name"].as_str(), Some("bom"));
+        manifest.set_package_version(&Version::new(0, 2, 0));
+        manifest.write().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "\u{feff}[package]\nname = \"bom\"\nversion = \"0.2.0\"\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_written_through() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let target = temp.path().join("shared.toml");
+        fs::write(&target, "[package]\nname = \"linked\"\n").unwrap();
+        fs::set_permissions(&target, std::os::unix::fs::PermissionsExt::from_mode(0o640)).unwrap();
+        let path = temp.path().join("Cargo.toml");
+        std::os::unix::fs::symlink(&target, &path).unwrap();
+
+        write_manifest_file(
+            &path,
+            b"[package]\nname = \"written\"\n",
+            &WriteOptions::new(),
+        )
+        .unwrap();
+        assert!(fs::symlink_metadata(&path)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(
+            fs::read_to_string(&target).unwrap(),
+            "[package]\nname = \"written\"\n"
+        );
+        let mode =
+            std::os::unix::fs::PermissionsExt::mode(&fs::metadata(&target).unwrap().permissions());
+        assert_eq!(mode & 0o777, 0o640);
+        assert_eq!(fs::read_dir(temp.path()).unwrap().count(), 2);
+
+        let err = write_manifest_file(&path, b"", &WriteOptions::new().set_follow_symlinks(false))
+            .unwrap_err()
+            .to_string();
+        assert!(err.ends_with("is a symlink, not writing through it with `--no-follow-symlinks`"));
+        assert!(write_manifest_file(temp.path(), b"", &WriteOptions::new()).is_err());
+    }
+
+    #[test]
+    fn vendored_manifests_are_refused() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let path = temp.path().join("Cargo.toml");
+        fs::write(&path, "[package]\nname = \"vendored\"\n").unwrap();
+        let refuse = WriteOptions::new().set_allow_vendored(false);
+        fs::write(temp.path().join("Cargo.toml.orig"), "").unwrap();
+        fs::write(temp.path().join(".cargo_vcs_info.json"), "{}").unwrap();
+        assert!(!is_vendored(temp.path()));
+        write_manifest_file(&path, b"[package]\nname = \"edited\"\n", &refuse).unwrap();
+
+        fs::write(temp.path().join(".cargo-checksum.json"), "{}").unwrap();
+        assert!(is_vendored(temp.path()));
+        let err = write_manifest_file(&path, b"", &refuse)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("(found `.cargo-checksum.json`)"), "{}", err);
+        assert!(err.contains("`Cargo.toml.orig`"), "{}", err);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "[package]\nname = \"edited\"\n"
+        );
+        write_manifest_file(&path, b"", &WriteOptions::new()).unwrap();
+    }
+
+    #[test]
+    fn non_utf8_is_located() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let path = temp.path().join("Cargo.toml");
+        fs::write(&path, b"[package]\nname = \"caf\xe9\"\n").unwrap();
+
+        let err = LocalManifest::try_new(&path).unwrap_err().to_string();
+        assert!(
+            err.ends_with("is not valid UTF-8 at line 2, column 12, re-save it as UTF-8"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn syntax_errors_are_located() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let path = temp.path().join("Cargo.toml");
+        fs::write(
+            &path,
+            "[package]\nname = \"app\"\n\n[dependencies]\nserde = { version = \"1.0\", }\n",
+        )
+        .unwrap();
+
+        let err = LocalManifest::try_new(&path).unwrap_err().to_string();
+        let location = format!(" --> {}:5:", path.display());
+        assert!(err.contains(&location), "{}", err);
+        assert!(
+            err.contains("\n5 | serde = { version = \"1.0\", }\n  |"),
+            "{}",
+            err
+        );
+        assert!(err.contains("trailing comma until TOML 1.1"), "{}", err);
+    }
+
+    #[test]
+    fn script_syntax_errors_count_lines_of_the_file() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let path = temp.path().join("script.rs");
+        fs::write(
+            &path,
+            "#!/usr/bin/env cargo\n---\n[dependencies]\nclap = \n---\n\nfn main() {}\n",
+        )
+        .unwrap();
+
+        let err = LocalManifest::try_new(&path).unwrap_err().to_string();
+        let location = format!(" --> {}:4:", path.display());
+        assert!(err.contains(&location), "{}", err);
+    }
+
+    #[test]
+    fn utf16_without_bom_is_reported() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let path = temp.path().join("Cargo.toml");
+        let utf16: Vec<u8> = "[package]\n"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        fs::write(&path, utf16).unwrap();
+
+        let err = LocalManifest::try_new(&path).unwrap_err().to_string();
+        assert!(err.contains("looks UTF-16 or UTF-32 encoded"), "{}", err);
+    }
+
+    #[test]
+    fn record_checksums() {
+        let mut manifest: Manifest = "[package]\nname = \"app\"\n".parse().unwrap();
+        manifest
+            .record_checksum("serde", "1.0.147", "d193")
+            .unwrap();
+        manifest.record_checksum("regex", "1.6.0", "4c4e").unwrap();
+        manifest
+            .record_checksum("serde", "1.0.148", "cdda")
+            .unwrap();
+        assert_eq!(
+            manifest.data.to_string(),
+            r#"[package]
+name = "app"
+
+[package.metadata.cargo-edit.checksums]
+serde = { version = "1.0.148", checksum = "cdda" }
+regex = { version = "1.6.0", checksum = "4c4e" }
+"#
+        );
+        assert_eq!(
+            manifest.recorded_checksums().unwrap()[1],
+            RecordedChecksum {
+                name: "regex".to_owned(),
+                version: "1.6.0".to_owned(),
+                checksum: "4c4e".to_owned(),
+            }
+        );
+
+        let invalid: Manifest = "[package.metadata.cargo-edit.checksums]\nserde = \"1.0\"\n"
+            .parse()
+            .unwrap();
+        assert!(invalid.recorded_checksums().is_err());
+    }
+
+    #[test]
+    fn upgrade_policy_from_metadata() {
+        let manifest: Manifest = r#"[package]
+name = "policy"
+
+[package.metadata.cargo-edit]
+upgrade-ignore = ["legacy-dep"]
+pin = { openssl = "=0.10.38" }
+"#
+        .parse()
+        .unwrap();
+        let policy = manifest.upgrade_policy().unwrap();
+        assert!(policy.is_ignored("legacy-dep"));
+        assert!(!policy.is_ignored("openssl"));
+        assert_eq!(
+            policy.pin.get("openssl").map(String::as_str),
+            Some("=0.10.38")
+        );
+
+        let manifest: Manifest = "[package.metadata.cargo-edit]\npin = { openssl = \"latest\" }\n"
+            .parse()
+            .unwrap();
+        assert!(manifest.upgrade_policy().is_err());
+    }
+
+    #[test]
+    fn default_features_policy() {
+        let manifest: Manifest = r#"[package.metadata.cargo-edit.default-features]
+dependencies = false
+dev-dependencies = true
+"#
+        .parse()
+        .unwrap();
+        let policy = manifest.upgrade_policy().unwrap();
+        assert_eq!(policy.default_features("dependencies"), Some(false));
+        assert_eq!(policy.default_features("dev-dependencies"), Some(true));
+        assert_eq!(policy.default_features("build-dependencies"), None);
+
+        let manifest: Manifest = "[package.metadata.cargo-edit.default-features]
+deps = false
+"
+        .parse()
+        .unwrap();
+        assert!(manifest.upgrade_policy().is_err());
+    }
+
+    #[test]
+    fn set_default_features() {
+        let mut manifest: Manifest = r#"[dependencies]
+bare = "1.0"
+off = { version = "1.0", default_features = false }
+on = { version = "1.0" }
+"#
+        .parse()
+        .unwrap();
+        let deps = manifest.data["dependencies"].as_table_mut().unwrap();
+        assert!(set_dep_default_features(&mut deps["bare"], false));
+        assert!(!set_dep_default_features(&mut deps["on"], true));
+        assert!(set_dep_default_features(&mut deps["off"], true));
+        assert_eq!(deps["bare"].get("version").unwrap().as_str(), Some("1.0"));
+        assert_eq!(
+            deps["bare"].get("default-features").unwrap().as_bool(),
+            Some(false)
+        );
+        assert!(deps["off"].get("default_features").is_none());
+        assert!(deps["off"].get("default-features").is_none());
+    }
+
+    #[test]
+    fn set_registry() {
+        let mut manifest: Manifest = r#"[dependencies]
+bare = "1.0"
+internal = { version = "1.0", registry = "internal" }
+"#
+        .parse()
+        .unwrap();
+        let deps = manifest.data["dependencies"].as_table_mut().unwrap();
+        assert!(!set_dep_registry(&mut deps["bare"], None));
+        assert!(set_dep_registry(&mut deps["bare"], Some("internal")));
+        assert!(!set_dep_registry(&mut deps["bare"], Some("internal")));
+        assert!(set_dep_registry(&mut deps["internal"], None));
+        assert_eq!(
+            deps["bare"].to_string().trim(),
+            r#"{ version = "1.0", registry = "internal" }"#
+        );
+        assert!(deps["internal"].get("registry").is_none());
+    }
+
+    const COMPLEX_TARGETS: &str = r#"[package]
+name = "targets"
+
+[target.'cfg(all(unix, any(target_arch = "x86_64", target_os = "macos")))'.dependencies]
+libc = "0.2"
+
+[target."cfg(not(target_os = \"windows\"))".dev-dependencies]
+tempfile = "3"
+
+[target.'cfg(target_os = "linux")']
+build-dependencies = { cc = "1.0" }
+rustflags = ["-Cforce-frame-pointers"]
+"#;
+
+    #[test]
+    fn target_tables_keep_cfg_expressions() {
+        let mut manifest = local_manifest(COMPLEX_TARGETS);
+        let platforms = manifest
+            .get_target_tables_mut()
+            .map(|(platform, _)| platform)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            platforms,
+            [
+                r#"cfg(all(unix, any(target_arch = "x86_64", target_os = "macos")))"#,
+                r#"cfg(not(target_os = "windows"))"#,
+                r#"cfg(target_os = "linux")"#,
+            ]
+        );
+
+        for table in manifest.get_dependency_tables_mut() {
+            for (_, dep) in table.iter_mut() {
+                set_dep_version(dep, "9").unwrap();
+            }
+        }
+        assert_eq!(
+            manifest.to_string(),
+            COMPLEX_TARGETS
+                .replace("\"0.2\"", "\"9\"")
+                .replace("\"3\"", "\"9\"")
+                .replace("\"1.0\"", "\"9\"")
+        );
+    }
+
+    #[test]
+    fn remove_from_target_ignores_cfg_whitespace() {
+        let mut manifest = local_manifest(COMPLEX_TARGETS);
+        let table_path = [
+            "target".to_owned(),
+            r#"cfg(all(unix,any(target_arch="x86_64",target_os="macos")))"#.to_owned(),
+            "dependencies".to_owned(),
+        ];
+        manifest.remove_from_table(&table_path, "libc").unwrap();
+        assert!(!manifest.to_string().contains("libc"));
+        assert!(manifest.to_string().contains("tempfile"));
+
+        let table_path = [
+            "target".to_owned(),
+            r#"cfg(target_os = "linu x")"#.to_owned(),
+            "build-dependencies".to_owned(),
+        ];
+        assert!(manifest.remove_from_table(&table_path, "cc").is_err());
+    }
+
+    #[test]
+    fn templated_dependencies() {
+        let manifest = local_manifest(
+            r#"[dependencies]
+serde = "1.0"
+tool = { version = "{{ version }}", features = ["std"] }
+sys = { path = "${SYS_PATH}/sys" }
+
+[dev-dependencies.helper]
+version = "0.1"
+features = ["{% if ci %}ci{% endif %}"]
+"#,
+        );
+        assert_eq!(
+            manifest.templated_dependencies(),
+            [
+                ("tool".to_owned(), "{{ version }}".to_owned()),
+                ("sys".to_owned(), "${SYS_PATH}".to_owned()),
+                ("helper".to_owned(), "{% if ci %}".to_owned()),
+            ]
+        );
+    }
+}
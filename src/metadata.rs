@@ -1,16 +1,12 @@
 use super::errors::*;
+use super::manifest::read_manifest_source;
 use cargo_metadata::Package;
 use std::convert::TryInto;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Takes a pkgid and attempts to find the path to it's `Cargo.toml`, using `cargo`'s metadata
 pub fn manifest_from_pkgid(manifest_path: Option<&Path>, pkgid: &str) -> CargoResult<Package> {
-    let mut cmd = cargo_metadata::MetadataCommand::new();
-    cmd.no_deps();
-    if let Some(manifest_path) = manifest_path {
-        cmd.manifest_path(manifest_path);
-    }
-    let result = cmd.exec().with_context(|| "Invalid manifest")?;
+    let result = workspace_metadata(manifest_path)?;
     let packages = result.packages;
     let package = packages
         .into_iter()
@@ -24,12 +20,7 @@ pub fn manifest_from_pkgid(manifest_path: Option<&Path>, pkgid: &str) -> CargoRe
 
 /// Lookup all members of the current workspace
 pub fn workspace_members(manifest_path: Option<&Path>) -> CargoResult<Vec<Package>> {
-    let mut cmd = cargo_metadata::MetadataCommand::new();
-    cmd.no_deps();
-    if let Some(manifest_path) = manifest_path {
-        cmd.manifest_path(manifest_path);
-    }
-    let result = cmd.exec().with_context(|| "Invalid manifest")?;
+    let result = workspace_metadata(manifest_path)?;
     let workspace_members: std::collections::BTreeSet<_> =
         result.workspace_members.into_iter().collect();
     let workspace_members: Vec<_> = result
@@ -47,6 +38,13 @@ pub fn workspace_members(manifest_path: Option<&Path>) -> CargoResult<Vec<Packag
     Ok(workspace_members)
 }
 
+/// Lookup the manifest at the root of the current workspace
+pub fn workspace_root_manifest(manifest_path: Option<&Path>) -> CargoResult<PathBuf> {
+    let result = workspace_metadata(manifest_path)?;
+    let root = canonicalize_path(result.workspace_root.join("Cargo.toml"));
+    Ok(root.into_std_path_buf())
+}
+
 fn canonicalize_path(
     path: cargo_metadata::camino::Utf8PathBuf,
 ) -> cargo_metadata::camino::Utf8PathBuf {
@@ -72,10 +70,10 @@ pub fn resolve_manifests(
     })?;
     let manifest_path = dunce::canonicalize(manifest_path)?;
 
-    let mut cmd = cargo_metadata::MetadataCommand::new();
-    cmd.no_deps();
-    cmd.manifest_path(&manifest_path);
-    let result = cmd.exec().with_context(|| "Invalid manifest")?;
+    let mut result = workspace_metadata(Some(&manifest_path))?;
+    for package in result.packages.iter_mut() {
+        package.manifest_path = canonicalize_path(package.manifest_path.clone());
+    }
     let pkgs = if workspace {
         result.packages
     } else if !pkgid.is_empty() {
@@ -96,11 +94,61 @@ pub fn resolve_manifests(
             .iter()
             .find(|p| p.manifest_path == manifest_path)
             .map(|p| vec![(p.to_owned())])
-            .unwrap_or_else(|| result.packages)
+            .map(Ok)
+            .unwrap_or_else(|| default_members(&result))?
     };
     Ok(pkgs)
 }
 
+/// Workspace members without their dependencies, as cargo sees them
+fn workspace_metadata(manifest_path: Option<&Path>) -> CargoResult<cargo_metadata::Metadata> {
+    let mut cmd = cargo_metadata::MetadataCommand::new();
+    cmd.no_deps();
+    cmd.other_options(super::registry::cargo_flags().cargo_args());
+    if let Some(manifest_path) = manifest_path {
+        cmd.manifest_path(manifest_path);
+    }
+    cmd.exec().with_context(|| "Invalid manifest")
+}
+
+/// Members a virtual manifest operates on, honoring `workspace.default-members`
+fn default_members(metadata: &cargo_metadata::Metadata) -> CargoResult<Vec<Package>> {
+    let root_manifest = metadata.workspace_root.join("Cargo.toml");
+    let root: toml_edit::Document = read_manifest_source(root_manifest.as_std_path())?
+        .parse()
+        .with_context(|| format!("Unable to parse `{}`", root_manifest))?;
+    let default_members = match root
+        .get("workspace")
+        .and_then(|w| w.get("default-members"))
+        .and_then(|m| m.as_array())
+    {
+        Some(default_members) => default_members,
+        None => return Ok(metadata.packages.clone()),
+    };
+
+    default_members
+        .iter()
+        .map(|member| {
+            let member = member
+                .as_str()
+                .with_context(|| format!("Invalid `default-members` in `{}`", root_manifest))?;
+            let manifest_path =
+                canonicalize_path(metadata.workspace_root.join(member).join("Cargo.toml"));
+            metadata
+                .packages
+                .iter()
+                .find(|p| p.manifest_path == manifest_path)
+                .cloned()
+                .with_context(|| {
+                    format!(
+                        "package `{}` is listed in `default-members` but is not a member",
+                        member
+                    )
+                })
+        })
+        .collect()
+}
+
 /// Search for Cargo.toml in this directory and recursively up the tree until one is found.
 pub(crate) fn find_manifest_path(dir: &Path) -> CargoResult<std::path::PathBuf> {
     const MANIFEST_FILENAME: &str = "Cargo.toml";
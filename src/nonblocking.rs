@@ -0,0 +1,129 @@
+//! Async versions of the fetch layer
+//!
+//! Registry access is git and disk I/O that can't be interrupted, so each call runs on its own
+//! thread and the returned future completes once that thread is done.  This keeps executors
+//! free without tying this crate to any particular async runtime.
+
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+
+use url::Url;
+
+use super::errors::*;
+use super::{
+    get_latest_dependency, update_registry_index, Dependency, LookupOptions, LookupWarning,
+};
+
+/// Async version of [`get_latest_dependency`]
+pub fn get_latest_dependency_async(
+    crate_name: &str,
+    options: &LookupOptions,
+    manifest_path: &Path,
+    registry: Option<&Url>,
+) -> impl Future<Output = CargoResult<(Dependency, Vec<LookupWarning>)>> + Send + 'static {
+    let crate_name = crate_name.to_owned();
+    let manifest_path = manifest_path.to_owned();
+    let registry = registry.cloned();
+    let options = *options;
+    Blocking::spawn(move || {
+        get_latest_dependency(&crate_name, &options, &manifest_path, registry.as_ref())
+    })
+}
+
+/// Async version of [`update_registry_index`]
+pub fn update_registry_index_async(
+    registry: &Url,
+    options: &LookupOptions,
+    manifest_path: &Path,
+    quiet: bool,
+) -> impl Future<Output = CargoResult<()>> + Send + 'static {
+    let registry = registry.clone();
+    let options = *options;
+    let manifest_path = manifest_path.to_owned();
+    Blocking::spawn(move || update_registry_index(&registry, &options, &manifest_path, quiet))
+}
+
+/// Future for the result of work running on a background thread
+struct Blocking<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+struct Shared<T> {
+    result: Option<std::thread::Result<T>>,
+    waker: Option<Waker>,
+}
+
+impl<T: Send + 'static> Blocking<T> {
+    fn spawn(work: impl FnOnce() -> T + Send + 'static) -> Self {
+        let shared = Arc::new(Mutex::new(Shared {
+            result: None,
+            waker: None,
+        }));
+        let thread_shared = shared.clone();
+        std::thread::spawn(move || {
+            // Hand panics to the awaiting task rather than leaving it pending forever
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(work));
+            let mut shared = thread_shared.lock().unwrap_or_else(|e| e.into_inner());
+            shared.result = Some(result);
+            if let Some(waker) = shared.waker.take() {
+                waker.wake();
+            }
+        });
+        Self { shared }
+    }
+}
+
+impl<T> Future for Blocking<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<T> {
+        let mut shared = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        match shared.result.take() {
+            Some(Ok(result)) => Poll::Ready(result),
+            Some(Err(panic)) => std::panic::resume_unwind(panic),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct ThreadWaker(std::thread::Thread);
+
+    impl std::task::Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+        let mut cx = std::task::Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn blocking_returns_result() {
+        assert_eq!(block_on(Blocking::spawn(|| 1 + 1)), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "boom")]
+    fn blocking_propagates_panics() {
+        block_on(Blocking::spawn(|| panic!("boom")));
+    }
+}
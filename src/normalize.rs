@@ -0,0 +1,202 @@
+//! Rewrite dependency entries in one canonical style
+use super::manifest::overwrite_value;
+use super::{find_template_placeholder, LocalManifest};
+
+/// How to write caret requirements
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RequirementStyle {
+    /// Leave out components that are `0` where that doesn't change the requirement, like `1.2`
+    Short,
+    /// Write all three components where that doesn't change the requirement, like `1.2.0`
+    Full,
+}
+
+/// Write a requirement in `style`, like `^1.2.0` as `1.2`
+///
+/// Only a single caret requirement, written with or without `^`, is rewritten.  Anything else
+/// is returned as written.
+pub fn normalize_requirement(req: &str, style: RequirementStyle) -> String {
+    let comparator = match req.parse::<semver::VersionReq>() {
+        Ok(parsed) if parsed.comparators.len() == 1 => parsed.comparators[0].clone(),
+        _ => return req.to_owned(),
+    };
+    if comparator.op != semver::Op::Caret || !comparator.pre.is_empty() {
+        return req.to_owned();
+    }
+
+    let major = comparator.major;
+    let (mut minor, mut patch) = (comparator.minor, comparator.patch);
+    match style {
+        RequirementStyle::Short => {
+            // `^0.0` allows `0.0.1` while `^0.0.0` doesn't, and `^0` allows `0.1.0`
+            if patch == Some(0) && (major > 0 || minor.unwrap_or(0) > 0) {
+                patch = None;
+            }
+            if patch.is_none() && minor == Some(0) && major > 0 {
+                minor = None;
+            }
+        }
+        RequirementStyle::Full => {
+            if major > 0 || minor.unwrap_or(0) > 0 {
+                minor = Some(minor.unwrap_or(0));
+                patch = Some(patch.unwrap_or(0));
+            }
+        }
+    }
+
+    let mut normalized = major.to_string();
+    for component in [minor, patch].into_iter().flatten() {
+        normalized.push_str(&format!(".{}", component));
+    }
+    normalized
+}
+
+/// Normalize every dependency entry of `manifest`, including `[workspace.dependencies]`
+///
+/// Requirements are written in `style`, `features` are sorted and deduplicated, entries that
+/// only set a version are written as a string, and inline tables are formatted uniformly.
+/// Entries with template placeholders are left alone.  Returns the keys of the dependencies that
+/// changed.
+pub fn normalize_dependencies(
+    manifest: &mut LocalManifest,
+    style: RequirementStyle,
+) -> Vec<String> {
+    let mut changed = Vec::new();
+    for table in manifest.get_dependency_tables_mut() {
+        changed.extend(normalize_table(table, style));
+    }
+    if let Some(table) = manifest.get_workspace_dependency_table_mut() {
+        changed.extend(normalize_table(table, style));
+    }
+    changed
+}
+
+fn normalize_table(table: &mut dyn toml_edit::TableLike, style: RequirementStyle) -> Vec<String> {
+    let mut changed = Vec::new();
+    for (key, item) in table.iter_mut() {
+        // Sorting or reformatting around placeholders could break the template
+        if find_template_placeholder(item).is_some() {
+            continue;
+        }
+        let original = item.to_string();
+        normalize_entry(item, style);
+        if item.to_string() != original {
+            changed.push(key.get().to_owned());
+        }
+    }
+    changed
+}
+
+fn normalize_entry(item: &mut toml_edit::Item, style: RequirementStyle) {
+    if let Some(req) = item.as_str() {
+        let normalized = normalize_requirement(req, style);
+        if normalized != req {
+            overwrite_value(item, normalized);
+        }
+        return;
+    }
+    let table = match item.as_table_like_mut() {
+        Some(table) => table,
+        None => return,
+    };
+
+    if let Some(version) = table.get_mut("version") {
+        if let Some(req) = version.as_str() {
+            let normalized = normalize_requirement(req, style);
+            if normalized != req {
+                overwrite_value(version, normalized);
+            }
+        }
+    }
+    if let Some(features) = table.get_mut("features") {
+        if let Some(array) = features.as_array() {
+            let written: Vec<&str> = array.iter().filter_map(|f| f.as_str()).collect();
+            let mut sorted = written.clone();
+            sorted.sort_unstable();
+            sorted.dedup();
+            if written.len() == array.len() && sorted != written {
+                let sorted: toml_edit::Array = sorted.into_iter().collect();
+                overwrite_value(features, sorted);
+            }
+        }
+    }
+
+    if let Some(inline) = item.as_inline_table_mut() {
+        if inline.len() == 1 {
+            if let Some(req) = inline.get("version").and_then(|v| v.as_str()) {
+                let req = req.to_owned();
+                overwrite_value(item, req);
+                return;
+            }
+        }
+        inline.fmt();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    #[test]
+    fn short_requirements() {
+        let short = |req| normalize_requirement(req, RequirementStyle::Short);
+        assert_eq!(short("^1.2.0"), "1.2");
+        assert_eq!(short("1.0.0"), "1");
+        assert_eq!(short("1.0.3"), "1.0.3");
+        assert_eq!(short("0.2.0"), "0.2");
+        assert_eq!(short("0.0.0"), "0.0.0");
+        assert_eq!(short("0.0"), "0.0");
+        assert_eq!(short("~1.2.0"), "~1.2.0");
+        assert_eq!(short(">=1.0, <2.0"), ">=1.0, <2.0");
+        assert_eq!(short("1.0.0-rc.1"), "1.0.0-rc.1");
+        assert_eq!(short("*"), "*");
+    }
+
+    #[test]
+    fn full_requirements() {
+        let full = |req| normalize_requirement(req, RequirementStyle::Full);
+        assert_eq!(full("^1"), "1.0.0");
+        assert_eq!(full("0.2"), "0.2.0");
+        assert_eq!(full("0"), "0");
+        assert_eq!(full("0.0"), "0.0");
+        assert_eq!(full("=1.2"), "=1.2");
+    }
+
+    #[test]
+    fn normalize_entries() {
+        let mut manifest = LocalManifest {
+            path: PathBuf::from("Cargo.toml"),
+            manifest: r#"[dependencies]
+serde = { version = "^1.0.130", features = ["rc", "derive", "rc"] }
+regex = {version="1.5.0"}
+log = "0.4.0" # logging
+
+[target.'cfg(unix)'.dependencies]
+nix = { version = "0.25", optional = true }
+
+[workspace.dependencies]
+toml_edit = "^0.14.0"
+"#
+            .parse()
+            .unwrap(),
+        };
+        let changed = normalize_dependencies(&mut manifest, RequirementStyle::Short);
+        assert_eq!(changed, ["serde", "regex", "log", "toml_edit"]);
+        assert_eq!(
+            manifest.to_string(),
+            r#"[dependencies]
+serde = { version = "1.0.130", features = ["derive", "rc"] }
+regex = "1.5"
+log = "0.4" # logging
+
+[target.'cfg(unix)'.dependencies]
+nix = { version = "0.25", optional = true }
+
+[workspace.dependencies]
+toml_edit = "0.14"
+"#
+        );
+    }
+}
@@ -0,0 +1,169 @@
+//! Tell someone about a run once it's done, through a chat webhook or a desktop notification
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use url::Url;
+
+use super::errors::*;
+
+/// Lines kept in a notification, so it fits the limits of chat services and desktop popups
+const MAX_LINES: usize = 20;
+
+/// Where to send a notification
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NotifyTarget {
+    /// The desktop, through `notify-send` or, on macOS, `osascript`
+    Desktop,
+    /// A webhook taking a JSON message, like Slack's and Discord's incoming webhooks
+    Webhook(Url),
+}
+
+impl FromStr for NotifyTarget {
+    type Err = Error;
+
+    fn from_str(target: &str) -> CargoResult<Self> {
+        if target == "desktop" {
+            return Ok(Self::Desktop);
+        }
+        let url = Url::parse(target).with_context(|| {
+            format!(
+                "Invalid notification target `{}`, expected `desktop` or a webhook URL",
+                target
+            )
+        })?;
+        if !matches!(url.scheme(), "http" | "https") {
+            anyhow::bail!("Webhook should be an `http` or `https` URL, not `{}`", url);
+        }
+        Ok(Self::Webhook(url))
+    }
+}
+
+/// Webhook URLs are shown by host only, as their path is usually the secret allowing to post
+impl fmt::Display for NotifyTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Desktop => f.write_str("the desktop"),
+            Self::Webhook(url) => write!(f, "the webhook at {}", url.host_str().unwrap_or("")),
+        }
+    }
+}
+
+/// Send `title`, followed by `lines`, to `target`
+///
+/// Long reports are cut short, saying how many lines were left out.  Webhooks get both a `text`
+/// and a `content` field, so the same message suits Slack and Discord, and are posted through the
+/// proxy configured for `manifest_path`.
+pub fn send_notification(
+    target: &NotifyTarget,
+    title: &str,
+    lines: &[String],
+    manifest_path: &Path,
+) -> CargoResult<()> {
+    let body = notification_body(lines);
+    match target {
+        NotifyTarget::Desktop => notify_desktop(title, &body),
+        NotifyTarget::Webhook(url) => {
+            let text = if body.is_empty() {
+                title.to_owned()
+            } else {
+                format!("{}\n{}", title, body)
+            };
+            let work_dir = manifest_path
+                .parent()
+                .expect("there must be a parent directory");
+            super::http::http_agent(url, work_dir)?
+                .post(url.as_str())
+                .send_json(serde_json::json!({
+                    "text": text,
+                    "content": text,
+                }))
+                .map_err(|err| {
+                    // ureq's errors include the URL
+                    let reason = match err {
+                        ureq::Error::Status(code, _) => format!("status code {}", code),
+                        ureq::Error::Transport(transport) => transport.kind().to_string(),
+                    };
+                    anyhow::format_err!("Failed to post to {}: {}", target, reason)
+                })?;
+            Ok(())
+        }
+    }
+}
+
+fn notification_body(lines: &[String]) -> String {
+    if lines.len() <= MAX_LINES {
+        return lines.join("\n");
+    }
+    let mut body = lines[..MAX_LINES - 1].join("\n");
+    body.push_str(&format!("\n… and {} more", lines.len() - (MAX_LINES - 1)));
+    body
+}
+
+fn notify_desktop(title: &str, body: &str) -> CargoResult<()> {
+    let mut cmd = if cfg!(target_os = "macos") {
+        let mut cmd = std::process::Command::new("osascript");
+        cmd.arg("-e").arg(format!(
+            "display notification {} with title {}",
+            applescript_string(body),
+            applescript_string(title)
+        ));
+        cmd
+    } else if cfg!(windows) {
+        anyhow::bail!("Desktop notifications aren't supported on Windows, use a webhook");
+    } else {
+        let mut cmd = std::process::Command::new("notify-send");
+        cmd.arg(title).arg(body);
+        cmd
+    };
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let output = cmd
+        .output()
+        .with_context(|| format!("Failed to run `{}`", program))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "`{}` failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+fn applescript_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_targets() {
+        assert_eq!(
+            "desktop".parse::<NotifyTarget>().unwrap(),
+            NotifyTarget::Desktop
+        );
+        let target: NotifyTarget = "https://hooks.slack.com/services/T0/B0/X".parse().unwrap();
+        assert_eq!(target.to_string(), "the webhook at hooks.slack.com");
+        assert!("file:///tmp/hook".parse::<NotifyTarget>().is_err());
+        assert!("slack".parse::<NotifyTarget>().is_err());
+    }
+
+    #[test]
+    fn long_bodies_are_cut() {
+        let lines: Vec<_> = (0..25).map(|i| i.to_string()).collect();
+        let body = notification_body(&lines);
+        assert_eq!(body.lines().count(), MAX_LINES);
+        assert!(body.ends_with("… and 6 more"));
+        assert_eq!(notification_body(&lines[..3]), "0\n1\n2");
+    }
+
+    #[test]
+    fn applescript_quoting() {
+        assert_eq!(
+            applescript_string(r#"say "hi" \o/"#),
+            r#""say \"hi\" \\o/""#
+        );
+    }
+}
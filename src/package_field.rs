@@ -0,0 +1,342 @@
+//! Address `[package]` fields, and check values against what cargo and crates.io accept
+use std::fmt;
+use std::str::FromStr;
+
+use super::errors::*;
+
+/// crates.io allows at most this many keywords, and as many categories
+const MAX_LIST_LEN: usize = 5;
+/// crates.io allows keywords up to this long
+const MAX_KEYWORD_LEN: usize = 20;
+
+/// Editions cargo knows about
+const EDITIONS: &[&str] = &["2015", "2018", "2021", "2024"];
+
+/// Category slugs crates.io accepts, from <https://crates.io/category_slugs>
+const CATEGORIES: &[&str] = &[
+    "accessibility",
+    "aerospace",
+    "aerospace::drones",
+    "aerospace::protocols",
+    "aerospace::simulation",
+    "aerospace::space-protocols",
+    "aerospace::unmanned-aerial-vehicles",
+    "algorithms",
+    "api-bindings",
+    "asynchronous",
+    "authentication",
+    "caching",
+    "command-line-interface",
+    "command-line-utilities",
+    "compilers",
+    "compression",
+    "computer-vision",
+    "concurrency",
+    "config",
+    "cryptography",
+    "cryptography::cryptocurrencies",
+    "data-structures",
+    "database",
+    "database-implementations",
+    "date-and-time",
+    "development-tools",
+    "development-tools::build-utils",
+    "development-tools::cargo-plugins",
+    "development-tools::debugging",
+    "development-tools::ffi",
+    "development-tools::procedural-macro-helpers",
+    "development-tools::profiling",
+    "development-tools::testing",
+    "email",
+    "embedded",
+    "emulators",
+    "encoding",
+    "external-ffi-bindings",
+    "filesystem",
+    "finance",
+    "game-development",
+    "game-engines",
+    "games",
+    "graphics",
+    "gui",
+    "hardware-support",
+    "internationalization",
+    "localization",
+    "mathematics",
+    "memory-management",
+    "multimedia",
+    "multimedia::audio",
+    "multimedia::encoding",
+    "multimedia::images",
+    "multimedia::video",
+    "network-programming",
+    "no-std",
+    "no-std::no-alloc",
+    "os",
+    "os::android-apis",
+    "os::freebsd-apis",
+    "os::linux-apis",
+    "os::macos-apis",
+    "os::unix-apis",
+    "os::windows-apis",
+    "parser-implementations",
+    "parsing",
+    "rendering",
+    "rendering::data-formats",
+    "rendering::engine",
+    "rendering::graphics-api",
+    "rust-patterns",
+    "science",
+    "science::bioinformatics",
+    "science::bioinformatics::genomics",
+    "science::bioinformatics::proteomics",
+    "science::bioinformatics::sequence-analysis",
+    "science::geo",
+    "science::neuroscience",
+    "science::robotics",
+    "security",
+    "simulation",
+    "template-engine",
+    "text-editors",
+    "text-processing",
+    "value-formatting",
+    "virtualization",
+    "visualization",
+    "wasm",
+    "web-programming",
+    "web-programming::http-client",
+    "web-programming::http-server",
+    "web-programming::websocket",
+];
+
+/// A `[package]` field that can be set from the command line
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PackageField {
+    /// `description`
+    Description,
+    /// `repository`, an absolute URL
+    Repository,
+    /// `keywords`, at most five of up to 20 characters
+    Keywords,
+    /// `categories`, at most five crates.io category slugs
+    Categories,
+    /// `rust-version`, like `1.60`
+    RustVersion,
+    /// `edition`, like `2021`
+    Edition,
+}
+
+impl PackageField {
+    const ALL: &'static [Self] = &[
+        Self::Description,
+        Self::Repository,
+        Self::Keywords,
+        Self::Categories,
+        Self::RustVersion,
+        Self::Edition,
+    ];
+
+    /// The key in `[package]`
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::Description => "description",
+            Self::Repository => "repository",
+            Self::Keywords => "keywords",
+            Self::Categories => "categories",
+            Self::RustVersion => "rust-version",
+            Self::Edition => "edition",
+        }
+    }
+
+    /// Whether the field is a list, set from several values
+    pub fn is_list(self) -> bool {
+        matches!(self, Self::Keywords | Self::Categories)
+    }
+
+    /// Check `values` suit the field and turn them into its value
+    ///
+    /// Lists take any number of values, up to the limit crates.io enforces, while other fields
+    /// take exactly one.
+    pub fn parse_value(self, values: &[String]) -> CargoResult<toml_edit::Value> {
+        if self.is_list() {
+            if values.len() > MAX_LIST_LEN {
+                anyhow::bail!(
+                    "crates.io allows at most {} `{}`, got {}",
+                    MAX_LIST_LEN,
+                    self,
+                    values.len()
+                );
+            }
+            for value in values {
+                self.check_item(value)?;
+            }
+            let mut seen = Vec::new();
+            for value in values {
+                if seen.contains(value) {
+                    anyhow::bail!("`{}` is listed twice in `{}`", value, self);
+                }
+                seen.push(value.clone());
+            }
+            let array: toml_edit::Array = values.iter().map(String::as_str).collect();
+            return Ok(array.into());
+        }
+
+        let value = match values {
+            [value] => value,
+            _ => anyhow::bail!("`{}` takes a single value, got {}", self, values.len()),
+        };
+        self.check_item(value)?;
+        Ok(value.as_str().into())
+    }
+
+    fn check_item(self, value: &str) -> CargoResult<()> {
+        match self {
+            Self::Description => {
+                if value.trim().is_empty() {
+                    anyhow::bail!("`description` can't be empty");
+                }
+            }
+            Self::Repository => {
+                let url = url::Url::parse(value)
+                    .with_context(|| format!("Invalid `repository` URL `{}`", value))?;
+                if !matches!(url.scheme(), "http" | "https") {
+                    anyhow::bail!(
+                        "`repository` should be an `http` or `https` URL, not `{}`",
+                        url
+                    );
+                }
+            }
+            Self::Keywords => {
+                let valid = value.len() <= MAX_KEYWORD_LEN
+                    && value.starts_with(|c: char| c.is_ascii_alphanumeric())
+                    && value
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+'));
+                if !valid {
+                    anyhow::bail!(
+                        "Invalid keyword `{}`, crates.io expects up to {} ASCII letters, digits, \
+                         `_`, `-` or `+`, starting with a letter or digit",
+                        value,
+                        MAX_KEYWORD_LEN
+                    );
+                }
+            }
+            Self::Categories => {
+                if !CATEGORIES.contains(&value) {
+                    anyhow::bail!(
+                        "Unknown category `{}`, see https://crates.io/category_slugs for the slugs \
+                         crates.io accepts",
+                        value
+                    );
+                }
+            }
+            Self::RustVersion => {
+                let components: Vec<_> = value.split('.').collect();
+                let valid = (2..=3).contains(&components.len())
+                    && components
+                        .iter()
+                        .all(|c| !c.is_empty() && c.chars().all(|c| c.is_ascii_digit()));
+                if !valid {
+                    anyhow::bail!(
+                        "Invalid `rust-version` `{}`, expected a version like `1.60` or `1.60.0`",
+                        value
+                    );
+                }
+            }
+            Self::Edition => {
+                if !EDITIONS.contains(&value) {
+                    anyhow::bail!(
+                        "Unknown edition `{}`, expected one of {}",
+                        value,
+                        EDITIONS.join(", ")
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PackageField {
+    type Err = Error;
+
+    fn from_str(key: &str) -> CargoResult<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|field| field.key() == key)
+            .ok_or_else(|| {
+                let known: Vec<_> = Self::ALL.iter().map(|field| field.key()).collect();
+                anyhow::format_err!(
+                    "Unsupported package field `{}`, expected one of {}",
+                    key,
+                    known.join(", ")
+                )
+            })
+    }
+}
+
+impl fmt::Display for PackageField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.key())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(field: PackageField, values: &[&str]) -> CargoResult<String> {
+        let values: Vec<_> = values.iter().map(|v| v.to_string()).collect();
+        field.parse_value(&values).map(|v| v.to_string())
+    }
+
+    #[test]
+    fn keywords() {
+        assert_eq!(
+            parse(PackageField::Keywords, &["cli", "cargo-plugin"]).unwrap(),
+            r#"["cli", "cargo-plugin"]"#
+        );
+        assert!(parse(PackageField::Keywords, &["a", "b", "c", "d", "e", "f"]).is_err());
+        assert!(parse(PackageField::Keywords, &["-cli"]).is_err());
+        assert!(parse(PackageField::Keywords, &["command line"]).is_err());
+        assert!(parse(PackageField::Keywords, &["a-very-long-keyword-indeed"]).is_err());
+        assert!(parse(PackageField::Keywords, &["cli", "cli"]).is_err());
+    }
+
+    #[test]
+    fn categories() {
+        assert!(parse(
+            PackageField::Categories,
+            &["development-tools::cargo-plugins"]
+        )
+        .is_ok());
+        assert!(parse(PackageField::Categories, &["cargo-plugins"]).is_err());
+    }
+
+    #[test]
+    fn single_values() {
+        assert_eq!(
+            parse(PackageField::RustVersion, &["1.60"]).unwrap(),
+            r#""1.60""#
+        );
+        assert!(parse(PackageField::RustVersion, &["1.60.0"]).is_ok());
+        assert!(parse(PackageField::RustVersion, &["^1.60"]).is_err());
+        assert!(parse(PackageField::RustVersion, &["1"]).is_err());
+        assert!(parse(PackageField::Edition, &["2021"]).is_ok());
+        assert!(parse(PackageField::Edition, &["2020"]).is_err());
+        assert!(parse(PackageField::Repository, &["https://github.com/o/r"]).is_ok());
+        assert!(parse(PackageField::Repository, &["github.com/o/r"]).is_err());
+        assert!(parse(PackageField::Description, &["a", "b"]).is_err());
+        assert!(parse(PackageField::Description, &[" "]).is_err());
+    }
+
+    #[test]
+    fn field_names() {
+        assert_eq!(
+            "rust-version".parse::<PackageField>().unwrap(),
+            PackageField::RustVersion
+        );
+        assert!("version".parse::<PackageField>().is_err());
+    }
+}
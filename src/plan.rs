@@ -0,0 +1,358 @@
+//! Plan upgrades of a manifest's requirements, to present them before applying them
+use std::path::PathBuf;
+
+use semver::{Op, VersionReq};
+use url::Url;
+
+use super::errors::*;
+use super::manifest::{find_template_placeholder, set_dep_version, LocalManifest};
+use super::{
+    get_latest_dependency, registry_url, split_build_metadata, update_registry_indexes,
+    upgrade_requirement, Dependency, IfYanked, LookupOptions, Source,
+};
+
+/// What [`plan_upgrade`] considers
+#[derive(Clone, Debug, Default)]
+pub struct UpgradeOptions {
+    compatible: bool,
+    pinned: bool,
+    offline: bool,
+    exclude: Vec<String>,
+    if_yanked: IfYanked,
+}
+
+impl UpgradeOptions {
+    /// Upgrade nothing excluded, leaving compatible versions to the lockfile, like `cargo upgrade`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also raise requirements that already allow the latest version
+    pub fn set_compatible(mut self, compatible: bool) -> Self {
+        self.compatible = compatible;
+        self
+    }
+
+    /// Also upgrade requirements pinned with `=`, `<`, `<=` or `*`, and renamed dependencies
+    pub fn set_pinned(mut self, pinned: bool) -> Self {
+        self.pinned = pinned;
+        self
+    }
+
+    /// Look versions up in the registry indexes already downloaded
+    pub fn set_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Leave a dependency alone, by its key in the manifest
+    pub fn exclude(mut self, dep_key: impl Into<String>) -> Self {
+        self.exclude.push(dep_key.into());
+        self
+    }
+
+    /// How to handle yanked newest versions
+    pub fn set_if_yanked(mut self, if_yanked: IfYanked) -> Self {
+        self.if_yanked = if_yanked;
+        self
+    }
+}
+
+/// Requirement changes proposed by [`plan_upgrade`], for [`UpgradePlan::apply`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UpgradePlan {
+    /// Manifest the plan is for
+    pub manifest_path: PathBuf,
+    /// Requirements to change
+    pub upgrades: Vec<PlannedUpgrade>,
+    /// Dependencies left alone, and why
+    pub skipped: Vec<PlannedSkip>,
+}
+
+/// A requirement [`plan_upgrade`] proposes to change
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlannedUpgrade {
+    /// Dependency table, like `["target", "cfg(unix)", "dependencies"]`
+    pub table: Vec<String>,
+    /// Key of the dependency in the table
+    pub name: String,
+    /// Requirement in the manifest
+    pub old_req: String,
+    /// Requirement to change it to
+    pub new_req: String,
+    /// Newest version found
+    pub latest: String,
+    /// Whether the new requirement allows no version the old one did
+    pub breaking: bool,
+}
+
+/// A dependency [`plan_upgrade`] leaves alone
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlannedSkip {
+    /// Dependency table, like `["dev-dependencies"]`
+    pub table: Vec<String>,
+    /// Key of the dependency in the table
+    pub name: String,
+    /// Why it is left alone
+    pub reason: PlanSkipReason,
+}
+
+/// Why [`plan_upgrade`] leaves a dependency alone
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PlanSkipReason {
+    /// The requirement already allows only the latest version, or a newer one
+    Latest,
+    /// The requirement already allows the latest version, see [`UpgradeOptions::set_compatible`]
+    Compatible,
+    /// Excluded with [`UpgradeOptions::exclude`], or by the `upgrade-ignore` policy
+    Excluded,
+    /// Pinned with `=`, `<`, `<=` or `*`, or renamed, see [`UpgradeOptions::set_pinned`]
+    Pinned,
+    /// Not from a registry, like git or path dependencies
+    NotRegistry,
+    /// Contains a template placeholder, like `{{version}}`
+    Templated,
+    /// The entry couldn't be read
+    Unsupported(String),
+    /// The registry lookup failed
+    RegistryError(String),
+}
+
+impl PlanSkipReason {
+    /// Short name of the reason, like `pinned`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Latest => "latest",
+            Self::Compatible => "compatible",
+            Self::Excluded => "excluded",
+            Self::Pinned => "pinned",
+            Self::NotRegistry => "not-registry",
+            Self::Templated => "templated",
+            Self::Unsupported(_) => "unsupported",
+            Self::RegistryError(_) => "registry-error",
+        }
+    }
+}
+
+/// Propose new requirements for the registry dependencies of `manifest`, without changing it
+///
+/// Registry indexes are updated first, unless [`UpgradeOptions::set_offline`] is set.
+pub fn plan_upgrade(
+    manifest: &LocalManifest,
+    options: &UpgradeOptions,
+) -> CargoResult<UpgradePlan> {
+    let policy = manifest.upgrade_policy()?;
+    let mut plan = UpgradePlan {
+        manifest_path: manifest.path.clone(),
+        upgrades: Vec::new(),
+        skipped: Vec::new(),
+    };
+
+    let mut lookups = Vec::new();
+    for (dep_table, item) in manifest.get_sections() {
+        let table: Vec<String> = dep_table.to_table().into_iter().map(String::from).collect();
+        let entries = item.as_table_like().into_iter().flat_map(|t| t.iter());
+        for (dep_key, dep_item) in entries {
+            let skip = |reason| PlannedSkip {
+                table: table.clone(),
+                name: dep_key.to_owned(),
+                reason,
+            };
+            if options.exclude.iter().any(|e| e == dep_key) || policy.is_ignored(dep_key) {
+                plan.skipped.push(skip(PlanSkipReason::Excluded));
+                continue;
+            }
+            if find_template_placeholder(dep_item).is_some() {
+                plan.skipped.push(skip(PlanSkipReason::Templated));
+                continue;
+            }
+            let dependency = match Dependency::from_toml(&manifest.path, dep_key, dep_item) {
+                Ok(dependency) => dependency,
+                Err(err) => {
+                    plan.skipped
+                        .push(skip(PlanSkipReason::Unsupported(err.to_string())));
+                    continue;
+                }
+            };
+            let old_req = match (&dependency.source, dependency.version()) {
+                (Some(Source::Registry(_)), Some(version)) => version.to_owned(),
+                _ => {
+                    plan.skipped.push(skip(PlanSkipReason::NotRegistry));
+                    continue;
+                }
+            };
+            if !options.pinned && (dependency.rename.is_some() || is_pinned_req(&old_req)) {
+                plan.skipped.push(skip(PlanSkipReason::Pinned));
+                continue;
+            }
+            let registry = registry_url(&manifest.path, dependency.registry())?;
+            lookups.push((
+                table.clone(),
+                dep_key.to_owned(),
+                dependency,
+                old_req,
+                registry,
+            ));
+        }
+    }
+
+    if !options.offline {
+        let mut registries: Vec<Url> = Vec::new();
+        for (_, _, _, _, registry) in &lookups {
+            if !registries.contains(registry) {
+                registries.push(registry.clone());
+            }
+        }
+        update_registry_indexes(&registries, &LookupOptions::new(), &manifest.path, true)?;
+    }
+
+    for (table, name, dependency, old_req, registry) in lookups {
+        let latest = get_latest_dependency(
+            &dependency.name,
+            &LookupOptions::new()
+                .set_allow_prerelease(old_req.contains('-'))
+                .set_if_yanked(options.if_yanked),
+            &manifest.path,
+            Some(&registry),
+        )
+        .map(|(latest, _)| {
+            latest
+                .version()
+                .expect("registry packages always have a version")
+                .to_owned()
+        });
+        let planned = match latest {
+            Ok(latest) => plan_requirement(&old_req, &latest, options.compatible),
+            Err(err) => Err(PlanSkipReason::RegistryError(format!("{:#}", err))),
+        };
+        match planned {
+            Ok((new_req, latest, breaking)) => plan.upgrades.push(PlannedUpgrade {
+                table,
+                name,
+                old_req,
+                new_req,
+                latest,
+                breaking,
+            }),
+            Err(reason) => plan.skipped.push(PlannedSkip {
+                table,
+                name,
+                reason,
+            }),
+        }
+    }
+    Ok(plan)
+}
+
+impl UpgradePlan {
+    /// Whether the plan changes nothing
+    pub fn is_empty(&self) -> bool {
+        self.upgrades.is_empty()
+    }
+
+    /// Change the requirements of `manifest` as planned, without writing it
+    ///
+    /// Fails when a requirement changed since planning, rather than overwrite it.
+    pub fn apply_to(&self, manifest: &mut LocalManifest) -> CargoResult<()> {
+        for upgrade in &self.upgrades {
+            let dep_item = manifest
+                .get_table_mut(&upgrade.table)
+                .ok()
+                .and_then(|table| table.as_table_like_mut())
+                .and_then(|table| table.get_mut(&upgrade.name))
+                .with_context(|| {
+                    format!(
+                        "`{}` is no longer in `[{}]`",
+                        upgrade.name,
+                        upgrade.table.join(".")
+                    )
+                })?;
+            let current = Dependency::from_toml(&self.manifest_path, &upgrade.name, dep_item)?;
+            if current.version() != Some(upgrade.old_req.as_str()) {
+                anyhow::bail!(
+                    "`{}` was changed since the upgrade was planned, from `{}`",
+                    upgrade.name,
+                    upgrade.old_req
+                );
+            }
+            set_dep_version(dep_item, &upgrade.new_req)?;
+        }
+        Ok(())
+    }
+
+    /// Change the requirements of the manifest the plan is for, and write it
+    pub fn apply(&self) -> CargoResult<()> {
+        let mut manifest = LocalManifest::try_new(&self.manifest_path)?;
+        self.apply_to(&mut manifest)?;
+        manifest.write()
+    }
+}
+
+/// The requirement `old_req` upgrades to for `latest`, with the version and whether it breaks
+fn plan_requirement(
+    old_req: &str,
+    latest: &str,
+    compatible: bool,
+) -> Result<(String, String, bool), PlanSkipReason> {
+    let version: semver::Version = latest
+        .parse()
+        .map_err(|err| PlanSkipReason::RegistryError(format!("invalid version: {}", err)))?;
+    let (req, _) = split_build_metadata(old_req);
+    let allowed = VersionReq::parse(&req)
+        .map_err(|err| PlanSkipReason::Unsupported(format!("invalid requirement: {}", err)))?
+        .matches(&version);
+    let new_req = match upgrade_requirement(old_req, &version) {
+        Ok(Some(new_req)) => new_req,
+        Ok(None) => return Err(PlanSkipReason::Latest),
+        Err(err) => return Err(PlanSkipReason::Unsupported(err.to_string())),
+    };
+    if allowed && !compatible {
+        return Err(PlanSkipReason::Compatible);
+    }
+    Ok((new_req, latest.to_owned(), !allowed))
+}
+
+/// Whether `req` pins its dependency, with `=`, `<`, `<=` or a wildcard like `1.*`, which
+/// upgrades leave alone unless asked to
+pub fn is_pinned_req(req: &str) -> bool {
+    VersionReq::parse(req).map_or(false, |req| {
+        req.comparators.iter().any(|comparator| {
+            matches!(
+                comparator.op,
+                Op::Exact | Op::Less | Op::LessEq | Op::Wildcard
+            )
+        })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn requirements() {
+        assert_eq!(
+            plan_requirement("0.1", "0.2.3", false),
+            Ok(("0.2".to_owned(), "0.2.3".to_owned(), true))
+        );
+        assert_eq!(
+            plan_requirement("1.0", "1.4.0", false),
+            Err(PlanSkipReason::Compatible)
+        );
+        assert_eq!(
+            plan_requirement("1.0", "1.4.0", true),
+            Ok(("1.4".to_owned(), "1.4.0".to_owned(), false))
+        );
+        assert_eq!(
+            plan_requirement("1.4.0", "1.4.0", true),
+            Err(PlanSkipReason::Latest)
+        );
+        assert!(matches!(
+            plan_requirement("1.x.y", "1.4.0", true),
+            Err(PlanSkipReason::Unsupported(_))
+        ));
+        assert!(is_pinned_req("=1.0.0"));
+        assert!(is_pinned_req(">=1.0, <1.5"));
+        assert!(!is_pinned_req("^1.0"));
+    }
+}
@@ -0,0 +1,316 @@
+//! Named groups of dependencies added together, like `web = ["axum", "tokio@1+full"]`
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::add::{insert_dependency, write_together};
+use super::errors::*;
+use super::manifest::Manifest;
+use super::registry::preset_config;
+use super::{
+    get_latest_dependency, workspace_root_manifest, CrateSpec, Dependency, DependencyAddition,
+    LocalManifest, LookupOptions, LookupWarning, MergeStrategy, RegistrySource, WriteOptions,
+};
+
+/// A dependency in a preset, like `tokio@1+full+macros`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PresetEntry {
+    /// Crate name
+    pub name: String,
+    /// Version requirement, or `None` for the latest release
+    pub version_req: Option<String>,
+    /// Features to activate
+    pub features: Vec<String>,
+}
+
+impl FromStr for PresetEntry {
+    type Err = Error;
+
+    /// Parse `<name>[@<version-req>][+<feature>...]`
+    ///
+    /// As `+` starts features, version requirements can't carry build metadata.
+    fn from_str(entry: &str) -> CargoResult<Self> {
+        let mut parts = entry.split('+');
+        let spec = CrateSpec::resolve(parts.next().expect("split always yields a part"))
+            .with_context(|| format!("Invalid preset entry `{}`", entry))?;
+        let features: Vec<String> = parts.map(str::to_owned).collect();
+        if features.iter().any(String::is_empty) {
+            anyhow::bail!("Invalid preset entry `{}`: empty feature after `+`", entry);
+        }
+        Ok(Self {
+            name: spec.name,
+            version_req: spec.version_req,
+            features,
+        })
+    }
+}
+
+/// A named group of dependencies, see [`find_preset`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DependencyPreset {
+    /// Name the preset is selected by
+    pub name: String,
+    /// Dependencies in the preset
+    pub entries: Vec<PresetEntry>,
+}
+
+impl DependencyPreset {
+    /// Parse a preset from its entries, like `["axum", "tokio@1+full"]`
+    pub fn new(name: impl Into<String>, entries: &[impl AsRef<str>]) -> CargoResult<Self> {
+        let name = name.into();
+        let entries = entries
+            .iter()
+            .map(|entry| entry.as_ref().parse())
+            .collect::<CargoResult<Vec<PresetEntry>>>()
+            .with_context(|| format!("Invalid preset `{}`", name))?;
+        Ok(Self { name, entries })
+    }
+}
+
+/// Find the preset `name` for the manifest at `manifest_path`
+///
+/// Presets are looked up in order in
+/// - `[package.metadata.cargo-edit.presets]` and `[workspace.metadata.cargo-edit.presets]` of
+///   the manifest
+/// - `[workspace.metadata.cargo-edit.presets]` of the workspace root
+/// - `[cargo-edit.presets]` in cargo config
+pub fn find_preset(manifest_path: &Path, name: &str) -> CargoResult<DependencyPreset> {
+    let manifest = LocalManifest::try_new(manifest_path)?;
+    if let Some(entries) = metadata_preset(&manifest, name)? {
+        return DependencyPreset::new(name, &entries);
+    }
+    let root_path = workspace_root_manifest(Some(manifest_path))?;
+    if root_path != manifest.path {
+        let root = LocalManifest::try_new(&root_path)?;
+        if let Some(entries) = metadata_preset(&root, name)? {
+            return DependencyPreset::new(name, &entries);
+        }
+    }
+    let work_dir = manifest_path
+        .parent()
+        .expect("there must be a parent directory");
+    match preset_config(work_dir, name)? {
+        Some(entries) => DependencyPreset::new(name, &entries),
+        None => anyhow::bail!(
+            "No preset `{}`, define it in `[package.metadata.cargo-edit.presets]`, \
+             `[workspace.metadata.cargo-edit.presets]` or `[cargo-edit.presets]` in cargo config",
+            name
+        ),
+    }
+}
+
+fn metadata_preset(manifest: &Manifest, name: &str) -> CargoResult<Option<Vec<String>>> {
+    for table in ["package", "workspace"] {
+        let entries = match manifest
+            .data
+            .get(table)
+            .and_then(|t| t.get("metadata"))
+            .and_then(|m| m.get("cargo-edit"))
+            .and_then(|c| c.get("presets"))
+            .and_then(|p| p.get(name))
+        {
+            Some(entries) => entries,
+            None => continue,
+        };
+        let invalid = || {
+            anyhow::format_err!(
+                "Expected `{}.metadata.cargo-edit.presets.{}` to be an array of dependencies, like `[\"tokio@1+full\"]`",
+                table,
+                name
+            )
+        };
+        let entries = entries
+            .as_array()
+            .ok_or_else(invalid)?
+            .iter()
+            .map(|entry| entry.as_str().map(str::to_owned).ok_or_else(invalid))
+            .collect::<CargoResult<_>>()?;
+        return Ok(Some(entries));
+    }
+    Ok(None)
+}
+
+/// Add the dependencies of `preset` to the `table` of `manifest`, like `["dev-dependencies"]`,
+/// without writing it
+///
+/// Versions are looked up, as `options` say, for every entry before the manifest is changed, so
+/// it is left alone if any lookup fails.  Dependencies already in the table are handled by
+/// `strategy`; [`MergeStrategy::Merge`] keeps their customizations and git source, with the
+/// preset's features added.  The warnings of the lookups are returned for the caller to report.
+pub fn add_preset(
+    manifest: &mut LocalManifest,
+    preset: &DependencyPreset,
+    table: &[String],
+    strategy: MergeStrategy,
+    options: &LookupOptions,
+) -> CargoResult<(Vec<DependencyAddition>, Vec<LookupWarning>)> {
+    let (dependencies, warnings) = resolve_preset(preset, options, &manifest.path)?;
+    let additions = dependencies
+        .iter()
+        .map(|dependency| insert_dependency(manifest, table, dependency, strategy))
+        .collect::<CargoResult<_>>()?;
+    Ok((additions, warnings))
+}
+
+/// Add the dependencies of `preset` to the `table` of each manifest, together
+///
+/// Like [`add_dependency_to_manifests`](crate::add_dependency_to_manifests), all manifests are
+/// changed or none are, and they are written as `write_options` says.  Versions are looked up
+/// once, for the first manifest, as `options` say.
+pub fn add_preset_to_manifests(
+    manifest_paths: &[PathBuf],
+    preset: &DependencyPreset,
+    table: &[String],
+    strategy: MergeStrategy,
+    options: &LookupOptions,
+    write_options: &WriteOptions,
+) -> CargoResult<(Vec<DependencyAddition>, Vec<LookupWarning>)> {
+    let first = match manifest_paths.first() {
+        Some(first) => first,
+        None => return Ok((Vec::new(), Vec::new())),
+    };
+    let (dependencies, warnings) = resolve_preset(preset, options, first)?;
+    let mut manifests = Vec::new();
+    let mut additions = Vec::new();
+    for path in manifest_paths {
+        let mut manifest = LocalManifest::try_new(path)?;
+        for dependency in &dependencies {
+            additions.push(
+                insert_dependency(&mut manifest, table, dependency, strategy).with_context(
+                    || {
+                        format!(
+                            "Failed to add preset `{}` to `{}`",
+                            preset.name,
+                            path.display()
+                        )
+                    },
+                )?,
+            );
+        }
+        manifests.push(manifest);
+    }
+    write_together(&manifests, write_options)?;
+    Ok((additions, warnings))
+}
+
+/// The dependencies of `preset`, with the latest version of entries that don't name one
+fn resolve_preset(
+    preset: &DependencyPreset,
+    options: &LookupOptions,
+    manifest_path: &Path,
+) -> CargoResult<(Vec<Dependency>, Vec<LookupWarning>)> {
+    let mut dependencies = Vec::new();
+    let mut warnings = Vec::new();
+    for entry in &preset.entries {
+        let (name, version) = match &entry.version_req {
+            Some(version_req) => (entry.name.clone(), version_req.clone()),
+            None => {
+                let (latest, lookup_warnings) =
+                    get_latest_dependency(&entry.name, options, manifest_path, None).with_context(
+                        || {
+                            format!(
+                                "Failed to resolve `{}` in preset `{}`",
+                                entry.name, preset.name
+                            )
+                        },
+                    )?;
+                warnings.extend(lookup_warnings);
+                let version = latest
+                    .version()
+                    .expect("registry packages always have a version")
+                    .to_owned();
+                (latest.name, version)
+            }
+        };
+        let dependency = Dependency::new(&name).set_source(RegistrySource::new(version));
+        let dependency = if entry.features.is_empty() {
+            dependency
+        } else {
+            dependency.set_features(entry.features.clone())
+        };
+        dependencies.push(dependency);
+    }
+    Ok((dependencies, warnings))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_entries() {
+        assert_eq!(
+            "tokio@1+full+macros".parse::<PresetEntry>().unwrap(),
+            PresetEntry {
+                name: "tokio".to_owned(),
+                version_req: Some("1".to_owned()),
+                features: vec!["full".to_owned(), "macros".to_owned()],
+            }
+        );
+        let axum: PresetEntry = "axum".parse().unwrap();
+        assert_eq!(axum.version_req, None);
+        assert!(axum.features.is_empty());
+        assert!("serde+".parse::<PresetEntry>().is_err());
+        assert!("serde@".parse::<PresetEntry>().is_err());
+    }
+
+    #[test]
+    fn add_preset_merges_existing() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let path = temp.path().join("Cargo.toml");
+        std::fs::write(
+            &path,
+            r#"[package]
+name = "app"
+version = "0.1.0"
+
+[package.metadata.cargo-edit.presets]
+web = ["tokio@1+full", "serde@1.0+derive"]
+
+[dependencies]
+serde = { version = "1.0.100", default-features = false }
+tokio = { git = "https://github.com/tokio-rs/tokio" }
+"#,
+        )
+        .unwrap();
+        let mut manifest = LocalManifest::try_new(&path).unwrap();
+        let entries = metadata_preset(&manifest, "web").unwrap().unwrap();
+        let preset = DependencyPreset::new("web", &entries).unwrap();
+        assert_eq!(metadata_preset(&manifest, "cli").unwrap(), None);
+
+        let (additions, _) = add_preset(
+            &mut manifest,
+            &preset,
+            &["dependencies".to_owned()],
+            MergeStrategy::Merge,
+            &LookupOptions::new(),
+        )
+        .unwrap();
+        assert_eq!(additions.len(), 2);
+        let deps = &manifest.data["dependencies"];
+        assert_eq!(
+            deps["tokio"]["git"].as_str(),
+            Some("https://github.com/tokio-rs/tokio")
+        );
+        assert!(deps["tokio"].get("version").is_none());
+        assert_eq!(
+            deps["tokio"]["features"]
+                .as_array()
+                .unwrap()
+                .get(0)
+                .unwrap()
+                .as_str(),
+            Some("full")
+        );
+        assert_eq!(deps["serde"]["version"].as_str(), Some("1.0"));
+        assert_eq!(deps["serde"]["default-features"].as_bool(), Some(false));
+        assert_eq!(
+            deps["serde"]["features"]
+                .as_array()
+                .unwrap()
+                .get(0)
+                .unwrap()
+                .as_str(),
+            Some("derive")
+        );
+    }
+}
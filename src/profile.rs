@@ -0,0 +1,281 @@
+//! Address `[profile]` settings, and check them against what cargo accepts
+use std::fmt;
+use std::str::FromStr;
+
+use super::errors::*;
+
+/// Settings cargo accepts in a profile, with the values they take
+const SETTINGS: &[(&str, &str)] = &[
+    ("opt-level", "0, 1, 2, 3, `s` or `z`"),
+    (
+        "debug",
+        "a boolean, 0, 1, 2, `none`, `line-directives-only`, `line-tables-only`, `limited` or `full`",
+    ),
+    ("split-debuginfo", "`off`, `packed` or `unpacked`"),
+    ("strip", "a boolean, `none`, `debuginfo` or `symbols`"),
+    ("debug-assertions", "a boolean"),
+    ("overflow-checks", "a boolean"),
+    ("lto", "a boolean, `off`, `thin` or `fat`"),
+    ("panic", "`unwind` or `abort`"),
+    ("incremental", "a boolean"),
+    ("codegen-units", "a positive integer"),
+    ("rpath", "a boolean"),
+    ("inherits", "a profile name"),
+];
+
+/// Settings that only apply to a whole profile, not to packages or build scripts
+const PROFILE_ONLY: &[&str] = &["panic", "lto", "rpath", "inherits"];
+
+/// A setting in `[profile]`, like `release.lto` or `release.package."*".opt-level`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProfileKey {
+    /// Name of the profile, like `release`
+    pub profile: String,
+    /// What the setting applies to
+    pub scope: ProfileScope,
+    /// Name of the setting, like `opt-level`
+    pub setting: String,
+}
+
+/// What a profile setting applies to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProfileScope {
+    /// Everything built with the profile
+    Profile,
+    /// Packages matching a spec, like `*` or `serde`, in `package.<spec>`
+    Package(String),
+    /// Build scripts, proc-macros and their dependencies, in `build-override`
+    BuildOverride,
+}
+
+impl ProfileKey {
+    /// Path of the table holding the setting, like `["profile", "release", "package", "*"]`
+    pub fn table_path(&self) -> Vec<String> {
+        let mut path = vec!["profile".to_owned(), self.profile.clone()];
+        match &self.scope {
+            ProfileScope::Profile => {}
+            ProfileScope::Package(spec) => {
+                path.push("package".to_owned());
+                path.push(spec.clone());
+            }
+            ProfileScope::BuildOverride => path.push("build-override".to_owned()),
+        }
+        path
+    }
+
+    /// Read a value written on the command line, like `thin`, `true` or `3`, and check the
+    /// setting accepts it
+    pub fn parse_value(&self, value: &str) -> CargoResult<toml_edit::Value> {
+        let parsed = match value {
+            "true" => toml_edit::Value::from(true),
+            "false" => toml_edit::Value::from(false),
+            _ => match value.parse::<i64>() {
+                Ok(integer) => toml_edit::Value::from(integer),
+                Err(_) => toml_edit::Value::from(value),
+            },
+        };
+        self.check_value(&parsed)?;
+        Ok(parsed)
+    }
+
+    /// Check the setting accepts `value`
+    pub fn check_value(&self, value: &toml_edit::Value) -> CargoResult<()> {
+        if accepts(&self.setting, value) {
+            return Ok(());
+        }
+        let expected = SETTINGS
+            .iter()
+            .find(|(name, _)| *name == self.setting)
+            .map(|(_, expected)| *expected)
+            .expect("checked when parsing the key");
+        let shown = match value.as_str() {
+            Some(s) => s.to_owned(),
+            None => value.to_string().trim().to_owned(),
+        };
+        anyhow::bail!(
+            "Invalid value `{}` for `{}`, expected {}",
+            shown,
+            self,
+            expected
+        )
+    }
+}
+
+impl FromStr for ProfileKey {
+    type Err = Error;
+
+    fn from_str(key: &str) -> CargoResult<Self> {
+        let segments = split_key(key)?;
+        let (profile, scope, setting) = match segments.as_slice() {
+            [profile, setting] => (profile, ProfileScope::Profile, setting),
+            [profile, scope, setting] if scope == "build-override" => {
+                (profile, ProfileScope::BuildOverride, setting)
+            }
+            [profile, package, spec, setting] if package == "package" => {
+                (profile, ProfileScope::Package(spec.clone()), setting)
+            }
+            _ => anyhow::bail!(
+                "Invalid profile key `{}`, expected `<PROFILE>.<SETTING>`, \
+                 `<PROFILE>.package.<SPEC>.<SETTING>` or `<PROFILE>.build-override.<SETTING>`",
+                key
+            ),
+        };
+        if !is_profile_name(profile) {
+            anyhow::bail!("Invalid profile name `{}`", profile);
+        }
+        if !SETTINGS.iter().any(|(name, _)| name == setting) {
+            let known: Vec<_> = SETTINGS.iter().map(|(name, _)| *name).collect();
+            anyhow::bail!(
+                "Unknown profile setting `{}`, expected one of {}",
+                setting,
+                known.join(", ")
+            );
+        }
+        if scope != ProfileScope::Profile && PROFILE_ONLY.contains(&setting.as_str()) {
+            anyhow::bail!(
+                "`{}` can only be set for a whole profile, like `{}.{}`",
+                setting,
+                profile,
+                setting
+            );
+        }
+        Ok(Self {
+            profile: profile.clone(),
+            scope,
+            setting: setting.clone(),
+        })
+    }
+}
+
+impl fmt::Display for ProfileKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.", self.profile)?;
+        match &self.scope {
+            ProfileScope::Profile => {}
+            ProfileScope::Package(spec) if is_bare_key(spec) => write!(f, "package.{}.", spec)?,
+            ProfileScope::Package(spec) => write!(f, "package.\"{}\".", spec)?,
+            ProfileScope::BuildOverride => write!(f, "build-override.")?,
+        }
+        write!(f, "{}", self.setting)
+    }
+}
+
+/// Split a dotted key, where quoted segments may contain dots, like `release.package."*".lto`
+fn split_key(key: &str) -> CargoResult<Vec<String>> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in key.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            '.' if !quoted => segments.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    if quoted {
+        anyhow::bail!("Unterminated quote in `{}`", key);
+    }
+    segments.push(current);
+    if segments.iter().any(|s| s.is_empty()) {
+        anyhow::bail!("Empty segment in `{}`", key);
+    }
+    Ok(segments)
+}
+
+fn is_bare_key(key: &str) -> bool {
+    key.chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn is_profile_name(name: &str) -> bool {
+    !name.is_empty() && is_bare_key(name)
+}
+
+fn accepts(setting: &str, value: &toml_edit::Value) -> bool {
+    use toml_edit::Value;
+
+    let one_of = |s: &str, allowed: &[&str]| allowed.contains(&s);
+    match (setting, value) {
+        ("opt-level", Value::Integer(i)) => (0..=3).contains(i.value()),
+        ("opt-level", Value::String(s)) => one_of(s.value(), &["s", "z"]),
+        ("debug", Value::Integer(i)) => (0..=2).contains(i.value()),
+        ("debug", Value::String(s)) => one_of(
+            s.value(),
+            &[
+                "none",
+                "line-directives-only",
+                "line-tables-only",
+                "limited",
+                "full",
+            ],
+        ),
+        ("split-debuginfo", Value::String(s)) => one_of(s.value(), &["off", "packed", "unpacked"]),
+        ("strip", Value::String(s)) => one_of(s.value(), &["none", "debuginfo", "symbols"]),
+        ("lto", Value::String(s)) => one_of(s.value(), &["off", "thin", "fat"]),
+        ("panic", Value::String(s)) => one_of(s.value(), &["unwind", "abort"]),
+        ("codegen-units", Value::Integer(i)) => *i.value() > 0,
+        ("inherits", Value::String(s)) => is_profile_name(s.value()),
+        (
+            "debug" | "strip" | "debug-assertions" | "overflow-checks" | "lto" | "incremental"
+            | "rpath",
+            Value::Boolean(_),
+        ) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_keys() {
+        let key: ProfileKey = "release.lto".parse().unwrap();
+        assert_eq!(key.table_path(), ["profile", "release"]);
+        assert_eq!(key.setting, "lto");
+
+        let key: ProfileKey = r#"dev.package."*".opt-level"#.parse().unwrap();
+        assert_eq!(key.scope, ProfileScope::Package("*".to_owned()));
+        assert_eq!(key.table_path(), ["profile", "dev", "package", "*"]);
+        assert_eq!(key.to_string(), r#"dev.package."*".opt-level"#);
+
+        let key: ProfileKey = r#"dev.package."serde@1.0.0".debug"#.parse().unwrap();
+        assert_eq!(key.scope, ProfileScope::Package("serde@1.0.0".to_owned()));
+
+        let key: ProfileKey = "release.build-override.opt-level".parse().unwrap();
+        assert_eq!(key.to_string(), "release.build-override.opt-level");
+    }
+
+    #[test]
+    fn reject_keys() {
+        assert!("release".parse::<ProfileKey>().is_err());
+        assert!("release.optimize".parse::<ProfileKey>().is_err());
+        assert!(r#"release.package."*".lto"#.parse::<ProfileKey>().is_err());
+        assert!("release.build-override.panic"
+            .parse::<ProfileKey>()
+            .is_err());
+        assert!(r#"release.package."*.opt-level"#.parse::<ProfileKey>().is_err());
+        assert!("release..lto".parse::<ProfileKey>().is_err());
+    }
+
+    #[test]
+    fn parse_values() {
+        let value = |key: &str, value| {
+            key.parse::<ProfileKey>()
+                .unwrap()
+                .parse_value(value)
+                .map(|v| v.to_string())
+        };
+        assert_eq!(value("release.lto", "thin").unwrap(), r#""thin""#);
+        assert_eq!(value("release.lto", "true").unwrap(), "true");
+        assert_eq!(value("release.opt-level", "3").unwrap(), "3");
+        assert_eq!(value("release.opt-level", "z").unwrap(), r#""z""#);
+        assert_eq!(value("dev.debug", "1").unwrap(), "1");
+        assert_eq!(value("release.panic", "abort").unwrap(), r#""abort""#);
+        assert!(value("release.lto", "thinner").is_err());
+        assert!(value("release.opt-level", "4").is_err());
+        assert!(value("release.panic", "true").is_err());
+        assert!(value("release.codegen-units", "0").is_err());
+        assert!(value("release.debug-assertions", "yes").is_err());
+    }
+}
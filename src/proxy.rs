@@ -0,0 +1,118 @@
+use std::env;
+use std::path::Path;
+
+use url::Url;
+
+use super::errors::*;
+use super::registry::http_proxy_config;
+
+/// Find the proxy to connect to `url` through, if any
+///
+/// Like cargo, `[http] proxy` in cargo config takes precedence over the environment.  Config
+/// files are looked up from `work_dir`, like the manifest's directory, up to `$CARGO_HOME`.  From
+/// the environment, the scheme-specific `https_proxy`/`http_proxy` win over `ALL_PROXY`.  Hosts
+/// matched by `NO_PROXY` always connect directly.
+pub fn proxy_for_url(url: &Url, work_dir: &Path) -> CargoResult<Option<String>> {
+    if let (Some(host), Some(no_proxy)) = (url.host_str(), env_var(&["no_proxy", "NO_PROXY"])) {
+        if no_proxy_matches(&no_proxy, host) {
+            return Ok(None);
+        }
+    }
+
+    if let Some(proxy) = http_proxy_config(work_dir)? {
+        return Ok(Some(proxy));
+    }
+
+    let scheme_proxy = match url.scheme() {
+        "https" => env_var(&["https_proxy", "HTTPS_PROXY"]),
+        // `HTTP_PROXY` is deliberately ignored, see httpoxy
+        "http" => env_var(&["http_proxy"]),
+        _ => None,
+    };
+    Ok(scheme_proxy.or_else(|| env_var(&["all_proxy", "ALL_PROXY"])))
+}
+
+/// libgit2 options to connect to `url` through the proxy [`proxy_for_url`] picks, or directly
+///
+/// They are passed to each fetch rather than set in the environment, so updates running in
+/// parallel and the commands run by hooks aren't affected.
+#[cfg(feature = "git")]
+pub(crate) fn git_proxy_options(
+    url: &Url,
+    work_dir: &Path,
+) -> CargoResult<git2::ProxyOptions<'static>> {
+    let mut options = git2::ProxyOptions::new();
+    if let Some(proxy) = proxy_for_url(url, work_dir)? {
+        options.url(&proxy);
+    }
+    Ok(options)
+}
+
+fn env_var(names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.trim().is_empty())
+}
+
+/// Check `host` against a comma-separated `NO_PROXY` list
+///
+/// Entries match the host itself and any of its subdomains; `*` matches everything.
+fn no_proxy_matches(no_proxy: &str, host: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    no_proxy
+        .split(',')
+        .map(|entry| entry.trim())
+        .filter(|entry| !entry.is_empty())
+        .any(|entry| {
+            if entry == "*" {
+                return true;
+            }
+            // Ignore ports, but not the colons in an IPv6 address
+            let entry = match entry.rsplit_once(':') {
+                Some((entry, port))
+                    if !entry.contains(':') && port.bytes().all(|b| b.is_ascii_digit()) =>
+                {
+                    entry
+                }
+                _ => entry,
+            };
+            let entry = entry
+                .trim_start_matches("*.")
+                .trim_start_matches('.')
+                .trim_end_matches('.')
+                .to_ascii_lowercase();
+            host == entry
+                || host
+                    .strip_suffix(&entry)
+                    .map(|prefix| prefix.ends_with('.'))
+                    .unwrap_or(false)
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn no_proxy_wildcard() {
+        assert!(no_proxy_matches("*", "crates.io"));
+    }
+
+    #[test]
+    fn no_proxy_domain_and_subdomains() {
+        assert!(no_proxy_matches("example.com", "example.com"));
+        assert!(no_proxy_matches("example.com", "index.example.com"));
+        assert!(no_proxy_matches(".example.com", "index.example.com"));
+        assert!(no_proxy_matches("*.example.com", "index.example.com"));
+        assert!(!no_proxy_matches("example.com", "badexample.com"));
+        assert!(!no_proxy_matches("index.example.com", "example.com"));
+    }
+
+    #[test]
+    fn no_proxy_list() {
+        assert!(no_proxy_matches("localhost, github.com", "github.com"));
+        assert!(no_proxy_matches("localhost,GitHub.com:443", "github.com"));
+        assert!(!no_proxy_matches("localhost,,", "github.com"));
+    }
+}
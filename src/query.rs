@@ -0,0 +1,219 @@
+//! Answer questions about the dependencies a workspace's manifests declare, for tooling
+use std::path::Path;
+
+use super::errors::*;
+use super::metadata::workspace_members;
+use super::{Dependency, LocalManifest, Source};
+
+/// A dependency, as a member's manifest declares it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeclaredDependency {
+    /// Name of the member declaring it, or the manifest's path for a virtual manifest
+    pub member: String,
+    /// Path to the dependency table, like `["target", "cfg(unix)", "dependencies"]`
+    pub table: Vec<String>,
+    /// The entry, with its requirement, features and source
+    pub dependency: Dependency,
+}
+
+/// Read-only queries over the manifests of a workspace
+#[derive(Debug)]
+pub struct ManifestQuery {
+    members: Vec<(String, LocalManifest)>,
+}
+
+impl ManifestQuery {
+    /// Query `manifests`, naming each by its `package.name`
+    pub fn new(manifests: Vec<LocalManifest>) -> Self {
+        let members = manifests
+            .into_iter()
+            .map(|manifest| {
+                let name = manifest
+                    .data
+                    .get("package")
+                    .and_then(|p| p.get("name"))
+                    .and_then(|n| n.as_str())
+                    .map(str::to_owned)
+                    .unwrap_or_else(|| manifest.path.display().to_string());
+                (name, manifest)
+            })
+            .collect();
+        Self { members }
+    }
+
+    /// Query the members of the workspace at `manifest_path`, or of the current directory
+    pub fn from_workspace(manifest_path: Option<&Path>) -> CargoResult<Self> {
+        let manifests = workspace_members(manifest_path)?
+            .iter()
+            .map(|package| LocalManifest::try_new(package.manifest_path.as_std_path()))
+            .collect::<CargoResult<Vec<_>>>()?;
+        Ok(Self::new(manifests))
+    }
+
+    /// Names of the members, in the order they're queried
+    pub fn members(&self) -> impl Iterator<Item = &str> {
+        self.members.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Every declaration of the crate `name` by a member, renamed or not
+    pub fn declarations_of(&self, name: &str) -> CargoResult<Vec<DeclaredDependency>> {
+        Ok(self
+            .declarations()?
+            .into_iter()
+            .filter(|declared| declared.dependency.name == name)
+            .collect())
+    }
+
+    /// Dependency tables of each member that contain the crate `name`, as `(member, table)`
+    pub fn sections_with(&self, name: &str) -> CargoResult<Vec<(String, Vec<String>)>> {
+        Ok(self
+            .declarations_of(name)?
+            .into_iter()
+            .map(|declared| (declared.member, declared.table))
+            .collect())
+    }
+
+    /// How `member` declares the crate `name`, once per table declaring it
+    pub fn declared_by(&self, member: &str, name: &str) -> CargoResult<Vec<DeclaredDependency>> {
+        if !self.members().any(|m| m == member) {
+            anyhow::bail!("`{}` isn't a member of the workspace", member);
+        }
+        Ok(self
+            .declarations_of(name)?
+            .into_iter()
+            .filter(|declared| declared.member == member)
+            .collect())
+    }
+
+    /// Members depending on `member`, by path or through `[workspace.dependencies]`
+    pub fn dependents_of(&self, member: &str) -> CargoResult<Vec<String>> {
+        let manifest = self
+            .members
+            .iter()
+            .find(|(name, _)| name == member)
+            .map(|(_, manifest)| manifest)
+            .with_context(|| format!("`{}` isn't a member of the workspace", member))?;
+        let root = manifest.path.parent().expect("at least a parent");
+        let root = dunce::canonicalize(root).unwrap_or_else(|_| root.to_owned());
+
+        let mut dependents = Vec::new();
+        for declared in self.declarations_of(member)? {
+            let depends = match declared.dependency.source() {
+                Some(Source::Path(source)) => {
+                    dunce::canonicalize(&source.path).map_or(false, |path| path == root)
+                }
+                Some(Source::Workspace(_)) => true,
+                _ => false,
+            };
+            if depends && !dependents.contains(&declared.member) {
+                dependents.push(declared.member);
+            }
+        }
+        Ok(dependents)
+    }
+
+    fn declarations(&self) -> CargoResult<Vec<DeclaredDependency>> {
+        let mut declarations = Vec::new();
+        for (member, manifest) in &self.members {
+            let crate_root = manifest.path.parent().expect("at least a parent");
+            for (table, item) in manifest.get_sections() {
+                let entries = item.as_table_like().into_iter().flat_map(|t| t.iter());
+                for (key, entry) in entries {
+                    let dependency =
+                        Dependency::from_toml(crate_root, key, entry).with_context(|| {
+                            format!(
+                                "Invalid dependency `{}` in {}",
+                                key,
+                                manifest.path.display()
+                            )
+                        })?;
+                    declarations.push(DeclaredDependency {
+                        member: member.clone(),
+                        table: table.to_table().into_iter().map(String::from).collect(),
+                        dependency,
+                    });
+                }
+            }
+        }
+        Ok(declarations)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn manifest(path: &str, toml: &str) -> LocalManifest {
+        LocalManifest {
+            path: path.into(),
+            manifest: toml.parse().unwrap(),
+        }
+    }
+
+    fn query() -> ManifestQuery {
+        ManifestQuery::new(vec![
+            manifest(
+                "/ws/app/Cargo.toml",
+                r#"[package]
+name = "app"
+
+[dependencies]
+core = { workspace = true }
+serde = { version = "1.0", features = ["derive"] }
+
+[target.'cfg(unix)'.dev-dependencies]
+serde1 = { package = "serde", version = "1" }
+"#,
+            ),
+            manifest(
+                "/ws/core/Cargo.toml",
+                r#"[package]
+name = "core"
+
+[dependencies]
+serde = "1.0.100"
+"#,
+            ),
+        ])
+    }
+
+    #[test]
+    fn sections() {
+        assert_eq!(
+            query().sections_with("serde").unwrap(),
+            [
+                ("app".to_owned(), vec!["dependencies".to_owned()]),
+                (
+                    "app".to_owned(),
+                    vec![
+                        "target".to_owned(),
+                        "cfg(unix)".to_owned(),
+                        "dev-dependencies".to_owned()
+                    ]
+                ),
+                ("core".to_owned(), vec!["dependencies".to_owned()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn declarations() {
+        let query = query();
+        let declared = query.declared_by("app", "serde").unwrap();
+        assert_eq!(declared.len(), 2);
+        assert_eq!(declared[0].dependency.version(), Some("1.0"));
+        assert_eq!(
+            declared[0].dependency.features.as_deref(),
+            Some(&["derive".to_owned()][..])
+        );
+        assert_eq!(declared[1].dependency.rename(), Some("serde1"));
+        assert!(query.declared_by("lib", "serde").is_err());
+    }
+
+    #[test]
+    fn dependents() {
+        let query = query();
+        assert_eq!(query.dependents_of("core").unwrap(), ["app"]);
+        assert!(query.dependents_of("app").unwrap().is_empty());
+    }
+}
@@ -1,78 +1,74 @@
+use super::blocklist::{BlockLevel, BlockedCrate};
 use super::errors::*;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::SystemTime;
 use url::Url;
 
-const CRATES_IO_INDEX: &str = "https://github.com/rust-lang/crates.io-index";
-const CRATES_IO_REGISTRY: &str = "crates-io";
+pub(crate) const CRATES_IO_INDEX: &str = "https://github.com/rust-lang/crates.io-index";
+pub(crate) const CRATES_IO_REGISTRY: &str = "crates-io";
 
 /// Find the URL of a registry
+///
+/// `registry` is the name a dependency's `registry` field refers to, with `None` (or
+/// `crates-io`) being the default registry.  Names are resolved like cargo does: from
+/// `CARGO_REGISTRIES_<NAME>_INDEX`, then `--config` overrides from [`set_cargo_flags`], then the
+/// `.cargo/config.toml` files from the manifest's directory up to the filesystem root, then
+/// `$CARGO_HOME/config.toml`, following any `[source]` replacement.
 pub fn registry_url(manifest_path: &Path, registry: Option<&str>) -> CargoResult<Url> {
+    let work_dir = manifest_path
+        .parent()
+        .expect("there must be a parent directory");
+    resolve_registry_url(&configs(work_dir)?, registry)
+}
+
+fn resolve_registry_url(configs: &[Arc<CargoConfig>], registry: Option<&str>) -> CargoResult<Url> {
     // TODO support local registry sources, directory sources, git sources: https://doc.rust-lang.org/cargo/reference/source-replacement.html?highlight=replace-with#source-replacement
-    fn read_config(
-        registries: &mut HashMap<String, Source>,
-        path: impl AsRef<Path>,
-    ) -> CargoResult<()> {
-        // TODO unit test for source replacement
-        let content = std::fs::read(path)?;
-        let config = toml_edit::easy::from_slice::<CargoConfig>(&content)
-            .map_err(|_| invalid_cargo_config())?;
-        for (key, value) in config.registries {
-            registries.entry(key).or_insert(Source {
-                registry: value.index,
+    fn read_config(registries: &mut HashMap<String, Source>, config: &CargoConfig) {
+        for (key, value) in &config.registries {
+            registries.entry(key.clone()).or_insert(Source {
+                registry: value.index.clone(),
                 replace_with: None,
             });
         }
-        for (key, value) in config.source {
-            registries.entry(key).or_insert(value);
+        for (key, value) in &config.source {
+            registries
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
         }
-        Ok(())
     }
     // registry might be replaced with another source
     // it's looks like a singly linked list
     // put relations in this map.
     let mut registries: HashMap<String, Source> = HashMap::new();
-    // ref: https://doc.rust-lang.org/cargo/reference/config.html#hierarchical-structure
-    for work_dir in manifest_path
-        .parent()
-        .expect("there must be a parent directory")
-        .ancestors()
-    {
-        let work_cargo_dir = work_dir.join(".cargo");
-        let config_path = work_cargo_dir.join("config");
-        if config_path.is_file() {
-            read_config(&mut registries, config_path)?;
-        } else {
-            let config_path = work_cargo_dir.join("config.toml");
-            if config_path.is_file() {
-                read_config(&mut registries, config_path)?;
-            }
+    if let Some(registry) = registry.filter(|r| !is_crates_io(r)) {
+        if let Some(index) = registry_index_from_env(registry) {
+            registries.insert(
+                registry.to_owned(),
+                Source {
+                    registry: Some(index),
+                    replace_with: None,
+                },
+            );
         }
     }
-
-    let default_cargo_home = cargo_home()?;
-    let default_config_path = default_cargo_home.join("config");
-    if default_config_path.is_file() {
-        read_config(&mut registries, default_config_path)?;
-    } else {
-        let default_config_path = default_cargo_home.join("config.toml");
-        if default_config_path.is_file() {
-            read_config(&mut registries, default_config_path)?;
-        }
+    for config in configs {
+        read_config(&mut registries, config);
     }
 
     // find head of the relevant linked list
     let mut source = match registry {
-        Some(CRATES_IO_INDEX) | None => {
+        Some(r) if !is_crates_io(r) => registries
+            .remove(r)
+            .with_context(|| anyhow::format_err!("The registry '{}' could not be found", r))?,
+        _ => {
             let mut source = registries.remove(CRATES_IO_REGISTRY).unwrap_or_default();
             source
                 .registry
                 .get_or_insert_with(|| CRATES_IO_INDEX.to_string());
             source
         }
-        Some(r) => registries
-            .remove(r)
-            .with_context(|| anyhow::format_err!("The registry '{}' could not be found", r))?,
     };
 
     // search this linked list and find the tail
@@ -96,15 +92,385 @@ pub fn registry_url(manifest_path: &Path, registry: Option<&str>) -> CargoResult
     Ok(registry_url)
 }
 
+fn is_crates_io(registry: &str) -> bool {
+    registry == CRATES_IO_REGISTRY || registry == CRATES_IO_INDEX
+}
+
+/// Cargo's `CARGO_REGISTRIES_<NAME>_INDEX` override
+fn registry_index_from_env(registry: &str) -> Option<String> {
+    let name = registry.to_ascii_uppercase().replace('-', "_");
+    std::env::var(format!("CARGO_REGISTRIES_{}_INDEX", name))
+        .ok()
+        .filter(|index| !index.is_empty())
+}
+
+/// Find the proxy set by `[http] proxy` in cargo config
+///
+/// Config files are looked up from `work_dir` like cargo does, with the closest one winning.
+pub(crate) fn http_proxy_config(work_dir: &Path) -> CargoResult<Option<String>> {
+    for config in configs(work_dir)? {
+        if let Some(proxy) = config.http.proxy.as_ref().filter(|p| !p.is_empty()) {
+            return Ok(Some(proxy.clone()));
+        }
+    }
+    Ok(None)
+}
+
+/// Find the dependency preset `name` in `[cargo-edit.presets]` of cargo config
+///
+/// Config files are looked up from `work_dir` like cargo does, with the closest one winning.
+pub(crate) fn preset_config(work_dir: &Path, name: &str) -> CargoResult<Option<Vec<String>>> {
+    for config in configs(work_dir)? {
+        if let Some(preset) = config.cargo_edit.presets.get(name) {
+            return Ok(Some(preset.clone()));
+        }
+    }
+    Ok(None)
+}
+
+/// The command of hook `name`, like `post-upgrade`, from `[cargo-edit.hooks]` in cargo config
+///
+/// Only `--config` overrides and `$CARGO_HOME/config.toml` are read, see [`user_configs`].
+pub(crate) fn hook_config(name: &str) -> CargoResult<Option<String>> {
+    for config in user_configs()? {
+        if let Some(command) = config.cargo_edit.hooks.get(name) {
+            return Ok(Some(command.clone()));
+        }
+    }
+    Ok(None)
+}
+
+/// Whether hooks in package metadata run, from `cargo-edit.allow-metadata-hooks` in cargo config
+///
+/// Off by default, as metadata comes with the manifests of whatever repository is being edited.
+/// Only `--config` overrides and `$CARGO_HOME/config.toml` are read, see [`user_configs`].
+pub(crate) fn metadata_hooks_allowed() -> CargoResult<bool> {
+    for config in user_configs()? {
+        if let Some(allowed) = config.cargo_edit.allow_metadata_hooks {
+            return Ok(allowed);
+        }
+    }
+    Ok(false)
+}
+
+/// The crate `name` in `[cargo-edit.blocked-crates]` in cargo config, if listed
+pub(crate) fn blocked_crate_config(
+    work_dir: &Path,
+    name: &str,
+) -> CargoResult<Option<BlockedCrate>> {
+    for config in configs(work_dir)? {
+        if let Some(blocked) = config.cargo_edit.blocked_crates.get(name) {
+            let level = match &blocked.level {
+                Some(level) => level
+                    .parse()
+                    .with_context(|| format!("Invalid `cargo-edit.blocked-crates.{}`", name))?,
+                None => BlockLevel::default(),
+            };
+            return Ok(Some(BlockedCrate {
+                name: name.to_owned(),
+                reason: blocked.reason.clone(),
+                alternatives: blocked.alternatives.clone(),
+                level,
+            }));
+        }
+    }
+    Ok(None)
+}
+
+/// Cargo's global flags, which every subcommand accepts
+///
+/// Install with [`set_cargo_flags`] so registry lookups and `cargo` invocations honor them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CargoFlags {
+    offline: bool,
+    locked: bool,
+    config: Vec<String>,
+    unstable: Vec<String>,
+}
+
+impl CargoFlags {
+    /// No flags
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run without accessing the network, like `--offline`
+    pub fn set_offline(mut self, offline: bool) -> Self {
+        self.offline |= offline;
+        self
+    }
+
+    /// Require `Cargo.lock` to be up to date, like `--locked`
+    pub fn set_locked(mut self, locked: bool) -> Self {
+        self.locked |= locked;
+        self
+    }
+
+    /// Both `--offline` and `--locked`, like `--frozen`
+    pub fn set_frozen(self, frozen: bool) -> Self {
+        self.set_offline(frozen).set_locked(frozen)
+    }
+
+    /// Override configuration with `KEY=VALUE` or a config file, like `--config`
+    ///
+    /// Later overrides take precedence.
+    pub fn add_config(mut self, config: impl Into<String>) -> Self {
+        self.config.push(config.into());
+        self
+    }
+
+    /// Enable an unstable cargo feature, like `-Z bindeps`
+    pub fn add_unstable(mut self, feature: impl Into<String>) -> Self {
+        self.unstable.push(feature.into());
+        self
+    }
+
+    /// Whether `Cargo.lock` must be up to date
+    pub fn locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Whether to stay off the network for the manifest at `manifest_path`
+    ///
+    /// Besides `--offline`, cargo goes offline for `CARGO_NET_OFFLINE` and `net.offline` config.
+    pub fn offline(&self, manifest_path: &Path) -> CargoResult<bool> {
+        if self.offline {
+            return Ok(true);
+        }
+        if let Some(offline) = std::env::var("CARGO_NET_OFFLINE")
+            .ok()
+            .filter(|o| !o.is_empty())
+        {
+            return Ok(offline == "true");
+        }
+        let work_dir = manifest_path
+            .parent()
+            .expect("there must be a parent directory");
+        let offline = configs_with(&self.parsed_config()?, work_dir)?
+            .iter()
+            .find_map(|config| config.net.offline);
+        Ok(offline.unwrap_or(false))
+    }
+
+    /// Whether the unstable cargo `feature` is enabled for the manifest at `manifest_path`
+    ///
+    /// Besides `-Z`, nightly cargo reads unstable features from `[unstable]` config.
+    pub fn unstable(&self, manifest_path: &Path, feature: &str) -> CargoResult<bool> {
+        if self.unstable.iter().any(|f| f == feature) {
+            return Ok(true);
+        }
+        let work_dir = manifest_path
+            .parent()
+            .expect("there must be a parent directory");
+        let enabled = configs_with(&self.parsed_config()?, work_dir)?
+            .iter()
+            .find_map(|config| config.unstable.get(feature).and_then(|v| v.as_bool()));
+        Ok(enabled.unwrap_or(false))
+    }
+
+    /// Arguments passing these flags on to `cargo`
+    pub fn cargo_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.offline {
+            args.push("--offline".to_owned());
+        }
+        if self.locked {
+            args.push("--locked".to_owned());
+        }
+        for config in &self.config {
+            args.push("--config".to_owned());
+            args.push(config.clone());
+        }
+        for feature in &self.unstable {
+            args.push("-Z".to_owned());
+            args.push(feature.clone());
+        }
+        args
+    }
+
+    /// `--config` overrides, highest priority first
+    fn parsed_config(&self) -> CargoResult<Vec<Arc<CargoConfig>>> {
+        self.config
+            .iter()
+            .rev()
+            .map(|c| parse_override(c))
+            .collect()
+    }
+}
+
+fn parse_override(config: &str) -> CargoResult<Arc<CargoConfig>> {
+    let path = Path::new(config);
+    if !config.contains('=') && path.is_file() {
+        return load_config(path);
+    }
+    let config = toml_edit::easy::from_str::<CargoConfig>(config).with_context(|| {
+        format!(
+            "`--config {}` is neither `KEY=VALUE` nor a config file",
+            config
+        )
+    })?;
+    Ok(Arc::new(config))
+}
+
+static CARGO_FLAGS: RwLock<Option<(CargoFlags, Vec<Arc<CargoConfig>>)>> = RwLock::new(None);
+
+/// Replace the global flags for the rest of the process
+///
+/// Fails on `--config` overrides cargo wouldn't accept.
+pub fn set_cargo_flags(flags: CargoFlags) -> CargoResult<()> {
+    let overrides = flags.parsed_config()?;
+    *CARGO_FLAGS.write().expect("not poisoned") = Some((flags, overrides));
+    Ok(())
+}
+
+/// The global flags installed with [`set_cargo_flags`]
+pub fn cargo_flags() -> CargoFlags {
+    CARGO_FLAGS
+        .read()
+        .expect("not poisoned")
+        .as_ref()
+        .map(|(flags, _)| flags.clone())
+        .unwrap_or_default()
+}
+
+/// Cargo config that applies to `work_dir`, highest priority first
+fn configs(work_dir: &Path) -> CargoResult<Vec<Arc<CargoConfig>>> {
+    configs_with(&config_overrides(), work_dir)
+}
+
+/// Cargo config the user wrote, rather than the project being edited, highest priority first
+///
+/// This is `--config` overrides and `$CARGO_HOME/config.toml`, skipping the `.cargo/config.toml`
+/// of the project and its parents, for settings that run commands.  Those come with whatever
+/// checkout is being edited.
+fn user_configs() -> CargoResult<Vec<Arc<CargoConfig>>> {
+    let mut configs = config_overrides();
+    if let Some(config_path) = config_path_in(&cargo_home()?) {
+        configs.push(load_config(&config_path)?);
+    }
+    Ok(configs)
+}
+
+fn config_overrides() -> Vec<Arc<CargoConfig>> {
+    CARGO_FLAGS
+        .read()
+        .expect("not poisoned")
+        .as_ref()
+        .map(|(_, overrides)| overrides.clone())
+        .unwrap_or_default()
+}
+
+fn configs_with(
+    overrides: &[Arc<CargoConfig>],
+    work_dir: &Path,
+) -> CargoResult<Vec<Arc<CargoConfig>>> {
+    let mut configs = overrides.to_vec();
+    for config_path in config_paths_in(work_dir, &cargo_home()?) {
+        configs.push(load_config(&config_path)?);
+    }
+    Ok(configs)
+}
+
+fn config_paths_in(work_dir: &Path, cargo_home: &Path) -> Vec<PathBuf> {
+    // ref: https://doc.rust-lang.org/cargo/reference/config.html#hierarchical-structure
+    work_dir
+        .ancestors()
+        .map(|dir| dir.join(".cargo"))
+        .chain(std::iter::once(cargo_home.to_owned()))
+        .filter_map(|cargo_dir| config_path_in(&cargo_dir))
+        .collect()
+}
+
+/// The config file of a `.cargo` directory, preferring the legacy `config` like cargo
+fn config_path_in(cargo_dir: &Path) -> Option<PathBuf> {
+    let config_path = cargo_dir.join("config");
+    if config_path.is_file() {
+        return Some(config_path);
+    }
+    let config_path = cargo_dir.join("config.toml");
+    if config_path.is_file() {
+        return Some(config_path);
+    }
+    None
+}
+
+/// Parsed config files, keyed by path
+///
+/// Entries are reused until the file's size or modification time changes, so resolving the
+/// registry of every dependency doesn't re-read the whole config hierarchy each time.
+#[allow(clippy::type_complexity)]
+static CONFIG_CACHE: Mutex<Option<HashMap<PathBuf, (FileStamp, Arc<CargoConfig>)>>> =
+    Mutex::new(None);
+
+type FileStamp = (u64, Option<SystemTime>);
+
+fn load_config(path: &Path) -> CargoResult<Arc<CargoConfig>> {
+    let metadata = std::fs::metadata(path)?;
+    let stamp = (metadata.len(), metadata.modified().ok());
+
+    let mut cache = CONFIG_CACHE.lock().expect("not poisoned");
+    let cache = cache.get_or_insert_with(HashMap::new);
+    if let Some((cached_stamp, config)) = cache.get(path) {
+        if stamp.1.is_some() && *cached_stamp == stamp {
+            return Ok(Arc::clone(config));
+        }
+    }
+
+    let content = std::fs::read(path)?;
+    let config =
+        toml_edit::easy::from_slice::<CargoConfig>(&content).map_err(|_| invalid_cargo_config())?;
+    let config = Arc::new(config);
+    cache.insert(path.to_owned(), (stamp, Arc::clone(&config)));
+    Ok(config)
+}
+
 #[derive(Debug, Deserialize)]
 struct CargoConfig {
     #[serde(default)]
     registries: HashMap<String, Registry>,
     #[serde(default)]
     source: HashMap<String, Source>,
+    #[serde(default)]
+    http: Http,
+    #[serde(default)]
+    net: Net,
+    #[serde(default)]
+    unstable: HashMap<String, toml_edit::easy::Value>,
+    #[serde(default, rename = "cargo-edit")]
+    cargo_edit: CargoEditConfig,
 }
 
 #[derive(Default, Debug, Deserialize)]
+struct CargoEditConfig {
+    #[serde(default)]
+    presets: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    hooks: HashMap<String, String>,
+    #[serde(rename = "allow-metadata-hooks")]
+    allow_metadata_hooks: Option<bool>,
+    #[serde(default, rename = "blocked-crates")]
+    blocked_crates: HashMap<String, BlockedCrateConfig>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+struct BlockedCrateConfig {
+    reason: Option<String>,
+    #[serde(default)]
+    alternatives: Vec<String>,
+    level: Option<String>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+struct Http {
+    proxy: Option<String>,
+}
+
+#[derive(Default, Debug, Deserialize)]
+struct Net {
+    offline: Option<bool>,
+}
+
+#[derive(Clone, Default, Debug, Deserialize)]
 struct Source {
     #[serde(rename = "replace-with")]
     replace_with: Option<String>,
@@ -116,7 +482,7 @@ struct Registry {
     index: Option<String>,
 }
 
-fn cargo_home() -> CargoResult<PathBuf> {
+pub(crate) fn cargo_home() -> CargoResult<PathBuf> {
     let default_cargo_home = dirs_next::home_dir()
         .map(|x| x.join(".cargo"))
         .with_context(|| anyhow::format_err!("Failed to read home directory"))?;
@@ -146,3 +512,189 @@ mod code_from_cargo {
         DefaultBranch,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A project inside a workspace, with its own `CARGO_HOME`
+    struct Layout {
+        _temp: assert_fs::TempDir,
+        home: PathBuf,
+        workspace: PathBuf,
+        project: PathBuf,
+    }
+
+    impl Layout {
+        fn new() -> Self {
+            let temp = assert_fs::TempDir::new().unwrap();
+            let home = temp.path().join("cargo-home");
+            let workspace = temp.path().join("workspace");
+            let project = workspace.join("project");
+            std::fs::create_dir_all(&home).unwrap();
+            std::fs::create_dir_all(project.join(".cargo")).unwrap();
+            std::fs::create_dir_all(workspace.join(".cargo")).unwrap();
+            Self {
+                _temp: temp,
+                home,
+                workspace,
+                project,
+            }
+        }
+
+        fn resolve(&self, registry: Option<&str>) -> CargoResult<Url> {
+            self.resolve_with(&CargoFlags::new(), registry)
+        }
+
+        fn resolve_with(&self, flags: &CargoFlags, registry: Option<&str>) -> CargoResult<Url> {
+            let mut configs = flags.parsed_config()?;
+            for config_path in config_paths_in(&self.project, &self.home) {
+                configs.push(load_config(&config_path)?);
+            }
+            resolve_registry_url(&configs, registry)
+        }
+    }
+
+    fn write_registry(cargo_dir: &Path, name: &str, index: &str) {
+        std::fs::write(
+            cargo_dir.join("config.toml"),
+            format!("[registries.{}]\nindex = \"{}\"\n", name, index),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn default_registry() {
+        let layout = Layout::new();
+        assert_eq!(layout.resolve(None).unwrap().as_str(), CRATES_IO_INDEX);
+        assert_eq!(
+            layout.resolve(Some(CRATES_IO_REGISTRY)).unwrap().as_str(),
+            CRATES_IO_INDEX
+        );
+    }
+
+    #[test]
+    fn project_config_overrides_workspace_and_home() {
+        let layout = Layout::new();
+        write_registry(&layout.home, "alt", "https://home.example.com/index");
+        write_registry(
+            &layout.workspace.join(".cargo"),
+            "alt",
+            "https://workspace.example.com/index",
+        );
+        write_registry(
+            &layout.project.join(".cargo"),
+            "alt",
+            "https://project.example.com/index",
+        );
+        assert_eq!(
+            layout.resolve(Some("alt")).unwrap().as_str(),
+            "https://project.example.com/index"
+        );
+    }
+
+    #[test]
+    fn falls_back_to_workspace_then_home() {
+        let layout = Layout::new();
+        write_registry(&layout.home, "home-only", "https://home.example.com/index");
+        write_registry(
+            &layout.workspace.join(".cargo"),
+            "workspace-only",
+            "https://workspace.example.com/index",
+        );
+        assert_eq!(
+            layout.resolve(Some("workspace-only")).unwrap().as_str(),
+            "https://workspace.example.com/index"
+        );
+        assert_eq!(
+            layout.resolve(Some("home-only")).unwrap().as_str(),
+            "https://home.example.com/index"
+        );
+        assert!(layout.resolve(Some("missing")).is_err());
+    }
+
+    #[test]
+    fn source_replacement() {
+        let layout = Layout::new();
+        std::fs::write(
+            layout.workspace.join(".cargo/config.toml"),
+            "[source.crates-io]
+replace-with = \"mirror\"
+
+[source.mirror]
+registry = \"https://mirror.example.com/index\"
+",
+        )
+        .unwrap();
+        assert_eq!(
+            layout.resolve(None).unwrap().as_str(),
+            "https://mirror.example.com/index"
+        );
+    }
+
+    #[test]
+    fn modified_config_is_reloaded() {
+        let layout = Layout::new();
+        let cargo_dir = layout.project.join(".cargo");
+        write_registry(&cargo_dir, "alt", "https://old.example.com/index");
+        assert_eq!(
+            layout.resolve(Some("alt")).unwrap().as_str(),
+            "https://old.example.com/index"
+        );
+        write_registry(&cargo_dir, "alt", "https://updated.example.com/index");
+        assert_eq!(
+            layout.resolve(Some("alt")).unwrap().as_str(),
+            "https://updated.example.com/index"
+        );
+    }
+
+    #[test]
+    fn config_overrides_take_precedence() {
+        let layout = Layout::new();
+        write_registry(
+            &layout.project.join(".cargo"),
+            "alt",
+            "https://project.example.com/index",
+        );
+        let flags = CargoFlags::new()
+            .add_config("registries.alt.index = \"https://first.example.com/index\"")
+            .add_config("registries.alt.index = \"https://last.example.com/index\"");
+        assert_eq!(
+            layout.resolve_with(&flags, Some("alt")).unwrap().as_str(),
+            "https://last.example.com/index"
+        );
+
+        let flags = CargoFlags::new().add_config("registries.alt");
+        assert!(layout.resolve_with(&flags, Some("alt")).is_err());
+    }
+
+    #[test]
+    fn frozen_is_offline_and_locked() {
+        let flags = CargoFlags::new()
+            .set_frozen(true)
+            .add_config("net.git-fetch-with-cli=true");
+        assert!(flags.locked());
+        assert!(flags.offline(Path::new("/project/Cargo.toml")).unwrap());
+        assert_eq!(
+            flags.cargo_args(),
+            [
+                "--offline",
+                "--locked",
+                "--config",
+                "net.git-fetch-with-cli=true"
+            ]
+        );
+    }
+
+    #[test]
+    fn unstable_features() {
+        let manifest_path = Path::new("/project/Cargo.toml");
+        let flags = CargoFlags::new().add_unstable("bindeps");
+        assert!(flags.unstable(manifest_path, "bindeps").unwrap());
+        assert!(!flags.unstable(manifest_path, "public-dependency").unwrap());
+        assert_eq!(flags.cargo_args(), ["-Z", "bindeps"]);
+
+        let flags = CargoFlags::new().add_config("unstable.bindeps=true");
+        assert!(flags.unstable(manifest_path, "bindeps").unwrap());
+    }
+}
@@ -0,0 +1,116 @@
+//! Well-known crates that have been deprecated or superseded
+
+/// A deprecated crate and what the ecosystem moved to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Replacement {
+    /// Name of the deprecated crate
+    pub name: &'static str,
+    /// Crates to use instead, in order of preference
+    pub replacements: &'static [&'static str],
+    /// Why the crate was superseded
+    pub reason: &'static str,
+}
+
+impl Replacement {
+    /// The crate to swap in, if there is a single obvious choice
+    pub fn unique_replacement(&self) -> Option<&'static str> {
+        match self.replacements {
+            [replacement] => Some(*replacement),
+            _ => None,
+        }
+    }
+}
+
+/// Keep sorted by `name`
+const REPLACEMENTS: &[Replacement] = &[
+    Replacement {
+        name: "ansi_term",
+        replacements: &["nu-ansi-term"],
+        reason: "unmaintained",
+    },
+    Replacement {
+        name: "atty",
+        replacements: &["is-terminal"],
+        reason: "unmaintained",
+    },
+    Replacement {
+        name: "dotenv",
+        replacements: &["dotenvy"],
+        reason: "unmaintained",
+    },
+    Replacement {
+        name: "error-chain",
+        replacements: &["anyhow", "thiserror"],
+        reason: "deprecated",
+    },
+    Replacement {
+        name: "failure",
+        replacements: &["anyhow", "thiserror"],
+        reason: "deprecated",
+    },
+    Replacement {
+        name: "lazy_static",
+        replacements: &["once_cell"],
+        reason: "superseded",
+    },
+    Replacement {
+        name: "memmap",
+        replacements: &["memmap2"],
+        reason: "unmaintained",
+    },
+    Replacement {
+        name: "quick-error",
+        replacements: &["thiserror"],
+        reason: "superseded",
+    },
+    Replacement {
+        name: "rustc-serialize",
+        replacements: &["serde"],
+        reason: "deprecated",
+    },
+    Replacement {
+        name: "structopt",
+        replacements: &["clap"],
+        reason: "merged into clap's `derive` feature",
+    },
+    Replacement {
+        name: "tempdir",
+        replacements: &["tempfile"],
+        reason: "merged into tempfile",
+    },
+    Replacement {
+        name: "tokio-core",
+        replacements: &["tokio"],
+        reason: "deprecated",
+    },
+];
+
+/// Look up what to use instead of the crate `name`, if it is known to be deprecated
+pub fn find_replacement(name: &str) -> Option<&'static Replacement> {
+    REPLACEMENTS
+        .binary_search_by(|r| r.name.cmp(name))
+        .ok()
+        .map(|i| &REPLACEMENTS[i])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn replacements_are_sorted() {
+        assert!(REPLACEMENTS.windows(2).all(|w| w[0].name < w[1].name));
+    }
+
+    #[test]
+    fn find_known_replacement() {
+        let structopt = find_replacement("structopt").unwrap();
+        assert_eq!(structopt.unique_replacement(), Some("clap"));
+
+        let failure = find_replacement("failure").unwrap();
+        assert_eq!(failure.replacements, ["anyhow", "thiserror"]);
+        assert_eq!(failure.unique_replacement(), None);
+
+        assert_eq!(find_replacement("clap"), None);
+    }
+}
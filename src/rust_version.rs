@@ -0,0 +1,127 @@
+//! Detect the installed Rust toolchain, and the `rust-version` dependencies require
+use std::path::Path;
+
+use super::errors::*;
+use super::registry::cargo_flags;
+use super::LocalManifest;
+
+/// A resolved dependency declaring the oldest Rust it supports
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DependencyRustVersion {
+    /// Package name
+    pub name: String,
+    /// Version in the lockfile
+    pub version: semver::Version,
+    /// Its `rust-version`, like `1.60`
+    pub rust_version: String,
+}
+
+/// Version of the toolchain `rustc`, or `$RUSTC`, runs, like `1.64`
+///
+/// Only the major and minor versions are kept, as `rust-version` is usually written.
+pub fn installed_rust_version() -> CargoResult<String> {
+    let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let output = std::process::Command::new(&rustc)
+        .arg("--version")
+        .output()
+        .with_context(|| format!("Failed to run `{} --version`", rustc.to_string_lossy()))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "`{} --version` failed: {}",
+            rustc.to_string_lossy(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    parse_rustc_version(&stdout)
+        .with_context(|| format!("Unexpected `rustc --version` output `{}`", stdout.trim()))
+}
+
+fn parse_rustc_version(output: &str) -> Option<String> {
+    let version = output.strip_prefix("rustc ")?.split_whitespace().next()?;
+    let version = semver::Version::parse(version).ok()?;
+    Some(format!("{}.{}", version.major, version.minor))
+}
+
+/// Whether `rust_version` is newer than `target`, comparing missing components as `0`
+///
+/// Either being invalid counts as not newer.
+pub fn rust_version_exceeds(rust_version: &str, target: &str) -> bool {
+    match (rust_version_key(rust_version), rust_version_key(target)) {
+        (Some(rust_version), Some(target)) => rust_version > target,
+        _ => false,
+    }
+}
+
+fn rust_version_key(version: &str) -> Option<(u64, u64, u64)> {
+    let mut components = version.trim().split('.').map(|c| c.parse::<u64>());
+    let major = components.next()?.ok()?;
+    let minor = components.next().unwrap_or(Ok(0)).ok()?;
+    let patch = components.next().unwrap_or(Ok(0)).ok()?;
+    components.next().is_none().then(|| (major, minor, patch))
+}
+
+/// The resolved dependencies of the workspace at `manifest_path` that declare a `rust-version`
+///
+/// Dependencies are resolved like `cargo metadata`, honoring the configured cargo flags, and
+/// their `rust-version` is read from the manifests cargo downloaded.
+pub fn dependency_rust_versions(manifest_path: &Path) -> CargoResult<Vec<DependencyRustVersion>> {
+    let mut cmd = cargo_metadata::MetadataCommand::new();
+    cmd.manifest_path(manifest_path);
+    cmd.other_options(cargo_flags().cargo_args());
+    let metadata = cmd
+        .exec()
+        .with_context(|| "Failed to resolve dependencies")?;
+
+    let mut dependencies = Vec::new();
+    for package in metadata.packages {
+        if metadata.workspace_members.contains(&package.id) {
+            continue;
+        }
+        let manifest = match LocalManifest::try_new(package.manifest_path.as_std_path()) {
+            Ok(manifest) => manifest,
+            Err(_) => continue,
+        };
+        let rust_version = manifest
+            .data
+            .get("package")
+            .and_then(|p| p.get("rust-version"))
+            .and_then(|v| v.as_str());
+        if let Some(rust_version) = rust_version {
+            dependencies.push(DependencyRustVersion {
+                name: package.name,
+                version: package.version,
+                rust_version: rust_version.to_owned(),
+            });
+        }
+    }
+    dependencies.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+    Ok(dependencies)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rustc_versions() {
+        assert_eq!(
+            parse_rustc_version("rustc 1.64.0 (a55dd71d5 2022-09-19)\n").as_deref(),
+            Some("1.64")
+        );
+        assert_eq!(
+            parse_rustc_version("rustc 1.66.0-nightly (b8c35ca26 2022-10-15)").as_deref(),
+            Some("1.66")
+        );
+        assert_eq!(parse_rustc_version("cargo 1.64.0"), None);
+    }
+
+    #[test]
+    fn compare_rust_versions() {
+        assert!(rust_version_exceeds("1.65", "1.64"));
+        assert!(rust_version_exceeds("1.64.1", "1.64"));
+        assert!(!rust_version_exceeds("1.64.0", "1.64"));
+        assert!(!rust_version_exceeds("1.9", "1.64"));
+        assert!(!rust_version_exceeds("latest", "1.64"));
+    }
+}
@@ -0,0 +1,336 @@
+//! Policies for picking which published version of a crate to use
+
+use semver::VersionReq;
+
+use super::fetch::CrateVersion;
+use super::rust_version::rust_version_exceeds;
+
+/// Policy for picking a version of a crate out of those published
+///
+/// Candidates include yanked versions and pre-releases, leaving it to the policy whether to
+/// consider them.  Pass one to [`get_selected_dependency`](crate::get_selected_dependency).
+pub trait VersionSelector {
+    /// Pick one of `candidates`, given the requirement currently in the manifest, if any
+    ///
+    /// `None` means no candidate is acceptable.
+    fn select<'c>(
+        &self,
+        candidates: &'c [CrateVersion],
+        current: Option<&VersionReq>,
+    ) -> Option<&'c CrateVersion>;
+}
+
+/// The newest version that isn't yanked or a pre-release
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LatestStable;
+
+impl VersionSelector for LatestStable {
+    fn select<'c>(
+        &self,
+        candidates: &'c [CrateVersion],
+        _current: Option<&VersionReq>,
+    ) -> Option<&'c CrateVersion> {
+        candidates
+            .iter()
+            .filter(|v| !v.yanked && v.version.pre.is_empty())
+            .max_by(|a, b| a.version.cmp(&b.version))
+    }
+}
+
+/// The newest version that isn't yanked, pre-releases included
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Latest;
+
+impl VersionSelector for Latest {
+    fn select<'c>(
+        &self,
+        candidates: &'c [CrateVersion],
+        _current: Option<&VersionReq>,
+    ) -> Option<&'c CrateVersion> {
+        candidates
+            .iter()
+            .filter(|v| !v.yanked)
+            .max_by(|a, b| a.version.cmp(&b.version))
+    }
+}
+
+/// The newest version that isn't yanked and matches the current requirement
+///
+/// Without a requirement, this is [`LatestStable`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LatestCompatible;
+
+impl VersionSelector for LatestCompatible {
+    fn select<'c>(
+        &self,
+        candidates: &'c [CrateVersion],
+        current: Option<&VersionReq>,
+    ) -> Option<&'c CrateVersion> {
+        let current = current.unwrap_or(&VersionReq::STAR);
+        candidates
+            .iter()
+            .filter(|v| !v.yanked && current.matches(&v.version))
+            .max_by(|a, b| a.version.cmp(&b.version))
+    }
+}
+
+/// The newest version that isn't yanked on the pre-release channel of the current requirement,
+/// like `rc` for `2.0.0-rc.3`
+///
+/// Stable versions are candidates too, as a release ends every channel.  Without a pre-release
+/// requirement, this is [`LatestStable`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LatestOnChannel;
+
+impl VersionSelector for LatestOnChannel {
+    fn select<'c>(
+        &self,
+        candidates: &'c [CrateVersion],
+        current: Option<&VersionReq>,
+    ) -> Option<&'c CrateVersion> {
+        let channel = current
+            .and_then(|req| req.comparators.iter().find(|c| !c.pre.is_empty()))
+            .and_then(|c| prerelease_channel(&c.pre));
+        let channel = match channel {
+            Some(channel) => channel,
+            None => return LatestStable.select(candidates, current),
+        };
+        candidates
+            .iter()
+            .filter(|v| {
+                !v.yanked
+                    && (v.version.pre.is_empty()
+                        || prerelease_channel(&v.version.pre) == Some(channel))
+            })
+            .max_by(|a, b| a.version.cmp(&b.version))
+    }
+}
+
+/// Name of the channel a pre-release is on, like `beta` for `beta.2` or `beta2`
+fn prerelease_channel(pre: &semver::Prerelease) -> Option<&str> {
+    let first = pre.as_str().split('.').next()?;
+    let name = first.trim_end_matches(|c: char| c.is_ascii_digit());
+    (!name.is_empty()).then(|| name)
+}
+
+/// The oldest version that isn't yanked and matches the current requirement, like cargo's
+/// `-Z minimal-versions`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MinimalVersion;
+
+impl VersionSelector for MinimalVersion {
+    fn select<'c>(
+        &self,
+        candidates: &'c [CrateVersion],
+        current: Option<&VersionReq>,
+    ) -> Option<&'c CrateVersion> {
+        let current = current.unwrap_or(&VersionReq::STAR);
+        candidates
+            .iter()
+            .filter(|v| !v.yanked && current.matches(&v.version))
+            .min_by(|a, b| a.version.cmp(&b.version))
+    }
+}
+
+/// The version `selector` picks among those a package can build with
+///
+/// Versions whose `rust-version` is newer than the package's are skipped, like cargo does unless
+/// passed `--ignore-rust-version`, and so are versions on a newer edition than the package.
+/// Versions not recording either are kept.
+#[derive(Copy, Clone)]
+pub struct SupportedBy<'s> {
+    selector: &'s dyn VersionSelector,
+    rust_version: Option<&'s str>,
+    edition: Option<&'s str>,
+}
+
+impl<'s> SupportedBy<'s> {
+    /// Pick with `selector`, without skipping any version yet
+    pub fn new(selector: &'s dyn VersionSelector) -> Self {
+        Self {
+            selector,
+            rust_version: None,
+            edition: None,
+        }
+    }
+
+    /// Skip versions needing a newer Rust than `rust_version`
+    pub fn set_rust_version(mut self, rust_version: Option<&'s str>) -> Self {
+        self.rust_version = rust_version;
+        self
+    }
+
+    /// Skip versions on a newer edition than `edition`
+    pub fn set_edition(mut self, edition: Option<&'s str>) -> Self {
+        self.edition = edition;
+        self
+    }
+
+    fn supports(&self, candidate: &CrateVersion) -> bool {
+        if let (Some(max), Some(needed)) = (self.rust_version, candidate.rust_version.as_deref()) {
+            if rust_version_exceeds(needed, max) {
+                return false;
+            }
+        }
+        if let (Some(max), Some(needed)) = (self.edition, candidate.edition.as_deref()) {
+            if let (Ok(max), Ok(needed)) = (max.parse::<u16>(), needed.parse::<u16>()) {
+                if max < needed {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+impl std::fmt::Debug for SupportedBy<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SupportedBy")
+            .field("rust_version", &self.rust_version)
+            .field("edition", &self.edition)
+            .finish_non_exhaustive()
+    }
+}
+
+impl VersionSelector for SupportedBy<'_> {
+    fn select<'c>(
+        &self,
+        candidates: &'c [CrateVersion],
+        current: Option<&VersionReq>,
+    ) -> Option<&'c CrateVersion> {
+        let supported: Vec<_> = candidates
+            .iter()
+            .filter(|v| self.supports(v))
+            .cloned()
+            .collect();
+        let picked = self.selector.select(&supported, current)?;
+        candidates.iter().find(|v| v.version == picked.version)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn versions() -> Vec<CrateVersion> {
+        [
+            ("1.0.0", false),
+            ("1.1.0", false),
+            ("1.2.0", true),
+            ("2.0.0-rc.1", false),
+        ]
+        .iter()
+        .map(|(version, yanked)| CrateVersion {
+            name: "foo".to_owned(),
+            version: version.parse().unwrap(),
+            yanked: *yanked,
+            available_features: Default::default(),
+            rust_version: None,
+            edition: None,
+            checksum: None,
+        })
+        .collect()
+    }
+
+    fn select(selector: &dyn VersionSelector, current: Option<&str>) -> Option<String> {
+        let versions = versions();
+        let current = current.map(|c| c.parse().unwrap());
+        selector
+            .select(&versions, current.as_ref())
+            .map(|v| v.version.to_string())
+    }
+
+    #[test]
+    fn built_in_selectors() {
+        assert_eq!(select(&LatestStable, None).as_deref(), Some("1.1.0"));
+        assert_eq!(select(&Latest, None).as_deref(), Some("2.0.0-rc.1"));
+        assert_eq!(
+            select(&LatestCompatible, Some("1.0")).as_deref(),
+            Some("1.1.0")
+        );
+        assert_eq!(select(&LatestCompatible, Some("3")), None);
+        assert_eq!(
+            select(&MinimalVersion, Some("1.1")).as_deref(),
+            Some("1.1.0")
+        );
+        assert_eq!(select(&MinimalVersion, None).as_deref(), Some("1.0.0"));
+    }
+
+    #[test]
+    fn prerelease_channels() {
+        let versions: Vec<_> = [
+            "1.9.0",
+            "2.0.0-alpha.4",
+            "2.0.0-beta.1",
+            "2.0.0-rc.2",
+            "2.0.0-rc3",
+            "2.1.0-alpha.1",
+        ]
+        .iter()
+        .map(|version| CrateVersion {
+            name: "foo".to_owned(),
+            version: version.parse().unwrap(),
+            yanked: false,
+            available_features: Default::default(),
+            rust_version: None,
+            edition: None,
+            checksum: None,
+        })
+        .collect();
+        let select = |current: &str| {
+            let current = current.parse().unwrap();
+            LatestOnChannel
+                .select(&versions, Some(&current))
+                .map(|v| v.version.to_string())
+        };
+        assert_eq!(select("2.0.0-rc.1").as_deref(), Some("2.0.0-rc3"));
+        assert_eq!(select("=2.0.0-beta.1").as_deref(), Some("2.0.0-beta.1"));
+        assert_eq!(select("2.0.0-alpha.1").as_deref(), Some("2.1.0-alpha.1"));
+        assert_eq!(select("1.0").as_deref(), Some("1.9.0"));
+    }
+
+    #[test]
+    fn supported_versions() {
+        let versions: Vec<_> = [
+            ("1.0.0", None, None),
+            ("1.1.0", Some("1.56"), Some("2021")),
+            ("1.2.0", Some("1.70"), Some("2021")),
+            ("1.3.0", None, Some("2024")),
+        ]
+        .iter()
+        .map(|(version, rust_version, edition)| CrateVersion {
+            name: "foo".to_owned(),
+            version: version.parse().unwrap(),
+            yanked: false,
+            available_features: Default::default(),
+            rust_version: rust_version.map(str::to_owned),
+            edition: edition.map(str::to_owned),
+            checksum: None,
+        })
+        .collect();
+        let select = |selector: SupportedBy<'_>| {
+            selector
+                .select(&versions, None)
+                .map(|v| v.version.to_string())
+        };
+        let supported = SupportedBy::new(&LatestStable);
+        assert_eq!(select(supported).as_deref(), Some("1.3.0"));
+        assert_eq!(
+            select(supported.set_edition(Some("2021"))).as_deref(),
+            Some("1.2.0")
+        );
+        assert_eq!(
+            select(
+                supported
+                    .set_edition(Some("2021"))
+                    .set_rust_version(Some("1.60"))
+            )
+            .as_deref(),
+            Some("1.1.0")
+        );
+        assert_eq!(
+            select(supported.set_rust_version(Some("1.40"))).as_deref(),
+            Some("1.3.0")
+        );
+    }
+}
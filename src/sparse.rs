@@ -0,0 +1,305 @@
+//! Read cargo's cache of sparse registry indexes
+//!
+//! Cargo 1.70 and later fetch crates.io through its sparse HTTP index, keeping what they fetched
+//! under `$CARGO_HOME/registry/index/<host>-<hash>/.cache`.  Those files answer lookups for
+//! crates the user has already built against, without a git checkout of the index.  They are
+//! only read, cargo keeps them up to date.
+//!
+//! Crates missing from the cache are fetched from the sparse index over HTTP instead.  Without
+//! the `git` feature, that is also how crates.io is read, and how a crates.io mirror is checked
+//! against crates.io.
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use url::Url;
+
+use super::errors::*;
+use super::fetch::CrateVersion;
+use super::registry::{cargo_home, CRATES_IO_INDEX};
+
+/// Cache format written since cargo 1.70
+const CACHE_VERSION: u8 = 3;
+
+/// Whether `registry` is a sparse index, read from cargo's cache or over HTTP
+pub(crate) fn is_sparse(registry: &Url) -> bool {
+    registry.scheme().starts_with("sparse+")
+}
+
+/// Versions of `crate_name` cached by cargo, or `None` if cargo hasn't cached the crate
+pub(crate) fn cached_crate_versions(
+    registry: &Url,
+    crate_name: &str,
+) -> CargoResult<Option<Vec<CrateVersion>>> {
+    let (host, relative) = match (sparse_host(registry), cache_path(crate_name)) {
+        (Some(host), Some(relative)) => (host, relative),
+        _ => return Ok(None),
+    };
+    let index_dir = cargo_home()?.join("registry").join("index");
+    let path = match newest_cache_file(&index_dir, &host, &relative) {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    let data =
+        std::fs::read(&path).with_context(|| format!("Failed to read `{}`", path.display()))?;
+    parse_cache(&data)
+        .with_context(|| format!("Invalid index cache `{}`", path.display()))
+        .map(Some)
+}
+
+/// Versions of `crate_name` fetched from the sparse index cargo uses for `registry`, or `None`
+/// if the registry has no such crate
+///
+/// The request goes through the proxy cargo config sets for `work_dir`.
+pub(crate) fn fetch_crate_versions(
+    registry: &Url,
+    crate_name: &str,
+    work_dir: &Path,
+) -> CargoResult<Option<Vec<CrateVersion>>> {
+    let base = sparse_url(registry).with_context(|| {
+        format!(
+            "`{}` is a git index, reading it needs cargo-edit built with the `git` feature",
+            registry
+        )
+    })?;
+    let relative = match index_path(crate_name) {
+        Some(relative) => relative,
+        None => return Ok(None),
+    };
+    let url = base.join(&relative)?;
+    let response = match super::http::http_agent(&url, work_dir)?
+        .get(url.as_str())
+        .call()
+    {
+        Ok(response) => response,
+        Err(ureq::Error::Status(404 | 410 | 451, _)) => return Ok(None),
+        Err(err) => return Err(err).with_context(|| format!("Failed to fetch `{}`", url)),
+    };
+    let body = response
+        .into_string()
+        .with_context(|| format!("Invalid response from `{}`", url))?;
+    parse_index_file(&body)
+        .with_context(|| format!("Invalid index file `{}`", url))
+        .map(Some)
+}
+
+/// URL of the sparse index cargo uses for `registry`, ending in `/`
+fn sparse_url(registry: &Url) -> Option<Url> {
+    if registry.as_str().trim_end_matches('/') == CRATES_IO_INDEX {
+        return Url::parse("https://index.crates.io/").ok();
+    }
+    let url = registry.as_str().strip_prefix("sparse+")?;
+    let url = if url.ends_with('/') {
+        url.to_owned()
+    } else {
+        format!("{}/", url)
+    };
+    Url::parse(&url).ok()
+}
+
+/// Parse an index file, one JSON entry per line
+fn parse_index_file(body: &str) -> CargoResult<Vec<CrateVersion>> {
+    body.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let entry: IndexEntry = serde_json::from_str(line)?;
+            entry.into_crate_version()
+        })
+        .collect()
+}
+
+/// Host of the sparse index cargo uses for `registry`
+fn sparse_host(registry: &Url) -> Option<String> {
+    if registry.as_str().trim_end_matches('/') == CRATES_IO_INDEX {
+        return Some("index.crates.io".to_owned());
+    }
+    if !is_sparse(registry) {
+        return None;
+    }
+    registry.host_str().map(ToOwned::to_owned)
+}
+
+/// Where a crate's entries are cached, relative to an index directory
+fn cache_path(crate_name: &str) -> Option<PathBuf> {
+    index_path(crate_name).map(|relative| Path::new(".cache").join(relative))
+}
+
+/// Where a crate's entries are in an index, like `se/rd/serde`
+fn index_path(crate_name: &str) -> Option<String> {
+    if crate_name.is_empty() || !crate_name.is_ascii() {
+        return None;
+    }
+    let name = crate_name.to_ascii_lowercase();
+    let path = match name.len() {
+        1 => format!("1/{}", name),
+        2 => format!("2/{}", name),
+        3 => format!("3/{}/{}", &name[..1], name),
+        _ => format!("{}/{}/{}", &name[..2], &name[2..4], name),
+    };
+    Some(path)
+}
+
+/// The most recently written cache of the crate, as several cargo versions may have one each
+fn newest_cache_file(index_dir: &Path, host: &str, relative: &Path) -> Option<PathBuf> {
+    let prefix = format!("{}-", host);
+    std::fs::read_dir(index_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .map_or(false, |name| name.starts_with(&prefix))
+        })
+        .filter_map(|entry| {
+            let path = entry.path().join(relative);
+            let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+            Some((modified, path))
+        })
+        .max()
+        .map(|(_, path)| path)
+}
+
+/// Parse a cache file: the format version, the index format version, and the revision it was
+/// fetched at, followed by each version and its index entry, all `NUL`-terminated
+fn parse_cache(data: &[u8]) -> CargoResult<Vec<CrateVersion>> {
+    let (&version, rest) = data.split_first().context("Empty cache file")?;
+    if version != CACHE_VERSION {
+        anyhow::bail!("Unsupported cache version {}", version);
+    }
+    let rest = rest.get(4..).context("Truncated cache file")?;
+    let mut fields = rest.split(|&b| b == 0).skip(1);
+    let mut versions = Vec::new();
+    while let (Some(version), Some(entry)) = (fields.next(), fields.next()) {
+        let entry: IndexEntry = serde_json::from_slice(entry).with_context(|| {
+            format!(
+                "Invalid entry for version {}",
+                String::from_utf8_lossy(version)
+            )
+        })?;
+        versions.push(entry.into_crate_version()?);
+    }
+    Ok(versions)
+}
+
+/// A line of a registry index
+#[derive(Deserialize)]
+struct IndexEntry {
+    name: String,
+    vers: String,
+    #[serde(default)]
+    deps: Vec<IndexDependency>,
+    #[serde(default)]
+    features: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    features2: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    yanked: bool,
+    rust_version: Option<String>,
+    cksum: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct IndexDependency {
+    name: String,
+    package: Option<String>,
+    #[serde(default)]
+    optional: bool,
+}
+
+impl IndexEntry {
+    fn into_crate_version(self) -> CargoResult<CrateVersion> {
+        let mut available_features = self.features;
+        available_features.extend(self.features2);
+        available_features.extend(
+            self.deps
+                .into_iter()
+                .filter(|d| d.optional)
+                .map(|d| (d.package.unwrap_or(d.name), vec![])),
+        );
+        Ok(CrateVersion {
+            name: self.name,
+            version: self.vers.parse()?,
+            yanked: self.yanked,
+            available_features,
+            rust_version: self.rust_version,
+            edition: None,
+            checksum: self.cksum,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cache_layout() {
+        let path = |name| cache_path(name).unwrap();
+        assert_eq!(path("a"), Path::new(".cache/1/a"));
+        assert_eq!(path("ab"), Path::new(".cache/2/ab"));
+        assert_eq!(path("abc"), Path::new(".cache/3/a/abc"));
+        assert_eq!(path("Serde_JSON"), Path::new(".cache/se/rd/serde_json"));
+        assert_eq!(cache_path(""), None);
+    }
+
+    #[test]
+    fn registry_hosts() {
+        let host = |url| sparse_host(&Url::parse(url).unwrap());
+        assert_eq!(
+            host("https://github.com/rust-lang/crates.io-index").as_deref(),
+            Some("index.crates.io")
+        );
+        assert_eq!(
+            host("sparse+https://my-registry.example/index/").as_deref(),
+            Some("my-registry.example")
+        );
+        assert_eq!(host("https://my-registry.example/index.git"), None);
+    }
+
+    #[test]
+    fn sparse_urls() {
+        let url = |registry| sparse_url(&Url::parse(registry).unwrap()).map(String::from);
+        assert_eq!(
+            url("https://github.com/rust-lang/crates.io-index").as_deref(),
+            Some("https://index.crates.io/")
+        );
+        assert_eq!(
+            url("sparse+https://my-registry.example/index").as_deref(),
+            Some("https://my-registry.example/index/")
+        );
+        assert_eq!(url("https://my-registry.example/index.git"), None);
+    }
+
+    #[test]
+    fn parse_cache_file() {
+        let mut data = vec![CACHE_VERSION];
+        data.extend(2u32.to_le_bytes());
+        data.extend(b"etag: \"abc\"\0");
+        data.extend(b"1.0.0\0");
+        data.extend(
+            br#"{"name":"foo","vers":"1.0.0","deps":[],"features":{},"cksum":"00","yanked":true}"#,
+        );
+        data.push(0);
+        data.extend(b"1.1.0\0");
+        data.extend(
+            br#"{"name":"foo","vers":"1.1.0","deps":[{"name":"bar","req":"^1","optional":true}],"features":{"std":[]},"features2":{"serde":["dep:serde"]},"cksum":"00","rust_version":"1.60"}"#,
+        );
+        data.push(0);
+
+        let versions = parse_cache(&data).unwrap();
+        assert_eq!(versions.len(), 2);
+        assert!(versions[0].yanked);
+        assert_eq!(versions[1].version, semver::Version::new(1, 1, 0));
+        assert!(!versions[1].yanked);
+        assert_eq!(versions[0].rust_version, None);
+        assert_eq!(versions[1].rust_version.as_deref(), Some("1.60"));
+        assert_eq!(versions[1].checksum.as_deref(), Some("00"));
+        assert_eq!(
+            versions[1].available_features.keys().collect::<Vec<_>>(),
+            ["bar", "serde", "std"]
+        );
+
+        data[0] = 1;
+        assert!(parse_cache(&data).is_err());
+    }
+}
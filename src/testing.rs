@@ -0,0 +1,259 @@
+//! Hermetic registry for testing tools built on this crate
+//!
+//! Once a [`MockRegistry`] is installed with [`set_mock_registry`], registry lookups like
+//! [`get_latest_dependency`](crate::get_latest_dependency) are answered from it instead of the
+//! network.  Subprocesses, like a binary under test, load one from the JSON fixture named by
+//! [`MOCK_REGISTRY_ENV`].
+//!
+//! Fixtures map crate names to their published versions:
+//!
+//! ```json
+//! {
+//!     "serde": [
+//!         { "version": "1.0.147", "features": { "derive": ["serde_derive"] } },
+//!         { "version": "1.0.148", "yanked": true }
+//!     ]
+//! }
+//! ```
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use super::errors::*;
+use super::fetch::CrateVersion;
+
+/// Environment variable naming a JSON fixture to load a [`MockRegistry`] from
+pub const MOCK_REGISTRY_ENV: &str = "CARGO_EDIT_MOCK_REGISTRY";
+
+/// In-memory registry index
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct MockRegistry {
+    crates: BTreeMap<String, Vec<MockVersion>>,
+}
+
+impl MockRegistry {
+    /// An empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Crates covering common cases: semver-compatible and breaking upgrades, and features
+    pub fn sample() -> Self {
+        Self::new()
+            .publish("test_breaking", MockVersion::new("0.1.0"))
+            .publish("test_breaking", MockVersion::new("0.2.0"))
+            .publish("test_nonbreaking", MockVersion::new("0.1.0"))
+            .publish("test_nonbreaking", MockVersion::new("0.1.1"))
+            .publish(
+                "your-face",
+                MockVersion::new("99999.0.0")
+                    .set_feature("nose", &[])
+                    .set_feature("mouth", &[])
+                    .set_feature("eyes", &[])
+                    .set_feature("ears", &[]),
+            )
+    }
+
+    /// Read a registry from a JSON fixture
+    pub fn from_json(json: &str) -> CargoResult<Self> {
+        let registry: Self =
+            serde_json::from_str(json).with_context(|| "Invalid mock registry fixture")?;
+        // Catch bad versions up front rather than on lookup
+        for name in registry.crates.keys() {
+            registry.crate_versions(name)?;
+        }
+        Ok(registry)
+    }
+
+    /// Read a registry from the JSON fixture at `path`
+    pub fn load(path: &Path) -> CargoResult<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read `{}`", path.display()))?;
+        Self::from_json(&json).with_context(|| format!("Failed to load `{}`", path.display()))
+    }
+
+    /// Add a version of the crate `name`
+    pub fn publish(mut self, name: impl Into<String>, version: MockVersion) -> Self {
+        self.crates.entry(name.into()).or_default().push(version);
+        self
+    }
+
+    pub(crate) fn crate_versions(&self, name: &str) -> CargoResult<Option<Vec<CrateVersion>>> {
+        let versions = match self.crates.get(name) {
+            Some(versions) => versions,
+            None => return Ok(None),
+        };
+        versions
+            .iter()
+            .map(|v| {
+                Ok(CrateVersion {
+                    name: name.to_owned(),
+                    version: v.version.parse().with_context(|| {
+                        format!("Invalid version `{}` of `{}`", v.version, name)
+                    })?,
+                    yanked: v.yanked,
+                    available_features: v.features.clone(),
+                    rust_version: v.rust_version.clone(),
+                    edition: v.edition.clone(),
+                    checksum: v.checksum.clone(),
+                })
+            })
+            .collect::<CargoResult<Vec<_>>>()
+            .map(Some)
+    }
+}
+
+/// A published version of a crate in a [`MockRegistry`]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct MockVersion {
+    /// Semver version
+    pub version: String,
+    /// Features and what they activate, including optional dependencies
+    #[serde(default)]
+    pub features: BTreeMap<String, Vec<String>>,
+    /// Whether the version was yanked
+    #[serde(default)]
+    pub yanked: bool,
+    /// Its `rust-version`, like `1.60`
+    #[serde(default)]
+    pub rust_version: Option<String>,
+    /// Its edition, like `2021`
+    #[serde(default)]
+    pub edition: Option<String>,
+    /// SHA-256 of its `.crate` file, in hex
+    #[serde(default)]
+    pub checksum: Option<String>,
+}
+
+impl MockVersion {
+    /// A version without features
+    pub fn new(version: impl Into<String>) -> Self {
+        Self {
+            version: version.into(),
+            features: BTreeMap::new(),
+            yanked: false,
+            rust_version: None,
+            edition: None,
+            checksum: None,
+        }
+    }
+
+    /// Add a feature activating `activates`
+    pub fn set_feature(mut self, name: impl Into<String>, activates: &[&str]) -> Self {
+        self.features.insert(
+            name.into(),
+            activates.iter().map(|a| (*a).to_owned()).collect(),
+        );
+        self
+    }
+
+    /// Mark the version as yanked
+    pub fn set_yanked(mut self, yanked: bool) -> Self {
+        self.yanked = yanked;
+        self
+    }
+
+    /// Declare the oldest Rust the version builds with
+    pub fn set_rust_version(mut self, rust_version: impl Into<String>) -> Self {
+        self.rust_version = Some(rust_version.into());
+        self
+    }
+
+    /// Declare the edition the version is written in
+    pub fn set_edition(mut self, edition: impl Into<String>) -> Self {
+        self.edition = Some(edition.into());
+        self
+    }
+
+    /// Record the SHA-256 of its `.crate` file
+    pub fn set_checksum(mut self, checksum: impl Into<String>) -> Self {
+        self.checksum = Some(checksum.into());
+        self
+    }
+}
+
+static MOCK_REGISTRY: RwLock<Option<Arc<MockRegistry>>> = RwLock::new(None);
+
+/// Answer registry lookups from `registry` for the rest of the process
+///
+/// `None` goes back to the real registries, unless [`MOCK_REGISTRY_ENV`] is set.
+pub fn set_mock_registry(registry: Option<MockRegistry>) {
+    *MOCK_REGISTRY.write().expect("not poisoned") = registry.map(Arc::new);
+}
+
+/// The registry installed with [`set_mock_registry`] or named by [`MOCK_REGISTRY_ENV`], if any
+pub(crate) fn mock_registry() -> CargoResult<Option<Arc<MockRegistry>>> {
+    if let Some(registry) = MOCK_REGISTRY.read().expect("not poisoned").clone() {
+        return Ok(Some(registry));
+    }
+    match std::env::var_os(MOCK_REGISTRY_ENV) {
+        Some(path) if !path.is_empty() => {
+            let registry = Arc::new(MockRegistry::load(Path::new(&path))?);
+            *MOCK_REGISTRY.write().expect("not poisoned") = Some(registry.clone());
+            Ok(Some(registry))
+        }
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fixture_from_json() {
+        let registry = MockRegistry::from_json(
+            r#"{
+                "serde": [
+                    { "version": "1.0.147", "features": { "derive": ["serde_derive"] } },
+                    { "version": "1.0.148", "yanked": true }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let versions = registry.crate_versions("serde").unwrap().unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].available_features["derive"], ["serde_derive"]);
+        assert!(versions[1].yanked);
+        assert!(registry.crate_versions("serde_json").unwrap().is_none());
+    }
+
+    #[test]
+    fn fixture_with_invalid_version() {
+        assert!(MockRegistry::from_json(r#"{ "serde": [{ "version": "1.0" }] }"#).is_err());
+    }
+
+    #[test]
+    fn lookups_use_installed_registry() {
+        let crates_io = url::Url::parse("https://github.com/rust-lang/crates.io-index").unwrap();
+        let latest = |name: &str| {
+            crate::get_latest_dependency(
+                name,
+                &crate::LookupOptions::new().set_fuzzy(true),
+                Path::new("Cargo.toml"),
+                Some(&crates_io),
+            )
+        };
+
+        set_mock_registry(Some(
+            MockRegistry::sample().publish("serde_json", MockVersion::new("1.0.0")),
+        ));
+        let breaking = latest("test_breaking");
+        let fuzzy = latest("serde-json");
+        let missing = latest("not-published");
+        set_mock_registry(None);
+
+        assert_eq!(breaking.unwrap().0.version(), Some("0.2.0"));
+        let (fuzzy, warnings) = fuzzy.unwrap();
+        assert_eq!(fuzzy.name, "serde_json");
+        assert_eq!(
+            warnings,
+            [crate::LookupWarning::Substituted {
+                requested: "serde-json".to_owned(),
+                found: "serde_json".to_owned(),
+            }]
+        );
+        assert!(missing.is_err());
+    }
+}
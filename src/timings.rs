@@ -0,0 +1,87 @@
+//! Where the time goes, for diagnosing slow registries and networks
+//!
+//! Nothing is recorded until [`set_timings_enabled`] is called, so instrumented steps cost next
+//! to nothing otherwise.
+use std::fmt::Display;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A timed step, like updating an index or looking up a crate
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Timing {
+    /// Kind of step: `index-update`, `lookup`, `api-request`, or `write`
+    pub step: &'static str,
+    /// What the step was for, like a registry, a crate, or a manifest
+    pub subject: String,
+    /// How long the step took
+    pub duration: Duration,
+    /// Whether the step was answered from an in-process cache
+    pub cached: bool,
+}
+
+static TIMINGS: Mutex<Option<Vec<Timing>>> = Mutex::new(None);
+
+/// Start recording timings, discarding any recorded so far, or stop recording them
+pub fn set_timings_enabled(enabled: bool) {
+    *TIMINGS.lock().expect("not poisoned") = enabled.then(Vec::new);
+}
+
+/// Timings recorded since [`set_timings_enabled`], in the order the steps finished
+pub fn take_timings() -> Vec<Timing> {
+    TIMINGS
+        .lock()
+        .expect("not poisoned")
+        .as_mut()
+        .map(std::mem::take)
+        .unwrap_or_default()
+}
+
+/// Run `f`, recording how long it took as `step`
+pub(crate) fn time<T>(step: &'static str, subject: impl Display, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    record_timing(step, subject, start.elapsed(), false);
+    result
+}
+
+pub(crate) fn record_timing(
+    step: &'static str,
+    subject: impl Display,
+    duration: Duration,
+    cached: bool,
+) {
+    if let Some(timings) = TIMINGS.lock().expect("not poisoned").as_mut() {
+        timings.push(Timing {
+            step,
+            subject: subject.to_string(),
+            duration,
+            cached,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn records_only_when_enabled() {
+        time("lookup", "serde", || ());
+        set_timings_enabled(true);
+        time("lookup", "serde", || ());
+        record_timing("lookup", "serde", Duration::ZERO, true);
+        let timings = take_timings();
+        set_timings_enabled(false);
+
+        // Other tests may write manifests meanwhile
+        let timings: Vec<_> = timings
+            .into_iter()
+            .filter(|t| t.subject == "serde")
+            .collect();
+        assert_eq!(timings.len(), 2);
+        assert_eq!(timings[0].step, "lookup");
+        assert_eq!(timings[0].subject, "serde");
+        assert!(!timings[0].cached);
+        assert!(timings[1].cached);
+    }
+}
@@ -0,0 +1,248 @@
+//! Preview how cargo unifies the features a workspace enables on a dependency
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{Dependency, LocalManifest, Source};
+
+/// What one dependency entry of a package enables on a crate
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeatureRequest {
+    /// Package declaring the dependency
+    pub package: String,
+    /// Dependency table, as a path like `["target", "cfg(unix)", "dependencies"]`
+    pub table: Vec<String>,
+    /// Version requirement, for registry dependencies
+    pub version_req: Option<String>,
+    /// Whether the crate's default features are enabled
+    pub default_features: bool,
+    /// Features the entry enables, including those inherited from `[workspace.dependencies]`
+    pub features: Vec<String>,
+    /// Features the package's own features enable, as `(package feature, crate feature)`
+    pub conditional: Vec<(String, String)>,
+}
+
+/// Collect what each of `members` enables on the crate `crate_name`
+///
+/// Dependencies are matched by package name, so renamed entries are included.  Entries with
+/// `workspace = true` are resolved against `[workspace.dependencies]` in `workspace_root`.
+pub fn feature_requests(
+    crate_name: &str,
+    members: &[LocalManifest],
+    workspace_root: Option<&LocalManifest>,
+) -> Vec<FeatureRequest> {
+    let mut requests = Vec::new();
+    for member in members {
+        let crate_root = member.path.parent().expect("manifests are files");
+        let package = member
+            .data
+            .get("package")
+            .and_then(|p| p.get("name"))
+            .and_then(|n| n.as_str())
+            .map(ToOwned::to_owned)
+            .unwrap_or_else(|| member.path.display().to_string());
+        for (table, item) in member.get_sections() {
+            let entries = item.as_table_like().expect("sections are table-like");
+            for (key, entry) in entries.iter() {
+                let dependency = match Dependency::from_toml(crate_root, key, entry) {
+                    Ok(dependency) => dependency,
+                    Err(_) => continue,
+                };
+                let inherited = match dependency.source() {
+                    Some(Source::Workspace(_)) => workspace_dependency(workspace_root, key),
+                    _ => None,
+                };
+                let base = inherited.as_ref().unwrap_or(&dependency);
+                if base.name != crate_name {
+                    continue;
+                }
+
+                let mut features = base.features.clone().unwrap_or_default();
+                if inherited.is_some() {
+                    features.extend(dependency.features.clone().unwrap_or_default());
+                }
+                requests.push(FeatureRequest {
+                    package: package.clone(),
+                    table: table.to_table().into_iter().map(String::from).collect(),
+                    version_req: base.version().map(ToOwned::to_owned),
+                    default_features: base.default_features.unwrap_or(true),
+                    features,
+                    conditional: conditional_features(member, key),
+                });
+            }
+        }
+    }
+    requests
+}
+
+fn workspace_dependency(workspace_root: Option<&LocalManifest>, key: &str) -> Option<Dependency> {
+    let workspace_root = workspace_root?;
+    let entry = workspace_root
+        .data
+        .get("workspace")?
+        .get("dependencies")?
+        .get(key)?;
+    let crate_root = workspace_root.path.parent().expect("manifests are files");
+    Dependency::from_toml(crate_root, key, entry).ok()
+}
+
+/// `dep_key/feature` and `dep_key?/feature` activations in the `[features]` of `manifest`
+fn conditional_features(manifest: &LocalManifest, dep_key: &str) -> Vec<(String, String)> {
+    let mut conditional = Vec::new();
+    if let Some(features) = manifest
+        .data
+        .get("features")
+        .and_then(|f| f.as_table_like())
+    {
+        for (feature, activations) in features.iter() {
+            let activations = activations
+                .as_array()
+                .into_iter()
+                .flat_map(|a| a.iter())
+                .filter_map(|a| a.as_str());
+            for activation in activations {
+                if let Some((dep, dep_feature)) = activation.split_once('/') {
+                    if dep.trim_end_matches('?') == dep_key {
+                        conditional.push((feature.to_owned(), dep_feature.to_owned()));
+                    }
+                }
+            }
+        }
+    }
+    conditional
+}
+
+/// The features cargo builds the crate with for `requests`, leaving out conditional features
+///
+/// With the crate's feature table, like [`Dependency::available_features`], the features that
+/// enabled features imply are included too.  Pass an empty table to only list features that are
+/// enabled directly.
+pub fn unify_features(
+    requests: &[FeatureRequest],
+    available: &BTreeMap<String, Vec<String>>,
+) -> BTreeSet<String> {
+    let mut unified: BTreeSet<String> = requests
+        .iter()
+        .flat_map(|request| request.features.iter().cloned())
+        .collect();
+    let has_default = available.is_empty() || available.contains_key("default");
+    if has_default && requests.iter().any(|request| request.default_features) {
+        unified.insert("default".to_owned());
+    }
+
+    let mut pending: Vec<String> = unified.iter().cloned().collect();
+    while let Some(feature) = pending.pop() {
+        let implied = available.get(&feature).into_iter().flatten();
+        // `dep:name` and `name/feature` are about the crate's own dependencies
+        for implied in implied.filter(|i| !i.starts_with("dep:") && !i.contains('/')) {
+            if available.contains_key(implied) && unified.insert(implied.clone()) {
+                pending.push(implied.clone());
+            }
+        }
+    }
+    unified
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    fn local_manifest(path: &str, data: &str) -> LocalManifest {
+        LocalManifest {
+            path: PathBuf::from(path),
+            manifest: data.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn requests_across_members() {
+        let root = local_manifest(
+            "/ws/Cargo.toml",
+            r#"[workspace.dependencies]
+serde = { version = "1.0", default-features = false, features = ["std"] }
+"#,
+        );
+        let members = [
+            local_manifest(
+                "/ws/app/Cargo.toml",
+                r#"[package]
+name = "app"
+
+[dependencies]
+serde = { workspace = true, features = ["derive"] }
+"#,
+            ),
+            local_manifest(
+                "/ws/core/Cargo.toml",
+                r#"[package]
+name = "core"
+
+[features]
+rc = ["serde_crate/rc"]
+
+[dev-dependencies]
+serde_crate = { package = "serde", version = "1.0.100" }
+"#,
+            ),
+        ];
+        let requests = feature_requests("serde", &members, Some(&root));
+        assert_eq!(
+            requests,
+            vec![
+                FeatureRequest {
+                    package: "app".to_owned(),
+                    table: vec!["dependencies".to_owned()],
+                    version_req: Some("1.0".to_owned()),
+                    default_features: false,
+                    features: vec!["std".to_owned(), "derive".to_owned()],
+                    conditional: Vec::new(),
+                },
+                FeatureRequest {
+                    package: "core".to_owned(),
+                    table: vec!["dev-dependencies".to_owned()],
+                    version_req: Some("1.0.100".to_owned()),
+                    default_features: true,
+                    features: Vec::new(),
+                    conditional: vec![("rc".to_owned(), "rc".to_owned())],
+                },
+            ]
+        );
+
+        assert_eq!(
+            unify_features(&requests, &BTreeMap::new()),
+            ["default", "derive", "std"]
+                .iter()
+                .map(|f| f.to_string())
+                .collect::<BTreeSet<_>>()
+        );
+    }
+
+    #[test]
+    fn implied_features() {
+        let request = FeatureRequest {
+            package: "app".to_owned(),
+            table: vec!["dependencies".to_owned()],
+            version_req: None,
+            default_features: false,
+            features: vec!["derive".to_owned()],
+            conditional: Vec::new(),
+        };
+        let available: BTreeMap<String, Vec<String>> = [
+            ("default", vec!["std"]),
+            ("std", vec![]),
+            ("derive", vec!["serde_derive", "dep:syn", "alloc"]),
+            ("alloc", vec!["indexmap/alloc"]),
+            ("serde_derive", vec![]),
+        ]
+        .into_iter()
+        .map(|(f, i)| (f.to_owned(), i.into_iter().map(String::from).collect()))
+        .collect();
+        assert_eq!(
+            unify_features(&[request], &available),
+            ["alloc", "derive", "serde_derive"]
+                .iter()
+                .map(|f| f.to_string())
+                .collect::<BTreeSet<_>>()
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//! Check what a mirror of crates.io serves against crates.io itself
+//!
+//! Mirrors configured through `[source.crates-io] replace-with` are trusted by cargo; comparing
+//! them with crates.io's sparse index catches one that was tampered with or stopped syncing.
+use std::fmt;
+use std::path::Path;
+
+use url::Url;
+
+use super::errors::*;
+use super::fetch::{fuzzy_query_registry_index, CrateVersion};
+use super::registry::{registry_url, CRATES_IO_INDEX};
+use super::sparse::fetch_crate_versions;
+
+/// How a mirror's versions of a crate differ from crates.io's
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UpstreamMismatch {
+    /// crates.io has no such version
+    NotUpstream {
+        /// Version served by the mirror
+        version: semver::Version,
+    },
+    /// The `.crate` files differ
+    Checksum {
+        /// Version served by both
+        version: semver::Version,
+        /// Checksum on the mirror
+        mirror: String,
+        /// Checksum on crates.io
+        upstream: String,
+    },
+    /// crates.io yanked the version, the mirror didn't
+    Yanked {
+        /// Version served by both
+        version: semver::Version,
+    },
+    /// crates.io has a newer version than the mirror's newest
+    Lagging {
+        /// Newest version on the mirror
+        mirror: semver::Version,
+        /// Newest version on crates.io
+        upstream: semver::Version,
+    },
+}
+
+impl fmt::Display for UpstreamMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotUpstream { version } => write!(f, "{} isn't on crates.io", version),
+            Self::Checksum {
+                version,
+                mirror,
+                upstream,
+            } => write!(
+                f,
+                "{} has checksum {} on the mirror but {} on crates.io",
+                version, mirror, upstream
+            ),
+            Self::Yanked { version } => {
+                write!(
+                    f,
+                    "{} is yanked on crates.io but not on the mirror",
+                    version
+                )
+            }
+            Self::Lagging { mirror, upstream } => write!(
+                f,
+                "the mirror's newest version is {}, crates.io has {}",
+                mirror, upstream
+            ),
+        }
+    }
+}
+
+/// The mirror replacing crates.io for `manifest_path`, if any
+pub fn crates_io_mirror(manifest_path: &Path) -> CargoResult<Option<Url>> {
+    let registry = registry_url(manifest_path, None)?;
+    let is_crates_io = matches!(
+        registry.as_str().trim_end_matches('/'),
+        CRATES_IO_INDEX | "sparse+https://index.crates.io"
+    );
+    Ok((!is_crates_io).then(|| registry))
+}
+
+/// Compare `version` of `crate_name`, as the crates.io mirror of `manifest_path` serves it, with
+/// crates.io
+///
+/// Without a mirror there is nothing to compare.
+pub fn verify_upstream(
+    crate_name: &str,
+    version: &str,
+    manifest_path: &Path,
+) -> CargoResult<Vec<UpstreamMismatch>> {
+    let mirror = match crates_io_mirror(manifest_path)? {
+        Some(mirror) => mirror,
+        None => return Ok(Vec::new()),
+    };
+    let version: semver::Version = version.parse()?;
+    let work_dir = manifest_path
+        .parent()
+        .expect("there must be a parent directory");
+    let mirrored = fuzzy_query_registry_index(crate_name, false, &mirror, manifest_path)?;
+    let upstream = fetch_crate_versions(&Url::parse(CRATES_IO_INDEX)?, crate_name, work_dir)
+        .with_context(|| format!("Failed to check `{}` against crates.io", crate_name))?
+        .unwrap_or_default();
+    Ok(compare_upstream(&mirrored, &upstream, &version))
+}
+
+fn compare_upstream(
+    mirrored: &[CrateVersion],
+    upstream: &[CrateVersion],
+    version: &semver::Version,
+) -> Vec<UpstreamMismatch> {
+    let mut mismatches = Vec::new();
+    let find = |versions: &'_ [CrateVersion]| versions.iter().find(|v| v.version == *version);
+    match (find(mirrored), find(upstream)) {
+        (_, None) => mismatches.push(UpstreamMismatch::NotUpstream {
+            version: version.clone(),
+        }),
+        (mirror, Some(upstream)) => {
+            let mirror_checksum = mirror.and_then(|m| m.checksum.as_ref());
+            if let (Some(mirror), Some(upstream)) = (mirror_checksum, &upstream.checksum) {
+                if !mirror.eq_ignore_ascii_case(upstream) {
+                    mismatches.push(UpstreamMismatch::Checksum {
+                        version: version.clone(),
+                        mirror: mirror.clone(),
+                        upstream: upstream.clone(),
+                    });
+                }
+            }
+            if upstream.yanked && !mirror.map_or(false, |m| m.yanked) {
+                mismatches.push(UpstreamMismatch::Yanked {
+                    version: version.clone(),
+                });
+            }
+        }
+    }
+
+    // Only versions the selection could have picked count, pre-releases only for a pre-release
+    let newest = |versions: &[CrateVersion]| {
+        versions
+            .iter()
+            .filter(|v| !v.yanked && (!version.pre.is_empty() || v.version.pre.is_empty()))
+            .map(|v| &v.version)
+            .max()
+            .cloned()
+    };
+    if let (Some(mirror), Some(upstream)) = (newest(mirrored), newest(upstream)) {
+        if mirror < upstream {
+            mismatches.push(UpstreamMismatch::Lagging { mirror, upstream });
+        }
+    }
+    mismatches
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn crate_version(version: &str, checksum: &str) -> CrateVersion {
+        CrateVersion {
+            name: "foo".to_owned(),
+            version: version.parse().unwrap(),
+            yanked: false,
+            available_features: Default::default(),
+            rust_version: None,
+            edition: None,
+            checksum: Some(checksum.to_owned()),
+        }
+    }
+
+    #[test]
+    fn in_sync() {
+        let versions = [crate_version("1.0.0", "aa"), crate_version("1.1.0", "bb")];
+        let version = semver::Version::new(1, 1, 0);
+        assert_eq!(compare_upstream(&versions, &versions, &version), []);
+    }
+
+    #[test]
+    fn mismatches() {
+        let mirrored = [crate_version("1.0.0", "aa"), crate_version("1.1.0", "cc")];
+        let mut upstream = vec![
+            crate_version("1.0.0", "AA"),
+            crate_version("1.1.0", "bb"),
+            crate_version("1.2.0", "dd"),
+            crate_version("2.0.0-rc.1", "ee"),
+        ];
+        upstream[0].yanked = true;
+
+        let mismatches = compare_upstream(&mirrored, &upstream, &semver::Version::new(1, 1, 0));
+        assert_eq!(
+            mismatches,
+            [
+                UpstreamMismatch::Checksum {
+                    version: semver::Version::new(1, 1, 0),
+                    mirror: "cc".to_owned(),
+                    upstream: "bb".to_owned(),
+                },
+                UpstreamMismatch::Lagging {
+                    mirror: semver::Version::new(1, 1, 0),
+                    upstream: semver::Version::new(1, 2, 0),
+                },
+            ]
+        );
+        assert_eq!(
+            mismatches[1].to_string(),
+            "the mirror's newest version is 1.1.0, crates.io has 1.2.0"
+        );
+
+        let mismatches = compare_upstream(&mirrored, &upstream, &semver::Version::new(1, 0, 0));
+        assert_eq!(
+            mismatches[0],
+            UpstreamMismatch::Yanked {
+                version: semver::Version::new(1, 0, 0)
+            }
+        );
+        let mismatches = compare_upstream(&mirrored, &upstream, &semver::Version::new(0, 9, 0));
+        assert_eq!(mismatches[0].to_string(), "0.9.0 isn't on crates.io");
+    }
+}
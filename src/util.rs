@@ -1,37 +1,327 @@
 use std::io::Write;
+use std::sync::{Arc, Mutex, RwLock};
 
 pub use termcolor::{Color, ColorChoice};
 use termcolor::{ColorSpec, StandardStream, WriteColor};
 
 use crate::{CargoResult, Context};
 
+/// When to color output, like cargo's `--color`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ColorWhen {
+    /// Color when writing to a terminal that supports it
+    #[default]
+    Auto,
+    /// Always color
+    Always,
+    /// Never color
+    Never,
+}
+
+impl std::str::FromStr for ColorWhen {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => anyhow::bail!(
+                "invalid color choice `{}`, expected one of `auto`, `always`, `never`",
+                s
+            ),
+        }
+    }
+}
+
+/// Settings shared by everything writing user-facing output
+///
+/// Install with [`set_output_config`] before producing any output.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct OutputConfig {
+    color: ColorWhen,
+}
+
+impl OutputConfig {
+    /// Defaults for a terminal, without looking at the environment
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Defaults taken from cargo's environment variables, like `CARGO_TERM_COLOR`
+    pub fn from_env() -> CargoResult<Self> {
+        let mut config = Self::new();
+        if let Some(color) = std::env::var("CARGO_TERM_COLOR")
+            .ok()
+            .filter(|c| !c.is_empty())
+        {
+            config.color = color
+                .parse()
+                .with_context(|| "Invalid `CARGO_TERM_COLOR`")?;
+        }
+        Ok(config)
+    }
+
+    /// Set when to color output
+    pub fn set_color(mut self, color: ColorWhen) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// When output is colored
+    pub fn color(&self) -> ColorWhen {
+        self.color
+    }
+}
+
+static OUTPUT_CONFIG: RwLock<Option<OutputConfig>> = RwLock::new(None);
+
+/// Replace the output settings for the rest of the process
+pub fn set_output_config(config: OutputConfig) {
+    *OUTPUT_CONFIG.write().expect("not poisoned") = Some(config);
+}
+
+/// The output settings installed with [`set_output_config`]
+pub fn output_config() -> OutputConfig {
+    OUTPUT_CONFIG
+        .read()
+        .expect("not poisoned")
+        .unwrap_or_default()
+}
+
 /// Whether to color logged output
 pub fn colorize_stderr() -> ColorChoice {
-    if concolor_control::get(concolor_control::Stream::Stderr).color() {
+    let color = match output_config().color() {
+        ColorWhen::Always => true,
+        ColorWhen::Never => false,
+        ColorWhen::Auto => concolor_control::get(concolor_control::Stream::Stderr).color(),
+    };
+    if color {
         ColorChoice::Always
     } else {
         ColorChoice::Never
     }
 }
 
-/// Print a message with a colored title in the style of Cargo shell messages.
-pub fn shell_print(status: &str, message: &str, color: Color, justified: bool) -> CargoResult<()> {
-    let color_choice = colorize_stderr();
-    let mut output = StandardStream::stderr(color_choice);
+/// Destination for all user-facing output
+///
+/// The `shell_*` functions forward to the sink installed with [`set_output_sink`], defaulting
+/// to [`TerminalSink`].  Tools embedding this crate (like editor extensions) can install a
+/// [`JsonLinesSink`], a [`SilentSink`], or their own implementation to get structured events
+/// instead of text written to the terminal.
+pub trait OutputSink: Send + Sync {
+    /// Print a message with a status title in the style of Cargo shell messages.
+    fn print(&self, status: &str, message: &str, color: Color, justified: bool) -> CargoResult<()>;
 
-    output.set_color(ColorSpec::new().set_fg(Some(color)).set_bold(true))?;
-    if justified {
-        write!(output, "{status:>12}")?;
-    } else {
-        write!(output, "{}", status)?;
-        output.set_color(ColorSpec::new().set_bold(true))?;
-        write!(output, ":")?;
+    /// Print a part of a line with formatting
+    fn write_fragment(&self, fragment: &str, spec: &ColorSpec) -> CargoResult<()>;
+
+    /// Report a structured event
+    ///
+    /// Human-oriented sinks ignore these as the same information is conveyed through
+    /// [`OutputSink::print`] and [`OutputSink::write_fragment`].
+    fn event(&self, _kind: &str, _data: &serde_json::Value) -> CargoResult<()> {
+        Ok(())
+    }
+}
+
+/// Write colored output to stderr, like cargo
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TerminalSink;
+
+impl OutputSink for TerminalSink {
+    fn print(&self, status: &str, message: &str, color: Color, justified: bool) -> CargoResult<()> {
+        let color_choice = colorize_stderr();
+        let mut output = StandardStream::stderr(color_choice);
+
+        output.set_color(ColorSpec::new().set_fg(Some(color)).set_bold(true))?;
+        if justified {
+            write!(output, "{status:>12}")?;
+        } else {
+            write!(output, "{}", status)?;
+            output.set_color(ColorSpec::new().set_bold(true))?;
+            write!(output, ":")?;
+        }
+        output.reset()?;
+
+        writeln!(output, " {message}").with_context(|| "Failed to write message")?;
+
+        Ok(())
+    }
+
+    fn write_fragment(&self, fragment: &str, spec: &ColorSpec) -> CargoResult<()> {
+        let color_choice = colorize_stderr();
+        let mut output = StandardStream::stderr(color_choice);
+
+        output.set_color(spec)?;
+        write!(output, "{}", fragment)?;
+        output.reset()?;
+        Ok(())
     }
-    output.reset()?;
+}
 
-    writeln!(output, " {message}").with_context(|| "Failed to write message")?;
+/// Version of the JSON output, reported as `schema_version` on every line [`JsonLinesSink`] writes
+///
+/// It is raised when a field or reason is removed or renamed, or a field changes type. Adding
+/// either doesn't raise it.
+pub const JSON_SCHEMA_VERSION: u32 = 1;
 
-    Ok(())
+/// [JSON Schema](https://json-schema.org) of the lines [`JsonLinesSink`] writes
+pub const JSON_SCHEMA: &str = include_str!("cargo-edit.json");
+
+/// Write one JSON object per line to stdout, as described by [`JSON_SCHEMA`]
+///
+/// Fragments are buffered until a newline is written so each line of text is reported as a
+/// single object.
+#[derive(Debug, Default)]
+pub struct JsonLinesSink {
+    line: Mutex<String>,
+}
+
+impl JsonLinesSink {
+    fn emit(&self, mut value: serde_json::Value) -> CargoResult<()> {
+        value["schema_version"] = JSON_SCHEMA_VERSION.into();
+        let stdout = std::io::stdout();
+        let mut output = stdout.lock();
+        writeln!(output, "{}", value).with_context(|| "Failed to write message")?;
+        Ok(())
+    }
+}
+
+impl OutputSink for JsonLinesSink {
+    fn print(
+        &self,
+        status: &str,
+        message: &str,
+        _color: Color,
+        _justified: bool,
+    ) -> CargoResult<()> {
+        self.emit(serde_json::json!({
+            "reason": "message",
+            "status": status,
+            "message": message,
+        }))
+    }
+
+    fn write_fragment(&self, fragment: &str, _spec: &ColorSpec) -> CargoResult<()> {
+        let mut line = self.line.lock().expect("not poisoned");
+        line.push_str(fragment);
+        while let Some(end) = line.find('\n') {
+            let text: String = line.drain(..=end).collect();
+            self.emit(serde_json::json!({
+                "reason": "text",
+                "text": text.trim_end_matches('\n'),
+            }))?;
+        }
+        Ok(())
+    }
+
+    fn event(&self, kind: &str, data: &serde_json::Value) -> CargoResult<()> {
+        self.emit(serde_json::json!({
+            "reason": kind,
+            "data": data,
+        }))
+    }
+}
+
+/// Discard all output
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SilentSink;
+
+impl OutputSink for SilentSink {
+    fn print(
+        &self,
+        _status: &str,
+        _message: &str,
+        _color: Color,
+        _justified: bool,
+    ) -> CargoResult<()> {
+        Ok(())
+    }
+
+    fn write_fragment(&self, _fragment: &str, _spec: &ColorSpec) -> CargoResult<()> {
+        Ok(())
+    }
+}
+
+/// Forward output to another sink, keeping the structured events for the command to act on
+///
+/// This lets a command use its own report, like summarizing it in a notification, whatever
+/// format the output is written in.
+pub struct RecordingSink {
+    inner: Box<dyn OutputSink>,
+    events: RecordedEvents,
+}
+
+impl RecordingSink {
+    /// Forward output to `inner`
+    pub fn new(inner: Box<dyn OutputSink>) -> Self {
+        Self {
+            inner,
+            events: RecordedEvents::default(),
+        }
+    }
+
+    /// The events recorded, which can still be read once the sink is installed
+    pub fn events(&self) -> RecordedEvents {
+        self.events.clone()
+    }
+}
+
+impl std::fmt::Debug for RecordingSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RecordingSink")
+            .field("events", &self.events)
+            .finish_non_exhaustive()
+    }
+}
+
+impl OutputSink for RecordingSink {
+    fn print(&self, status: &str, message: &str, color: Color, justified: bool) -> CargoResult<()> {
+        self.inner.print(status, message, color, justified)
+    }
+
+    fn write_fragment(&self, fragment: &str, spec: &ColorSpec) -> CargoResult<()> {
+        self.inner.write_fragment(fragment, spec)
+    }
+
+    fn event(&self, kind: &str, data: &serde_json::Value) -> CargoResult<()> {
+        self.events
+            .0
+            .lock()
+            .expect("not poisoned")
+            .push((kind.to_owned(), data.clone()));
+        self.inner.event(kind, data)
+    }
+}
+
+/// Events a [`RecordingSink`] kept, by kind
+#[derive(Clone, Debug, Default)]
+pub struct RecordedEvents(Arc<Mutex<Vec<(String, serde_json::Value)>>>);
+
+impl RecordedEvents {
+    /// Remove the events recorded so far, oldest first
+    pub fn take(&self) -> Vec<(String, serde_json::Value)> {
+        std::mem::take(&mut *self.0.lock().expect("not poisoned"))
+    }
+}
+
+static OUTPUT_SINK: RwLock<Option<Box<dyn OutputSink>>> = RwLock::new(None);
+
+/// Replace where user-facing output is written for the rest of the process
+pub fn set_output_sink(sink: Box<dyn OutputSink>) {
+    *OUTPUT_SINK.write().expect("not poisoned") = Some(sink);
+}
+
+fn with_output_sink<R>(f: impl FnOnce(&dyn OutputSink) -> R) -> R {
+    let sink = OUTPUT_SINK.read().expect("not poisoned");
+    let default: &dyn OutputSink = &TerminalSink;
+    f(sink.as_deref().unwrap_or(default))
+}
+
+/// Print a message with a colored title in the style of Cargo shell messages.
+pub fn shell_print(status: &str, message: &str, color: Color, justified: bool) -> CargoResult<()> {
+    with_output_sink(|sink| sink.print(status, message, color, justified))
 }
 
 /// Print a styled action message.
@@ -51,11 +341,25 @@ pub fn shell_note(message: &str) -> CargoResult<()> {
 
 /// Print a part of a line with formatting
 pub fn shell_write_stderr(fragment: impl std::fmt::Display, spec: &ColorSpec) -> CargoResult<()> {
-    let color_choice = colorize_stderr();
-    let mut output = StandardStream::stderr(color_choice);
+    let fragment = fragment.to_string();
+    with_output_sink(|sink| sink.write_fragment(&fragment, spec))
+}
+
+/// Report a structured event to sinks that understand them
+pub fn shell_event(kind: &str, data: &serde_json::Value) -> CargoResult<()> {
+    with_output_sink(|sink| sink.event(kind, data))
+}
 
-    output.set_color(spec)?;
-    write!(output, "{}", fragment)?;
-    output.reset()?;
-    Ok(())
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn schema_matches_version() {
+        let schema: serde_json::Value = serde_json::from_str(JSON_SCHEMA).unwrap();
+        assert_eq!(
+            schema["properties"]["schema_version"]["const"],
+            JSON_SCHEMA_VERSION
+        );
+    }
 }
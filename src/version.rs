@@ -1,3 +1,9 @@
+//! Version and requirement helpers shared by the commands, for release tools too
+//!
+//! - [`VersionExt`] bumps versions and tells pre-releases apart
+//! - [`classify_version_change`] says whether cargo treats a new version as compatible
+//! - [`upgrade_requirement`] rewrites a requirement for a new version, keeping its operators and
+//!   precision, so `1.2` becomes `1.3` rather than `1.3.0`
 use std::str::FromStr;
 
 use super::errors::*;
@@ -122,6 +128,61 @@ impl VersionExt for semver::Version {
     }
 }
 
+/// How a version relates to an older one, by cargo's compatibility rules
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VersionChange {
+    /// The same version, ignoring build metadata
+    Unchanged,
+    /// A newer version a caret requirement on the old one allows, like `1.2.3` to `1.4.0`, or
+    /// `0.2.1` to `0.2.5`
+    Compatible,
+    /// A newer version a caret requirement on the old one doesn't allow, like `1.2.3` to `2.0.0`,
+    /// `0.2.1` to `0.3.0`, or `1.2.3` to `1.3.0-alpha.1`
+    Breaking,
+    /// An older version
+    Downgrade,
+}
+
+impl VersionChange {
+    /// Short name of the change, like `breaking`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Unchanged => "unchanged",
+            Self::Compatible => "compatible",
+            Self::Breaking => "breaking",
+            Self::Downgrade => "downgrade",
+        }
+    }
+}
+
+/// Classify going from `old` to `new`
+///
+/// Cargo treats versions as compatible when they share the leftmost non-zero component, and only
+/// picks pre-releases of the exact version a requirement names, which is what `^old` allows.
+pub fn classify_version_change(old: &semver::Version, new: &semver::Version) -> VersionChange {
+    match old.cmp_precedence(new) {
+        std::cmp::Ordering::Equal => VersionChange::Unchanged,
+        std::cmp::Ordering::Greater => VersionChange::Downgrade,
+        std::cmp::Ordering::Less => {
+            let caret = semver::Comparator {
+                op: semver::Op::Caret,
+                major: old.major,
+                minor: Some(old.minor),
+                patch: Some(old.patch),
+                pre: old.pre.clone(),
+            };
+            let caret = semver::VersionReq {
+                comparators: vec![caret],
+            };
+            if caret.matches(new) {
+                VersionChange::Compatible
+            } else {
+                VersionChange::Breaking
+            }
+        }
+    }
+}
+
 static VERSION_ALPHA: &str = "alpha";
 static VERSION_BETA: &str = "beta";
 static VERSION_RC: &str = "rc";
@@ -142,20 +203,19 @@ fn prerelease_id_version(version: &semver::Version) -> CargoResult<Option<(Strin
 }
 
 /// Upgrade an existing requirement to a new version
+///
+/// Cargo ignores build metadata in requirements, so a requirement like `=1.2.3+mycorp.1` is left
+/// alone for `1.2.3`.  When it is upgraded, the metadata is kept, or replaced by that of `version`
+/// if it has any, as long as the new requirement names a single full version.
 pub fn upgrade_requirement(req: &str, version: &semver::Version) -> CargoResult<Option<String>> {
-    let req_text = req.to_string();
+    let (req_text, build) = split_build_metadata(req);
     let raw_req = semver::VersionReq::parse(&req_text)
         .expect("semver to generate valid version requirements");
     if raw_req.comparators.is_empty() {
         // Empty matches everything, no-change.
         Ok(None)
     } else {
-        let comparators: CargoResult<Vec<_>> = raw_req
-            .comparators
-            .into_iter()
-            .map(|p| set_comparator(p, version))
-            .collect();
-        let comparators = comparators?;
+        let comparators = upgrade_comparators(raw_req.comparators, version)?;
         let new_req = semver::VersionReq { comparators };
         let mut new_req_text = new_req.to_string();
         if new_req_text.starts_with('^') && !req.starts_with('^') {
@@ -173,11 +233,76 @@ pub fn upgrade_requirement(req: &str, version: &semver::Version) -> CargoResult<
         if new_req_text == req_text {
             Ok(None)
         } else {
+            let names_version = match new_req.comparators.as_slice() {
+                [pred] => pred.patch.is_some(),
+                _ => false,
+            };
+            let build = build.map(|build| match version.build.as_str() {
+                "" => build,
+                new_build => new_build.to_owned(),
+            });
+            if let (Some(build), true) = (build, names_version) {
+                new_req_text.push('+');
+                new_req_text.push_str(&build);
+            }
             Ok(Some(new_req_text))
         }
     }
 }
 
+/// Split build metadata, like `+mycorp.1`, off the comparators of a requirement
+///
+/// Returns the requirement as cargo reads it, and the first metadata found.
+pub fn split_build_metadata(req: &str) -> (String, Option<String>) {
+    let mut stripped = String::with_capacity(req.len());
+    let mut build = None;
+    let mut rest = req;
+    while let Some(start) = rest.find('+') {
+        stripped.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find(|c: char| c == ',' || c.is_whitespace())
+            .map_or(rest.len(), |len| start + len);
+        build.get_or_insert_with(|| rest[start + 1..end].to_owned());
+        rest = &rest[end..];
+    }
+    stripped.push_str(rest);
+    (stripped, build)
+}
+
+/// Rewrite each comparator for `version`, keeping its operator
+///
+/// Upper bounds (`<`, `<=`) are raised just enough to admit `version`.  Lower bounds in the same
+/// requirement move up alongside them so a range like `>=0.4, <0.6` keeps its width.
+fn upgrade_comparators(
+    comparators: Vec<semver::Comparator>,
+    version: &semver::Version,
+) -> CargoResult<Vec<semver::Comparator>> {
+    let upper = comparators
+        .iter()
+        .position(|c| matches!(c.op, semver::Op::Less | semver::Op::LessEq));
+    let upper = match upper {
+        Some(upper) => upper,
+        None => {
+            return comparators
+                .into_iter()
+                .map(|p| set_comparator(p, version))
+                .collect();
+        }
+    };
+
+    let old_upper = comparators[upper].clone();
+    let new_upper = raise_upper_bound(old_upper.clone(), version);
+    comparators
+        .into_iter()
+        .map(|pred| match pred.op {
+            semver::Op::Greater | semver::Op::GreaterEq => {
+                Ok(shift_lower_bound(pred, &old_upper, &new_upper, version))
+            }
+            _ => set_comparator(pred, version),
+        })
+        .collect()
+}
+
 fn set_comparator(
     mut pred: semver::Comparator,
     version: &semver::Version,
@@ -194,10 +319,17 @@ fn set_comparator(
             Ok(pred)
         }
         semver::Op::Exact => Ok(assign_partial_req(version, pred)),
-        semver::Op::Greater | semver::Op::GreaterEq | semver::Op::Less | semver::Op::LessEq => {
-            let user_pred = pred.to_string();
-            Err(unsupported_version_req(user_pred))
+        // Already admits anything newer
+        semver::Op::Greater => Ok(pred),
+        semver::Op::GreaterEq => {
+            let raised = assign_partial_req(version, pred.clone());
+            if bound_key(&pred) < bound_key(&raised) {
+                Ok(raised)
+            } else {
+                Ok(pred)
+            }
         }
+        semver::Op::Less | semver::Op::LessEq => Ok(raise_upper_bound(pred, version)),
         semver::Op::Tilde => Ok(assign_partial_req(version, pred)),
         semver::Op::Caret => Ok(assign_partial_req(version, pred)),
         _ => {
@@ -207,6 +339,74 @@ fn set_comparator(
     }
 }
 
+/// Raise `<`/`<=` to the lowest bound, at the same precision, that admits `version`
+///
+/// Bounds that already admit `version` are left alone.
+fn raise_upper_bound(pred: semver::Comparator, version: &semver::Version) -> semver::Comparator {
+    let step = u64::from(pred.op == semver::Op::Less);
+    let mut raised = pred.clone();
+    match (pred.minor, pred.patch) {
+        (None, _) => {
+            raised.major = version.major + step;
+        }
+        (Some(_), None) => {
+            raised.major = version.major;
+            raised.minor = Some(version.minor + step);
+        }
+        (Some(_), Some(_)) => {
+            raised.major = version.major;
+            raised.minor = Some(version.minor);
+            raised.patch = Some(version.patch + step);
+        }
+    }
+    raised.pre = semver::Prerelease::EMPTY;
+    if bound_key(&pred) < bound_key(&raised) {
+        raised
+    } else {
+        pred
+    }
+}
+
+/// Move a lower bound up as far as its upper bound moved
+fn shift_lower_bound(
+    pred: semver::Comparator,
+    old_upper: &semver::Comparator,
+    new_upper: &semver::Comparator,
+    version: &semver::Version,
+) -> semver::Comparator {
+    let mut shifted = pred.clone();
+    if pred.major == old_upper.major {
+        // Keep the same distance below the upper bound
+        shifted.major = new_upper.major;
+        if let (Some(minor), Some(old_minor), Some(new_minor)) =
+            (pred.minor, old_upper.minor, new_upper.minor)
+        {
+            shifted.minor = Some(new_minor.saturating_sub(old_minor.saturating_sub(minor)));
+            if let (true, Some(patch), Some(old_patch), Some(new_patch)) = (
+                minor == old_minor,
+                pred.patch,
+                old_upper.patch,
+                new_upper.patch,
+            ) {
+                shifted.patch = Some(new_patch.saturating_sub(old_patch.saturating_sub(patch)));
+            }
+        }
+    } else {
+        shifted.major += new_upper.major.saturating_sub(old_upper.major);
+    }
+
+    if shifted.matches(version) {
+        shifted
+    } else {
+        pred
+    }
+}
+
+/// Order comparators by the version they name, treating missing fields as `0`
+fn bound_key(pred: &semver::Comparator) -> (u64, u64, u64) {
+    (pred.major, pred.minor.unwrap_or(0), pred.patch.unwrap_or(0))
+}
+
 fn assign_partial_req(
     version: &semver::Version,
     mut pred: semver::Comparator,
@@ -303,6 +503,25 @@ mod test {
             assert_eq!(actual.as_deref(), expected);
         }
 
+        #[test]
+        fn build_metadata() {
+            assert_req_bump("1.2.3+mycorp.1", "=1.2.3+mycorp.1", None);
+            assert_req_bump("1.2.3+mycorp.2", "=1.2.3+mycorp.1", None);
+            assert_req_bump("1.3.0+mycorp.2", "=1.2.3+mycorp.1", "=1.3.0+mycorp.2");
+            assert_req_bump("1.3.0", "=1.2.3+mycorp.1", "=1.3.0+mycorp.1");
+            assert_req_bump("1.3.0+mycorp.2", "=1.2.3", "=1.3.0");
+            assert_req_bump("2.0.0+mycorp.2", "1+mycorp.1", "2");
+        }
+
+        #[test]
+        fn split_metadata() {
+            assert_eq!(
+                split_build_metadata(">=1.2.3+a.1, <2.0.0+b"),
+                (">=1.2.3, <2.0.0".to_owned(), Some("a.1".to_owned()))
+            );
+            assert_eq!(split_build_metadata("1.2"), ("1.2".to_owned(), None));
+        }
+
         #[test]
         fn wildcard_major() {
             assert_req_bump("1.0.0", "*", None);
@@ -403,6 +622,41 @@ mod test {
             assert_req_bump("2.0.0", "=1.0", "=2.0");
         }
 
+        #[test]
+        fn less_than() {
+            assert_req_bump("0.5.0", "<0.6", None);
+            assert_req_bump("0.6.2", "<0.6", "<0.7");
+            assert_req_bump("2.0.0", "<2", "<3");
+            assert_req_bump("1.2.3", "<1.2.3", "<1.2.4");
+        }
+
+        #[test]
+        fn less_than_equal() {
+            assert_req_bump("0.5.1", "<=0.5", None);
+            assert_req_bump("0.6.2", "<=0.5", "<=0.6");
+            assert_req_bump("2.1.0", "<=1", "<=2");
+        }
+
+        #[test]
+        fn greater_than() {
+            assert_req_bump("99999.0.0", ">0.2", None);
+        }
+
+        #[test]
+        fn greater_than_equal() {
+            assert_req_bump("1.0.0", ">=1", None);
+            assert_req_bump("0.2.3", ">=0.2.0", ">=0.2.3");
+        }
+
+        #[test]
+        fn range() {
+            assert_req_bump("0.5.0", ">=0.4, <0.6", None);
+            assert_req_bump("0.6.2", ">=0.4, <0.6", ">=0.5, <0.7");
+            assert_req_bump("2.3.0", ">=0.4, <0.6", ">=2.2, <2.4");
+            assert_req_bump("2.5.0", ">=1.2, <2", ">=2.2, <3");
+            assert_req_bump("1.2.9", ">=1.2.3, <1.2.8", ">=1.2.5, <1.2.10");
+        }
+
         #[test]
         fn equal_patch() {
             assert_req_bump("1.0.0", "=1.0.0", None);
@@ -411,4 +665,110 @@ mod test {
             assert_req_bump("2.0.0", "=1.0.0", "=2.0.0");
         }
     }
+
+    /// Properties checked over every combination of small versions and requirements
+    mod properties {
+        use super::*;
+
+        fn versions() -> Vec<semver::Version> {
+            let mut versions = Vec::new();
+            for major in 0..3 {
+                for minor in 0..3 {
+                    for patch in 0..3 {
+                        for pre in ["", "alpha.1", "rc.2"] {
+                            let mut version = semver::Version::new(major, minor, patch);
+                            version.pre = semver::Prerelease::new(pre).unwrap();
+                            versions.push(version);
+                        }
+                    }
+                }
+            }
+            versions
+        }
+
+        /// Requirements of every supported operator and precision, with the version they name
+        fn requirements() -> Vec<(String, semver::Version)> {
+            let mut reqs = Vec::new();
+            for version in versions().into_iter().filter(|v| !v.is_prerelease()) {
+                let parts = [version.major, version.minor, version.patch].map(|p| p.to_string());
+                for precision in 1..=3 {
+                    for op in ["", "^", "~", "=", ">="] {
+                        let req = format!("{}{}", op, parts[..precision].join("."));
+                        reqs.push((req, version.clone()));
+                    }
+                }
+            }
+            reqs
+        }
+
+        fn operator(req: &str) -> &str {
+            let end = req.find(|c: char| c.is_ascii_digit()).unwrap();
+            &req[..end]
+        }
+
+        /// Components of the requirement, which upgrading keeps
+        fn precision(req: &str) -> usize {
+            req.matches('.').count() + 1
+        }
+
+        #[test]
+        fn upgrades_match_and_keep_precision() {
+            let releases: Vec<_> = versions()
+                .into_iter()
+                .filter(|v| !v.is_prerelease())
+                .collect();
+            for (req, base) in requirements() {
+                for version in releases.iter().filter(|v| **v >= base) {
+                    let upgraded = upgrade_requirement(&req, version).unwrap();
+                    let new_req = upgraded.as_deref().unwrap_or(&req);
+                    assert!(
+                        semver::VersionReq::parse(new_req).unwrap().matches(version),
+                        "`{}` upgraded to `{}` for {}",
+                        req,
+                        new_req,
+                        version
+                    );
+                    assert_eq!(operator(new_req), operator(&req), "`{}`", req);
+                    assert_eq!(precision(new_req), precision(&req), "`{}`", req);
+                    assert_ne!(upgraded.as_deref(), Some(req.as_str()));
+                }
+            }
+        }
+
+        /// The component cargo keeps compatible, the leftmost non-zero one
+        fn compatibility_key(version: &semver::Version) -> (u64, u64, u64) {
+            match (version.major, version.minor) {
+                (0, 0) => (0, 0, version.patch),
+                (0, minor) => (0, minor, 0),
+                (major, _) => (major, 0, 0),
+            }
+        }
+
+        #[test]
+        fn changes_are_classified() {
+            for old in versions() {
+                assert_eq!(
+                    classify_version_change(&old, &old),
+                    VersionChange::Unchanged
+                );
+                for new in versions().into_iter().filter(|new| *new > old) {
+                    let change = classify_version_change(&old, &new);
+                    assert_eq!(
+                        classify_version_change(&new, &old),
+                        VersionChange::Downgrade
+                    );
+                    let expected = if new.is_prerelease()
+                        && (new.major, new.minor, new.patch) != (old.major, old.minor, old.patch)
+                    {
+                        VersionChange::Breaking
+                    } else if compatibility_key(&new) == compatibility_key(&old) {
+                        VersionChange::Compatible
+                    } else {
+                        VersionChange::Breaking
+                    };
+                    assert_eq!(change, expected, "{} to {}", old, new);
+                }
+            }
+        }
+    }
 }
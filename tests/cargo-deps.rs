@@ -0,0 +1,11 @@
+#[test]
+fn cli_tests() {
+    let cases = trycmd::TestCases::new();
+    cases.case("tests/cmd/deps/*.toml");
+    // These read a mock registry, which only `test-support` builds honor
+    if !cfg!(feature = "test-support") {
+        cases
+            .skip("tests/cmd/deps/verify.toml")
+            .skip("tests/cmd/deps/why.toml");
+    }
+}
@@ -17,13 +17,17 @@ mod rm_avoid_empty_tables;
 mod rm_build;
 mod rm_dev;
 mod rm_existing;
+mod rm_gc_workspace_deps;
+mod rm_ignores_project_hooks;
 mod rm_multiple_deps;
 mod rm_multiple_dev;
 mod rm_optional_dep_feature;
 mod rm_optional_feature;
 mod rm_target;
 mod rm_target_build;
+mod rm_target_cfg;
 mod rm_target_dev;
+mod rm_workspace;
 
 fn init_registry() {
     cargo_test_support::registry::init();
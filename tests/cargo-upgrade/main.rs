@@ -6,36 +6,56 @@
 extern crate cargo_test_macro;
 
 mod alt_registry;
+mod apply_replacements;
+mod build_metadata;
+mod confirm;
+mod default_features_policy;
+mod dedupe;
 mod dry_run;
 mod exclude_dep;
 mod exclude_renamed;
+mod fix_duplicates;
+mod github_output;
 mod implicit_prerelease;
 mod invalid_dep;
 mod invalid_flag;
 mod invalid_manifest;
+mod invalid_notify;
 mod invalid_workspace_root_manifest;
 mod locked;
+mod message_format_json;
+mod minimal;
 mod optional_dep;
 mod pinned;
+mod pre_release_channel;
 mod preserve_op;
 mod preserve_precision_major;
 mod preserve_precision_minor;
 mod preserve_precision_patch;
 mod preserves_inline_table;
 mod preserves_std_table;
+mod registry_for;
+mod removed_features;
+mod replacement_precision;
+mod show_skipped;
 mod single_dep;
 mod skip_compatible;
+mod skip_templated;
 mod specified;
+mod summary_only;
 mod to_lockfile;
 mod to_version;
 mod upgrade_all;
 mod upgrade_everything;
+mod upgrade_policy;
 mod upgrade_renamed;
 mod upgrade_verbose;
 mod upgrade_workspace;
 mod virtual_manifest;
+mod virtual_manifest_default_members;
 mod workspace_member_cwd;
 mod workspace_member_manifest_path;
+mod yanked_current;
 
 fn init_registry() {
     cargo_test_support::registry::init();
@@ -146,6 +166,60 @@ fn add_everything_registry_packages(alt: bool) {
     }
 }
 
+fn add_feature_registry_packages(alt: bool) {
+    cargo_test_support::registry::Package::new("feature-pkg", "0.1.0")
+        .feature("kept", &[])
+        .feature("old", &[])
+        .alternative(alt)
+        .publish();
+    cargo_test_support::registry::Package::new("feature-pkg", "0.2.0")
+        .feature("kept", &[])
+        .alternative(alt)
+        .publish();
+}
+
+fn add_dedupe_registry_packages(alt: bool) {
+    for version in ["0.1.0", "0.2.0"] {
+        cargo_test_support::registry::Package::new("shared", version)
+            .alternative(alt)
+            .publish();
+    }
+    cargo_test_support::registry::Package::new("dep-a", "1.0.0")
+        .dep("shared", "0.2")
+        .alternative(alt)
+        .publish();
+    cargo_test_support::registry::Package::new("old-dep", "1.0.0")
+        .dep("shared", "0.1")
+        .alternative(alt)
+        .publish();
+}
+
+fn add_replacement_registry_packages(alt: bool) {
+    for (name, version) in [
+        ("failure", "0.1.8"),
+        ("anyhow", "1.0.0"),
+        ("thiserror", "1.0.0"),
+    ] {
+        cargo_test_support::registry::Package::new(name, version)
+            .alternative(alt)
+            .publish();
+    }
+    for (name, version) in [("structopt", "0.3.26"), ("clap", "3.2.0")] {
+        cargo_test_support::registry::Package::new(name, version)
+            .feature("color", &[])
+            .alternative(alt)
+            .publish();
+    }
+}
+
+fn add_prerelease_registry_packages(alt: bool) {
+    for version in ["1.0.0", "2.0.0-beta.1", "2.0.0-beta.2", "2.0.0-rc.1"] {
+        cargo_test_support::registry::Package::new("train", version)
+            .alternative(alt)
+            .publish();
+    }
+}
+
 fn add_git_registry_packages() {
     cargo_test_support::git::new("serde", |project| {
         project